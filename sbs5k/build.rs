@@ -0,0 +1,115 @@
+//! Packs the block face textures named in `textures/blocks.json` into a single image, one layer
+//! per distinct texture, and generates the table the mesher uses to look those layers up.
+
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[path = "src/loading/block_texture_packing.rs"]
+mod block_texture_packing;
+
+const BLOCK_DEFINITIONS: &str = "../textures/blocks.json";
+const BLOCK_TEXTURES_DIR: &str = "../textures/blocks";
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/loading/block_texture_packing.rs");
+    println!("cargo:rerun-if-changed={}", BLOCK_DEFINITIONS);
+    println!("cargo:rerun-if-changed={}", BLOCK_TEXTURES_DIR);
+
+    let definitions =
+        fs::read_to_string(BLOCK_DEFINITIONS).expect("Failed to read block definitions");
+    let packed = block_texture_packing::pack(&definitions, |file| {
+        let path = Path::new(BLOCK_TEXTURES_DIR).join(file);
+        fs::read(&path).map_err(|err| format!("Failed to read {}: {}", path.display(), err))
+    })
+    .unwrap_or_else(|err| panic!("Failed to pack block textures: {}", err));
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    fs::write(out_dir.join("blocks.png"), &packed.png).expect("Failed to save block textures");
+    fs::write(
+        out_dir.join("block_textures.rs"),
+        generate_lookup_table(&packed, &built_in_texture_files()),
+    )
+    .expect("Failed to write block texture lookup table");
+}
+
+/// The names of every built-in block face texture, which resource packs can refer to without
+/// supplying themselves
+fn built_in_texture_files() -> Vec<String> {
+    let mut files: Vec<String> = fs::read_dir(BLOCK_TEXTURES_DIR)
+        .expect("Failed to list block textures")
+        .map(|entry| {
+            entry
+                .expect("Failed to list block textures")
+                .file_name()
+                .into_string()
+                .expect("Block texture file names must be valid UTF-8")
+        })
+        .filter(|name| name.ends_with(".png"))
+        .collect();
+    files.sort();
+    files
+}
+
+fn generate_lookup_table(
+    packed: &block_texture_packing::PackedBlockTextures,
+    files: &[String],
+) -> String {
+    let mut code = String::new();
+    writeln!(
+        code,
+        "// Generated by build.rs from textures/blocks.json. Do not edit.
+
+/// The width and height in pixels of each block face texture
+pub(crate) const BLOCK_TEXTURE_SIZE: u32 = {};
+
+/// Every block face texture, stacked vertically one layer after another
+pub(crate) const BLOCKS: &[u8] = include_bytes!(concat!(env!(\"OUT_DIR\"), \"/blocks.png\"));
+
+/// The layer of `BLOCKS` used by each face of each block, in the order of the `CubeFace` variants
+pub(crate) const BLOCK_TEXTURE_LAYOUT: [(sbs5k_core::block::Block, [u32; 6]); {}] = [",
+        packed.tile_size,
+        packed.layers.len()
+    )
+    .unwrap();
+    for (block, layers) in &packed.layers {
+        writeln!(
+            code,
+            "    (sbs5k_core::block::Block::{}, {:?}),",
+            block, layers
+        )
+        .unwrap();
+    }
+    writeln!(
+        code,
+        "];
+
+/// The first layer and number of frames of each animated texture in `BLOCKS`
+pub(crate) const BLOCK_TEXTURE_ANIMATIONS: [(u32, u32); {}] = {:?};
+
+/// The block definitions that `BLOCKS` was packed from
+pub(crate) const BLOCK_DEFINITIONS: &str =
+    include_str!(concat!(env!(\"CARGO_MANIFEST_DIR\"), \"/{}\"));
+
+/// Every built-in block face texture, by file name
+pub(crate) const BLOCK_TEXTURE_FILES: [(&str, &[u8]); {}] = [",
+        packed.animations.len(),
+        packed.animations,
+        BLOCK_DEFINITIONS,
+        files.len()
+    )
+    .unwrap();
+    for file in files {
+        writeln!(
+            code,
+            "    ({:?}, include_bytes!(concat!(env!(\"CARGO_MANIFEST_DIR\"), \"/{}/{}\"))),",
+            file, BLOCK_TEXTURES_DIR, file
+        )
+        .unwrap();
+    }
+    writeln!(code, "];").unwrap();
+
+    code
+}
@@ -1,12 +1,102 @@
-use clap::Parser;
+use std::path::PathBuf;
 
-#[derive(Clone, Copy, Parser)]
+use clap::{Parser, ValueEnum};
+
+use sbs5k_core::generators::DEFAULT_SEA_LEVEL;
+
+#[derive(Clone, Parser)]
 #[clap(author, version, about, long_about = None)]
 pub(crate) struct Args {
     #[clap(short, long, default_value_t = 10)]
     /// The radius of blocks around the player to render
     pub render_distance: u32,
 
+    #[clap(long, default_value_t = 16)]
+    /// The number of chunks beyond the render distance to draw as low-detail terrain
+    pub lod_distance: u32,
+
+    #[clap(long, value_enum, default_value_t = FogMode::Linear)]
+    /// How the fog thickens towards the edge of the rendered terrain
+    pub fog_mode: FogMode,
+
+    #[clap(long)]
+    /// Override the thickness of exponential fog, which is otherwise set from the render distances
+    pub fog_density: Option<f32>,
+
+    #[clap(long, value_delimiter = ',', num_args = 3)]
+    /// Fade into a fixed red,green,blue fog colour instead of the sky
+    pub fog_colour: Option<Vec<f32>>,
+
+    #[clap(long, default_value_t = 0.0)]
+    /// The density of low-lying fog at its base altitude. Zero disables it
+    pub height_fog_density: f32,
+
+    #[clap(long, default_value_t = 0.05)]
+    /// How quickly low-lying fog thins out per block of altitude
+    pub height_fog_falloff: f32,
+
+    #[clap(long, default_value_t = 64.0)]
+    /// The altitude at which low-lying fog is at its base density
+    pub height_fog_base: f32,
+
+    #[clap(long)]
+    /// Limit the frame rate to this many frames per second
+    pub max_fps: Option<u32>,
+
+    #[clap(long)]
+    /// Synchronise frames with the display's refresh rate
+    pub vsync: bool,
+
+    #[clap(long, default_value_t = 1200.0)]
+    /// The length of a whole day and night in seconds. Zero stops time from passing
+    pub day_length: f32,
+
+    #[clap(long, default_value_t = 0.35)]
+    /// The time of day to start at, as a fraction of a day starting from midnight
+    pub time_of_day: f32,
+
+    #[clap(long, value_delimiter = ',', default_values_t = [20.0, 60.0, 200.0])]
+    /// The distances from the camera at which each of the three shadow cascades ends, nearest first
+    pub shadow_distances: Vec<f32>,
+
+    #[clap(long, default_value_t = 2048)]
+    /// The width and height in texels of each shadow cascade
+    pub shadow_map_size: u32,
+
+    #[clap(long)]
+    /// The number of samples of anisotropic filtering to apply to block textures, which keeps
+    /// them sharp at glancing angles. Clamped to the maximum the graphics driver supports
+    pub anisotropic_filtering: Option<f32>,
+
+    #[clap(long)]
+    /// Draw the sky from images rather than a gradient. Either a directory holding six faces named
+    /// px.png, nx.png, py.png, ny.png, pz.png and nz.png, or a single equirectangular panorama
+    pub skybox: Option<PathBuf>,
+
+    #[clap(long = "resource-pack")]
+    /// A directory of textures, shaders and block definitions that replace the built-in ones. Can
+    /// be given more than once, in which case later packs take priority over earlier ones
+    pub resource_packs: Vec<PathBuf>,
+
+    #[clap(long)]
+    /// Development mode: use the shaders and textures from the source tree, and reload them and
+    /// those in any resource packs whenever they're edited
+    pub dev: bool,
+
+    #[clap(long)]
+    /// The directory in which the world's data is saved. Defaults to a directory inside the
+    /// user's data directory
+    pub world_dir: Option<PathBuf>,
+
+    #[clap(long)]
+    /// The seed to generate a new world from. Ignored if the world already exists
+    pub seed: Option<u64>,
+
+    #[clap(long, default_value_t = DEFAULT_SEA_LEVEL)]
+    /// The height of the sea in a new world, below which the terrain is flooded. Ignored if the
+    /// world already exists
+    pub sea_level: i32,
+
     #[clap(short, long)]
     /// Print additional information to the console
     pub verbose: bool,
@@ -20,6 +110,14 @@ pub(crate) struct Args {
     pub debug_print_player_position: bool,
 }
 
+/// How the fog thickens with distance
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub(crate) enum FogMode {
+    Linear,
+    Exponential,
+    ExponentialSquared,
+}
+
 impl Args {
     #[inline(always)]
     pub(crate) fn is_in_debug_mode(&self) -> bool {
@@ -1,5 +1,42 @@
+use sbs5k_core::block::Block;
+
+/// The number of times per second that the game simulation is updated
+pub const SIMULATION_TICK_RATE: f64 = 60.0;
+
 /// The movement speed of the player in blocks per second
 pub const MOVE_SPEED: f32 = 8.0;
 
+/// The walking speed of the player in blocks per second
+pub const WALK_SPEED: f32 = 4.3;
+
 /// The turn speed of the player in radians per half-screen-width of mouse movement
 pub const TURN_SENSITIVITY: f32 = 2.0;
+
+/// The furthest distance in blocks at which the player can break or place blocks
+pub const BLOCK_REACH_DISTANCE: f32 = 6.0;
+
+/// The blocks that the player can place, selected with the number keys in this order
+pub const PLACEABLE_BLOCKS: [Block; 7] = [
+    Block::Stone,
+    Block::Dirt,
+    Block::Grass,
+    Block::Torch,
+    Block::Glass,
+    Block::Leaves,
+    Block::Water,
+];
+
+/// The number of simulation ticks it takes for fluid to flow one block
+pub const FLUID_FLOW_DELAY_TICKS: u64 = 15;
+
+/// The most torches whose light is drawn at once, taking the closest to the player
+pub const MAX_VISIBLE_TORCH_LIGHTS: usize = 256;
+
+/// How fast the clouds drift across the sky along the x and z axes, in blocks per second
+pub const CLOUD_VELOCITY: [f32; 2] = [1.5, 0.5];
+
+/// The colour of the light cast by torches
+pub const TORCH_LIGHT_COLOUR: [f32; 3] = [1.0, 0.7, 0.4];
+
+/// The radiant intensity of the light cast by torches
+pub const TORCH_LIGHT_INTENSITY: f32 = 2.5;
@@ -0,0 +1,164 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use sbs5k_core::block::Block;
+use sbs5k_core::chunk::{block_position_within_chunk, ChunkCoordinate, CHUNK_DEPTH, CHUNK_WIDTH};
+use sbs5k_core::fluid::FluidSimulation;
+use sbs5k_core::geometry::{BlockPosition, EntityPosition};
+use sbs5k_core::physics::Aabb;
+use sbs5k_core::world::BlockStorage;
+use sbs5k_core::{light, raycast};
+
+use crate::constants;
+use crate::event;
+use crate::event::Event;
+use crate::state::ChunksState;
+
+/// Breaks and places the blocks that the player is looking at
+pub(crate) struct BlockEditor {
+    player_position: Rc<RefCell<EntityPosition>>,
+    chunks_state: Rc<RefCell<ChunksState>>,
+    fluids: Rc<RefCell<FluidSimulation>>,
+    event_submitter: event::EventSubmitter,
+}
+
+impl BlockEditor {
+    pub(crate) fn new(
+        player_position: Rc<RefCell<EntityPosition>>,
+        chunks_state: Rc<RefCell<ChunksState>>,
+        fluids: Rc<RefCell<FluidSimulation>>,
+        event_submitter: event::EventSubmitter,
+    ) -> Self {
+        BlockEditor {
+            player_position,
+            chunks_state,
+            fluids,
+            event_submitter,
+        }
+    }
+
+    fn break_block(&self) {
+        let mut chunks_state = self.chunks_state.borrow_mut();
+        let hit = raycast::cast_ray_from_entity(
+            &*chunks_state,
+            &self.player_position.borrow(),
+            constants::BLOCK_REACH_DISTANCE,
+        );
+
+        if let Some(hit) = hit {
+            if chunks_state.set_block_at(hit.block, Block::Empty) {
+                let relit = light::update_light_at(&mut *chunks_state, hit.block);
+                self.fluids.borrow_mut().block_changed(hit.block);
+                self.submit_chunk_modified_events(hit.block, relit);
+            }
+        }
+    }
+
+    fn place_block(&self, block: Block) {
+        let mut chunks_state = self.chunks_state.borrow_mut();
+        let player_position = self.player_position.borrow();
+        let hit = raycast::cast_ray_from_entity(
+            &*chunks_state,
+            &player_position,
+            constants::BLOCK_REACH_DISTANCE,
+        );
+
+        if let Some(hit) = hit {
+            // The new block goes against the face that the player is looking at
+            let position = hit.block + hit.face.normal();
+
+            // Don't let the player entomb themselves
+            if Aabb::for_player(player_position.location).overlaps_block(position) {
+                return;
+            }
+
+            if chunks_state.set_block_at(position, block) {
+                let relit = light::update_light_at(&mut *chunks_state, position);
+                self.fluids.borrow_mut().block_changed(position);
+                self.submit_chunk_modified_events(position, relit);
+            }
+        }
+    }
+
+    /// Ask for every chunk touched by an edit at `position` to be remeshed, including the ones
+    /// whose lighting changed as a result
+    fn submit_chunk_modified_events(&self, position: BlockPosition, relit: Vec<ChunkCoordinate>) {
+        let mut affected = chunks_affected_by_edit_at(position);
+        for coordinate in relit {
+            if !affected.contains(&coordinate) {
+                affected.push(coordinate);
+            }
+        }
+
+        for coordinate in affected {
+            self.event_submitter
+                .submit_event(Event::ChunkModified(coordinate));
+        }
+    }
+}
+
+impl event::EventListener for BlockEditor {
+    fn on_event(&mut self, event: &Event) {
+        match event {
+            Event::BreakBlock => self.break_block(),
+            Event::PlaceBlock(block) => self.place_block(*block),
+            _ => (),
+        }
+    }
+}
+
+/// Work out which chunks need remeshing after the block at `position` changes
+///
+/// This is the chunk containing the block, plus any neighbouring chunks that share a face with it.
+pub(crate) fn chunks_affected_by_edit_at(position: BlockPosition) -> Vec<ChunkCoordinate> {
+    let coordinate = ChunkCoordinate::from_block_position(position);
+    let mut affected = vec![coordinate];
+
+    if let Some((x, _, z)) = block_position_within_chunk(position) {
+        if x == 0 {
+            affected.push(ChunkCoordinate {
+                i: coordinate.i - 1,
+                j: coordinate.j,
+            });
+        }
+        if x == CHUNK_WIDTH - 1 {
+            affected.push(ChunkCoordinate {
+                i: coordinate.i + 1,
+                j: coordinate.j,
+            });
+        }
+        if z == 0 {
+            affected.push(ChunkCoordinate {
+                i: coordinate.i,
+                j: coordinate.j - 1,
+            });
+        }
+        if z == CHUNK_DEPTH - 1 {
+            affected.push(ChunkCoordinate {
+                i: coordinate.i,
+                j: coordinate.j + 1,
+            });
+        }
+    }
+
+    affected
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    #[case(BlockPosition::new(5, 64, 5), vec![ChunkCoordinate{i: 0, j: 0}])]
+    #[case(BlockPosition::new(0, 64, 5), vec![ChunkCoordinate{i: 0, j: 0}, ChunkCoordinate{i: -1, j: 0}])]
+    #[case(BlockPosition::new(15, 64, 5), vec![ChunkCoordinate{i: 0, j: 0}, ChunkCoordinate{i: 1, j: 0}])]
+    #[case(BlockPosition::new(5, 64, -16), vec![ChunkCoordinate{i: 0, j: -1}, ChunkCoordinate{i: 0, j: -2}])]
+    #[case(BlockPosition::new(-1, 64, 31), vec![ChunkCoordinate{i: -1, j: 1}, ChunkCoordinate{i: 0, j: 1}, ChunkCoordinate{i: -1, j: 2}])]
+    fn chunks_affected_by_edit_works(
+        #[case] position: BlockPosition,
+        #[case] expected: Vec<ChunkCoordinate>,
+    ) {
+        assert_eq!(expected, chunks_affected_by_edit_at(position));
+    }
+}
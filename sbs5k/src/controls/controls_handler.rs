@@ -1,9 +1,10 @@
 use std::cell::RefCell;
 use std::rc::Rc;
 
+use sbs5k_core::block::Block;
 use sbs5k_core::geometry;
 use sbs5k_engine::events as engine_events;
-use sbs5k_engine::inputs::Key;
+use sbs5k_engine::inputs::{Key, MouseButton};
 
 use crate::constants;
 use crate::controls::movement::{Rotatable, Translatable};
@@ -19,11 +20,40 @@ struct MovementState {
     moving_right: bool,
     moving_up: bool,
     moving_down: bool,
+    jumping: bool,
+}
+
+/// How the player moves around the world
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum MovementMode {
+    /// The player flies freely through the world, ignoring terrain
+    Flying,
+
+    /// The player walks on the terrain, subject to gravity and collisions
+    Walking,
+}
+
+/// The player's movement inputs for one frame while walking
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct WalkInput {
+    /// +1 if moving forwards, -1 if moving backwards, otherwise 0
+    pub forwards: f32,
+
+    /// +1 if moving right, -1 if moving left, otherwise 0
+    pub right: f32,
+
+    /// Whether the player is trying to jump
+    pub jump: bool,
+
+    /// The time step to simulate, in seconds
+    pub dt: f32,
 }
 
 /// The main handler for processing input events
 pub(crate) struct ControlsHandler {
     movement_state: MovementState,
+    movement_mode: MovementMode,
+    selected_block: Block,
     mouse_accumulated_dx: f32,
     mouse_accumulated_dy: f32,
     event_submitter: event::EventSubmitter,
@@ -33,29 +63,43 @@ impl ControlsHandler {
     pub(crate) fn new(event_submitter: event::EventSubmitter) -> Self {
         ControlsHandler {
             movement_state: MovementState::default(),
+            movement_mode: MovementMode::Flying,
+            selected_block: constants::PLACEABLE_BLOCKS[0],
             mouse_accumulated_dx: 0.0,
             mouse_accumulated_dy: 0.0,
             event_submitter,
         }
     }
 
-    pub(crate) fn pump_window_events(
-        &mut self,
-        source: &mut impl engine_events::EventSource,
-        dt: f32,
-    ) {
+    /// Process any pending window events, and turn the player to follow the mouse
+    ///
+    /// This should be called once per frame, so that looking around is as responsive as possible.
+    pub(crate) fn pump_window_events(&mut self, source: &mut impl engine_events::EventSource) {
         for event in source.poll_events() {
             match event {
                 engine_events::WindowEvent::KeyPress(key) => self.on_key_press(key),
                 engine_events::WindowEvent::KeyRelease(key) => self.on_key_release(key),
+                engine_events::WindowEvent::MouseButtonPress(button) => {
+                    self.on_mouse_button_press(button)
+                }
+                engine_events::WindowEvent::MouseButtonRelease(_) => {}
                 engine_events::WindowEvent::MouseMove(dx, dy) => self.on_mouse_move(dx, dy),
             }
         }
 
-        self.emit_motion_event(dt);
         self.emit_rotation_event();
     }
 
+    /// Move the player according to the keys currently held down
+    ///
+    /// This should be called once per simulation tick, with `dt` being the length of a tick.
+    pub(crate) fn emit_movement_events(&self, dt: f32) {
+        match self.movement_mode {
+            MovementMode::Flying => self.emit_motion_event(dt),
+            MovementMode::Walking => self.emit_walk_event(dt),
+        }
+    }
+
     fn on_key_press(&mut self, key: Key) {
         match key {
             Key::Escape => self.event_submitter.submit_event(Event::EndGame),
@@ -65,6 +109,18 @@ impl ControlsHandler {
             Key::D => self.movement_state.moving_right = true,
             Key::R | Key::LeftShift => self.movement_state.moving_up = true,
             Key::F | Key::LeftCtrl => self.movement_state.moving_down = true,
+            Key::Space => self.movement_state.jumping = true,
+            Key::G => self.toggle_movement_mode(),
+            Key::T => self
+                .event_submitter
+                .submit_event(Event::SkipToNextPhaseOfDay),
+            Key::Num1 => self.select_block(0),
+            Key::Num2 => self.select_block(1),
+            Key::Num3 => self.select_block(2),
+            Key::Num4 => self.select_block(3),
+            Key::Num5 => self.select_block(4),
+            Key::Num6 => self.select_block(5),
+            Key::Num7 => self.select_block(6),
             _ => {}
         }
     }
@@ -77,6 +133,23 @@ impl ControlsHandler {
             Key::D => self.movement_state.moving_right = false,
             Key::R | Key::LeftShift => self.movement_state.moving_up = false,
             Key::F | Key::LeftCtrl => self.movement_state.moving_down = false,
+            Key::Space => self.movement_state.jumping = false,
+            _ => {}
+        }
+    }
+
+    fn select_block(&mut self, index: usize) {
+        if let Some(block) = constants::PLACEABLE_BLOCKS.get(index) {
+            self.selected_block = *block;
+        }
+    }
+
+    fn on_mouse_button_press(&mut self, button: MouseButton) {
+        match button {
+            MouseButton::Left => self.event_submitter.submit_event(Event::BreakBlock),
+            MouseButton::Right => self
+                .event_submitter
+                .submit_event(Event::PlaceBlock(self.selected_block)),
             _ => {}
         }
     }
@@ -120,6 +193,31 @@ impl ControlsHandler {
         }
     }
 
+    fn toggle_movement_mode(&mut self) {
+        self.movement_mode = match self.movement_mode {
+            MovementMode::Flying => MovementMode::Walking,
+            MovementMode::Walking => MovementMode::Flying,
+        };
+        self.event_submitter
+            .submit_event(Event::MovementModeChanged(self.movement_mode));
+    }
+
+    fn emit_walk_event(&self, dt: f32) {
+        let state = &self.movement_state;
+        let forwards = axis_input(state.moving_forwards, state.moving_backwards);
+        let right = axis_input(state.moving_right, state.moving_left);
+
+        // Gravity still applies when the player isn't pressing anything, so this is sent on every
+        // tick
+        self.event_submitter
+            .submit_event(Event::WalkPlayer(WalkInput {
+                forwards,
+                right,
+                jump: state.jumping,
+                dt,
+            }));
+    }
+
     fn emit_rotation_event(&mut self) {
         let delta = geometry::OrientationDelta {
             delta_pitch: self.mouse_accumulated_dy * constants::TURN_SENSITIVITY,
@@ -136,6 +234,16 @@ impl ControlsHandler {
     }
 }
 
+/// Combine a pair of opposing inputs into a value of -1, 0 or +1
+#[inline]
+fn axis_input(positive: bool, negative: bool) -> f32 {
+    match (positive, negative) {
+        (true, false) => 1.0,
+        (false, true) => -1.0,
+        _ => 0.0,
+    }
+}
+
 pub(crate) struct MovementApplier<T>
 where
     T: Translatable + Rotatable,
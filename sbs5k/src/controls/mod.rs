@@ -1,4 +1,8 @@
+mod block_editing;
 mod controls_handler;
 pub(crate) mod movement;
+mod physics;
 
-pub(crate) use controls_handler::{ControlsHandler, MovementApplier};
+pub(crate) use block_editing::{chunks_affected_by_edit_at, BlockEditor};
+pub(crate) use controls_handler::{ControlsHandler, MovementApplier, MovementMode, WalkInput};
+pub(crate) use physics::PhysicsApplier;
@@ -3,7 +3,7 @@ use std::f32::consts::PI;
 use glm::{cos, fmod, sin};
 use nalgebra::Vector3;
 
-use sbs5k_core::geometry;
+use sbs5k_core::geometry::{self, LocationDelta};
 
 /// An object that can be translated relative to its current orientation
 pub trait Translatable {
@@ -26,21 +26,23 @@ pub trait Rotatable {
     fn adjust_yaw(&mut self, angle: f32);
 }
 
+/// The horizontal unit vector pointing forwards for something facing `yaw`
+pub(crate) fn forwards_direction(yaw: f32) -> LocationDelta {
+    Vector3::new(-sin(yaw), 0.0, cos(yaw))
+}
+
+/// The horizontal unit vector pointing to the right for something facing `yaw`
+pub(crate) fn right_direction(yaw: f32) -> LocationDelta {
+    Vector3::new(-cos(yaw), 0.0, -sin(yaw))
+}
+
 impl Translatable for geometry::EntityPosition {
     fn translate_forwards(&mut self, distance: f32) {
-        let x = -sin(self.orientation.yaw);
-        let y = 0.0;
-        let z = cos(self.orientation.yaw);
-        let direction = Vector3::new(x, y, z);
-        self.location += direction * distance;
+        self.location += forwards_direction(self.orientation.yaw) * distance;
     }
 
     fn translate_right(&mut self, distance: f32) {
-        let x = -cos(self.orientation.yaw);
-        let y = 0.0;
-        let z = -sin(self.orientation.yaw);
-        let direction = Vector3::new(x, y, z);
-        self.location += direction * distance;
+        self.location += right_direction(self.orientation.yaw) * distance;
     }
 
     fn translate_up(&mut self, distance: f32) {
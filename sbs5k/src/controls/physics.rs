@@ -0,0 +1,65 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use sbs5k_core::geometry::{EntityPosition, LocationDelta};
+use sbs5k_core::physics::PlayerBody;
+
+use crate::constants;
+use crate::controls::movement::{forwards_direction, right_direction};
+use crate::controls::{MovementMode, WalkInput};
+use crate::event;
+use crate::event::Event;
+use crate::state::ChunksState;
+
+/// Moves the player around the world while they are walking
+pub(crate) struct PhysicsApplier {
+    body: PlayerBody,
+    player_position: Rc<RefCell<EntityPosition>>,
+    chunks_state: Rc<RefCell<ChunksState>>,
+}
+
+impl PhysicsApplier {
+    pub(crate) fn new(
+        player_position: Rc<RefCell<EntityPosition>>,
+        chunks_state: Rc<RefCell<ChunksState>>,
+    ) -> Self {
+        PhysicsApplier {
+            body: PlayerBody::default(),
+            player_position,
+            chunks_state,
+        }
+    }
+
+    fn walk(&mut self, input: &WalkInput) {
+        let mut player_position = self.player_position.borrow_mut();
+
+        let yaw = player_position.orientation.yaw;
+        let forwards = forwards_direction(yaw);
+        let right = right_direction(yaw);
+        let mut walk_velocity = forwards * input.forwards + right * input.right;
+        if walk_velocity != LocationDelta::zeros() {
+            walk_velocity = walk_velocity.normalize() * constants::WALK_SPEED;
+        }
+
+        self.body.step(
+            &*self.chunks_state.borrow(),
+            &mut player_position.location,
+            walk_velocity,
+            input.jump,
+            input.dt,
+        );
+    }
+}
+
+impl event::EventListener for PhysicsApplier {
+    fn on_event(&mut self, event: &Event) {
+        match event {
+            Event::WalkPlayer(input) => self.walk(input),
+            Event::MovementModeChanged(MovementMode::Walking) => {
+                // Don't carry over any momentum from a previous spell of walking
+                self.body = PlayerBody::default()
+            }
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use nalgebra::{Vector2, Vector3};
+
+use sbs5k_core::clock::{WorldClock, NOON, SUNRISE, SUNSET};
+use sbs5k_engine::lighting::GlobalLight;
+use sbs5k_engine::SkyParameters;
+
+/// How the sky and sunlight look at one moment of the day
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Keyframe {
+    time: f32,
+    zenith_colour: Vector3<f32>,
+    horizon_colour: Vector3<f32>,
+    light_colour: Vector3<f32>,
+    light_intensity: f32,
+    ambient_intensity: f32,
+    sun_colour: Vector3<f32>,
+    star_visibility: f32,
+    cloud_colour: Vector3<f32>,
+}
+
+const NIGHT: Keyframe = Keyframe {
+    time: 0.0,
+    zenith_colour: Vector3::new(0.01, 0.01, 0.04),
+    horizon_colour: Vector3::new(0.03, 0.04, 0.08),
+    light_colour: Vector3::new(0.6, 0.7, 1.0),
+    light_intensity: 0.08,
+    ambient_intensity: 0.15,
+    sun_colour: Vector3::new(1.0, 0.5, 0.25),
+    star_visibility: 1.0,
+    cloud_colour: Vector3::new(0.08, 0.09, 0.12),
+};
+
+const TWILIGHT: Keyframe = Keyframe {
+    time: SUNRISE,
+    zenith_colour: Vector3::new(0.25, 0.25, 0.55),
+    horizon_colour: Vector3::new(0.95, 0.55, 0.3),
+    light_colour: Vector3::new(1.0, 0.6, 0.35),
+    light_intensity: 0.15,
+    ambient_intensity: 0.5,
+    sun_colour: Vector3::new(1.0, 0.6, 0.3),
+    star_visibility: 0.0,
+    cloud_colour: Vector3::new(1.0, 0.75, 0.6),
+};
+
+const DAY: Keyframe = Keyframe {
+    time: NOON,
+    zenith_colour: Vector3::new(28.0 / 255.0, 17.0 / 255.0, 188.0 / 255.0),
+    horizon_colour: Vector3::new(168.0 / 255.0, 226.0 / 255.0, 231.0 / 255.0),
+    light_colour: Vector3::new(1.0, 1.0, 1.0),
+    light_intensity: 0.5,
+    ambient_intensity: 1.0,
+    sun_colour: Vector3::new(1.0, 0.95, 0.8),
+    star_visibility: 0.0,
+    cloud_colour: Vector3::new(1.0, 1.0, 1.0),
+};
+
+/// How long dawn and dusk take to fade between night and day, as a fraction of a day
+const TWILIGHT_DURATION: f32 = 0.05;
+
+/// The appearance of the day at each key moment, in order of time
+///
+/// The appearance at any other time is found by blending between the two keyframes either side of
+/// it.
+const KEYFRAMES: [Keyframe; 8] = [
+    NIGHT,
+    Keyframe {
+        time: SUNRISE - TWILIGHT_DURATION,
+        ..NIGHT
+    },
+    TWILIGHT,
+    Keyframe {
+        time: SUNRISE + TWILIGHT_DURATION,
+        ..DAY
+    },
+    Keyframe {
+        time: SUNSET - TWILIGHT_DURATION,
+        ..DAY
+    },
+    Keyframe {
+        time: SUNSET,
+        ..TWILIGHT
+    },
+    Keyframe {
+        time: SUNSET + TWILIGHT_DURATION,
+        ..NIGHT
+    },
+    Keyframe { time: 1.0, ..NIGHT },
+];
+
+impl Keyframe {
+    fn lerp(&self, other: &Keyframe, t: f32) -> Keyframe {
+        Keyframe {
+            time: self.time + (other.time - self.time) * t,
+            zenith_colour: self.zenith_colour.lerp(&other.zenith_colour, t),
+            horizon_colour: self.horizon_colour.lerp(&other.horizon_colour, t),
+            light_colour: self.light_colour.lerp(&other.light_colour, t),
+            light_intensity: self.light_intensity
+                + (other.light_intensity - self.light_intensity) * t,
+            ambient_intensity: self.ambient_intensity
+                + (other.ambient_intensity - self.ambient_intensity) * t,
+            sun_colour: self.sun_colour.lerp(&other.sun_colour, t),
+            star_visibility: self.star_visibility
+                + (other.star_visibility - self.star_visibility) * t,
+            cloud_colour: self.cloud_colour.lerp(&other.cloud_colour, t),
+        }
+    }
+}
+
+/// Work out how the day looks at `time_of_day`
+fn keyframe_at(time_of_day: f32) -> Keyframe {
+    let next_index = KEYFRAMES
+        .iter()
+        .position(|keyframe| keyframe.time > time_of_day)
+        .unwrap_or(KEYFRAMES.len() - 1)
+        .max(1);
+    let previous = &KEYFRAMES[next_index - 1];
+    let next = &KEYFRAMES[next_index];
+
+    let t = (time_of_day - previous.time) / (next.time - previous.time);
+    previous.lerp(next, t.clamp(0.0, 1.0))
+}
+
+/// Get the light shining on the world at the clock's current time
+///
+/// This comes from the sun during the day, and from the moon opposite it at night.
+pub(crate) fn global_light(clock: &WorldClock) -> GlobalLight {
+    let keyframe = keyframe_at(clock.time_of_day());
+    let sun_direction = clock.sun_direction();
+    let direction = if sun_direction.y >= 0.0 {
+        sun_direction
+    } else {
+        -sun_direction
+    };
+
+    GlobalLight {
+        direction,
+        colour: keyframe.light_colour,
+        intensity: keyframe.light_intensity,
+        ambient_intensity: keyframe.ambient_intensity,
+    }
+}
+
+/// Get the appearance of the sky at the clock's current time, with the clouds drifted by
+/// `cloud_offset`
+pub(crate) fn sky_parameters(clock: &WorldClock, cloud_offset: Vector2<f32>) -> SkyParameters {
+    let keyframe = keyframe_at(clock.time_of_day());
+    SkyParameters {
+        zenith_colour: keyframe.zenith_colour,
+        horizon_colour: keyframe.horizon_colour,
+        sun_direction: clock.sun_direction(),
+        sun_colour: keyframe.sun_colour,
+        star_visibility: keyframe.star_visibility,
+        cloud_colour: keyframe.cloud_colour,
+        cloud_offset,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use sbs5k_core::clock::MIDNIGHT;
+
+    #[rstest]
+    fn keyframes_are_in_order() {
+        assert!(KEYFRAMES.windows(2).all(|pair| pair[0].time < pair[1].time));
+        assert_eq!(0.0, KEYFRAMES[0].time);
+        assert_eq!(1.0, KEYFRAMES[KEYFRAMES.len() - 1].time);
+    }
+
+    #[rstest]
+    #[case(MIDNIGHT, NIGHT)]
+    #[case(SUNRISE, TWILIGHT)]
+    #[case(NOON, DAY)]
+    #[case(SUNSET, TWILIGHT)]
+    #[case(0.9, NIGHT)]
+    fn key_moments_match_their_keyframes(#[case] time: f32, #[case] expected: Keyframe) {
+        let actual = keyframe_at(time);
+        assert_eq!(expected.zenith_colour, actual.zenith_colour);
+        assert_eq!(expected.light_intensity, actual.light_intensity);
+        assert_eq!(expected.ambient_intensity, actual.ambient_intensity);
+    }
+
+    #[rstest]
+    fn dawn_blends_between_night_and_twilight() {
+        let actual = keyframe_at(SUNRISE - TWILIGHT_DURATION / 2.0);
+        let expected = (NIGHT.light_intensity + TWILIGHT.light_intensity) / 2.0;
+        assert!((expected - actual.light_intensity).abs() < 1e-5);
+    }
+
+    #[rstest]
+    #[case(MIDNIGHT, 1.0)]
+    #[case(SUNRISE, 0.0)]
+    #[case(NOON, 0.0)]
+    #[case(SUNSET + TWILIGHT_DURATION / 2.0, 0.5)]
+    fn stars_only_come_out_at_night(#[case] time: f32, #[case] expected: f32) {
+        let sky = sky_parameters(&WorldClock::new(time, 1200.0), Vector2::zeros());
+        assert!((expected - sky.star_visibility).abs() < 1e-5);
+    }
+
+    #[rstest]
+    #[case(NOON)]
+    #[case(MIDNIGHT)]
+    #[case(0.1)]
+    #[case(0.7)]
+    fn light_always_comes_from_above(#[case] time: f32) {
+        let light = global_light(&WorldClock::new(time, 1200.0));
+        assert!(light.direction.y >= 0.0);
+        assert!((light.direction.norm() - 1.0).abs() < 1e-5);
+    }
+}
@@ -1,9 +1,18 @@
+use sbs5k_engine::TimeTracker;
+
 use crate::args::Args;
 use crate::state::ClientState;
 
-pub(crate) fn print_debug_output(state: &ClientState, dt: f64, config: &Args) {
+pub(crate) fn print_debug_output(state: &ClientState, time_tracker: &TimeTracker, config: &Args) {
     if config.debug_print_fps {
-        println!("FPS: {}", 1.0 / dt);
+        let stats = time_tracker.frame_stats();
+        println!(
+            "FPS: {:.0}  AVG: {:.2}ms  P99: {:.2}ms  1% LOW: {:.0}",
+            1.0 / time_tracker.dt(),
+            stats.average_frame_time().unwrap_or(0.0) * 1000.0,
+            stats.p99_frame_time().unwrap_or(0.0) * 1000.0,
+            stats.one_percent_low_fps().unwrap_or(0.0),
+        );
     }
     if config.debug_print_player_position {
         let player_pos = state.player_position.borrow();
@@ -2,16 +2,25 @@ use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 use std::sync::Arc;
 
-use sbs5k_core::{chunk, geometry};
+use nalgebra::Vector2;
+
+use sbs5k_core::clock::WorldClock;
+use sbs5k_core::fluid::FluidSimulation;
+use sbs5k_core::world::BlockLookup;
+use sbs5k_core::{chunk, geometry, light};
 use sbs5k_engine as engine;
 
 use crate::args;
+use crate::constants;
 use crate::controls;
+use crate::daylight;
 use crate::debug;
 use crate::event;
 use crate::event::Event;
+use crate::hot_reload;
 use crate::initialisation;
 use crate::loading;
+use crate::resources;
 use crate::state;
 
 const TITLE: &str = "Super Block Simulator 5000";
@@ -23,8 +32,10 @@ pub(crate) struct Driver {
     running: Rc<Cell<bool>>,
     config: Arc<args::Args>,
     controls: Rc<RefCell<controls::ControlsHandler>>,
-    skybox: engine::Skybox,
     renderer: engine::Renderer,
+    resource_packs: resources::ResourcePacks,
+    resource_watcher: Option<hot_reload::ResourceWatcher>,
+    mesh_generator: Rc<RefCell<loading::MeshGenerator>>,
     scene_lighting: engine::lighting::SceneLighting,
     fog_parameters: engine::FogParameters,
     event_queue: event::EventQueue,
@@ -38,8 +49,9 @@ impl Driver {
     pub(crate) fn new(
         config: Arc<args::Args>,
         chunk_source: Box<dyn chunk::ChunkSource + Send>,
+        spawn_point: geometry::Location,
     ) -> Self {
-        let state = Box::new(state::ClientState::new(&config));
+        let state = Box::new(state::ClientState::new(&config, spawn_point));
 
         // We have two "running" flags, one in `state` and the other here. `state`'s flag is
         // intended for communicating to background threads (e.g. the chunk loading worker thread)
@@ -48,51 +60,100 @@ impl Driver {
         // on every tick.
         let running_flag = Rc::new(Cell::new(true));
 
-        let window = engine::Window::new(INITIAL_WIDTH, INITIAL_HEIGHT, TITLE);
+        let mut window = engine::Window::new(INITIAL_WIDTH, INITIAL_HEIGHT, TITLE);
+        window.set_vsync(config.vsync);
+
+        let mut time_tracker = engine::TimeTracker::new(constants::SIMULATION_TICK_RATE);
+        time_tracker.set_target_fps(config.max_fps.map(|fps| fps as f32));
 
         let fog_parameters = initialisation::make_fog_parameters(&config);
 
+        // In development mode, the source tree acts as a resource pack underneath any others
+        let mut pack_dirs = vec![];
+        if config.dev {
+            pack_dirs.push(resources::SOURCE_TREE.into());
+        }
+        pack_dirs.extend(config.resource_packs.iter().cloned());
+        let resource_packs = resources::ResourcePacks::new(&pack_dirs);
+        let resource_watcher = config
+            .dev
+            .then(|| hot_reload::ResourceWatcher::new(&resource_packs));
+        let mut renderer = engine::Renderer::with_shader_sources(&resource_packs);
+        renderer.set_shadow_settings(initialisation::make_shadow_settings(&config));
+        renderer.set_skybox(initialisation::make_skybox(&config));
+
         let mut event_queue = event::EventQueue::new(state.is_live.clone());
         let event_submitter = event_queue.get_submitter();
 
         let movement_applier = Rc::new(RefCell::new(controls::MovementApplier::new(
             state.player_position.clone(),
         )));
+        let physics_applier = Rc::new(RefCell::new(controls::PhysicsApplier::new(
+            state.player_position.clone(),
+            state.chunks_state.clone(),
+        )));
         let stopper = Rc::new(RefCell::new(Stopper {
             flag: running_flag.clone(),
         }));
+        let time_skipper = Rc::new(RefCell::new(TimeSkipper {
+            clock: state.world_clock.clone(),
+        }));
+        let fluid_resumer = Rc::new(RefCell::new(FluidResumer {
+            fluids: state.fluids.clone(),
+        }));
         let chunk_loader = Rc::new(RefCell::new(loading::ChunkLoader::new(
             chunk_source,
+            chunk::ChunkCoordinate::from_player_position(spawn_point),
             event_queue.get_submitter(),
             config.clone(),
             state.is_live.clone(),
         )));
+        let mesh_generator = Rc::new(RefCell::new(loading::MeshGenerator::new(
+            loading::BlockTextures::load(&resource_packs),
+            config.anisotropic_filtering,
+        )));
         let chunk_mesh_builder = Rc::new(RefCell::new(ChunkMeshCreator {
-            mesh_generator: loading::MeshGenerator::new(),
+            mesh_generator: mesh_generator.clone(),
             chunks_state: state.chunks_state.clone(),
+            event_submitter: event_queue.get_submitter(),
         }));
 
+        let block_editor = Rc::new(RefCell::new(controls::BlockEditor::new(
+            state.player_position.clone(),
+            state.chunks_state.clone(),
+            state.fluids.clone(),
+            event_queue.get_submitter(),
+        )));
+
         event_queue.add_listener(movement_applier);
+        event_queue.add_listener(physics_applier);
         event_queue.add_listener(stopper);
+        event_queue.add_listener(time_skipper);
+        event_queue.add_listener(fluid_resumer);
         event_queue.add_listener(chunk_loader);
         event_queue.add_listener(chunk_mesh_builder);
+        event_queue.add_listener(block_editor);
 
         let controls = Rc::new(RefCell::new(controls::ControlsHandler::new(
             event_queue.get_submitter(),
         )));
 
+        let scene_lighting = initialisation::make_scene_lighting(&state.world_clock.borrow());
+
         Driver {
             running: running_flag,
             config,
             controls,
-            skybox: engine::Skybox::new(),
-            renderer: engine::Renderer::new(),
-            scene_lighting: initialisation::make_scene_lighting(),
+            renderer,
+            resource_packs,
+            resource_watcher,
+            mesh_generator,
+            scene_lighting,
             fog_parameters,
             event_queue,
             event_submitter,
             state,
-            time_tracker: engine::TimeTracker::new(),
+            time_tracker,
             window,
         }
     }
@@ -116,19 +177,33 @@ impl Driver {
             self.time_tracker.tick();
 
             self.handle_inputs();
+            self.reload_changed_resources();
 
-            // Then perform the main event queue dispatch, which may include motion events triggered by the above
+            // Then perform the main event queue dispatch, which may include rotation events
+            // triggered by the above
             self.event_queue.dispatch_all_events();
 
+            // Step the simulation forwards by however many ticks have become due since the last
+            // frame
+            for _ in 0..self.time_tracker.ticks_due() {
+                self.run_simulation_tick();
+            }
+
             let (player_current_location, player_current_orientation) = {
                 let player_pos = self.state.player_position.borrow();
                 (player_pos.location, player_pos.orientation)
             };
 
-            self.render(player_current_location, player_current_orientation);
+            // The simulation usually won't line up exactly with the frame being drawn, so draw the
+            // player part way between where they were at the last two ticks
+            let previous_location = self.state.previous_player_location;
+            let render_location = previous_location
+                + (player_current_location - previous_location) * self.time_tracker.alpha();
+
+            self.render(render_location, player_current_orientation);
 
             if self.config.is_in_debug_mode() {
-                debug::print_debug_output(&self.state, self.time_tracker.dt(), &self.config);
+                debug::print_debug_output(&self.state, &self.time_tracker, &self.config);
             }
 
             let current_player_chunk =
@@ -138,6 +213,9 @@ impl Driver {
                     .submit_event(Event::PlayerEnteredNewChunk(current_player_chunk));
             }
             prev_player_chunk = current_player_chunk;
+
+            // Hold off on starting the next frame if we're running faster than the frame rate cap
+            self.time_tracker.wait_for_next_frame();
         }
 
         // There are two possible states here: either the worker thread might be in the middle of a
@@ -151,7 +229,81 @@ impl Driver {
     fn handle_inputs(&mut self) {
         self.controls
             .borrow_mut()
-            .pump_window_events(&mut self.window, self.time_tracker.dt() as f32);
+            .pump_window_events(&mut self.window);
+    }
+
+    /// In development mode, reload any shaders and textures that have been edited since they were
+    /// last loaded, keeping the old ones if the new ones have mistakes in them
+    fn reload_changed_resources(&mut self) {
+        let changes = match &mut self.resource_watcher {
+            Some(watcher) => watcher.poll(),
+            None => return,
+        };
+
+        if changes.shaders {
+            match self.renderer.reload_shaders(&self.resource_packs) {
+                Ok(()) => println!("Reloaded shaders"),
+                Err(err) => println!("Keeping the previous shaders:\n{}", err),
+            }
+        }
+
+        if changes.textures {
+            match loading::BlockTextures::try_load(&self.resource_packs) {
+                Ok(block_textures) => {
+                    let layout_changed = self
+                        .mesh_generator
+                        .borrow_mut()
+                        .set_block_textures(block_textures);
+
+                    // Faces may now use different layers, so rebuild the chunks' meshes. Distant
+                    // low-detail tiles pick up the new layout as they're next loaded.
+                    if layout_changed {
+                        for coordinate in
+                            self.state.chunks_state.borrow().loaded_chunk_coordinates()
+                        {
+                            self.event_submitter
+                                .submit_event(Event::ChunkModified(coordinate));
+                        }
+                    }
+                    println!("Reloaded block textures");
+                }
+                Err(err) => println!("Keeping the previous block textures: {}", err),
+            }
+        }
+    }
+
+    fn run_simulation_tick(&mut self) {
+        self.state.previous_player_location = self.state.player_position.borrow().location;
+
+        let dt = self.time_tracker.tick_dt() as f32;
+        self.state.world_clock.borrow_mut().advance(dt);
+        self.controls.borrow().emit_movement_events(dt);
+        self.run_fluid_tick();
+        self.event_queue.dispatch_all_events();
+    }
+
+    /// Let any disturbed fluids flow, and remesh the chunks they flowed through
+    ///
+    /// Fluid is neither opaque nor emissive, so nothing needs relighting when it flows.
+    fn run_fluid_tick(&mut self) {
+        let changed = self
+            .state
+            .fluids
+            .borrow_mut()
+            .tick(&mut *self.state.chunks_state.borrow_mut());
+
+        let mut affected = vec![];
+        for position in changed {
+            for coordinate in controls::chunks_affected_by_edit_at(position) {
+                if !affected.contains(&coordinate) {
+                    affected.push(coordinate);
+                }
+            }
+        }
+        for coordinate in affected {
+            self.event_submitter
+                .submit_event(Event::ChunkModified(coordinate));
+        }
     }
 
     fn render(
@@ -159,24 +311,68 @@ impl Driver {
         player_location: geometry::Location,
         player_orientation: geometry::Orientation,
     ) {
-        let chunks_state = self.state.chunks_state.borrow_mut();
+        let mut chunks_state = self.state.chunks_state.borrow_mut();
+        let player_chunk = chunk::ChunkCoordinate::from_player_position(player_location);
+
+        // The sun, moon and sky all follow the time of day, while the clouds drift along steadily
+        // with the simulation
+        let elapsed = self.time_tracker.tick_count() as f32 * self.time_tracker.tick_dt() as f32;
+        let cloud_offset = Vector2::from(constants::CLOUD_VELOCITY) * elapsed;
+        let (global_light, sky) = {
+            let clock = self.state.world_clock.borrow();
+            (
+                daylight::global_light(&clock),
+                daylight::sky_parameters(&clock, cloud_offset),
+            )
+        };
+        self.scene_lighting.global_light = global_light;
+
+        // Light the scene with the torches closest to the player
+        self.scene_lighting.point_lights = chunks_state
+            .nearest_light_sources(player_location, constants::MAX_VISIBLE_TORCH_LIGHTS)
+            .into_iter()
+            .map(initialisation::make_torch_light)
+            .collect();
+
+        chunks_state.sort_translucent_faces(player_location);
+        let mut objects = chunks_state.renderable_chunks();
+        objects.extend(chunks_state.renderable_lod_tiles(player_chunk));
+        let translucent_objects =
+            chunks_state.renderable_translucent_chunks(player_chunk, player_location);
+        // Swap the usual fog for a murky one while the camera is underwater
+        let eye_block = player_location.map(|c| c.floor() as i32);
+        let underwater_fog;
+        let fog_parameters = if chunks_state
+            .get_block_at(eye_block)
+            .is_some_and(|block| block.is_fluid())
+        {
+            underwater_fog = engine::FogParameters::underwater();
+            &underwater_fog
+        } else {
+            &self.fog_parameters
+        };
+
         let camera_pos = engine::CameraPosition {
             position: player_location,
             yaw: player_orientation.yaw,
             pitch: player_orientation.pitch,
         };
+        let scene = engine::Scene {
+            lighting: &self.scene_lighting,
+            camera: &camera_pos,
+            fog: fog_parameters,
+            sky: &sky,
+        };
         self.renderer
-            .do_render_pass(&mut self.window, &|render_target| {
-                // Render each chunk
-                render_target.render_objects(
-                    &chunks_state.renderable_chunks(),
-                    &self.scene_lighting,
-                    &camera_pos,
-                    &self.fog_parameters,
-                );
+            .do_render_pass(&mut self.window, &scene, &|render_target| {
+                // Render each chunk, followed by the low-detail terrain beyond them
+                render_target.render_objects(&objects);
 
                 // Render the skybox
-                render_target.render_skybox(&self.skybox, &camera_pos);
+                render_target.render_skybox();
+
+                // Blend anything see-through over the top, once everything behind it is drawn
+                render_target.render_translucent_objects(&translucent_objects);
             });
     }
 }
@@ -193,20 +389,82 @@ impl event::EventListener for Stopper {
     }
 }
 
+/// Jumps the world's clock forwards when the player asks to skip ahead
+struct TimeSkipper {
+    clock: Rc<RefCell<WorldClock>>,
+}
+
+impl event::EventListener for TimeSkipper {
+    fn on_event(&mut self, event: &Event) {
+        if let Event::SkipToNextPhaseOfDay = event {
+            self.clock.borrow_mut().skip_to_next_phase();
+        }
+    }
+}
+
+/// Lets fluid flow into chunks that weren't loaded when it reached them, once they are
+struct FluidResumer {
+    fluids: Rc<RefCell<FluidSimulation>>,
+}
+
+impl event::EventListener for FluidResumer {
+    fn on_event(&mut self, event: &Event) {
+        if let Event::ChunkLoaded(result) = event {
+            self.fluids.borrow_mut().chunk_loaded(result.coordinate);
+        }
+    }
+}
+
 struct ChunkMeshCreator {
-    mesh_generator: loading::MeshGenerator,
+    mesh_generator: Rc<RefCell<loading::MeshGenerator>>,
     chunks_state: Rc<RefCell<state::ChunksState>>,
+    event_submitter: event::EventSubmitter,
 }
 
 impl event::EventListener for ChunkMeshCreator {
     fn on_event(&mut self, event: &Event) {
-        if let Event::ChunkLoaded(result) = event {
-            let mut chunks_state = self.chunks_state.borrow_mut();
-            let mesh = self
-                .mesh_generator
-                .chunk_to_scene_object(result.chunk.as_ref(), result.coordinate);
-            chunks_state.set_chunk(result.coordinate, Some(*result.chunk));
-            chunks_state.set_chunk_mesh(result.coordinate, Some(mesh));
+        match event {
+            Event::ChunkLoaded(result) => {
+                let mut chunks_state = self.chunks_state.borrow_mut();
+                chunks_state.set_chunk(
+                    result.coordinate,
+                    Some((*result.chunk, result.light.clone())),
+                );
+
+                // Light spilling over the borders may brighten the neighbouring chunks, and the
+                // new chunk hides the faces along their borders with it, so they all need
+                // remeshing
+                light::stitch_chunk(&mut *chunks_state, result.coordinate);
+                for coordinate in result.coordinate.adjacent() {
+                    if chunks_state.get_chunk(coordinate).is_some() {
+                        self.event_submitter
+                            .submit_event(Event::ChunkModified(coordinate));
+                    }
+                }
+
+                let mesh = self.mesh_generator.borrow().chunk_to_meshes(
+                    result.chunk.as_ref(),
+                    result.coordinate,
+                    &*chunks_state,
+                );
+                chunks_state.set_chunk_mesh(result.coordinate, Some(mesh));
+            }
+            Event::ChunkModified(coordinate) => {
+                let mut chunks_state = self.chunks_state.borrow_mut();
+                let mesh = chunks_state.get_chunk(*coordinate).map(|chunk| {
+                    self.mesh_generator
+                        .borrow()
+                        .chunk_to_meshes(chunk, *coordinate, &*chunks_state)
+                });
+                if let Some(mesh) = mesh {
+                    chunks_state.set_chunk_mesh(*coordinate, Some(mesh));
+                }
+            }
+            Event::LodTileLoaded(tile) => {
+                let meshes = self.mesh_generator.borrow().lod_tile_to_meshes(tile);
+                self.chunks_state.borrow_mut().set_lod_meshes(meshes);
+            }
+            _ => (),
         }
     }
 }
@@ -4,19 +4,29 @@ use std::sync::{mpsc, Arc, RwLock};
 
 use nalgebra as na;
 
-use sbs5k_core::{chunk, geometry};
+use sbs5k_core::{block, chunk, geometry, lod};
 
+use crate::controls;
 use crate::loading;
 
 pub(crate) enum Event {
     EndGame,
 
     ChunkLoaded(loading::ChunkLoadResult),
+    LodTileLoaded(lod::LodTile),
 
     TranslatePlayer(na::Vector3<f32>),
+    WalkPlayer(controls::WalkInput),
+    MovementModeChanged(controls::MovementMode),
     RotatePlayer(geometry::OrientationDelta),
 
     PlayerEnteredNewChunk(chunk::ChunkCoordinate),
+
+    BreakBlock,
+    PlaceBlock(block::Block),
+    ChunkModified(chunk::ChunkCoordinate),
+
+    SkipToNextPhaseOfDay,
     // TODO: MoveOtherPlayer, RotateOtherPlayer once we have multiplayer
 }
 
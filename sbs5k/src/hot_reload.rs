@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::resources::ResourcePacks;
+
+/// How long to wait between checks for edited files
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The kinds of resources that have changed since they were last loaded
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct ResourceChanges {
+    pub(crate) shaders: bool,
+    pub(crate) textures: bool,
+}
+
+/// The last modified time of every file under the packs' `shaders` and `textures` directories,
+/// keyed by the index of the pack and the file's path within it
+type Snapshot = HashMap<(usize, PathBuf), SystemTime>;
+
+/// Watches the files in the resource packs for edits, so that they can be reloaded while the game
+/// is running
+///
+/// Files are watched by comparing their modification times at regular intervals.
+pub(crate) struct ResourceWatcher {
+    pack_dirs: Vec<PathBuf>,
+    snapshot: Snapshot,
+    last_poll: Instant,
+}
+
+impl ResourceWatcher {
+    pub(crate) fn new(packs: &ResourcePacks) -> Self {
+        let pack_dirs = packs.dirs().to_vec();
+        let snapshot = take_snapshot(&pack_dirs);
+        ResourceWatcher {
+            pack_dirs,
+            snapshot,
+            last_poll: Instant::now(),
+        }
+    }
+
+    /// Find out which kinds of resource have been added, edited or removed since the last poll
+    ///
+    /// This is cheap to call every frame, as the files are only checked every `POLL_INTERVAL`.
+    pub(crate) fn poll(&mut self) -> ResourceChanges {
+        if self.last_poll.elapsed() < POLL_INTERVAL {
+            return ResourceChanges::default();
+        }
+        self.last_poll = Instant::now();
+
+        let snapshot = take_snapshot(&self.pack_dirs);
+        let changes = compare_snapshots(&self.snapshot, &snapshot);
+        self.snapshot = snapshot;
+        changes
+    }
+}
+
+fn take_snapshot(pack_dirs: &[PathBuf]) -> Snapshot {
+    let mut snapshot = HashMap::new();
+    for (pack, dir) in pack_dirs.iter().enumerate() {
+        for subdir in ["shaders", "textures"] {
+            record_modified_times(dir, Path::new(subdir), pack, &mut snapshot);
+        }
+    }
+    snapshot
+}
+
+/// Record the modified time of every file under `dir/relative`, skipping any that can't be read
+fn record_modified_times(dir: &Path, relative: &Path, pack: usize, snapshot: &mut Snapshot) {
+    let entries = match fs::read_dir(dir.join(relative)) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.flatten() {
+        let path = relative.join(entry.file_name());
+        let metadata = match entry.metadata() {
+            Ok(metadata) => metadata,
+            Err(_) => continue,
+        };
+
+        if metadata.is_dir() {
+            record_modified_times(dir, &path, pack, snapshot);
+        } else if let Ok(modified) = metadata.modified() {
+            snapshot.insert((pack, path), modified);
+        }
+    }
+}
+
+fn compare_snapshots(before: &Snapshot, after: &Snapshot) -> ResourceChanges {
+    let mut changes = ResourceChanges::default();
+
+    let added_or_edited = after
+        .iter()
+        .filter(|(file, modified)| before.get(file) != Some(modified));
+    let removed = before.iter().filter(|(file, _)| !after.contains_key(file));
+
+    for ((_, path), _) in added_or_edited.chain(removed) {
+        if path.starts_with("shaders") {
+            changes.shaders = true;
+        } else if path.starts_with("textures") {
+            changes.textures = true;
+        }
+    }
+
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn snapshot(files: &[(&str, u64)]) -> Snapshot {
+        files
+            .iter()
+            .map(|(path, secs)| {
+                (
+                    (0, PathBuf::from(path)),
+                    SystemTime::UNIX_EPOCH + Duration::from_secs(*secs),
+                )
+            })
+            .collect()
+    }
+
+    #[rstest]
+    fn unchanged_files_need_no_reload() {
+        let files = snapshot(&[("shaders/skybox.frag", 1), ("textures/blocks.json", 1)]);
+        assert_eq!(
+            ResourceChanges::default(),
+            compare_snapshots(&files, &files.clone())
+        );
+    }
+
+    #[rstest]
+    #[case(&[("shaders/skybox.frag", 2), ("textures/blocks/dirt.png", 1)], true, false)]
+    #[case(&[("shaders/skybox.frag", 1), ("textures/blocks/dirt.png", 2)], false, true)]
+    #[case(&[("shaders/skybox.frag", 1)], false, true)]
+    #[case(&[("shaders/skybox.frag", 1), ("shaders/types.glsl", 1), ("textures/blocks/dirt.png", 1)], true, false)]
+    fn changes_are_sorted_by_kind(
+        #[case] after: &[(&str, u64)],
+        #[case] shaders: bool,
+        #[case] textures: bool,
+    ) {
+        let before = snapshot(&[("shaders/skybox.frag", 1), ("textures/blocks/dirt.png", 1)]);
+        assert_eq!(
+            ResourceChanges { shaders, textures },
+            compare_snapshots(&before, &snapshot(after))
+        );
+    }
+}
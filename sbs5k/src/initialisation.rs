@@ -1,20 +1,29 @@
 use std::cmp::min;
+use std::fs;
+use std::path::Path;
 
 use nalgebra::Vector3;
 
 use sbs5k_core::chunk;
-use sbs5k_engine::{lighting, FogParameters};
+use sbs5k_core::clock::WorldClock;
+use sbs5k_core::geometry::BlockPosition;
+use sbs5k_engine::texture::{CubemapTexture, ImageFileFormat};
+use sbs5k_engine::{
+    lighting, FogColour, FogMode, FogParameters, ShadowSettings, Skybox, NUM_SHADOW_CASCADES,
+};
 
+use crate::args;
+use crate::constants;
+use crate::daylight;
 use crate::Args;
 
 /// Set up the initial lighting parameters for the scene
-pub(crate) fn make_scene_lighting() -> lighting::SceneLighting {
+///
+/// The point lights start off empty, and are filled in with nearby torches every frame. The global
+/// light is likewise updated every frame to follow the time of day.
+pub(crate) fn make_scene_lighting(clock: &WorldClock) -> lighting::SceneLighting {
     let point_lights = vec![];
-    let global_light = lighting::GlobalLight {
-        direction: Vector3::new(1.0, 1.2, 1.5).normalize(),
-        colour: Vector3::new(1.0, 1.0, 1.0),
-        intensity: 0.5,
-    };
+    let global_light = daylight::global_light(clock);
 
     lighting::SceneLighting {
         point_lights,
@@ -22,17 +31,125 @@ pub(crate) fn make_scene_lighting() -> lighting::SceneLighting {
     }
 }
 
+/// Create the point light cast by a torch standing in the block at `position`
+pub(crate) fn make_torch_light(position: BlockPosition) -> lighting::PointLight {
+    let [r, g, b] = constants::TORCH_LIGHT_COLOUR;
+    lighting::PointLight {
+        // The light comes from the flame at the top of the torch
+        position: position.cast::<f32>() + Vector3::new(0.5, 0.7, 0.5),
+        colour: Vector3::new(r, g, b),
+        intensity: constants::TORCH_LIGHT_INTENSITY,
+    }
+}
+
 /// Set up the fog parameters
 pub(crate) fn make_fog_parameters(config: &Args) -> FogParameters {
-    let near_chunks = config.render_distance - 1;
-    let far_chunks = config.render_distance;
+    // When distant terrain is being drawn, the fog thickens gradually across it so that the
+    // low-detail ring blends into the horizon. Otherwise it only covers the outermost chunks.
+    let (near_chunks, far_chunks) = if config.lod_distance > 0 {
+        (
+            config.render_distance,
+            config.render_distance + config.lod_distance,
+        )
+    } else {
+        (config.render_distance - 1, config.render_distance)
+    };
     let chunk_size = min(chunk::CHUNK_WIDTH, chunk::CHUNK_DEPTH) as u32;
 
     let near_distance = (near_chunks * chunk_size) as f32;
     let far_distance = (far_chunks * chunk_size) as f32;
 
-    FogParameters {
-        start_threshold: near_distance,
-        end_threshold: far_distance,
+    let mut fog = match config.fog_mode {
+        args::FogMode::Linear => FogParameters::linear(near_distance, far_distance),
+        args::FogMode::Exponential => {
+            FogParameters::exponential(FogMode::Exponential, far_distance)
+        }
+        args::FogMode::ExponentialSquared => {
+            FogParameters::exponential(FogMode::ExponentialSquared, far_distance)
+        }
+    };
+    if let Some(density) = config.fog_density {
+        fog.density = density;
+    }
+    if let Some(colour) = &config.fog_colour {
+        fog.colour = FogColour::Fixed(Vector3::from_column_slice(colour));
+    }
+    fog.height_density = config.height_fog_density;
+    fog.height_falloff = config.height_fog_falloff;
+    fog.height_base = config.height_fog_base;
+
+    fog
+}
+
+/// Set up the shadows cast by the global light
+pub(crate) fn make_shadow_settings(config: &Args) -> ShadowSettings {
+    let cascade_distances: [f32; NUM_SHADOW_CASCADES] = config
+        .shadow_distances
+        .as_slice()
+        .try_into()
+        .unwrap_or_else(|_| {
+            panic!(
+                "Expected {} shadow distances but got {}",
+                NUM_SHADOW_CASCADES,
+                config.shadow_distances.len()
+            )
+        });
+    if cascade_distances.windows(2).any(|pair| pair[0] >= pair[1]) {
+        panic!("Shadow distances must be in increasing order");
+    }
+
+    ShadowSettings {
+        cascade_distances,
+        map_size: config.shadow_map_size,
+    }
+}
+
+/// The file names of the faces of a skybox, in the order that they're passed to the engine
+const SKYBOX_FACE_FILE_NAMES: [&str; 6] =
+    ["px.png", "nx.png", "py.png", "ny.png", "pz.png", "nz.png"];
+
+/// Set up the skybox, loading its images if the player has chosen any
+///
+/// The gradient sky is used instead if the images can't be loaded.
+pub(crate) fn make_skybox(config: &Args) -> Skybox {
+    let path = match &config.skybox {
+        Some(path) => path,
+        None => return Skybox::new(),
+    };
+
+    match load_skybox_texture(path) {
+        Ok(texture) => Skybox::with_texture(texture),
+        Err(e) => {
+            eprintln!("Warning: {}. Falling back to the gradient sky", e);
+            Skybox::new()
+        }
+    }
+}
+
+fn load_skybox_texture(path: &Path) -> Result<CubemapTexture, String> {
+    if path.is_dir() {
+        let face_paths = SKYBOX_FACE_FILE_NAMES.map(|name| path.join(name));
+        let faces = face_paths
+            .iter()
+            .map(|face_path| read_skybox_image(face_path))
+            .collect::<Result<Vec<_>, _>>()?;
+        let names = face_paths
+            .each_ref()
+            .map(|face_path| face_path.display().to_string());
+        CubemapTexture::from_faces(
+            std::array::from_fn(|i| faces[i].as_slice()),
+            names.each_ref().map(String::as_str),
+            ImageFileFormat::Png,
+        )
+    } else {
+        CubemapTexture::from_equirectangular(
+            &read_skybox_image(path)?,
+            &path.display().to_string(),
+            ImageFileFormat::Guess,
+        )
     }
 }
+
+fn read_skybox_image(path: &Path) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|e| format!("Failed to read skybox image {}: {}", path.display(), e))
+}
@@ -0,0 +1,242 @@
+//! Packs block face textures into a single image, one layer per distinct texture.
+//!
+//! An image that's several times taller than it is wide is an animation, played from top to
+//! bottom, and each of its frames gets a layer of its own.
+//!
+//! This module is shared with the build script, which packs the built-in textures, so it may only
+//! use `std`, `image` and `serde_json`.
+
+use std::collections::BTreeMap;
+use std::io::Cursor;
+
+use image::{GenericImage, ImageOutputFormat, RgbaImage};
+
+/// The keys in the block definitions that can set the texture of each face of a block, most
+/// specific first. The faces are in the order of the `CubeFace` variants.
+const FACES: [&[&str]; 6] = [
+    &["pos_x", "sides", "all"],
+    &["neg_x", "sides", "all"],
+    &["top", "all"],
+    &["bottom", "all"],
+    &["pos_z", "sides", "all"],
+    &["neg_z", "sides", "all"],
+];
+
+const FACE_KEYS: [&str; 8] = [
+    "all", "sides", "top", "bottom", "pos_x", "neg_x", "pos_z", "neg_z",
+];
+
+/// Maps each block, by its `Block` variant name, to the image files used for each of its faces
+type BlockDefinitions = BTreeMap<String, BTreeMap<String, String>>;
+
+/// Block face textures packed into one image
+pub(crate) struct PackedBlockTextures {
+    /// The PNG-encoded image, with the textures stacked one above the other
+    pub(crate) png: Vec<u8>,
+
+    /// The width and height in pixels of each texture
+    pub(crate) tile_size: u32,
+
+    /// For each block, by its `Block` variant name, the layer used by each of its faces in the
+    /// order of the `CubeFace` variants
+    ///
+    /// Animated faces use the layer holding their first frame.
+    pub(crate) layers: Vec<(String, [u32; 6])>,
+
+    /// The first layer and number of frames of each animated texture, whose frames are in
+    /// consecutive layers
+    pub(crate) animations: Vec<(u32, u32)>,
+}
+
+/// Pack the textures named in the JSON block `definitions`, using `read_texture` to fetch the
+/// contents of each image file
+pub(crate) fn pack(
+    definitions: &str,
+    read_texture: impl Fn(&str) -> Result<Vec<u8>, String>,
+) -> Result<PackedBlockTextures, String> {
+    let definitions: BlockDefinitions = serde_json::from_str(definitions)
+        .map_err(|err| format!("Invalid block definitions: {}", err))?;
+
+    // Give each distinct image its own layers, in the order they're first needed
+    let mut files: Vec<&str> = vec![];
+    let mut file_indices = vec![];
+    for (block, faces) in &definitions {
+        if let Some(key) = faces.keys().find(|key| !FACE_KEYS.contains(&key.as_str())) {
+            return Err(format!("Unknown face \"{}\" for {}", key, block));
+        }
+
+        let mut block_files = [0; 6];
+        for (index, keys) in block_files.iter_mut().zip(FACES) {
+            let file = keys
+                .iter()
+                .find_map(|key| faces.get(*key))
+                .ok_or_else(|| format!("No texture for the \"{}\" face of {}", keys[0], block))?;
+            *index = match files.iter().position(|f| f == file) {
+                Some(index) => index,
+                None => {
+                    files.push(file);
+                    files.len() - 1
+                }
+            };
+        }
+        file_indices.push((block, block_files));
+    }
+
+    let mut images = vec![];
+    for file in &files {
+        let image = image::load_from_memory(&read_texture(file)?)
+            .map_err(|err| format!("Failed to decode block texture {}: {}", file, err))?
+            .to_rgba8();
+        images.push((file, image));
+    }
+
+    let tile_size = images.first().map_or(1, |(_, image)| image.width());
+    let mut first_layers = vec![];
+    let mut animations = vec![];
+    let mut layer_count = 0;
+    for (file, image) in &images {
+        if image.width() != tile_size || image.height() == 0 || image.height() % tile_size != 0 {
+            return Err(format!(
+                "Block texture {} is {}x{}, but block textures must all be {} pixels wide and a \
+                 whole number of {}x{} frames tall",
+                file,
+                image.width(),
+                image.height(),
+                tile_size,
+                tile_size,
+                tile_size
+            ));
+        }
+
+        let frames = image.height() / tile_size;
+        if frames > 1 {
+            animations.push((layer_count, frames));
+        }
+        first_layers.push(layer_count);
+        layer_count += frames;
+    }
+
+    let mut packed = RgbaImage::new(tile_size, tile_size * layer_count.max(1));
+    for ((file, image), first_layer) in images.iter().zip(&first_layers) {
+        packed
+            .copy_from(image, 0, first_layer * tile_size)
+            .map_err(|err| format!("Failed to pack block texture {}: {}", file, err))?;
+    }
+
+    let layers = file_indices
+        .into_iter()
+        .map(|(block, files)| (block.clone(), files.map(|index| first_layers[index])))
+        .collect();
+
+    let mut png = Cursor::new(vec![]);
+    packed
+        .write_to(&mut png, ImageOutputFormat::Png)
+        .map_err(|err| format!("Failed to encode block textures: {}", err))?;
+
+    Ok(PackedBlockTextures {
+        png: png.into_inner(),
+        tile_size,
+        layers,
+        animations,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    /// Encode a single-coloured square image as a PNG
+    fn solid_png(size: u32, shade: u8) -> Vec<u8> {
+        let mut png = Cursor::new(vec![]);
+        RgbaImage::from_pixel(size, size, image::Rgba([shade, shade, shade, 255]))
+            .write_to(&mut png, ImageOutputFormat::Png)
+            .unwrap();
+        png.into_inner()
+    }
+
+    /// Encode a transparent image as a PNG
+    fn blank_png(width: u32, height: u32) -> Vec<u8> {
+        let mut png = Cursor::new(vec![]);
+        RgbaImage::new(width, height)
+            .write_to(&mut png, ImageOutputFormat::Png)
+            .unwrap();
+        png.into_inner()
+    }
+
+    fn read_texture(file: &str) -> Result<Vec<u8>, String> {
+        match file {
+            "a.png" => Ok(solid_png(4, 10)),
+            "b.png" => Ok(solid_png(4, 20)),
+            "c.png" => Ok(solid_png(4, 30)),
+            "big.png" => Ok(solid_png(8, 40)),
+            "animated.png" => Ok(blank_png(4, 12)),
+            "ragged.png" => Ok(blank_png(4, 6)),
+            _ => Err(format!("No texture {}", file)),
+        }
+    }
+
+    #[rstest]
+    fn more_specific_keys_override_less_specific_ones() {
+        let packed = pack(
+            r#"{"Grass": {"all": "a.png", "sides": "b.png", "neg_z": "c.png"}}"#,
+            read_texture,
+        )
+        .unwrap();
+
+        // Layers are numbered in the order they're first needed, starting from the PosX face
+        assert_eq!(
+            vec![("Grass".to_owned(), [0, 0, 1, 1, 0, 2])],
+            packed.layers
+        );
+    }
+
+    #[rstest]
+    fn shared_images_are_packed_once() {
+        let packed = pack(
+            r#"{"Dirt": {"all": "a.png"}, "Grass": {"top": "b.png", "bottom": "a.png", "sides": "c.png"}}"#,
+            read_texture,
+        )
+        .unwrap();
+        let image = image::load_from_memory(&packed.png).unwrap();
+
+        assert_eq!(4, packed.tile_size);
+        assert_eq!((4, 12), (image.width(), image.height()));
+        assert_eq!(packed.layers[0].1[0], packed.layers[1].1[3]);
+    }
+
+    #[rstest]
+    fn animations_get_a_layer_for_each_frame() {
+        let packed = pack(
+            r#"{"Dirt": {"all": "a.png"}, "Water": {"top": "animated.png", "sides": "b.png", "bottom": "a.png"}}"#,
+            read_texture,
+        )
+        .unwrap();
+        let image = image::load_from_memory(&packed.png).unwrap();
+
+        assert_eq!(vec![(2, 3)], packed.animations);
+        assert_eq!([1, 1, 2, 0, 1, 1], packed.layers[1].1);
+        assert_eq!(4 * 5, image.height());
+    }
+
+    #[rstest]
+    #[case(
+        r#"{"Dirt": {"sides": "a.png"}}"#,
+        "No texture for the \"top\" face of Dirt"
+    )]
+    #[case(
+        r#"{"Dirt": {"all": "a.png", "front": "b.png"}}"#,
+        "Unknown face \"front\""
+    )]
+    #[case(r#"{"Dirt": {"all": "missing.png"}}"#, "No texture missing.png")]
+    #[case(r#"{"Dirt": {"all": "a.png", "top": "big.png"}}"#, "big.png is 8x8")]
+    #[case(
+        r#"{"Dirt": {"all": "a.png", "top": "ragged.png"}}"#,
+        "ragged.png is 4x6"
+    )]
+    #[case(r#"{"Dirt": "a.png"}"#, "Invalid block definitions")]
+    fn bad_definitions_are_reported(#[case] definitions: &str, #[case] expected_error: &str) {
+        let error = pack(definitions, read_texture).err().unwrap();
+        assert!(error.contains(expected_error), "{}", error);
+    }
+}
@@ -0,0 +1,219 @@
+use std::borrow::Cow;
+
+use sbs5k_core::block::Block;
+use sbs5k_core::cube::CubeFace;
+use sbs5k_engine::texture::{ImageFileFormat, TextureArray};
+
+use crate::loading::block_texture_packing;
+use crate::resources::{textures, ResourcePacks};
+
+const BLOCK_DEFINITIONS_PATH: &str = "textures/blocks.json";
+const BLOCK_TEXTURES_PATH: &str = "textures/blocks";
+
+/// The textures for every face of every block, packed into one image, along with which layer of
+/// it each face uses
+pub(crate) struct BlockTextures {
+    /// The PNG-encoded textures, stacked one above the other
+    png: Cow<'static, [u8]>,
+
+    /// The width and height in pixels of each texture
+    tile_size: u32,
+
+    /// The layer used by each face of each block, in the order of the `CubeFace` variants
+    layers: Vec<(Block, [u32; 6])>,
+
+    /// The first layer and number of frames of each animated texture
+    animations: Vec<(u32, u32)>,
+}
+
+impl BlockTextures {
+    /// The block textures that were packed into the game when it was built
+    pub(crate) fn built_in() -> Self {
+        BlockTextures {
+            png: Cow::Borrowed(textures::BLOCKS),
+            tile_size: textures::BLOCK_TEXTURE_SIZE,
+            layers: textures::BLOCK_TEXTURE_LAYOUT.to_vec(),
+            animations: textures::BLOCK_TEXTURE_ANIMATIONS.to_vec(),
+        }
+    }
+
+    /// Load the block textures, taking the block definitions and any of the images from the
+    /// resource packs that have them
+    pub(crate) fn load(packs: &ResourcePacks) -> Self {
+        BlockTextures::try_load(packs).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Like `load`, but returns an error rather than panicking if the resource packs have bad
+    /// block definitions or textures
+    pub(crate) fn try_load(packs: &ResourcePacks) -> Result<Self, String> {
+        if !packs.contains(BLOCK_DEFINITIONS_PATH) && !packs.contains(BLOCK_TEXTURES_PATH) {
+            return Ok(BlockTextures::built_in());
+        }
+
+        let (definitions, origin) = match packs.read(BLOCK_DEFINITIONS_PATH) {
+            Some((contents, path)) => (
+                String::from_utf8(contents).map_err(|_| {
+                    format!("Block definitions {} aren't valid UTF-8", path.display())
+                })?,
+                path.display().to_string(),
+            ),
+            None => (
+                textures::BLOCK_DEFINITIONS.to_owned(),
+                "the built-in block definitions".to_owned(),
+            ),
+        };
+
+        let packed = block_texture_packing::pack(&definitions, |file| {
+            if let Some((contents, _)) = packs.read(format!("{}/{}", BLOCK_TEXTURES_PATH, file)) {
+                return Ok(contents);
+            }
+            textures::BLOCK_TEXTURE_FILES
+                .iter()
+                .find(|(name, _)| *name == file)
+                .map(|(_, contents)| contents.to_vec())
+                .ok_or_else(|| format!("No resource pack has the block texture {}", file))
+        })
+        .map_err(|err| format!("Failed to load block textures from {}: {}", origin, err))?;
+
+        let layers = packed
+            .layers
+            .into_iter()
+            .map(|(name, layers)| {
+                let block = serde_json::from_value(serde_json::Value::String(name.clone()))
+                    .map_err(|_| format!("Unknown block \"{}\" in {}", name, origin))?;
+                Ok((block, layers))
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(BlockTextures {
+            png: Cow::Owned(packed.png),
+            tile_size: packed.tile_size,
+            layers,
+            animations: packed.animations,
+        })
+    }
+
+    /// Find the layer that holds the texture for one face of a block, if the block has textures
+    ///
+    /// Flowing water looks the same as its source, whatever its level.
+    pub(crate) fn layer(&self, block: Block, face: CubeFace) -> Option<u32> {
+        let block = if block.is_fluid() {
+            Block::Water
+        } else {
+            block
+        };
+        let face_index = match face {
+            CubeFace::PosX => 0,
+            CubeFace::NegX => 1,
+            CubeFace::PosY => 2,
+            CubeFace::NegY => 3,
+            CubeFace::PosZ => 4,
+            CubeFace::NegZ => 5,
+        };
+        self.layers
+            .iter()
+            .find(|(b, _)| *b == block)
+            .map(|(_, layers)| layers[face_index])
+    }
+
+    /// The number of frames of animation starting at `layer`, which is 1 if it isn't animated
+    pub(crate) fn frames_from(&self, layer: u32) -> u32 {
+        self.animations
+            .iter()
+            .find(|(first_layer, _)| *first_layer == layer)
+            .map_or(1, |(_, frames)| *frames)
+    }
+
+    /// Whether every face of every block uses the same layers in both sets of textures
+    pub(crate) fn has_same_layout_as(&self, other: &BlockTextures) -> bool {
+        self.layers == other.layers && self.animations == other.animations
+    }
+
+    /// Replace the contents of a texture array made by `to_texture_array` with these textures
+    pub(crate) fn reload_texture_array(&self, texture: &TextureArray) {
+        texture.reload(&self.png, ImageFileFormat::Png, self.tile_size);
+    }
+
+    /// Upload the textures to the GPU as a texture array
+    pub(crate) fn to_texture_array(&self, max_anisotropy: Option<f32>) -> TextureArray {
+        TextureArray::from_grid(
+            &self.png,
+            ImageFileFormat::Png,
+            self.tile_size,
+            max_anisotropy,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    const FACES: [CubeFace; 6] = [
+        CubeFace::PosX,
+        CubeFace::NegX,
+        CubeFace::PosY,
+        CubeFace::NegY,
+        CubeFace::PosZ,
+        CubeFace::NegZ,
+    ];
+
+    #[rstest]
+    #[case(Block::Grass)]
+    #[case(Block::Dirt)]
+    #[case(Block::Stone)]
+    #[case(Block::Torch)]
+    #[case(Block::Glass)]
+    #[case(Block::Leaves)]
+    #[case(Block::Water)]
+    #[case(Block::Sand)]
+    #[case(Block::Gravel)]
+    #[case(Block::FlowingWater3)]
+    fn every_face_of_a_built_in_block_has_a_layer(#[case] block: Block) {
+        let textures = BlockTextures::built_in();
+        let layer_count =
+            image::load_from_memory(&textures.png).unwrap().height() / textures.tile_size;
+
+        for face in FACES {
+            assert!(textures.layer(block, face).unwrap() < layer_count);
+        }
+    }
+
+    #[rstest]
+    fn faces_using_the_same_image_share_a_layer() {
+        let textures = BlockTextures::built_in();
+        let dirt = textures.layer(Block::Dirt, CubeFace::PosX);
+
+        assert_eq!(dirt, textures.layer(Block::Grass, CubeFace::NegY));
+        assert_ne!(dirt, textures.layer(Block::Grass, CubeFace::PosX));
+        assert_ne!(
+            textures.layer(Block::Grass, CubeFace::PosY),
+            textures.layer(Block::Grass, CubeFace::PosX)
+        );
+    }
+
+    #[rstest]
+    fn water_is_animated() {
+        let textures = BlockTextures::built_in();
+        let layer_count =
+            image::load_from_memory(&textures.png).unwrap().height() / textures.tile_size;
+
+        let water = textures.layer(Block::Water, CubeFace::PosY).unwrap();
+        let frames = textures.frames_from(water);
+        assert!(frames > 1);
+        assert!(water + frames <= layer_count);
+        assert_eq!(
+            1,
+            textures.frames_from(textures.layer(Block::Stone, CubeFace::PosY).unwrap())
+        );
+    }
+
+    #[rstest]
+    fn blocks_without_textures_have_no_layer() {
+        assert_eq!(
+            None,
+            BlockTextures::built_in().layer(Block::Empty, CubeFace::PosY)
+        );
+    }
+}
@@ -1,19 +1,20 @@
 use std::sync::{mpsc, Arc, RwLock};
 use std::thread;
 
-use sbs5k_core::chunk;
+use sbs5k_core::{chunk, light, lod};
 
 use crate::event::Event;
 use crate::{args, event};
 
 pub(crate) enum ChunkLoadRequest {
-    InitialLoad,
+    InitialLoad(chunk::ChunkCoordinate),
     ChunkChangeLoad(chunk::ChunkCoordinate),
     Stop,
 }
 
 pub(crate) struct ChunkLoadResult {
     pub chunk: Box<chunk::Chunk>,
+    pub light: light::ChunkLight,
     pub coordinate: chunk::ChunkCoordinate,
 }
 
@@ -54,8 +55,8 @@ impl ChunkLoaderWorker {
 
                 // Process the latest request
                 match chunk_load_request {
-                    ChunkLoadRequest::InitialLoad => {
-                        self.process_initial_load();
+                    ChunkLoadRequest::InitialLoad(coordinate) => {
+                        self.process_initial_load(coordinate);
                     }
 
                     ChunkLoadRequest::ChunkChangeLoad(coordinate) => {
@@ -71,54 +72,36 @@ impl ChunkLoaderWorker {
     }
 
     #[inline(always)]
-    fn process_initial_load(&mut self) {
+    fn process_initial_load(&mut self, initial_coordinate: chunk::ChunkCoordinate) {
         let live_flag = self.is_live_flag.clone();
 
-        let mut load_chunk = |i, j| {
-            let coordinate = chunk::ChunkCoordinate { i, j };
-            let chunk = self.chunk_source.get_chunk_at(coordinate);
-            let result = ChunkLoadResult { coordinate, chunk };
-            self.event_submitter
-                .submit_event(Event::ChunkLoaded(result));
-        };
-
-        let initial_coordinate: chunk::ChunkCoordinate = Default::default();
-        load_chunk(initial_coordinate.i, initial_coordinate.j);
+        self.load_chunk(initial_coordinate);
 
         self.current_chunk_coordinate = initial_coordinate;
 
         // Load the remaining initial chunks in a spiral shape around the player so that
         // the chunks closest to the player get loaded first
-        if self.config.render_distance > 0 {
-            for d in 1..=(self.config.render_distance as i32) {
-                if !*live_flag.read().unwrap() {
-                    return;
-                }
-
-                let i_left = initial_coordinate.i - d;
-                let i_right = initial_coordinate.i + d;
-                let j_top = initial_coordinate.j + d;
-                let j_bottom = initial_coordinate.j - d;
-
-                // Top
-                for i in i_left..i_right {
-                    load_chunk(i, j_top);
-                }
+        for d in 1..=self.config.render_distance {
+            if !*live_flag.read().unwrap() {
+                return;
+            }
 
-                // Bottom
-                for i in (i_left + 1)..=i_right {
-                    load_chunk(i, j_bottom);
-                }
+            for coordinate in chunk::square_ring(initial_coordinate, d) {
+                self.load_chunk(coordinate);
+            }
+        }
 
-                // Left
-                for j in j_bottom..j_top {
-                    load_chunk(i_left, j);
-                }
+        // Only once all the full-detail chunks are available, continue the spiral outwards with
+        // the low-detail tiles
+        let lod_rings = (self.config.render_distance + 1)
+            ..=(self.config.render_distance + self.config.lod_distance);
+        for d in lod_rings {
+            if !*live_flag.read().unwrap() {
+                return;
+            }
 
-                // Right
-                for j in (j_bottom + 1)..=j_top {
-                    load_chunk(i_right, j);
-                }
+            for coordinate in chunk::square_ring(initial_coordinate, d) {
+                self.load_lod_tile(coordinate, initial_coordinate);
             }
         }
     }
@@ -130,16 +113,53 @@ impl ChunkLoaderWorker {
             new_coordinate,
             self.config.render_distance,
         );
+        let lod_coordinates_to_load = compute_lod_tiles_to_load_after_player_current_chunk_change(
+            self.current_chunk_coordinate,
+            new_coordinate,
+            self.config.render_distance,
+            self.config.lod_distance,
+        );
 
         self.current_chunk_coordinate = new_coordinate;
 
         // TODO: Consider invalidating the old chunks
 
         for coordinate in coordinates_to_load {
-            let chunk = self.chunk_source.get_chunk_at(coordinate);
-            let result = ChunkLoadResult { coordinate, chunk };
+            self.load_chunk(coordinate);
+        }
+
+        for coordinate in lod_coordinates_to_load {
+            self.load_lod_tile(coordinate, new_coordinate);
+        }
+    }
+
+    #[inline]
+    fn load_chunk(&mut self, coordinate: chunk::ChunkCoordinate) {
+        let chunk = self.chunk_source.get_chunk_at(coordinate);
+        let light = light::ChunkLight::compute(&chunk);
+        let result = ChunkLoadResult {
+            chunk,
+            light,
+            coordinate,
+        };
+        self.event_submitter
+            .submit_event(Event::ChunkLoaded(result));
+    }
+
+    #[inline]
+    fn load_lod_tile(
+        &mut self,
+        coordinate: chunk::ChunkCoordinate,
+        player_chunk_coordinate: chunk::ChunkCoordinate,
+    ) {
+        let step = lod_step_at(
+            coordinate,
+            player_chunk_coordinate,
+            self.config.render_distance,
+        );
+        if let Some(tile) = lod::LodTile::generate(self.chunk_source.as_mut(), coordinate, step) {
             self.event_submitter
-                .submit_event(Event::ChunkLoaded(result));
+                .submit_event(Event::LodTileLoaded(tile));
         }
     }
 }
@@ -152,6 +172,7 @@ pub(crate) struct ChunkLoader {
 impl ChunkLoader {
     pub(crate) fn new(
         chunk_source: Box<dyn chunk::ChunkSource + Send>,
+        initial_coordinate: chunk::ChunkCoordinate,
         event_submitter: event::EventSubmitter,
         config: Arc<args::Args>,
         is_live_flag: Arc<RwLock<bool>>,
@@ -173,7 +194,7 @@ impl ChunkLoader {
 
         // Get the initial load started
         chunk_load_request_tx
-            .send(ChunkLoadRequest::InitialLoad)
+            .send(ChunkLoadRequest::InitialLoad(initial_coordinate))
             .expect("Failed to send initial load request to queue");
 
         Self {
@@ -227,6 +248,62 @@ fn compute_chunks_to_load_after_player_current_chunk_change(
     coords
 }
 
+/// Compute the set of low-detail tiles that must be loaded (or reloaded at a different level of
+/// detail) if the player moved from `old_chunk_coord` to `new_chunk_coord`
+fn compute_lod_tiles_to_load_after_player_current_chunk_change(
+    old_chunk_coord: chunk::ChunkCoordinate,
+    new_chunk_coord: chunk::ChunkCoordinate,
+    render_distance: u32,
+    lod_distance: u32,
+) -> Vec<chunk::ChunkCoordinate> {
+    let (min_chunk, max_chunk) =
+        renderable_chunk_indices_range(new_chunk_coord, render_distance + lod_distance);
+
+    let mut coords = vec![];
+    for i in min_chunk.i..=max_chunk.i {
+        for j in min_chunk.j..=max_chunk.j {
+            let coord = chunk::ChunkCoordinate { i, j };
+            if !is_in_lod_ring(coord, new_chunk_coord, render_distance, lod_distance) {
+                continue;
+            }
+            let already_loaded =
+                is_in_lod_ring(coord, old_chunk_coord, render_distance, lod_distance)
+                    && lod_step_at(coord, old_chunk_coord, render_distance)
+                        == lod_step_at(coord, new_chunk_coord, render_distance);
+            if !already_loaded {
+                coords.push(coord);
+            }
+        }
+    }
+    coords
+}
+
+/// Determine whether a chunk should be drawn as a low-detail tile when the player is in
+/// `player_chunk_coord`
+#[inline]
+fn is_in_lod_ring(
+    coord: chunk::ChunkCoordinate,
+    player_chunk_coord: chunk::ChunkCoordinate,
+    render_distance: u32,
+    lod_distance: u32,
+) -> bool {
+    let distance = coord.distance_to(player_chunk_coord);
+    distance > render_distance && distance <= render_distance + lod_distance
+}
+
+/// Choose the sample spacing for the low-detail tile at `coord`
+#[inline]
+fn lod_step_at(
+    coord: chunk::ChunkCoordinate,
+    player_chunk_coord: chunk::ChunkCoordinate,
+    render_distance: u32,
+) -> u32 {
+    let ring = coord
+        .distance_to(player_chunk_coord)
+        .saturating_sub(render_distance + 1);
+    lod::lod_step_for_ring(ring)
+}
+
 /// Compute the range of chunks that should be renderable for a given player index
 fn renderable_chunk_indices_range(
     current_chunk_coord: chunk::ChunkCoordinate,
@@ -274,4 +351,55 @@ mod tests {
         assert_eq!(expected_min, actual_min);
         assert_eq!(expected_max, actual_max);
     }
+
+    #[rstest]
+    #[case(chunk::ChunkCoordinate{i: 0, j: 0}, false)]
+    #[case(chunk::ChunkCoordinate{i: 2, j: -2}, false)]
+    #[case(chunk::ChunkCoordinate{i: 3, j: 0}, true)]
+    #[case(chunk::ChunkCoordinate{i: -5, j: 5}, true)]
+    #[case(chunk::ChunkCoordinate{i: 0, j: 6}, false)]
+    fn is_in_lod_ring_works(#[case] coord: chunk::ChunkCoordinate, #[case] expected: bool) {
+        let player_chunk = chunk::ChunkCoordinate { i: 0, j: 0 };
+        assert_eq!(expected, is_in_lod_ring(coord, player_chunk, 2, 3));
+    }
+
+    #[rstest]
+    fn lod_tiles_to_load_after_moving_one_chunk() {
+        let render_distance = 2;
+        let lod_distance = 10;
+        let old = chunk::ChunkCoordinate { i: 0, j: 0 };
+        let new = chunk::ChunkCoordinate { i: 1, j: 0 };
+        let coords = compute_lod_tiles_to_load_after_player_current_chunk_change(
+            old,
+            new,
+            render_distance,
+            lod_distance,
+        );
+
+        // Everything returned must belong to the new ring
+        for coord in coords.iter() {
+            assert!(is_in_lod_ring(*coord, new, render_distance, lod_distance));
+        }
+
+        // The new far edge must be loaded
+        assert!(coords.contains(&chunk::ChunkCoordinate { i: 13, j: 0 }));
+
+        // Chunks that were drawn in full detail but have fallen outside the render distance
+        assert!(coords.contains(&chunk::ChunkCoordinate { i: -2, j: 0 }));
+
+        // Tiles whose level of detail changes must be reloaded
+        assert!(coords.contains(&chunk::ChunkCoordinate { i: 7, j: 0 }));
+
+        // Tiles whose level of detail is unchanged must not be reloaded
+        assert!(!coords.contains(&chunk::ChunkCoordinate { i: 5, j: 0 }));
+        assert!(!coords.contains(&chunk::ChunkCoordinate { i: -8, j: 0 }));
+    }
+
+    #[rstest]
+    fn no_lod_tiles_to_load_without_moving() {
+        let coord = chunk::ChunkCoordinate { i: 4, j: -7 };
+        let coords =
+            compute_lod_tiles_to_load_after_player_current_chunk_change(coord, coord, 3, 5);
+        assert!(coords.is_empty());
+    }
 }
@@ -2,16 +2,35 @@ use std::rc::Rc;
 
 use nalgebra::{Point3, Vector3};
 
-use sbs5k_core::block::{Block, NON_EMPTY_BLOCKS_COUNT};
+use sbs5k_core::block::{Block, RenderLayer};
 use sbs5k_core::chunk::{Chunk, ChunkCoordinate, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH};
 use sbs5k_core::cube::CubeFace;
-use sbs5k_engine::model::{Model, VertexData, VertexDataLayoutInfo};
-use sbs5k_engine::texture::{ImageFileFormat, Texture, TextureCoordinate};
+use sbs5k_core::fluid::SOURCE_LEVEL;
+use sbs5k_core::geometry::{BlockPosition, Location};
+use sbs5k_core::light::{LightChannel, LightStorage, MAX_LIGHT_LEVEL};
+use sbs5k_core::lod::LodTile;
+use sbs5k_core::world::BlockLookup;
+use sbs5k_engine::model::{AlphaMode, Material, Model, VertexData, VertexDataLayoutInfo};
+use sbs5k_engine::texture::TextureArray;
 use sbs5k_engine::SceneObject;
 
-use crate::resources;
+use crate::loading::BlockTextures;
 
-const EPSILON: f32 = 0.01;
+/// The number of floats making up each vertex: a position, a normal, a texture coordinate, layer
+/// and frame count, and the sky and block light levels
+const FLOATS_PER_VERTEX: u32 = 12;
+
+/// The light given to surfaces that aren't part of any loaded chunk, such as low-detail terrain
+const OPEN_SKY_LIGHT: [f32; 2] = [1.0, 0.0];
+
+/// Half the width of a torch's stick, in blocks
+const TORCH_HALF_WIDTH: f32 = 0.125;
+
+/// The height of a torch, in blocks
+const TORCH_HEIGHT: f32 = 0.625;
+
+/// How far the skirts around low-detail tiles hang down, as a multiple of the tile's step
+const LOD_SKIRT_DEPTH_STEPS: f32 = 2.0;
 
 /// Generates renderable meshes from chunks.
 ///
@@ -19,37 +38,101 @@ const EPSILON: f32 = 0.01;
 /// a new object for each mesh to generate, because doing so will enable it to reuse its block
 /// texture across all chunks.
 pub struct MeshGenerator {
-    blocks_texture: Rc<Texture>,
+    blocks_texture: Rc<TextureArray>,
+    block_textures: BlockTextures,
+}
+
+/// The part of the blocks texture that covers a face
+#[derive(Clone, Copy, Debug)]
+struct FaceTexture {
+    /// The layer holding the texture, or its first frame if it's animated
+    layer: u32,
+
+    /// How many consecutive layers the texture cycles through, which is 1 if it isn't animated
+    frames: u32,
+}
+
+/// The meshes drawn for a chunk, or for a low-detail tile
+pub(crate) struct ChunkMeshes {
+    pub(crate) coordinate: ChunkCoordinate,
+
+    /// The opaque faces, along with cutout faces such as leaves, which can be drawn in any order
+    pub(crate) opaque: SceneObject,
+
+    /// The translucent faces, such as glass, if the chunk has any
+    pub(crate) translucent: Option<TranslucentMesh>,
+}
+
+/// A mesh of translucent faces, which have to be drawn from back to front to blend properly
+pub(crate) struct TranslucentMesh {
+    pub(crate) object: SceneObject,
+
+    /// The centre of each face, relative to the chunk's origin, in the order they're stored
+    face_centres: Vec<Point3<f32>>,
+
+    /// Where the faces were last sorted from, relative to the chunk's origin
+    sorted_from: Option<Point3<f32>>,
 }
 
 impl MeshGenerator {
-    pub(crate) fn new() -> Self {
+    /// Create a mesh generator, uploading the block textures with up to `max_anisotropy` samples
+    /// of anisotropic filtering
+    pub(crate) fn new(block_textures: BlockTextures, max_anisotropy: Option<f32>) -> Self {
         MeshGenerator {
-            blocks_texture: Rc::new(Texture::new(
-                resources::textures::CUBES,
-                ImageFileFormat::Png,
-            )),
+            blocks_texture: Rc::new(block_textures.to_texture_array(max_anisotropy)),
+            block_textures,
         }
     }
 
-    /// Compute a renderable mesh from the blocks in a chunk.
+    /// Switch to a new set of block textures, updating the texture used by every mesh already made
     ///
-    /// This function omits any faces that wouldn't be externally visible. If two blocks are adjacent,
-    /// then it'll elide the two faces that are touching each other, since there's no way they could be
-    /// seen.
+    /// Returns whether any faces have moved to a different layer, in which case existing meshes
+    /// must be rebuilt to pick up the new layout.
+    pub(crate) fn set_block_textures(&mut self, block_textures: BlockTextures) -> bool {
+        block_textures.reload_texture_array(&self.blocks_texture);
+        let layout_changed = !block_textures.has_same_layout_as(&self.block_textures);
+        self.block_textures = block_textures;
+        layout_changed
+    }
+
+    /// Compute renderable meshes from the blocks in a chunk.
+    ///
+    /// This function omits any faces that wouldn't be externally visible. If a block is next to an
+    /// opaque block, then it'll elide the face that's touching it, since there's no way it could be
+    /// seen. The same goes for the faces between two of the same translucent block, so that a wall
+    /// of glass looks like one pane. Blocks along the edges of the chunk are compared with those
+    /// in the neighbouring chunks in `world`, if they're loaded.
+    ///
+    /// Translucent faces are put in a mesh of their own, since they need to be drawn separately
+    /// after everything else.
+    ///
+    /// Each vertex is lit by averaging the light levels of the blocks in front of the face that
+    /// touch it, which are looked up in `world` so that faces at the edges of the chunk blend
+    /// smoothly with their neighbours.
     ///
     /// The structure generated by this function will need to be rebuild whenever a block is modified.
     ///
     /// TODO: Cull more aggressively (only emit the 3D convex hull) for chunks that the player's not currently in
-    ///
-    /// TODO: Also don't emit if there is still a block there in another chunk
-    pub(crate) fn chunk_to_scene_object(
+    pub(crate) fn chunk_to_meshes(
         &self,
         chunk: &Chunk,
         coordinate: ChunkCoordinate,
-    ) -> SceneObject {
-        let mut vertex_buffer: Vec<f32> = vec![];
-        let mut index_buffer: Vec<u32> = vec![];
+        world: &impl LightStorage,
+    ) -> ChunkMeshes {
+        let mut opaque_vertices: Vec<f32> = vec![];
+        let mut opaque_indices: Vec<u32> = vec![];
+        let mut translucent_vertices: Vec<f32> = vec![];
+        let mut translucent_indices: Vec<u32> = vec![];
+        let mut has_cutout_faces = false;
+        let chunk_origin = BlockPosition::new(
+            coordinate.i * CHUNK_WIDTH as i32,
+            0,
+            coordinate.j * CHUNK_DEPTH as i32,
+        );
+        let lighting = VertexLighting {
+            world,
+            chunk_origin,
+        };
 
         for x in 0..CHUNK_WIDTH as i32 {
             for y in 0..CHUNK_HEIGHT as i32 {
@@ -58,90 +141,239 @@ impl MeshGenerator {
                         continue;
                     }
                     let block = chunk.get_block_at(x as usize, y as usize, z as usize);
+                    let (vertex_buffer, index_buffer) = match block.render_layer() {
+                        RenderLayer::Opaque => (&mut opaque_vertices, &mut opaque_indices),
+                        RenderLayer::Cutout => {
+                            has_cutout_faces = true;
+                            (&mut opaque_vertices, &mut opaque_indices)
+                        }
+                        RenderLayer::Translucent => {
+                            (&mut translucent_vertices, &mut translucent_indices)
+                        }
+                    };
 
-                    if !chunk.has_block_at(x + 1, y, z) {
-                        emit_pos_x_face(
-                            block,
+                    if block == Block::Torch {
+                        let light = lighting.at_block(BlockPosition::new(x, y, z));
+                        emit_torch(
+                            |face| self.face_texture(Block::Torch, face),
                             x as f32,
                             y as f32,
                             z as f32,
-                            &mut vertex_buffer,
-                            &mut index_buffer,
+                            light,
+                            vertex_buffer,
+                            index_buffer,
                         );
+                        continue;
                     }
 
-                    if !chunk.has_block_at(x - 1, y, z) {
-                        emit_neg_x_face(
-                            block,
-                            x as f32,
-                            y as f32,
-                            z as f32,
-                            &mut vertex_buffer,
-                            &mut index_buffer,
-                        );
-                    }
+                    emit_block(
+                        block,
+                        |face| self.face_texture(block, face),
+                        |dx, dy, dz| {
+                            block_beside(chunk, world, chunk_origin, x + dx, y + dy, z + dz)
+                        },
+                        Point3::new(x as f32, y as f32, z as f32),
+                        &lighting,
+                        vertex_buffer,
+                        index_buffer,
+                    );
+                }
+            }
+        }
 
-                    if !chunk.has_block_at(x, y + 1, z) {
-                        emit_pos_y_face(
-                            block,
-                            x as f32,
-                            y as f32,
-                            z as f32,
-                            &mut vertex_buffer,
-                            &mut index_buffer,
-                        );
-                    }
+        let opaque_alpha_mode = if has_cutout_faces {
+            AlphaMode::Cutout
+        } else {
+            AlphaMode::Opaque
+        };
+        let translucent = (!translucent_indices.is_empty()).then(|| TranslucentMesh {
+            object: self.make_scene_object(
+                &translucent_vertices,
+                &translucent_indices,
+                coordinate,
+                AlphaMode::Blend,
+            ),
+            face_centres: face_centres(&translucent_vertices),
+            sorted_from: None,
+        });
 
-                    if !chunk.has_block_at(x, y - 1, z) {
-                        emit_neg_y_face(
-                            block,
-                            x as f32,
-                            y as f32,
-                            z as f32,
-                            &mut vertex_buffer,
-                            &mut index_buffer,
-                        );
-                    }
+        ChunkMeshes {
+            coordinate,
+            opaque: self.make_scene_object(
+                &opaque_vertices,
+                &opaque_indices,
+                coordinate,
+                opaque_alpha_mode,
+            ),
+            translucent,
+        }
+    }
 
-                    if !chunk.has_block_at(x, y, z + 1) {
-                        emit_pos_z_face(
-                            block,
-                            x as f32,
-                            y as f32,
-                            z as f32,
-                            &mut vertex_buffer,
-                            &mut index_buffer,
-                        );
-                    }
+    /// Compute renderable meshes approximating the terrain surface described by a low-detail tile.
+    ///
+    /// Each cell between four adjacent samples becomes a single quad, textured like the top of the
+    /// block on the ground at its first corner. Cells with any underwater corners get a second,
+    /// translucent quad for the water's surface. A skirt hangs down from each edge of the tile to
+    /// hide the cracks that appear where it meets a neighbour with a different level of detail.
+    pub(crate) fn lod_tile_to_meshes(&self, tile: &LodTile) -> ChunkMeshes {
+        let mut vertex_buffer: Vec<f32> = vec![];
+        let mut index_buffer: Vec<u32> = vec![];
+        let mut water_vertices: Vec<f32> = vec![];
+        let mut water_indices: Vec<u32> = vec![];
 
-                    if !chunk.has_block_at(x, y, z - 1) {
-                        emit_neg_z_face(
-                            block,
-                            x as f32,
-                            y as f32,
-                            z as f32,
-                            &mut vertex_buffer,
-                            &mut index_buffer,
-                        );
-                    }
+        let n = tile.samples_per_edge() as i32;
+        let step = tile.step as f32;
+        let skirt_depth = step * LOD_SKIRT_DEPTH_STEPS;
+
+        let top = |i: i32, j: i32| {
+            Point3::new(
+                i as f32 * step,
+                tile.height_at(i, j) as f32,
+                j as f32 * step,
+            )
+        };
+        let bottom = |i: i32, j: i32| top(i, j) - Vector3::new(0.0, skirt_depth, 0.0);
+
+        // Where the water meets the shore, its surface rises to meet the ground
+        let water_top = |i: i32, j: i32| {
+            let surface = tile.surface_at(i, j);
+            Point3::new(
+                i as f32 * step,
+                surface.water_height.unwrap_or(surface.ground_height) as f32,
+                j as f32 * step,
+            )
+        };
+
+        let water_texture = self.face_texture(Block::Water, CubeFace::PosY);
+        for i in 0..(n - 1) {
+            for j in 0..(n - 1) {
+                let corners = [(i, j), (i, j + 1), (i + 1, j + 1), (i + 1, j)];
+
+                let ground_block = tile.surface_at(i, j).ground_block;
+                emit_quad(
+                    &corners.map(|(i, j)| top(i, j)),
+                    &corners.map(|(i, j)| tile.normal_at(i, j)),
+                    self.face_texture(ground_block, CubeFace::PosY),
+                    &[OPEN_SKY_LIGHT; 4],
+                    &mut vertex_buffer,
+                    &mut index_buffer,
+                );
+
+                if corners
+                    .iter()
+                    .any(|(i, j)| tile.surface_at(*i, *j).water_height.is_some())
+                {
+                    emit_quad(
+                        &corners.map(|(i, j)| water_top(i, j)),
+                        &[Vector3::new(0.0, 1.0, 0.0); 4],
+                        water_texture,
+                        &[OPEN_SKY_LIGHT; 4],
+                        &mut water_vertices,
+                        &mut water_indices,
+                    );
                 }
             }
         }
 
+        let skirt_texture = self.face_texture(Block::Dirt, CubeFace::PosX);
+        let mut emit_skirt = |points: [Point3<f32>; 4], normal: Vector3<f32>| {
+            emit_quad(
+                &points,
+                &[normal; 4],
+                skirt_texture,
+                &[OPEN_SKY_LIGHT; 4],
+                &mut vertex_buffer,
+                &mut index_buffer,
+            );
+        };
+        for k in 0..(n - 1) {
+            let last = n - 1;
+            emit_skirt(
+                [top(0, k), bottom(0, k), bottom(0, k + 1), top(0, k + 1)],
+                Vector3::new(-1.0, 0.0, 0.0),
+            );
+            emit_skirt(
+                [
+                    top(last, k + 1),
+                    bottom(last, k + 1),
+                    bottom(last, k),
+                    top(last, k),
+                ],
+                Vector3::new(1.0, 0.0, 0.0),
+            );
+            emit_skirt(
+                [top(k + 1, 0), bottom(k + 1, 0), bottom(k, 0), top(k, 0)],
+                Vector3::new(0.0, 0.0, -1.0),
+            );
+            emit_skirt(
+                [
+                    top(k, last),
+                    bottom(k, last),
+                    bottom(k + 1, last),
+                    top(k + 1, last),
+                ],
+                Vector3::new(0.0, 0.0, 1.0),
+            );
+        }
+
+        let translucent = (!water_indices.is_empty()).then(|| TranslucentMesh {
+            object: self.make_scene_object(
+                &water_vertices,
+                &water_indices,
+                tile.coordinate,
+                AlphaMode::Blend,
+            ),
+            face_centres: face_centres(&water_vertices),
+            sorted_from: None,
+        });
+
+        ChunkMeshes {
+            coordinate: tile.coordinate,
+            opaque: self.make_scene_object(
+                &vertex_buffer,
+                &index_buffer,
+                tile.coordinate,
+                AlphaMode::Opaque,
+            ),
+            translucent,
+        }
+    }
+
+    /// Find the part of the blocks texture that covers one face of a block
+    #[inline]
+    fn face_texture(&self, block: Block, face: CubeFace) -> FaceTexture {
+        let layer = self
+            .block_textures
+            .layer(block, face)
+            .unwrap_or_else(|| panic!("Don't have a texture mapping for block type: {:?}", block));
+        FaceTexture {
+            layer,
+            frames: self.block_textures.frames_from(layer),
+        }
+    }
+
+    /// Upload a mesh built by this generator, positioning it at the origin of a chunk
+    fn make_scene_object(
+        &self,
+        vertex_buffer: &[f32],
+        index_buffer: &[u32],
+        coordinate: ChunkCoordinate,
+        alpha_mode: AlphaMode,
+    ) -> SceneObject {
         let model_layout_info = VertexDataLayoutInfo {
             position_offset: 0,
             normal_offset: Some(3),
             texture_offset: Some(6),
+            light_offset: Some(10),
         };
-        let vertices = VertexData::new(
-            vertex_buffer.as_slice(),
-            index_buffer.as_slice(),
-            model_layout_info,
-        );
+        let vertices = VertexData::new(vertex_buffer, index_buffer, model_layout_info);
 
         let model = Model {
             vertices,
-            texture: self.blocks_texture.clone(),
+            material: Material {
+                alpha_mode,
+                ..Material::new(self.blocks_texture.clone())
+            },
         };
 
         let chunk_x = (coordinate.i * CHUNK_WIDTH as i32) as f32;
@@ -161,28 +393,236 @@ impl MeshGenerator {
     }
 }
 
+impl TranslucentMesh {
+    /// Reorder the faces so that they're drawn from furthest to nearest when seen from `viewpoint`
+    ///
+    /// This only needs doing for the chunk that the viewpoint is in, as faces further away are
+    /// hardly ever seen through one another out of order.
+    pub(crate) fn sort_faces(&mut self, viewpoint: Location) {
+        let viewpoint = viewpoint - self.object.position.coords;
+        if self.sorted_from == Some(viewpoint) {
+            return;
+        }
+
+        let indices = back_to_front_indices(&self.face_centres, &viewpoint);
+        self.object.model.vertices.set_indices(&indices);
+        self.sorted_from = Some(viewpoint);
+    }
+}
+
+/// The block at `(x, y, z)` relative to the chunk at `chunk_origin`, which is looked up in `world`
+/// if it's outside the chunk
+///
+/// Returns `None` if the position lies above or below the world, or in a chunk that isn't loaded.
+fn block_beside(
+    chunk: &Chunk,
+    world: &impl BlockLookup,
+    chunk_origin: BlockPosition,
+    x: i32,
+    y: i32,
+    z: i32,
+) -> Option<Block> {
+    let in_chunk = (0..CHUNK_WIDTH as i32).contains(&x)
+        && (0..CHUNK_HEIGHT as i32).contains(&y)
+        && (0..CHUNK_DEPTH as i32).contains(&z);
+    if in_chunk {
+        Some(chunk.get_block_at(x as usize, y as usize, z as usize))
+    } else {
+        world.get_block_at(chunk_origin + Vector3::new(x, y, z))
+    }
+}
+
+/// Whether `neighbour` hides the face of `block` that touches it, where a neighbour of `None`
+/// isn't loaded and so hides nothing
+///
+/// Opaque blocks hide every face that touches them, while translucent blocks hide the faces of
+/// other blocks of the same kind, with flowing water counting as the same kind as its source.
+/// Cutout blocks like leaves can be seen through to the faces behind them, so they don't hide
+/// anything.
+fn hides_face(block: Block, neighbour: Option<Block>) -> bool {
+    neighbour.is_some_and(|neighbour| {
+        let same_material = neighbour == block || (neighbour.is_fluid() && block.is_fluid());
+        neighbour.is_opaque() || (same_material && block.render_layer() == RenderLayer::Translucent)
+    })
+}
+
+/// The height from which the side of `block` that touches `neighbour` can be seen, up to the top
+/// of the block at `block_height(block)`
+///
+/// A fluid beside another fluid is hidden only as far up as the other fluid reaches, leaving a
+/// strip showing above it if it's the higher of the two. Otherwise, the side is either hidden
+/// completely or not at all.
+fn exposed_side_bottom(block: Block, neighbour: Option<Block>) -> f32 {
+    match neighbour {
+        Some(neighbour) if block.is_fluid() && neighbour.is_fluid() => {
+            block_height(neighbour).min(block_height(block))
+        }
+        _ if hides_face(block, neighbour) => block_height(block),
+        _ => 0.0,
+    }
+}
+
+/// How high up its block the top of a block is drawn, which is lower for fluids that aren't full
 #[inline]
-fn emit_pos_x_face(
+fn block_height(block: Block) -> f32 {
+    block
+        .fluid_level()
+        .map_or(1.0, |level| level as f32 / SOURCE_LEVEL as f32)
+}
+
+/// Find the centre of each quad in a vertex buffer made by `emit_quad`
+fn face_centres(vertex_buffer: &[f32]) -> Vec<Point3<f32>> {
+    vertex_buffer
+        .chunks_exact(4 * FLOATS_PER_VERTEX as usize)
+        .map(|quad| {
+            let sum = quad
+                .chunks_exact(FLOATS_PER_VERTEX as usize)
+                .fold(Vector3::zeros(), |sum, vertex| {
+                    sum + Vector3::new(vertex[0], vertex[1], vertex[2])
+                });
+            Point3::from(sum / 4.0)
+        })
+        .collect()
+}
+
+/// Build an index buffer that draws the quads with the given centres from furthest to nearest to
+/// `viewpoint`
+fn back_to_front_indices(face_centres: &[Point3<f32>], viewpoint: &Point3<f32>) -> Vec<u32> {
+    let mut order: Vec<(f32, u32)> = face_centres
+        .iter()
+        .enumerate()
+        .map(|(i, centre)| ((centre - viewpoint).norm_squared(), i as u32))
+        .collect();
+    order.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+    order
+        .into_iter()
+        .flat_map(|(_, face)| quad_indices(face * 4))
+        .collect()
+}
+
+/// The indices of the two triangles making up a quad whose vertices start at `first_vertex`
+#[inline]
+fn quad_indices(first_vertex: u32) -> [u32; 6] {
+    let i = first_vertex;
+    [i, i + 1, i + 2, i + 2, i + 3, i]
+}
+
+/// Create the faces of a cube-shaped block at `position` that aren't hidden by the blocks beside
+/// it, which `beside` looks up by their offset from the block
+///
+/// A fluid that isn't full is drawn only as high as its level, so its top is never hidden, and
+/// it doesn't hide the bottom of a fluid above it.
+fn emit_block(
     block: Block,
-    x: f32,
-    y: f32,
-    z: f32,
+    face_texture: impl Fn(CubeFace) -> FaceTexture,
+    beside: impl Fn(i32, i32, i32) -> Option<Block>,
+    position: Point3<f32>,
+    lighting: &VertexLighting<impl LightStorage>,
     vertex_buffer: &mut Vec<f32>,
     index_buffer: &mut Vec<u32>,
 ) {
+    let height = block_height(block);
+
+    let bottom = exposed_side_bottom(block, beside(1, 0, 0));
+    if bottom < height {
+        emit_pos_x_face(
+            face_texture(CubeFace::PosX),
+            position,
+            bottom,
+            height,
+            lighting,
+            vertex_buffer,
+            index_buffer,
+        );
+    }
+
+    let bottom = exposed_side_bottom(block, beside(-1, 0, 0));
+    if bottom < height {
+        emit_neg_x_face(
+            face_texture(CubeFace::NegX),
+            position,
+            bottom,
+            height,
+            lighting,
+            vertex_buffer,
+            index_buffer,
+        );
+    }
+
+    if height < 1.0 || !hides_face(block, beside(0, 1, 0)) {
+        emit_pos_y_face(
+            face_texture(CubeFace::PosY),
+            position,
+            height,
+            lighting,
+            vertex_buffer,
+            index_buffer,
+        );
+    }
+
+    let below = beside(0, -1, 0);
+    if !hides_face(block, below) || below.is_some_and(|below| block_height(below) < 1.0) {
+        emit_neg_y_face(
+            face_texture(CubeFace::NegY),
+            position,
+            lighting,
+            vertex_buffer,
+            index_buffer,
+        );
+    }
+
+    let bottom = exposed_side_bottom(block, beside(0, 0, 1));
+    if bottom < height {
+        emit_pos_z_face(
+            face_texture(CubeFace::PosZ),
+            position,
+            bottom,
+            height,
+            lighting,
+            vertex_buffer,
+            index_buffer,
+        );
+    }
+
+    let bottom = exposed_side_bottom(block, beside(0, 0, -1));
+    if bottom < height {
+        emit_neg_z_face(
+            face_texture(CubeFace::NegZ),
+            position,
+            bottom,
+            height,
+            lighting,
+            vertex_buffer,
+            index_buffer,
+        );
+    }
+}
+
+#[inline]
+fn emit_pos_x_face(
+    texture: FaceTexture,
+    position: Point3<f32>,
+    bottom: f32,
+    height: f32,
+    lighting: &VertexLighting<impl LightStorage>,
+    vertex_buffer: &mut Vec<f32>,
+    index_buffer: &mut Vec<u32>,
+) {
+    let (x, y, z) = (position.x, position.y, position.z);
     let normal = Vector3::new(1.0, 0.0, 0.0);
     let points = [
-        Point3::new(x + 1.0, y + 1.0, z + 1.0),
-        Point3::new(x + 1.0, y + 0.0, z + 1.0),
-        Point3::new(x + 1.0, y + 0.0, z + 0.0),
-        Point3::new(x + 1.0, y + 1.0, z + 0.0),
+        Point3::new(x + 1.0, y + height, z + 1.0),
+        Point3::new(x + 1.0, y + bottom, z + 1.0),
+        Point3::new(x + 1.0, y + bottom, z + 0.0),
+        Point3::new(x + 1.0, y + height, z + 0.0),
     ];
 
     emit_face(
         &points,
         normal,
-        block,
-        CubeFace::PosX,
+        texture,
+        lighting,
         vertex_buffer,
         index_buffer,
     );
@@ -190,26 +630,28 @@ fn emit_pos_x_face(
 
 #[inline]
 fn emit_neg_x_face(
-    block: Block,
-    x: f32,
-    y: f32,
-    z: f32,
+    texture: FaceTexture,
+    position: Point3<f32>,
+    bottom: f32,
+    height: f32,
+    lighting: &VertexLighting<impl LightStorage>,
     vertex_buffer: &mut Vec<f32>,
     index_buffer: &mut Vec<u32>,
 ) {
+    let (x, y, z) = (position.x, position.y, position.z);
     let normal = Vector3::new(-1.0, 0.0, 0.0);
     let points = [
-        Point3::new(x, y + 1.0, z),
-        Point3::new(x, y, z),
-        Point3::new(x, y, z + 1.0),
-        Point3::new(x, y + 1.0, z + 1.0),
+        Point3::new(x, y + height, z),
+        Point3::new(x, y + bottom, z),
+        Point3::new(x, y + bottom, z + 1.0),
+        Point3::new(x, y + height, z + 1.0),
     ];
 
     emit_face(
         &points,
         normal,
-        block,
-        CubeFace::NegX,
+        texture,
+        lighting,
         vertex_buffer,
         index_buffer,
     );
@@ -217,26 +659,27 @@ fn emit_neg_x_face(
 
 #[inline]
 fn emit_pos_y_face(
-    block: Block,
-    x: f32,
-    y: f32,
-    z: f32,
+    texture: FaceTexture,
+    position: Point3<f32>,
+    height: f32,
+    lighting: &VertexLighting<impl LightStorage>,
     vertex_buffer: &mut Vec<f32>,
     index_buffer: &mut Vec<u32>,
 ) {
+    let (x, y, z) = (position.x, position.y, position.z);
     let normal = Vector3::new(0.0, 1.0, 0.0);
     let points = [
-        Point3::new(x, y + 1.0, z),
-        Point3::new(x, y + 1.0, z + 1.0),
-        Point3::new(x + 1.0, y + 1.0, z + 1.0),
-        Point3::new(x + 1.0, y + 1.0, z),
+        Point3::new(x, y + height, z),
+        Point3::new(x, y + height, z + 1.0),
+        Point3::new(x + 1.0, y + height, z + 1.0),
+        Point3::new(x + 1.0, y + height, z),
     ];
 
     emit_face(
         &points,
         normal,
-        block,
-        CubeFace::PosY,
+        texture,
+        lighting,
         vertex_buffer,
         index_buffer,
     );
@@ -244,13 +687,13 @@ fn emit_pos_y_face(
 
 #[inline]
 fn emit_neg_y_face(
-    block: Block,
-    x: f32,
-    y: f32,
-    z: f32,
+    texture: FaceTexture,
+    position: Point3<f32>,
+    lighting: &VertexLighting<impl LightStorage>,
     vertex_buffer: &mut Vec<f32>,
     index_buffer: &mut Vec<u32>,
 ) {
+    let (x, y, z) = (position.x, position.y, position.z);
     let normal = Vector3::new(0.0, -1.0, 0.0);
     let points = [
         Point3::new(x, y, z + 1.0),
@@ -262,8 +705,8 @@ fn emit_neg_y_face(
     emit_face(
         &points,
         normal,
-        block,
-        CubeFace::NegY,
+        texture,
+        lighting,
         vertex_buffer,
         index_buffer,
     );
@@ -271,26 +714,28 @@ fn emit_neg_y_face(
 
 #[inline]
 fn emit_pos_z_face(
-    block: Block,
-    x: f32,
-    y: f32,
-    z: f32,
+    texture: FaceTexture,
+    position: Point3<f32>,
+    bottom: f32,
+    height: f32,
+    lighting: &VertexLighting<impl LightStorage>,
     vertex_buffer: &mut Vec<f32>,
     index_buffer: &mut Vec<u32>,
 ) {
+    let (x, y, z) = (position.x, position.y, position.z);
     let normal = Vector3::new(0.0, 0.0, 1.0);
     let points = [
-        Point3::new(x + 0.0, y + 1.0, z + 1.0),
-        Point3::new(x + 0.0, y + 0.0, z + 1.0),
-        Point3::new(x + 1.0, y + 0.0, z + 1.0),
-        Point3::new(x + 1.0, y + 1.0, z + 1.0),
+        Point3::new(x + 0.0, y + height, z + 1.0),
+        Point3::new(x + 0.0, y + bottom, z + 1.0),
+        Point3::new(x + 1.0, y + bottom, z + 1.0),
+        Point3::new(x + 1.0, y + height, z + 1.0),
     ];
 
     emit_face(
         &points,
         normal,
-        block,
-        CubeFace::NegZ,
+        texture,
+        lighting,
         vertex_buffer,
         index_buffer,
     );
@@ -298,130 +743,471 @@ fn emit_pos_z_face(
 
 #[inline]
 fn emit_neg_z_face(
-    block: Block,
-    x: f32,
-    y: f32,
-    z: f32,
+    texture: FaceTexture,
+    position: Point3<f32>,
+    bottom: f32,
+    height: f32,
+    lighting: &VertexLighting<impl LightStorage>,
     vertex_buffer: &mut Vec<f32>,
     index_buffer: &mut Vec<u32>,
 ) {
+    let (x, y, z) = (position.x, position.y, position.z);
     let normal = Vector3::new(0.0, 0.0, -1.0);
     let points = [
-        Point3::new(x + 1.0, y + 1.0, z),
-        Point3::new(x + 1.0, y, z),
-        Point3::new(x, y, z),
-        Point3::new(x, y + 1.0, z),
+        Point3::new(x + 1.0, y + height, z),
+        Point3::new(x + 1.0, y + bottom, z),
+        Point3::new(x, y + bottom, z),
+        Point3::new(x, y + height, z),
     ];
 
     emit_face(
         &points,
         normal,
-        block,
-        CubeFace::PosZ,
+        texture,
+        lighting,
         vertex_buffer,
         index_buffer,
     );
 }
 
+/// Create a torch, which is a thin upright stick in the middle of its block
+fn emit_torch(
+    face_texture: impl Fn(CubeFace) -> FaceTexture,
+    x: f32,
+    y: f32,
+    z: f32,
+    light: [f32; 2],
+    vertex_buffer: &mut Vec<f32>,
+    index_buffer: &mut Vec<u32>,
+) {
+    let min = Point3::new(x + 0.5 - TORCH_HALF_WIDTH, y, z + 0.5 - TORCH_HALF_WIDTH);
+    let max = Point3::new(
+        x + 0.5 + TORCH_HALF_WIDTH,
+        y + TORCH_HEIGHT,
+        z + 0.5 + TORCH_HALF_WIDTH,
+    );
+
+    // The corners of each face of a unit cube, in the same order as the full-size block faces
+    let faces = [
+        (CubeFace::PosX, [[1, 1, 1], [1, 0, 1], [1, 0, 0], [1, 1, 0]]),
+        (CubeFace::NegX, [[0, 1, 0], [0, 0, 0], [0, 0, 1], [0, 1, 1]]),
+        (CubeFace::PosY, [[0, 1, 0], [0, 1, 1], [1, 1, 1], [1, 1, 0]]),
+        (CubeFace::NegY, [[0, 0, 1], [0, 0, 0], [1, 0, 0], [1, 0, 1]]),
+        (CubeFace::PosZ, [[0, 1, 1], [0, 0, 1], [1, 0, 1], [1, 1, 1]]),
+        (CubeFace::NegZ, [[1, 1, 0], [1, 0, 0], [0, 0, 0], [0, 1, 0]]),
+    ];
+
+    for (face, corners) in faces {
+        let points = corners.map(|corner| {
+            Point3::new(
+                if corner[0] == 0 { min.x } else { max.x },
+                if corner[1] == 0 { min.y } else { max.y },
+                if corner[2] == 0 { min.z } else { max.z },
+            )
+        });
+        let normal = face.normal().map(|c| c as f32);
+        emit_quad(
+            &points,
+            &[normal; 4],
+            face_texture(face),
+            &[light; 4],
+            vertex_buffer,
+            index_buffer,
+        );
+    }
+}
+
 /// Create a face for a cube
 fn emit_face(
     points: &[Point3<f32>; 4],
     normal: Vector3<f32>,
-    block: Block,
-    face: CubeFace,
+    texture: FaceTexture,
+    lighting: &VertexLighting<impl LightStorage>,
+    vertex_buffer: &mut Vec<f32>,
+    index_buffer: &mut Vec<u32>,
+) {
+    let lights = points.map(|point| lighting.sample(&point, &normal));
+    emit_quad(
+        points,
+        &[normal; 4],
+        texture,
+        &lights,
+        vertex_buffer,
+        index_buffer,
+    );
+}
+
+/// Create a quad from four points given in counter-clockwise order, each with its own normal and
+/// light levels, covered by one whole layer of the blocks texture or by each frame in turn
+fn emit_quad(
+    points: &[Point3<f32>; 4],
+    normals: &[Vector3<f32>; 4],
+    texture: FaceTexture,
+    lights: &[[f32; 2]; 4],
     vertex_buffer: &mut Vec<f32>,
     index_buffer: &mut Vec<u32>,
 ) {
-    let index = (vertex_buffer.len() as u32) / 8;
-    let (tex_coords_start, tex_coords_end) = get_texture_coordinates(block, face);
+    let index = (vertex_buffer.len() as u32) / FLOATS_PER_VERTEX;
+    let layer = texture.layer as f32;
+    let frames = texture.frames as f32;
 
     vertex_buffer.extend_from_slice(&[
         points[0].x,
         points[0].y,
         points[0].z,
-        normal.x,
-        normal.y,
-        normal.z,
-        tex_coords_start.u,
-        tex_coords_start.v,
+        normals[0].x,
+        normals[0].y,
+        normals[0].z,
+        0.0,
+        0.0,
+        layer,
+        frames,
+        lights[0][0],
+        lights[0][1],
     ]);
 
     vertex_buffer.extend_from_slice(&[
         points[1].x,
         points[1].y,
         points[1].z,
-        normal.x,
-        normal.y,
-        normal.z,
-        tex_coords_start.u,
-        tex_coords_end.v,
+        normals[1].x,
+        normals[1].y,
+        normals[1].z,
+        0.0,
+        1.0,
+        layer,
+        frames,
+        lights[1][0],
+        lights[1][1],
     ]);
 
     vertex_buffer.extend_from_slice(&[
         points[2].x,
         points[2].y,
         points[2].z,
-        normal.x,
-        normal.y,
-        normal.z,
-        tex_coords_end.u,
-        tex_coords_end.v,
+        normals[2].x,
+        normals[2].y,
+        normals[2].z,
+        1.0,
+        1.0,
+        layer,
+        frames,
+        lights[2][0],
+        lights[2][1],
     ]);
 
     vertex_buffer.extend_from_slice(&[
         points[3].x,
         points[3].y,
         points[3].z,
-        normal.x,
-        normal.y,
-        normal.z,
-        tex_coords_end.u,
-        tex_coords_start.v,
+        normals[3].x,
+        normals[3].y,
+        normals[3].z,
+        1.0,
+        0.0,
+        layer,
+        frames,
+        lights[3][0],
+        lights[3][1],
     ]);
 
-    index_buffer.extend_from_slice(&[index, index + 1, index + 2, index + 2, index + 3, index]);
+    index_buffer.extend_from_slice(&quad_indices(index));
 }
 
-#[inline]
-pub(crate) fn get_texture_coordinates(
-    block: Block,
-    cube_face: CubeFace,
-) -> (TextureCoordinate, TextureCoordinate) {
-    let u_index = match cube_face {
-        CubeFace::PosX => 5,
-        CubeFace::NegX => 4,
-        CubeFace::PosY => 0,
-        CubeFace::NegY => 1,
-        CubeFace::PosZ => 3,
-        CubeFace::NegZ => 2,
-    };
-
-    let v_index = match block {
-        Block::Grass => 0,
-        Block::Dirt => 1,
-        Block::Stone => 2,
-
-        _ => panic!("Don't have a texture mapping for block type: {:?}", block),
-    };
-
-    // We have to add an epsilon to avoid a rendering bug in which texture coordinates accidentally
-    // round to an adjacent texture, causing a random colour border around blocks' edges.
-
-    // TODO: Come up with a better fix
-
-    let u_start = (u_index as f32) / 6.0;
-    let v_start = (v_index as f32) / (NON_EMPTY_BLOCKS_COUNT as f32);
-    let start_coords = TextureCoordinate {
-        u: u_start + EPSILON,
-        v: v_start + EPSILON,
-    };
-
-    let u_end = u_start + 1.0 / 6.0;
-    let v_end = v_start + (1.0 / (NON_EMPTY_BLOCKS_COUNT as f32));
-    let end_coords = TextureCoordinate {
-        u: u_end - EPSILON,
-        v: v_end - EPSILON,
-    };
-
-    (start_coords, end_coords)
+/// Looks up the light reaching the corners of faces in a chunk
+struct VertexLighting<'a, W: LightStorage> {
+    world: &'a W,
+    chunk_origin: BlockPosition,
+}
+
+impl<W: LightStorage> VertexLighting<'_, W> {
+    /// Work out the sky and block light levels at a corner of a face, each between 0 and 1
+    ///
+    /// `corner` is relative to the chunk's origin. The light is averaged over the four blocks that
+    /// touch the corner on the side of the face that `normal` points towards, ignoring any opaque
+    /// or unloaded blocks.
+    fn sample(&self, corner: &Point3<f32>, normal: &Vector3<f32>) -> [f32; 2] {
+        let corner = self.chunk_origin + corner.coords.map(|c| c.round() as i32);
+
+        let mut total = [0u32; 2];
+        let mut count = 0;
+        for i in 0..4 {
+            let mut tangent_steps = [i & 1, (i >> 1) & 1].into_iter();
+            let mut position = corner;
+            for axis in 0..3 {
+                if normal[axis] < 0.0 {
+                    position[axis] -= 1;
+                } else if normal[axis] == 0.0 {
+                    position[axis] -= tangent_steps.next().unwrap();
+                }
+            }
+
+            if self
+                .world
+                .get_block_at(position)
+                .is_none_or(|block| block.is_opaque())
+            {
+                continue;
+            }
+            let sky = self.world.get_light_at(position, LightChannel::Sky);
+            let block = self.world.get_light_at(position, LightChannel::Block);
+            if let (Some(sky), Some(block)) = (sky, block) {
+                total[0] += sky as u32;
+                total[1] += block as u32;
+                count += 1;
+            }
+        }
+
+        if count == 0 {
+            return [0.0, 0.0];
+        }
+        total.map(|level| level as f32 / (count * MAX_LIGHT_LEVEL as u32) as f32)
+    }
+
+    /// Get the sky and block light levels of a single block, each between 0 and 1
+    ///
+    /// `position` is relative to the chunk's origin.
+    fn at_block(&self, position: BlockPosition) -> [f32; 2] {
+        let position = self.chunk_origin + position.coords;
+        [LightChannel::Sky, LightChannel::Block].map(|channel| {
+            let level = self.world.get_light_at(position, channel).unwrap_or(0);
+            level as f32 / MAX_LIGHT_LEVEL as f32
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::ChunksState;
+    use rstest::*;
+    use sbs5k_core::light::ChunkLight;
+    use sbs5k_core::world::BlockStorage;
+
+    fn state_with_chunk(coordinate: ChunkCoordinate, chunk: Chunk) -> ChunksState {
+        let mut state = ChunksState::new(1, 0);
+        let light = ChunkLight::compute(&chunk);
+        state.set_chunk(coordinate, Some((chunk, light)));
+        state
+    }
+
+    #[rstest]
+    #[case(ChunkCoordinate{i: 0, j: 0})]
+    #[case(ChunkCoordinate{i: -1, j: 1})]
+    fn corners_open_to_the_sky_are_fully_lit(#[case] coordinate: ChunkCoordinate) {
+        let mut chunk = Chunk::default();
+        chunk.set_block_at(5, 64, 5, Block::Stone);
+        let state = state_with_chunk(coordinate, chunk);
+        let lighting = VertexLighting {
+            world: &state,
+            chunk_origin: BlockPosition::new(coordinate.i * 16, 0, coordinate.j * 16),
+        };
+
+        let light = lighting.sample(&Point3::new(5.0, 65.0, 5.0), &Vector3::new(0.0, 1.0, 0.0));
+        assert_eq!([1.0, 0.0], light);
+    }
+
+    #[rstest]
+    fn light_is_averaged_over_the_blocks_in_front_of_the_corner() {
+        let coordinate = ChunkCoordinate { i: 0, j: 0 };
+        let mut state = ChunksState::new(1, 0);
+        state.set_chunk(coordinate, Some(Default::default()));
+        let levels = [
+            (BlockPosition::new(4, 9, 4), 15),
+            (BlockPosition::new(4, 9, 5), 15),
+            (BlockPosition::new(4, 10, 4), 0),
+            (BlockPosition::new(4, 10, 5), 6),
+            // Behind the face, so shouldn't be counted
+            (BlockPosition::new(5, 10, 5), 15),
+        ];
+        for (position, level) in levels {
+            state.set_light_at(position, LightChannel::Block, level);
+        }
+        let lighting = VertexLighting {
+            world: &state,
+            chunk_origin: BlockPosition::new(0, 0, 0),
+        };
+
+        let light = lighting.sample(&Point3::new(5.0, 10.0, 5.0), &Vector3::new(-1.0, 0.0, 0.0));
+        assert_eq!([0.0, 0.6], light);
+    }
+
+    #[rstest]
+    fn opaque_blocks_are_left_out_of_the_average() {
+        let coordinate = ChunkCoordinate { i: 0, j: 0 };
+        let mut state = ChunksState::new(1, 0);
+        state.set_chunk(coordinate, Some(Default::default()));
+        state.set_light_at(BlockPosition::new(2, 3, 5), LightChannel::Sky, 12);
+        state.set_block_at(BlockPosition::new(3, 3, 5), Block::Stone);
+        state.set_block_at(BlockPosition::new(2, 4, 5), Block::Stone);
+        state.set_block_at(BlockPosition::new(3, 4, 5), Block::Stone);
+        let lighting = VertexLighting {
+            world: &state,
+            chunk_origin: BlockPosition::new(0, 0, 0),
+        };
+
+        let light = lighting.sample(&Point3::new(3.0, 4.0, 5.0), &Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!([0.8, 0.0], light);
+
+        state.set_block_at(BlockPosition::new(2, 3, 5), Block::Stone);
+        let lighting = VertexLighting {
+            world: &state,
+            chunk_origin: BlockPosition::new(0, 0, 0),
+        };
+        let light = lighting.sample(&Point3::new(3.0, 4.0, 5.0), &Vector3::new(0.0, 0.0, 1.0));
+        assert_eq!([0.0, 0.0], light);
+    }
+
+    #[rstest]
+    #[case(Block::Glass, Block::Stone, true)]
+    #[case(Block::Glass, Block::Glass, true)]
+    #[case(Block::Stone, Block::Glass, false)]
+    #[case(Block::Leaves, Block::Leaves, false)]
+    #[case(Block::Stone, Block::Leaves, false)]
+    #[case(Block::Glass, Block::Leaves, false)]
+    fn faces_are_hidden_by_opaque_blocks_and_matching_translucent_blocks(
+        #[case] block: Block,
+        #[case] neighbour: Block,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(expected, hides_face(block, Some(neighbour)));
+    }
+
+    #[rstest]
+    fn water_hides_the_faces_of_water_in_the_next_chunk_once_it_loads() {
+        let west = ChunkCoordinate { i: 0, j: 0 };
+        let east = ChunkCoordinate { i: 1, j: 0 };
+        let mut chunk = Chunk::default();
+        for z in 0..CHUNK_DEPTH {
+            chunk.set_block_at(0, 60, z, Block::Water);
+            chunk.set_block_at(CHUNK_WIDTH - 1, 60, z, Block::Water);
+        }
+        let mut state = state_with_chunk(west, chunk);
+        let west_origin = BlockPosition::new(0, 0, 0);
+        let east_origin = BlockPosition::new(CHUNK_WIDTH as i32, 0, 0);
+
+        let across_border = block_beside(&chunk, &state, west_origin, 16, 60, 5);
+        assert!(!hides_face(Block::Water, across_border));
+
+        state.set_chunk(east, Some((chunk, ChunkLight::compute(&chunk))));
+        let across_border = block_beside(&chunk, &state, west_origin, 16, 60, 5);
+        assert!(hides_face(Block::Water, across_border));
+        let across_border = block_beside(&chunk, &state, east_origin, -1, 60, 5);
+        assert!(hides_face(Block::Water, across_border));
+
+        // Nothing lies above or below the world to hide the faces there
+        assert_eq!(None, block_beside(&chunk, &state, west_origin, 5, -1, 5));
+    }
+
+    /// The heights of the vertices in `vertices` that belong to faces facing along `normal`
+    fn face_heights(vertices: &[f32], normal: [f32; 3]) -> Vec<f32> {
+        vertices
+            .chunks_exact(FLOATS_PER_VERTEX as usize)
+            .filter(|vertex| vertex[3..6] == normal)
+            .map(|vertex| vertex[1])
+            .collect()
+    }
+
+    #[rstest]
+    fn a_fluid_beside_a_lower_fluid_shows_only_the_strip_above_it() {
+        let state = state_with_chunk(ChunkCoordinate { i: 0, j: 0 }, Chunk::default());
+        let lighting = VertexLighting {
+            world: &state,
+            chunk_origin: BlockPosition::new(0, 0, 0),
+        };
+        let texture = |_| FaceTexture {
+            layer: 0,
+            frames: 1,
+        };
+        let (mut vertices, mut indices) = (vec![], vec![]);
+
+        // Source water, with water at half its level to the east
+        emit_block(
+            Block::Water,
+            texture,
+            |dx, dy, dz| match (dx, dy, dz) {
+                (1, 0, 0) => Some(Block::FlowingWater4),
+                _ => Some(Block::Empty),
+            },
+            Point3::new(4.0, 60.0, 4.0),
+            &lighting,
+            &mut vertices,
+            &mut indices,
+        );
+        let mut heights = face_heights(&vertices, [1.0, 0.0, 0.0]);
+        heights.sort_by(f32::total_cmp);
+        assert_eq!(vec![60.5, 60.5, 61.0, 61.0], heights);
+
+        // The lower water, whose side is hidden by the source beside it
+        vertices.clear();
+        emit_block(
+            Block::FlowingWater4,
+            texture,
+            |dx, dy, dz| match (dx, dy, dz) {
+                (-1, 0, 0) => Some(Block::Water),
+                _ => Some(Block::Empty),
+            },
+            Point3::new(5.0, 60.0, 4.0),
+            &lighting,
+            &mut vertices,
+            &mut indices,
+        );
+        assert!(face_heights(&vertices, [-1.0, 0.0, 0.0]).is_empty());
+        assert_eq!(vec![60.5; 4], face_heights(&vertices, [0.0, 1.0, 0.0]));
+    }
+
+    #[rstest]
+    #[case(Block::Stone, 1.0)]
+    #[case(Block::Water, 1.0)]
+    #[case(Block::FlowingWater8, 1.0)]
+    #[case(Block::FlowingWater6, 0.75)]
+    #[case(Block::FlowingWater1, 0.125)]
+    fn fluids_are_drawn_as_high_as_their_level(#[case] block: Block, #[case] expected: f32) {
+        assert_eq!(expected, block_height(block));
+    }
+
+    #[rstest]
+    fn faces_are_sorted_from_furthest_to_nearest() {
+        let centres = [
+            Point3::new(0.5, 64.0, 0.5),
+            Point3::new(0.5, 64.0, 8.5),
+            Point3::new(0.5, 64.0, 4.5),
+        ];
+
+        let indices = back_to_front_indices(&centres, &Point3::new(0.5, 64.0, 10.0));
+
+        let mut expected = vec![];
+        for face in [0, 2, 1] {
+            expected.extend_from_slice(&quad_indices(face * 4));
+        }
+        assert_eq!(expected, indices);
+    }
+
+    #[rstest]
+    fn face_centres_are_found_from_the_vertex_buffer() {
+        let mut vertex_buffer = vec![];
+        let mut index_buffer = vec![];
+        let points = [
+            Point3::new(1.0, 2.0, 3.0),
+            Point3::new(1.0, 3.0, 3.0),
+            Point3::new(1.0, 3.0, 4.0),
+            Point3::new(1.0, 2.0, 4.0),
+        ];
+        emit_quad(
+            &points,
+            &[Vector3::new(1.0, 0.0, 0.0); 4],
+            FaceTexture {
+                layer: 0,
+                frames: 1,
+            },
+            &[[1.0, 0.0]; 4],
+            &mut vertex_buffer,
+            &mut index_buffer,
+        );
+
+        assert_eq!(
+            vec![Point3::new(1.0, 2.5, 3.5)],
+            face_centres(&vertex_buffer)
+        );
+    }
 }
@@ -1,5 +1,8 @@
+mod block_texture_packing;
+mod block_textures;
 mod chunk_loading;
 mod mesh_generation;
 
+pub(crate) use block_textures::BlockTextures;
 pub(crate) use chunk_loading::{ChunkLoadResult, ChunkLoader};
-pub(crate) use mesh_generation::MeshGenerator;
+pub(crate) use mesh_generation::{ChunkMeshes, MeshGenerator};
@@ -1,13 +1,16 @@
 mod args;
 mod constants;
 mod controls;
+mod daylight;
 mod debug;
 mod driver;
 mod event;
+mod hot_reload;
 mod initialisation;
 mod loading;
 mod resources;
 mod state;
+mod world_metadata;
 
 use std::sync::Arc;
 
@@ -21,7 +24,10 @@ use crate::driver::Driver;
 fn main() {
     let config = Arc::new(Args::parse());
 
-    let chunks_source = Box::new(PerlinNoiseGenerator::new());
-    let mut driver = Driver::new(config, chunks_source);
+    let metadata = world_metadata::load_or_create(&config);
+
+    let chunks_source =
+        Box::new(PerlinNoiseGenerator::with_seed(metadata.seed).with_sea_level(metadata.sea_level));
+    let mut driver = Driver::new(config, chunks_source, metadata.spawn_point);
     driver.run_game();
 }
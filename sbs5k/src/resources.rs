@@ -1,3 +1,72 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use sbs5k_engine::ShaderSourceProvider;
+
+/// The root of the source tree, which is laid out like a resource pack. In development mode it's
+/// used as one, so that edits to the built-in shaders and textures take effect without a rebuild.
+pub(crate) const SOURCE_TREE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/..");
+
 pub(crate) mod textures {
-    pub const CUBES: &[u8] = include_bytes!("../../textures/cubes.png");
+    include!(concat!(env!("OUT_DIR"), "/block_textures.rs"));
+}
+
+/// Directories of files that replace the textures, shaders and block definitions built into the
+/// game
+///
+/// A resource pack is laid out like the repository: shaders go in `shaders/`, block definitions
+/// in `textures/blocks.json` and block face textures in `textures/blocks/`. Any file that a pack
+/// leaves out falls back to the built-in one.
+pub(crate) struct ResourcePacks {
+    /// The packs' directories, with the highest priority first
+    dirs: Vec<PathBuf>,
+}
+
+impl ResourcePacks {
+    /// Use the packs in the given directories, where packs later in the list take priority over
+    /// earlier ones
+    pub(crate) fn new(dirs: &[PathBuf]) -> Self {
+        for dir in dirs {
+            if !dir.is_dir() {
+                panic!("Resource pack {} is not a directory", dir.display());
+            }
+        }
+
+        ResourcePacks {
+            dirs: dirs.iter().rev().cloned().collect(),
+        }
+    }
+
+    /// The packs' directories, with the highest priority first
+    pub(crate) fn dirs(&self) -> &[PathBuf] {
+        &self.dirs
+    }
+
+    /// Read a file, given relative to the root of a pack, from the highest-priority pack that has
+    /// it. Returns the file's contents and where it was found, or `None` if no pack has it.
+    pub(crate) fn read(&self, path: impl AsRef<Path>) -> Option<(Vec<u8>, PathBuf)> {
+        let path = self.find(path)?;
+        let contents = fs::read(&path)
+            .unwrap_or_else(|err| panic!("Failed to read {}: {}", path.display(), err));
+        Some((contents, path))
+    }
+
+    /// Whether any pack has a file or directory at the given path
+    pub(crate) fn contains(&self, path: impl AsRef<Path>) -> bool {
+        self.find(path).is_some()
+    }
+
+    fn find(&self, path: impl AsRef<Path>) -> Option<PathBuf> {
+        self.dirs
+            .iter()
+            .map(|dir| dir.join(path.as_ref()))
+            .find(|path| path.exists())
+    }
+}
+
+impl ShaderSourceProvider for ResourcePacks {
+    fn shader_source(&self, name: &str) -> Option<(Vec<u8>, String)> {
+        self.read(Path::new("shaders").join(name))
+            .map(|(src, path)| (src, path.display().to_string()))
+    }
 }
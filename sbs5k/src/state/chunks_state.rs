@@ -1,16 +1,40 @@
-use sbs5k_core::chunk::{Chunk, ChunkCoordinate};
+use nalgebra::Vector3;
+
+use sbs5k_core::block::Block;
+use sbs5k_core::chunk::{
+    block_position_within_chunk, Chunk, ChunkCoordinate, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH,
+};
+use sbs5k_core::geometry::{BlockPosition, Location};
+use sbs5k_core::light::{ChunkLight, LightChannel, LightStorage};
 use sbs5k_core::maths::modulo;
+use sbs5k_core::world::{BlockLookup, BlockStorage};
 use sbs5k_engine::SceneObject;
 
+use crate::loading::ChunkMeshes;
+
+/// A chunk held in memory, along with the light levels of its blocks
+struct LoadedChunk {
+    coordinate: ChunkCoordinate,
+    chunk: Chunk,
+    light: ChunkLight,
+
+    /// The global positions of the blocks in the chunk that give off light
+    light_sources: Vec<BlockPosition>,
+}
+
 /// A wrapper struct to encode all state relating to the management of chunks in the client
 pub(crate) struct ChunksState {
+    render_distance: u32,
+    lod_distance: u32,
     renderable_chunks_square_edge_size: u32,
-    chunks: Vec<Option<Chunk>>,
-    chunk_meshes: Vec<Option<SceneObject>>,
+    chunks: Vec<Option<LoadedChunk>>,
+    chunk_meshes: Vec<Option<ChunkMeshes>>,
+    lod_tiles_square_edge_size: u32,
+    lod_meshes: Vec<Option<ChunkMeshes>>,
 }
 
 impl ChunksState {
-    pub(crate) fn new(render_distance: u32) -> Self {
+    pub(crate) fn new(render_distance: u32, lod_distance: u32) -> Self {
         let renderable_chunks_square_edge_size = 1 + 2 * render_distance;
         let num_renderable_chunks =
             renderable_chunks_square_edge_size * renderable_chunks_square_edge_size;
@@ -21,35 +45,244 @@ impl ChunksState {
         let mut chunk_meshes = vec![];
         chunk_meshes.resize_with(num_renderable_chunks as usize, || None);
 
+        let lod_tiles_square_edge_size = 1 + 2 * (render_distance + lod_distance);
+        let num_lod_tiles = lod_tiles_square_edge_size * lod_tiles_square_edge_size;
+
+        let mut lod_meshes = vec![];
+        lod_meshes.resize_with(num_lod_tiles as usize, || None);
+
         ChunksState {
+            render_distance,
+            lod_distance,
             renderable_chunks_square_edge_size,
             chunks,
             chunk_meshes,
+            lod_tiles_square_edge_size,
+            lod_meshes,
         }
     }
 
     pub(crate) fn renderable_chunks(&self) -> Vec<&SceneObject> {
         self.chunk_meshes
             .iter()
-            .filter_map(|chunk| chunk.as_ref())
+            .filter_map(|meshes| meshes.as_ref().map(|meshes| &meshes.opaque))
+            .collect()
+    }
+
+    /// Get the translucent parts of the chunks and of the low-detail tiles drawn when the player is
+    /// in `player_chunk`, sorted from furthest to nearest to `location`
+    pub(crate) fn renderable_translucent_chunks(
+        &self,
+        player_chunk: ChunkCoordinate,
+        location: Location,
+    ) -> Vec<&SceneObject> {
+        let mut chunks: Vec<(f32, &SceneObject)> = self
+            .chunk_meshes
+            .iter()
+            .flatten()
+            .chain(self.visible_lod_meshes(player_chunk))
+            .filter_map(|meshes| {
+                let translucent = meshes.translucent.as_ref()?;
+                let centre = translucent.object.position
+                    + Vector3::new(CHUNK_WIDTH as f32 / 2.0, 0.0, CHUNK_DEPTH as f32 / 2.0);
+                let distance = (centre.xz() - location.xz()).norm_squared();
+                Some((distance, &translucent.object))
+            })
+            .collect();
+
+        chunks.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        chunks.into_iter().map(|(_, object)| object).collect()
+    }
+
+    /// Sort the translucent faces in the chunk containing `location` from furthest to nearest to
+    /// it, so that they blend properly when seen from there
+    pub(crate) fn sort_translucent_faces(&mut self, location: Location) {
+        let coordinate = ChunkCoordinate::from_player_position(location);
+        let index = get_chunk_index(coordinate, self.renderable_chunks_square_edge_size);
+        if let Some(translucent) = self.chunk_meshes[index]
+            .as_mut()
+            .filter(|meshes| meshes.coordinate == coordinate)
+            .and_then(|meshes| meshes.translucent.as_mut())
+        {
+            translucent.sort_faces(location);
+        }
+    }
+
+    /// Get the low-detail tiles that should be drawn when the player is in `player_chunk`
+    ///
+    /// This excludes any tiles that overlap the full-detail chunks around the player, as well as
+    /// stale tiles that are waiting to be replaced.
+    pub(crate) fn renderable_lod_tiles(&self, player_chunk: ChunkCoordinate) -> Vec<&SceneObject> {
+        self.visible_lod_meshes(player_chunk)
+            .map(|meshes| &meshes.opaque)
+            .collect()
+    }
+
+    /// The meshes of the low-detail tiles that should be drawn when the player is in
+    /// `player_chunk`
+    fn visible_lod_meshes(
+        &self,
+        player_chunk: ChunkCoordinate,
+    ) -> impl Iterator<Item = &ChunkMeshes> {
+        let min_distance = self.render_distance + 1;
+        let max_distance = self.render_distance + self.lod_distance;
+        self.lod_meshes.iter().flatten().filter(move |meshes| {
+            let distance = meshes.coordinate.distance_to(player_chunk);
+            distance >= min_distance && distance <= max_distance
+        })
+    }
+
+    #[inline(always)]
+    pub(crate) fn set_chunk(
+        &mut self,
+        chunk_coord: ChunkCoordinate,
+        value: Option<(Chunk, ChunkLight)>,
+    ) {
+        let index = get_chunk_index(chunk_coord, self.renderable_chunks_square_edge_size);
+        self.chunks[index] = value.map(|(chunk, light)| LoadedChunk {
+            coordinate: chunk_coord,
+            light_sources: find_light_sources(&chunk, chunk_coord),
+            chunk,
+            light,
+        });
+    }
+
+    /// The coordinates of every chunk currently held in memory
+    pub(crate) fn loaded_chunk_coordinates(&self) -> Vec<ChunkCoordinate> {
+        self.chunks
+            .iter()
+            .filter_map(|chunk| chunk.as_ref().map(|chunk| chunk.coordinate))
             .collect()
     }
 
+    /// Find the light-emitting blocks in the loaded chunks that are closest to `location`
+    ///
+    /// At most `max_count` are returned, nearest first.
+    pub(crate) fn nearest_light_sources(
+        &self,
+        location: Location,
+        max_count: usize,
+    ) -> Vec<BlockPosition> {
+        let mut sources: Vec<(f32, BlockPosition)> = self
+            .chunks
+            .iter()
+            .flatten()
+            .flat_map(|loaded| loaded.light_sources.iter())
+            .map(|position| {
+                let centre = position.cast::<f32>() + Vector3::new(0.5, 0.5, 0.5);
+                ((centre - location).norm_squared(), *position)
+            })
+            .collect();
+
+        sources.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        sources.truncate(max_count);
+        sources.into_iter().map(|(_, position)| position).collect()
+    }
+
+    /// Get the chunk at `chunk_coord`, if it is currently loaded
+    #[inline(always)]
+    pub(crate) fn get_chunk(&self, chunk_coord: ChunkCoordinate) -> Option<&Chunk> {
+        self.get_loaded_chunk(chunk_coord)
+            .map(|loaded| &loaded.chunk)
+    }
+
     #[inline(always)]
-    pub(crate) fn set_chunk(&mut self, chunk_coord: ChunkCoordinate, value: Option<Chunk>) {
+    fn get_loaded_chunk(&self, chunk_coord: ChunkCoordinate) -> Option<&LoadedChunk> {
         let index = get_chunk_index(chunk_coord, self.renderable_chunks_square_edge_size);
-        self.chunks[index] = value;
+        self.chunks[index]
+            .as_ref()
+            .filter(|loaded| loaded.coordinate == chunk_coord)
+    }
+
+    #[inline(always)]
+    fn get_loaded_chunk_mut(&mut self, chunk_coord: ChunkCoordinate) -> Option<&mut LoadedChunk> {
+        let index = get_chunk_index(chunk_coord, self.renderable_chunks_square_edge_size);
+        self.chunks[index]
+            .as_mut()
+            .filter(|loaded| loaded.coordinate == chunk_coord)
     }
 
     #[inline(always)]
     pub(crate) fn set_chunk_mesh(
         &mut self,
         chunk_coord: ChunkCoordinate,
-        value: Option<SceneObject>,
+        value: Option<ChunkMeshes>,
     ) {
         let index = get_chunk_index(chunk_coord, self.renderable_chunks_square_edge_size);
         self.chunk_meshes[index] = value;
     }
+
+    #[inline(always)]
+    pub(crate) fn set_lod_meshes(&mut self, value: ChunkMeshes) {
+        let index = get_chunk_index(value.coordinate, self.lod_tiles_square_edge_size);
+        self.lod_meshes[index] = Some(value);
+    }
+}
+
+impl BlockLookup for ChunksState {
+    fn get_block_at(&self, position: BlockPosition) -> Option<Block> {
+        let (x, y, z) = block_position_within_chunk(position)?;
+        let chunk = self.get_chunk(ChunkCoordinate::from_block_position(position))?;
+        Some(chunk.get_block_at(x, y, z))
+    }
+}
+
+impl BlockStorage for ChunksState {
+    fn set_block_at(&mut self, position: BlockPosition, block: Block) -> bool {
+        let chunk_coord = ChunkCoordinate::from_block_position(position);
+        let (x, y, z) = match block_position_within_chunk(position) {
+            Some(local_position) => local_position,
+            None => return false,
+        };
+        match self.get_loaded_chunk_mut(chunk_coord) {
+            Some(loaded) => {
+                loaded.chunk.set_block_at(x, y, z, block);
+                loaded.light_sources.retain(|source| *source != position);
+                if block.light_emission() > 0 {
+                    loaded.light_sources.push(position);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl LightStorage for ChunksState {
+    fn get_light_at(&self, position: BlockPosition, channel: LightChannel) -> Option<u8> {
+        let (x, y, z) = block_position_within_chunk(position)?;
+        let loaded = self.get_loaded_chunk(ChunkCoordinate::from_block_position(position))?;
+        Some(loaded.light.get(x, y, z, channel))
+    }
+
+    fn set_light_at(&mut self, position: BlockPosition, channel: LightChannel, level: u8) {
+        let chunk_coord = ChunkCoordinate::from_block_position(position);
+        if let (Some((x, y, z)), Some(loaded)) = (
+            block_position_within_chunk(position),
+            self.get_loaded_chunk_mut(chunk_coord),
+        ) {
+            loaded.light.set(x, y, z, channel, level);
+        }
+    }
+}
+
+/// Find the global positions of every light-emitting block in a chunk
+fn find_light_sources(chunk: &Chunk, coordinate: ChunkCoordinate) -> Vec<BlockPosition> {
+    let mut sources = vec![];
+    for x in 0..CHUNK_WIDTH {
+        for y in 0..CHUNK_HEIGHT {
+            for z in 0..CHUNK_DEPTH {
+                if chunk.get_block_at(x, y, z).light_emission() > 0 {
+                    sources.push(BlockPosition::new(
+                        coordinate.i * CHUNK_WIDTH as i32 + x as i32,
+                        y as i32,
+                        coordinate.j * CHUNK_DEPTH as i32 + z as i32,
+                    ));
+                }
+            }
+        }
+    }
+    sources
 }
 
 #[inline(always)]
@@ -77,4 +310,96 @@ mod tests {
         let actual = get_chunk_index(chunk_coord, edge_length);
         assert_eq!(expected, actual);
     }
+
+    #[rstest]
+    fn blocks_can_be_read_and_written_by_global_position() {
+        let mut state = ChunksState::new(1, 0);
+        state.set_chunk(ChunkCoordinate { i: -1, j: 0 }, Some(Default::default()));
+
+        let position = BlockPosition::new(-1, 64, 15);
+        assert_eq!(Some(Block::Empty), state.get_block_at(position));
+        assert!(state.set_block_at(position, Block::Stone));
+        assert_eq!(Some(Block::Stone), state.get_block_at(position));
+
+        let chunk = state.get_chunk(ChunkCoordinate { i: -1, j: 0 }).unwrap();
+        assert_eq!(Block::Stone, chunk.get_block_at(15, 64, 15));
+    }
+
+    #[rstest]
+    #[case(BlockPosition::new(16, 64, 0))]
+    #[case(BlockPosition::new(0, -1, 0))]
+    #[case(BlockPosition::new(0, 256, 0))]
+    fn blocks_outside_loaded_chunks_are_unavailable(#[case] position: BlockPosition) {
+        let mut state = ChunksState::new(1, 0);
+        state.set_chunk(ChunkCoordinate { i: 0, j: 0 }, Some(Default::default()));
+
+        assert_eq!(None, state.get_block_at(position));
+        assert!(!state.set_block_at(position, Block::Stone));
+    }
+
+    #[rstest]
+    fn light_can_be_read_and_written_by_global_position() {
+        let mut state = ChunksState::new(1, 0);
+        state.set_chunk(ChunkCoordinate { i: 0, j: -1 }, Some(Default::default()));
+
+        let position = BlockPosition::new(3, 64, -2);
+        assert_eq!(Some(0), state.get_light_at(position, LightChannel::Block));
+        state.set_light_at(position, LightChannel::Block, 9);
+        assert_eq!(Some(9), state.get_light_at(position, LightChannel::Block));
+        assert_eq!(Some(0), state.get_light_at(position, LightChannel::Sky));
+        assert_eq!(
+            None,
+            state.get_light_at(BlockPosition::new(3, 64, 2), LightChannel::Block)
+        );
+    }
+
+    #[rstest]
+    fn light_sources_are_tracked_through_loads_and_edits() {
+        let mut state = ChunksState::new(1, 0);
+        let mut chunk = Chunk::default();
+        chunk.set_block_at(2, 70, 3, Block::Torch);
+        state.set_chunk(
+            ChunkCoordinate { i: -1, j: 0 },
+            Some((chunk, ChunkLight::default())),
+        );
+        state.set_chunk(ChunkCoordinate { i: 0, j: 0 }, Some(Default::default()));
+
+        let player = Location::new(0.5, 70.5, 0.5);
+        assert_eq!(
+            vec![BlockPosition::new(-14, 70, 3)],
+            state.nearest_light_sources(player, 10)
+        );
+
+        state.set_block_at(BlockPosition::new(1, 70, 0), Block::Torch);
+        state.set_block_at(BlockPosition::new(5, 70, 5), Block::Torch);
+        assert_eq!(
+            vec![
+                BlockPosition::new(1, 70, 0),
+                BlockPosition::new(5, 70, 5),
+                BlockPosition::new(-14, 70, 3),
+            ],
+            state.nearest_light_sources(player, 10)
+        );
+        assert_eq!(
+            vec![BlockPosition::new(1, 70, 0)],
+            state.nearest_light_sources(player, 1)
+        );
+
+        state.set_block_at(BlockPosition::new(1, 70, 0), Block::Stone);
+        state.set_block_at(BlockPosition::new(-14, 70, 3), Block::Empty);
+        assert_eq!(
+            vec![BlockPosition::new(5, 70, 5)],
+            state.nearest_light_sources(player, 10)
+        );
+    }
+
+    #[rstest]
+    fn stale_chunks_sharing_a_slot_are_not_returned() {
+        let mut state = ChunksState::new(1, 0);
+        state.set_chunk(ChunkCoordinate { i: 0, j: 0 }, Some(Default::default()));
+
+        // With a render distance of 1, chunks three apart share the same slot
+        assert!(state.get_chunk(ChunkCoordinate { i: 3, j: 0 }).is_none());
+        assert!(state.get_chunk(ChunkCoordinate { i: 0, j: 0 }).is_some());
+    }
 }
@@ -3,10 +3,11 @@ use std::f32::consts::PI;
 use std::rc::Rc;
 use std::sync::{Arc, RwLock};
 
-use nalgebra as na;
-
+use sbs5k_core::clock::WorldClock;
+use sbs5k_core::fluid::FluidSimulation;
 use sbs5k_core::geometry;
 
+use crate::constants;
 use crate::state::chunks_state::ChunksState;
 use crate::Args;
 
@@ -15,17 +16,27 @@ pub(crate) struct ClientState {
     /// The player's current position in the world
     pub player_position: Rc<RefCell<geometry::EntityPosition>>,
 
+    /// Where the player was at the start of the most recent simulation tick, used for smoothing
+    /// out their motion between ticks
+    pub previous_player_location: geometry::Location,
+
     /// The state of the currently-loaded chunks
     pub chunks_state: Rc<RefCell<ChunksState>>,
 
+    /// The time of day in the world
+    pub world_clock: Rc<RefCell<WorldClock>>,
+
+    /// The blocks whose fluid is waiting to flow
+    pub fluids: Rc<RefCell<FluidSimulation>>,
+
     /// Whether the game is currently "live". This is expected to be `true` until we enter the
     /// shutdown phase. May be accessed by multiple threads.
     pub is_live: Arc<RwLock<bool>>,
 }
 
 impl ClientState {
-    pub(crate) fn new(config: &Args) -> Self {
-        let location = na::Point3::new(8.0, 66.0, 8.0);
+    pub(crate) fn new(config: &Args, spawn_point: geometry::Location) -> Self {
+        let location = spawn_point;
         let orientation = geometry::Orientation {
             yaw: PI,
             pitch: 0.0,
@@ -36,12 +47,25 @@ impl ClientState {
             orientation,
         }));
 
-        let chunks_state = Rc::new(RefCell::new(ChunksState::new(config.render_distance)));
+        let chunks_state = Rc::new(RefCell::new(ChunksState::new(
+            config.render_distance,
+            config.lod_distance,
+        )));
+        let world_clock = Rc::new(RefCell::new(WorldClock::new(
+            config.time_of_day,
+            config.day_length,
+        )));
+        let fluids = Rc::new(RefCell::new(FluidSimulation::new(
+            constants::FLUID_FLOW_DELAY_TICKS,
+        )));
         let is_live = Arc::new(RwLock::new(true));
 
         ClientState {
             player_position,
+            previous_player_location: location,
             chunks_state,
+            world_clock,
+            fluids,
             is_live,
         }
     }
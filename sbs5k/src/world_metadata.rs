@@ -0,0 +1,164 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rand::Rng;
+
+use sbs5k_core::generators::PerlinNoiseGenerator;
+use sbs5k_core::geometry::Location;
+use sbs5k_core::spawn;
+use sbs5k_core::world::WorldMetadata;
+
+use crate::args::Args;
+
+/// The name of the file inside the world directory that stores the world's metadata
+const METADATA_FILE_NAME: &str = "metadata.json";
+
+/// How many rings of chunks around the origin to search for a safe spawn point
+const SPAWN_SEARCH_RADIUS: u32 = 8;
+
+/// Where to put the player if no safe spawn point could be found
+const FALLBACK_SPAWN_POINT: (f32, f32, f32) = (8.0, 66.0, 8.0);
+
+/// Load the metadata for the world in the configured directory, creating a new world if there
+/// isn't one there yet
+///
+/// If the metadata can't be read, it's moved out of the way and a new world is created in its
+/// place.
+pub(crate) fn load_or_create(config: &Args) -> WorldMetadata {
+    let world_dir = config.world_dir.clone().unwrap_or_else(default_world_dir);
+    let path = world_dir.join(METADATA_FILE_NAME);
+
+    if path.exists() {
+        match load(&path) {
+            Ok(metadata) => return metadata,
+            Err(e) => {
+                let backup_path = path.with_extension("json.bak");
+                eprintln!(
+                    "Warning: {}. Moving it to {} and creating a new world",
+                    e,
+                    backup_path.display()
+                );
+                if let Err(e) = fs::rename(&path, &backup_path) {
+                    eprintln!(
+                        "Warning: failed to move {}, so the new world won't be saved: {}",
+                        path.display(),
+                        e
+                    );
+                    return create(config);
+                }
+            }
+        }
+    }
+
+    let metadata = create(config);
+    if let Err(e) = save(&metadata, &world_dir, &path) {
+        eprintln!(
+            "Warning: failed to save world metadata to {}: {}",
+            path.display(),
+            e
+        );
+    }
+    metadata
+}
+
+fn load(path: &Path) -> Result<WorldMetadata, String> {
+    let contents = fs::read_to_string(path).map_err(|e| {
+        format!(
+            "Failed to read world metadata from {}: {}",
+            path.display(),
+            e
+        )
+    })?;
+    serde_json::from_str(&contents)
+        .map_err(|e| format!("World metadata in {} is corrupt: {}", path.display(), e))
+}
+
+fn create(config: &Args) -> WorldMetadata {
+    let seed = config.seed.unwrap_or_else(|| rand::thread_rng().gen());
+
+    if config.verbose {
+        println!("Creating new world with seed {}", seed);
+    }
+
+    let mut generator = PerlinNoiseGenerator::with_seed(seed).with_sea_level(config.sea_level);
+    let spawn_point =
+        spawn::find_spawn_point(&mut generator, SPAWN_SEARCH_RADIUS).unwrap_or_else(|| {
+            let (x, y, z) = FALLBACK_SPAWN_POINT;
+            Location::new(x, y, z)
+        });
+
+    WorldMetadata {
+        seed,
+        spawn_point,
+        sea_level: config.sea_level,
+    }
+}
+
+fn save(metadata: &WorldMetadata, world_dir: &Path, path: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(world_dir)?;
+    let contents = serde_json::to_string_pretty(metadata).expect("Failed to serialise metadata");
+    fs::write(path, contents)
+}
+
+/// Where the world is saved if no directory is chosen, which is inside the user's data directory
+/// or, failing that, the current directory
+fn default_world_dir() -> PathBuf {
+    let world_dir = PathBuf::from("sbs5k").join("world");
+    match user_data_dir() {
+        Some(data_dir) => data_dir.join(world_dir),
+        None => world_dir,
+    }
+}
+
+/// The directory in which applications should keep the user's data, following the conventions of
+/// the platform
+fn user_data_dir() -> Option<PathBuf> {
+    let env_dir = |name| {
+        env::var_os(name)
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+    };
+
+    if cfg!(windows) {
+        env_dir("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        env_dir("HOME").map(|home| home.join("Library").join("Application Support"))
+    } else {
+        env_dir("XDG_DATA_HOME").or_else(|| env_dir("HOME").map(|home| home.join(".local/share")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+    use rstest::*;
+
+    #[rstest]
+    fn corrupt_metadata_is_backed_up_and_replaced() {
+        let world_dir =
+            env::temp_dir().join(format!("sbs5k-corrupt-metadata-{}", std::process::id()));
+        fs::create_dir_all(&world_dir).unwrap();
+        let path = world_dir.join(METADATA_FILE_NAME);
+        fs::write(&path, "not json").unwrap();
+
+        let config = Args::parse_from([
+            "sbs5k",
+            "--world-dir",
+            world_dir.to_str().unwrap(),
+            "--seed",
+            "7",
+        ]);
+        let metadata = load_or_create(&config);
+
+        assert_eq!(7, metadata.seed);
+        assert_eq!(
+            "not json",
+            fs::read_to_string(world_dir.join("metadata.json.bak")).unwrap()
+        );
+        assert_eq!(Ok(metadata), load(&path));
+
+        fs::remove_dir_all(&world_dir).unwrap();
+    }
+}
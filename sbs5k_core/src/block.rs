@@ -1,12 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-/// The number of non-empty blocks included in the `Block` enum.
-///
-/// This is used by the rendering process for dividing the block textures file into segments. If
-/// there are `N` textured blocks whose textures are included in the file, then the `i`th's texture
-/// will range from (zero-based) width `i/N` to `(i+1)/N`.
-// TODO: Set this properly once we have textures for the other block types
-pub const NON_EMPTY_BLOCKS_COUNT: u32 = 3;
+use crate::fluid::SOURCE_LEVEL;
 
 /// Encodes all possible block types in the game world.
 #[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
@@ -17,6 +11,122 @@ pub enum Block {
     Grass = 1,
     Dirt = 2,
     Stone = 3,
-    // TODO: Water
-    // TODO: Torch
+    Torch = 4,
+    Glass = 5,
+    Leaves = 6,
+    Water = 7,
+    Sand = 8,
+    Gravel = 9,
+
+    /// Water that has spread out from a source, with one variant for each level from 1 up to
+    /// `SOURCE_LEVEL`, which is only reached by falling water
+    ///
+    /// The level isn't a field, so that every block still fits in one byte.
+    FlowingWater1 = 10,
+    FlowingWater2 = 11,
+    FlowingWater3 = 12,
+    FlowingWater4 = 13,
+    FlowingWater5 = 14,
+    FlowingWater6 = 15,
+    FlowingWater7 = 16,
+    FlowingWater8 = 17,
+}
+
+// Chunks hold a great many blocks, so they need to stay small
+const _: () = assert!(std::mem::size_of::<Block>() == 1);
+
+/// The flowing water blocks, in order of their levels starting from 1
+const FLOWING_WATER: [Block; SOURCE_LEVEL as usize] = [
+    Block::FlowingWater1,
+    Block::FlowingWater2,
+    Block::FlowingWater3,
+    Block::FlowingWater4,
+    Block::FlowingWater5,
+    Block::FlowingWater6,
+    Block::FlowingWater7,
+    Block::FlowingWater8,
+];
+
+/// How a block's faces are drawn, which decides which of its chunk's meshes they go in
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RenderLayer {
+    /// Drawn as fully opaque
+    Opaque,
+
+    /// Drawn as opaque, but with any transparent texels cut out, such as the gaps between leaves
+    Cutout,
+
+    /// Blended over whatever is behind it, so has to be drawn back to front after everything else
+    Translucent,
+}
+
+impl Block {
+    /// The flowing water block with the given level, which must be between 1 and `SOURCE_LEVEL`
+    #[inline]
+    pub fn flowing_water(level: u8) -> Block {
+        assert!(
+            (1..=SOURCE_LEVEL).contains(&level),
+            "Fluid level {} is out of range",
+            level
+        );
+        FLOWING_WATER[level as usize - 1]
+    }
+
+    /// Whether entities collide with this block
+    #[inline]
+    pub fn is_solid(&self) -> bool {
+        !matches!(self, Block::Empty | Block::Torch) && !self.is_fluid()
+    }
+
+    /// Whether this block stops light from passing through it
+    #[inline]
+    pub fn is_opaque(&self) -> bool {
+        !matches!(
+            self,
+            Block::Empty | Block::Torch | Block::Glass | Block::Leaves
+        ) && !self.is_fluid()
+    }
+
+    /// How this block's faces are drawn
+    #[inline]
+    pub fn render_layer(&self) -> RenderLayer {
+        match self {
+            Block::Glass => RenderLayer::Translucent,
+            Block::Leaves => RenderLayer::Cutout,
+            _ if self.is_fluid() => RenderLayer::Translucent,
+            _ => RenderLayer::Opaque,
+        }
+    }
+
+    /// Whether this block is made of fluid, whether a source or flowing
+    #[inline]
+    pub fn is_fluid(&self) -> bool {
+        self.fluid_level().is_some()
+    }
+
+    /// How full of fluid this block is, from 1 up to `SOURCE_LEVEL`, or `None` if it isn't a fluid
+    #[inline]
+    pub fn fluid_level(&self) -> Option<u8> {
+        match self {
+            Block::Water => Some(SOURCE_LEVEL),
+            Block::FlowingWater1 => Some(1),
+            Block::FlowingWater2 => Some(2),
+            Block::FlowingWater3 => Some(3),
+            Block::FlowingWater4 => Some(4),
+            Block::FlowingWater5 => Some(5),
+            Block::FlowingWater6 => Some(6),
+            Block::FlowingWater7 => Some(7),
+            Block::FlowingWater8 => Some(8),
+            _ => None,
+        }
+    }
+
+    /// The level of block light that this block gives off
+    #[inline]
+    pub fn light_emission(&self) -> u8 {
+        match self {
+            Block::Torch => 14,
+            _ => 0,
+        }
+    }
 }
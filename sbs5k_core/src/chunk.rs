@@ -4,6 +4,7 @@ use serde::{Deserialize, Serialize};
 use serde_big_array::BigArray;
 
 use crate::block::Block;
+use crate::geometry::BlockPosition;
 
 pub const CHUNK_WIDTH: usize = 16;
 pub const CHUNK_DEPTH: usize = 16;
@@ -16,6 +17,11 @@ pub const BLOCKS_IN_CHUNK: usize = CHUNK_WIDTH * CHUNK_DEPTH * CHUNK_HEIGHT;
 /// Blocks should be stored in X-major, followed by Y-major, order.
 pub type ChunkBlocks = [Block; BLOCKS_IN_CHUNK];
 
+/// The y coordinate of the highest solid block in each column of a chunk, indexed by `[x][z]`
+///
+/// Columns containing no solid blocks at all are `None`.
+pub type ChunkHeightmap = [[Option<usize>; CHUNK_DEPTH]; CHUNK_WIDTH];
+
 /// A 16x16x256 volume of space
 #[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Chunk {
@@ -37,7 +43,7 @@ impl Default for Chunk {
 /// The `i` coordinate corresponds to its position in the x dimension; the `j` coordinate
 /// corresponds to its position in the z dimension. The index (0, 0) is the chunk that the player
 /// first spawns in.
-#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub struct ChunkCoordinate {
     pub i: i32,
     pub j: i32,
@@ -48,6 +54,29 @@ pub struct ChunkCoordinate {
 /// Possible implementations may include loading chunks from a file or over a network.
 pub trait ChunkSource {
     fn get_chunk_at(&mut self, coordinate: ChunkCoordinate) -> Box<Chunk>;
+
+    /// Get the terrain's surface in the column at global coordinates (x, z), without generating
+    /// the chunk that contains it
+    ///
+    /// Sources for which this can't be computed cheaply should return `None`, in which case no
+    /// low-detail terrain will be generated for them.
+    fn get_surface_at(&mut self, _global_x: i32, _global_z: i32) -> Option<Surface> {
+        None
+    }
+}
+
+/// The top of one column of terrain, as reported by a `ChunkSource`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Surface {
+    /// The y coordinate of the lowest block above the ground, which may be underwater
+    pub ground_height: i32,
+
+    /// The block on top of the ground
+    pub ground_block: Block,
+
+    /// The y coordinate of the lowest empty block above the water covering the ground, or `None`
+    /// if the ground isn't underwater
+    pub water_height: Option<i32>,
 }
 
 // TODO: Define a type for PlayerPosition? (WorldPosition?)
@@ -66,6 +95,83 @@ impl ChunkCoordinate {
         };
         ChunkCoordinate { i, j }
     }
+
+    /// The number of chunks between this coordinate and `other`, counting diagonal steps as one
+    #[inline]
+    pub fn distance_to(&self, other: ChunkCoordinate) -> u32 {
+        let di = (self.i - other.i).unsigned_abs();
+        let dj = (self.j - other.j).unsigned_abs();
+        di.max(dj)
+    }
+
+    /// The four chunks that share a face with this one
+    pub fn adjacent(&self) -> [ChunkCoordinate; 4] {
+        [(-1, 0), (1, 0), (0, -1), (0, 1)].map(|(di, dj)| ChunkCoordinate {
+            i: self.i + di,
+            j: self.j + dj,
+        })
+    }
+
+    /// Get the coordinate of the chunk containing the block at `position`
+    #[inline]
+    pub fn from_block_position(position: BlockPosition) -> Self {
+        ChunkCoordinate {
+            i: position.x.div_euclid(CHUNK_WIDTH as i32),
+            j: position.z.div_euclid(CHUNK_DEPTH as i32),
+        }
+    }
+}
+
+/// Convert a global block position into the (x, y, z) position of that block within its chunk
+///
+/// Returns `None` if the position lies above or below the world.
+#[inline]
+pub fn block_position_within_chunk(position: BlockPosition) -> Option<(usize, usize, usize)> {
+    if position.y < 0 || position.y >= CHUNK_HEIGHT as i32 {
+        return None;
+    }
+    let x = position.x.rem_euclid(CHUNK_WIDTH as i32) as usize;
+    let z = position.z.rem_euclid(CHUNK_DEPTH as i32) as usize;
+    Some((x, position.y as usize, z))
+}
+
+/// Compute the coordinates of the square ring of chunks at distance `d` from `centre`
+///
+/// The ring is traversed one side at a time, and each coordinate is included exactly once.
+pub fn square_ring(centre: ChunkCoordinate, d: u32) -> Vec<ChunkCoordinate> {
+    if d == 0 {
+        return vec![centre];
+    }
+
+    let d = d as i32;
+    let i_left = centre.i - d;
+    let i_right = centre.i + d;
+    let j_top = centre.j + d;
+    let j_bottom = centre.j - d;
+
+    let mut coords = vec![];
+
+    // Top
+    for i in i_left..i_right {
+        coords.push(ChunkCoordinate { i, j: j_top });
+    }
+
+    // Bottom
+    for i in (i_left + 1)..=i_right {
+        coords.push(ChunkCoordinate { i, j: j_bottom });
+    }
+
+    // Left
+    for j in j_bottom..j_top {
+        coords.push(ChunkCoordinate { i: i_left, j });
+    }
+
+    // Right
+    for j in (j_bottom + 1)..=j_top {
+        coords.push(ChunkCoordinate { i: i_right, j });
+    }
+
+    coords
 }
 
 pub fn empty_blocks() -> ChunkBlocks {
@@ -73,7 +179,7 @@ pub fn empty_blocks() -> ChunkBlocks {
 }
 
 #[inline(always)]
-fn block_index(x: usize, y: usize, z: usize) -> usize {
+pub(crate) fn block_index(x: usize, y: usize, z: usize) -> usize {
     (CHUNK_HEIGHT * CHUNK_DEPTH) * x + (CHUNK_WIDTH) * y + z
 }
 
@@ -82,7 +188,6 @@ impl Chunk {
         Chunk { blocks }
     }
 
-    #[allow(dead_code)]
     #[inline(always)]
     pub fn set_block_at(&mut self, x: usize, y: usize, z: usize, block: Block) {
         let idx = block_index(x, y, z);
@@ -95,6 +200,26 @@ impl Chunk {
         self.blocks[idx]
     }
 
+    /// Find the y coordinate of the highest solid block in the column at (x, z)
+    ///
+    /// Returns `None` if the column contains no solid blocks.
+    pub fn highest_solid_block_at(&self, x: usize, z: usize) -> Option<usize> {
+        (0..CHUNK_HEIGHT)
+            .rev()
+            .find(|&y| self.get_block_at(x, y, z).is_solid())
+    }
+
+    /// Compute the height of the highest solid block in every column of this chunk
+    pub fn heightmap(&self) -> ChunkHeightmap {
+        let mut heightmap = [[None; CHUNK_DEPTH]; CHUNK_WIDTH];
+        for (x, column) in heightmap.iter_mut().enumerate() {
+            for (z, height) in column.iter_mut().enumerate() {
+                *height = self.highest_solid_block_at(x, z);
+            }
+        }
+        heightmap
+    }
+
     #[inline]
     pub fn has_block_at(&self, x: i32, y: i32, z: i32) -> bool {
         let x_in_bounds = x >= 0 && x < CHUNK_WIDTH as i32;
@@ -106,6 +231,15 @@ impl Chunk {
             false
         }
     }
+
+    /// Whether there is a block at (x, y, z) that hides the faces of the blocks next to it
+    #[inline]
+    pub fn has_opaque_block_at(&self, x: i32, y: i32, z: i32) -> bool {
+        self.has_block_at(x, y, z)
+            && self
+                .get_block_at(x as usize, y as usize, z as usize)
+                .is_opaque()
+    }
 }
 
 #[cfg(test)]
@@ -119,7 +253,7 @@ mod tests {
     #[case(1, 0, 0, CHUNK_HEIGHT*CHUNK_DEPTH)]
     #[case(0, 1, 0, CHUNK_DEPTH)]
     #[case(0, 0, 1, 1)]
-    #[case(1, 2, 3, 1*CHUNK_HEIGHT*CHUNK_DEPTH + 2*CHUNK_DEPTH + 3)]
+    #[case(1, 2, 3, CHUNK_HEIGHT*CHUNK_DEPTH + 2*CHUNK_DEPTH + 3)]
     fn block_index_works(
         #[case] x: usize,
         #[case] y: usize,
@@ -130,6 +264,38 @@ mod tests {
         assert_eq!(expected_idx, idx);
     }
 
+    #[rstest]
+    fn highest_solid_block_ignores_empty_gaps() {
+        let mut chunk = Chunk::default();
+        chunk.set_block_at(3, 10, 4, Block::Stone);
+        chunk.set_block_at(3, 80, 4, Block::Grass);
+        chunk.set_block_at(0, 0, 0, Block::Dirt);
+        chunk.set_block_at(15, 255, 15, Block::Stone);
+
+        assert_eq!(Some(80), chunk.highest_solid_block_at(3, 4));
+        assert_eq!(Some(0), chunk.highest_solid_block_at(0, 0));
+        assert_eq!(Some(255), chunk.highest_solid_block_at(15, 15));
+        assert_eq!(None, chunk.highest_solid_block_at(3, 5));
+    }
+
+    #[rstest]
+    fn heightmap_matches_column_queries() {
+        let mut chunk = Chunk::default();
+        for x in 0..CHUNK_WIDTH {
+            for z in 0..CHUNK_DEPTH {
+                for y in 0..(x + z) {
+                    chunk.set_block_at(x, y, z, Block::Stone);
+                }
+            }
+        }
+
+        let heightmap = chunk.heightmap();
+        assert_eq!(None, heightmap[0][0]);
+        assert_eq!(Some(0), heightmap[1][0]);
+        assert_eq!(Some(6), heightmap[3][4]);
+        assert_eq!(Some(29), heightmap[15][15]);
+    }
+
     #[rstest]
     #[case(Point3::new(1.0, 0.0, 1.0), ChunkCoordinate{i: 0, j: 0})]
     #[case(Point3::new(1.0, 64.0, 1.0), ChunkCoordinate{i: 0, j: 0})]
@@ -146,4 +312,71 @@ mod tests {
         let result = ChunkCoordinate::from_player_position(player_pos);
         assert_eq!(expected_index, result);
     }
+
+    #[rstest]
+    #[case(ChunkCoordinate{i: 0, j: 0}, ChunkCoordinate{i: 0, j: 0}, 0)]
+    #[case(ChunkCoordinate{i: 0, j: 0}, ChunkCoordinate{i: 1, j: 1}, 1)]
+    #[case(ChunkCoordinate{i: 2, j: -3}, ChunkCoordinate{i: -1, j: 4}, 7)]
+    #[case(ChunkCoordinate{i: -5, j: 0}, ChunkCoordinate{i: 1, j: 2}, 6)]
+    fn chunkcoordinate_distance_to_works(
+        #[case] a: ChunkCoordinate,
+        #[case] b: ChunkCoordinate,
+        #[case] expected: u32,
+    ) {
+        assert_eq!(expected, a.distance_to(b));
+        assert_eq!(expected, b.distance_to(a));
+    }
+
+    #[rstest]
+    fn adjacent_chunks_are_one_step_away_in_a_straight_line() {
+        let centre = ChunkCoordinate { i: 3, j: -2 };
+        let adjacent = centre.adjacent();
+        for (index, coord) in adjacent.iter().enumerate() {
+            assert_eq!(1, coord.distance_to(centre));
+            assert!(coord.i == centre.i || coord.j == centre.j);
+            assert!(!adjacent[index + 1..].contains(coord));
+        }
+    }
+
+    #[rstest]
+    #[case(0, 1)]
+    #[case(1, 8)]
+    #[case(2, 16)]
+    #[case(5, 40)]
+    fn square_ring_covers_each_coordinate_once(#[case] d: u32, #[case] expected_count: usize) {
+        let centre = ChunkCoordinate { i: 3, j: -2 };
+        let ring = square_ring(centre, d);
+        assert_eq!(expected_count, ring.len());
+        for (index, coord) in ring.iter().enumerate() {
+            assert_eq!(d, coord.distance_to(centre));
+            assert!(!ring[index + 1..].contains(coord));
+        }
+    }
+
+    #[rstest]
+    #[case(BlockPosition::new(0, 64, 0), ChunkCoordinate{i: 0, j: 0})]
+    #[case(BlockPosition::new(15, 64, 15), ChunkCoordinate{i: 0, j: 0})]
+    #[case(BlockPosition::new(16, 64, 31), ChunkCoordinate{i: 1, j: 1})]
+    #[case(BlockPosition::new(-1, 64, 0), ChunkCoordinate{i: -1, j: 0})]
+    #[case(BlockPosition::new(-16, 64, -17), ChunkCoordinate{i: -1, j: -2})]
+    fn chunkcoordinate_from_block_position_works(
+        #[case] position: BlockPosition,
+        #[case] expected: ChunkCoordinate,
+    ) {
+        assert_eq!(expected, ChunkCoordinate::from_block_position(position));
+    }
+
+    #[rstest]
+    #[case(BlockPosition::new(0, 0, 0), Some((0, 0, 0)))]
+    #[case(BlockPosition::new(17, 64, 33), Some((1, 64, 1)))]
+    #[case(BlockPosition::new(-1, 255, -16), Some((15, 255, 0)))]
+    #[case(BlockPosition::new(-17, 3, -18), Some((15, 3, 14)))]
+    #[case(BlockPosition::new(0, -1, 0), None)]
+    #[case(BlockPosition::new(0, 256, 0), None)]
+    fn block_position_within_chunk_works(
+        #[case] position: BlockPosition,
+        #[case] expected: Option<(usize, usize, usize)>,
+    ) {
+        assert_eq!(expected, block_position_within_chunk(position));
+    }
 }
@@ -0,0 +1,148 @@
+use std::f32::consts::PI;
+
+use nalgebra::Vector3;
+
+/// The time of day at which the sun is directly below the world
+pub const MIDNIGHT: f32 = 0.0;
+
+/// The time of day at which the sun rises in the east
+pub const SUNRISE: f32 = 0.25;
+
+/// The time of day at which the sun is at its highest
+pub const NOON: f32 = 0.5;
+
+/// The time of day at which the sun sets in the west
+pub const SUNSET: f32 = 0.75;
+
+/// How far the sun's path is tilted away from passing directly overhead, in radians
+const SUN_PATH_TILT: f32 = 0.4;
+
+/// Keeps track of the time of day in the world
+///
+/// Times of day are fractions of a whole day, running from 0 (inclusive) to 1 (exclusive) and
+/// starting at midnight.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WorldClock {
+    time_of_day: f32,
+    day_length: f32,
+}
+
+impl WorldClock {
+    /// Create a clock starting at `time_of_day`, where a whole day lasts `day_length` seconds
+    ///
+    /// A day length of zero stops time from passing.
+    pub fn new(time_of_day: f32, day_length: f32) -> Self {
+        WorldClock {
+            time_of_day: time_of_day.rem_euclid(1.0),
+            day_length,
+        }
+    }
+
+    /// The current time of day
+    #[inline]
+    pub fn time_of_day(&self) -> f32 {
+        self.time_of_day
+    }
+
+    /// Jump straight to `time_of_day`
+    pub fn set_time_of_day(&mut self, time_of_day: f32) {
+        self.time_of_day = time_of_day.rem_euclid(1.0);
+    }
+
+    /// Jump forwards to the next sunrise, noon, sunset or midnight
+    pub fn skip_to_next_phase(&mut self) {
+        let next = [SUNRISE, NOON, SUNSET]
+            .into_iter()
+            .find(|&phase| phase > self.time_of_day)
+            .unwrap_or(MIDNIGHT);
+        self.set_time_of_day(next);
+    }
+
+    /// Let `dt` seconds pass
+    pub fn advance(&mut self, dt: f32) {
+        if self.day_length > 0.0 {
+            self.set_time_of_day(self.time_of_day + dt / self.day_length);
+        }
+    }
+
+    /// The unit vector pointing from the world towards the sun
+    ///
+    /// The sun rises in the +x direction, passes high in the sky slightly towards +z at noon,
+    /// and sets in the -x direction. At night the vector points below the horizon.
+    pub fn sun_direction(&self) -> Vector3<f32> {
+        let angle = (self.time_of_day - SUNRISE) * 2.0 * PI;
+        Vector3::new(
+            angle.cos(),
+            angle.sin() * SUN_PATH_TILT.cos(),
+            angle.sin() * SUN_PATH_TILT.sin(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn assert_vector_eq(expected: Vector3<f32>, actual: Vector3<f32>) {
+        assert!(
+            (expected - actual).norm() < 1e-5,
+            "Expected {} but got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[rstest]
+    #[case(0.3, 60.0, 0.35)]
+    #[case(0.9, 240.0, 0.1)]
+    #[case(0.5, 0.0, 0.5)]
+    fn advancing_moves_time_forwards_and_wraps(
+        #[case] start: f32,
+        #[case] dt: f32,
+        #[case] expected: f32,
+    ) {
+        let mut clock = WorldClock::new(start, 1200.0);
+        clock.advance(dt);
+        assert!((expected - clock.time_of_day()).abs() < 1e-5);
+    }
+
+    #[rstest]
+    fn zero_day_length_freezes_time() {
+        let mut clock = WorldClock::new(0.4, 0.0);
+        clock.advance(100.0);
+        assert_eq!(0.4, clock.time_of_day());
+    }
+
+    #[rstest]
+    #[case(1.25, 0.25)]
+    #[case(-0.25, 0.75)]
+    fn times_are_wrapped_into_a_single_day(#[case] time: f32, #[case] expected: f32) {
+        let mut clock = WorldClock::new(0.0, 1200.0);
+        clock.set_time_of_day(time);
+        assert_eq!(expected, clock.time_of_day());
+    }
+
+    #[rstest]
+    #[case(0.0, SUNRISE)]
+    #[case(0.1, SUNRISE)]
+    #[case(SUNRISE, NOON)]
+    #[case(0.6, SUNSET)]
+    #[case(SUNSET, MIDNIGHT)]
+    #[case(0.99, MIDNIGHT)]
+    fn skipping_jumps_to_the_next_phase(#[case] start: f32, #[case] expected: f32) {
+        let mut clock = WorldClock::new(start, 1200.0);
+        clock.skip_to_next_phase();
+        assert_eq!(expected, clock.time_of_day());
+    }
+
+    #[rstest]
+    #[case(SUNRISE, Vector3::new(1.0, 0.0, 0.0))]
+    #[case(NOON, Vector3::new(0.0, SUN_PATH_TILT.cos(), SUN_PATH_TILT.sin()))]
+    #[case(SUNSET, Vector3::new(-1.0, 0.0, 0.0))]
+    #[case(MIDNIGHT, Vector3::new(0.0, -SUN_PATH_TILT.cos(), -SUN_PATH_TILT.sin()))]
+    fn sun_follows_its_path(#[case] time: f32, #[case] expected: Vector3<f32>) {
+        let clock = WorldClock::new(time, 1200.0);
+        assert_vector_eq(expected, clock.sun_direction());
+    }
+}
@@ -1,5 +1,7 @@
+use nalgebra::Vector3;
+
 /// The six possible faces of a cube
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum CubeFace {
     PosX,
     NegX,
@@ -8,3 +10,17 @@ pub enum CubeFace {
     PosZ,
     NegZ,
 }
+
+impl CubeFace {
+    /// The unit vector pointing outwards from this face
+    pub fn normal(&self) -> Vector3<i32> {
+        match self {
+            CubeFace::PosX => Vector3::new(1, 0, 0),
+            CubeFace::NegX => Vector3::new(-1, 0, 0),
+            CubeFace::PosY => Vector3::new(0, 1, 0),
+            CubeFace::NegY => Vector3::new(0, -1, 0),
+            CubeFace::PosZ => Vector3::new(0, 0, 1),
+            CubeFace::NegZ => Vector3::new(0, 0, -1),
+        }
+    }
+}
@@ -0,0 +1,437 @@
+//! Fluids that spread out from their sources a block at a time, over a series of world ticks
+//!
+//! A source block is always full. Fluid next to a source flows sideways across the top of whatever
+//! it's resting on, losing a level for each block it travels, until it runs out. Fluid with
+//! nothing underneath it falls instead, and a falling column is full again when it lands. When a
+//! source is taken away, the fluid it was feeding drains away in the same way that it spread.
+//!
+//! Nothing in the world moves on its own accord, so fluids are only updated once a nearby block
+//! changes. `FluidSimulation` keeps track of the positions waiting to be updated, including those
+//! in chunks that aren't loaded, which wait until their chunk is.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use nalgebra::Vector3;
+
+use crate::block::Block;
+use crate::chunk::{block_position_within_chunk, ChunkCoordinate};
+use crate::geometry::BlockPosition;
+use crate::world::BlockStorage;
+
+/// The level of a source block, and of a falling column of fluid
+pub const SOURCE_LEVEL: u8 = 8;
+
+/// The four neighbours of a block that fluid can flow sideways into
+const HORIZONTAL_NEIGHBOURS: [Vector3<i32>; 4] = [
+    Vector3::new(1, 0, 0),
+    Vector3::new(-1, 0, 0),
+    Vector3::new(0, 0, 1),
+    Vector3::new(0, 0, -1),
+];
+
+/// The six face-adjacent neighbours of a block
+const NEIGHBOURS: [Vector3<i32>; 6] = [
+    Vector3::new(1, 0, 0),
+    Vector3::new(-1, 0, 0),
+    Vector3::new(0, 1, 0),
+    Vector3::new(0, -1, 0),
+    Vector3::new(0, 0, 1),
+    Vector3::new(0, 0, -1),
+];
+
+/// The blocks waiting for their fluid to be updated
+#[derive(Debug)]
+pub struct FluidSimulation {
+    /// How many ticks pass between a block changing and the fluid around it reacting
+    flow_delay_ticks: u64,
+
+    /// The number of ticks simulated so far
+    tick: u64,
+
+    /// The positions waiting to be updated and the tick on which each is due, in the order
+    /// they're due
+    scheduled: VecDeque<(u64, BlockPosition)>,
+
+    /// The positions in `scheduled`, so that each is only waiting once
+    pending: HashSet<BlockPosition>,
+
+    /// The positions that came due while their chunk wasn't loaded, which are scheduled again
+    /// once it is
+    parked: HashMap<ChunkCoordinate, HashSet<BlockPosition>>,
+}
+
+impl FluidSimulation {
+    /// Create a simulation in which fluid spreads one block every `flow_delay_ticks` ticks
+    pub fn new(flow_delay_ticks: u64) -> Self {
+        FluidSimulation {
+            flow_delay_ticks: flow_delay_ticks.max(1),
+            tick: 0,
+            scheduled: VecDeque::new(),
+            pending: HashSet::new(),
+            parked: HashMap::new(),
+        }
+    }
+
+    /// Let any fluid around `position` react to the block there having changed
+    ///
+    /// This must be called whenever a block is placed or broken, including when fluid is placed.
+    pub fn block_changed(&mut self, position: BlockPosition) {
+        self.schedule(position);
+        for offset in NEIGHBOURS {
+            self.schedule(position + offset);
+        }
+    }
+
+    /// Simulate one tick, updating the fluid in any blocks that are due
+    ///
+    /// Returns the positions of the blocks that changed.
+    pub fn tick(&mut self, world: &mut impl BlockStorage) -> Vec<BlockPosition> {
+        self.tick += 1;
+
+        let mut due = vec![];
+        while let Some(&(tick, position)) = self.scheduled.front() {
+            if tick > self.tick {
+                break;
+            }
+            self.scheduled.pop_front();
+            self.pending.remove(&position);
+            due.push(position);
+        }
+
+        // Work out every change before making any of them, so that fluid only ever moves one block
+        // per update whatever order the blocks are updated in
+        let mut changes: Vec<(BlockPosition, Block)> = vec![];
+        for position in due {
+            if world.get_block_at(position).is_none() {
+                self.park(position);
+            } else if let Some(block) = next_block_at(world, position) {
+                changes.push((position, block));
+            }
+        }
+
+        let mut changed = vec![];
+        for (position, block) in changes {
+            if world.set_block_at(position, block) {
+                self.block_changed(position);
+                changed.push(position);
+            } else {
+                self.park(position);
+            }
+        }
+        changed
+    }
+
+    /// Schedule any updates that were waiting for the chunk at `coordinate` to load
+    pub fn chunk_loaded(&mut self, coordinate: ChunkCoordinate) {
+        for position in self.parked.remove(&coordinate).unwrap_or_default() {
+            self.schedule(position);
+        }
+    }
+
+    /// Whether there are no blocks waiting to be updated, meaning the fluids have settled
+    pub fn is_settled(&self) -> bool {
+        self.scheduled.is_empty()
+    }
+
+    /// Set aside an update for a block that isn't loaded, until its chunk is
+    ///
+    /// Positions above or below the world are dropped, since there's never anything there.
+    fn park(&mut self, position: BlockPosition) {
+        if block_position_within_chunk(position).is_some() {
+            self.parked
+                .entry(ChunkCoordinate::from_block_position(position))
+                .or_default()
+                .insert(position);
+        }
+    }
+
+    fn schedule(&mut self, position: BlockPosition) {
+        if self.pending.insert(position) {
+            self.scheduled
+                .push_back((self.tick + self.flow_delay_ticks, position));
+        }
+    }
+}
+
+/// Whether fluid can flow into a block, replacing whatever is there
+#[inline]
+fn can_flow_into(block: Block) -> bool {
+    block == Block::Empty || (block.is_fluid() && block != Block::Water)
+}
+
+/// Work out what the block at `position` should become given the fluid around it, or `None` if it
+/// should stay as it is
+fn next_block_at(world: &impl BlockStorage, position: BlockPosition) -> Option<Block> {
+    let current = world.get_block_at(position)?;
+    if !can_flow_into(current) {
+        return None;
+    }
+
+    let above = world.get_block_at(position + Vector3::new(0, 1, 0));
+    let next = if above.is_some_and(|block| block.is_fluid()) {
+        Block::flowing_water(SOURCE_LEVEL)
+    } else {
+        HORIZONTAL_NEIGHBOURS
+            .iter()
+            .filter_map(|offset| sideways_level_from(world, position + offset))
+            .max()
+            .filter(|level| *level > 0)
+            .map_or(Block::Empty, Block::flowing_water)
+    };
+
+    (next != current).then_some(next)
+}
+
+/// The level of the fluid that the block at `position` passes on to its horizontal neighbours, if
+/// it has any fluid to pass on
+///
+/// Fluid only spreads sideways once it's resting on something, since otherwise it falls.
+fn sideways_level_from(world: &impl BlockStorage, position: BlockPosition) -> Option<u8> {
+    let level = world.get_block_at(position)?.fluid_level()?;
+    let below = world.get_block_at(position - Vector3::new(0, 1, 0))?;
+    if can_flow_into(below) {
+        return None;
+    }
+    Some(level - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::BlockLookup;
+    use rstest::*;
+    use std::collections::HashMap;
+
+    /// The furthest that the test world extends from the origin along the x and z axes
+    const WORLD_RADIUS: i32 = 16;
+
+    /// A world with a stone floor at y = 0, and nothing beyond `WORLD_RADIUS`
+    #[derive(Default)]
+    struct TestWorld {
+        blocks: HashMap<BlockPosition, Block>,
+    }
+
+    impl TestWorld {
+        fn block(&self, x: i32, y: i32, z: i32) -> Block {
+            self.get_block_at(BlockPosition::new(x, y, z)).unwrap()
+        }
+
+        /// Change a block as the player would, letting the fluids around it react
+        fn edit(&mut self, fluids: &mut FluidSimulation, x: i32, y: i32, z: i32, block: Block) {
+            let position = BlockPosition::new(x, y, z);
+            self.set_block_at(position, block);
+            fluids.block_changed(position);
+        }
+
+        /// Run the simulation until nothing else changes
+        fn settle(&mut self, fluids: &mut FluidSimulation) {
+            for _ in 0..1000 {
+                if fluids.is_settled() {
+                    return;
+                }
+                fluids.tick(self);
+            }
+            panic!("The fluids never settled");
+        }
+    }
+
+    impl BlockLookup for TestWorld {
+        fn get_block_at(&self, position: BlockPosition) -> Option<Block> {
+            if position.y < 0 || position.x.abs() > WORLD_RADIUS || position.z.abs() > WORLD_RADIUS
+            {
+                None
+            } else if position.y == 0 {
+                Some(Block::Stone)
+            } else {
+                Some(self.blocks.get(&position).copied().unwrap_or_default())
+            }
+        }
+    }
+
+    impl BlockStorage for TestWorld {
+        fn set_block_at(&mut self, position: BlockPosition, block: Block) -> bool {
+            if self.get_block_at(position).is_none() {
+                return false;
+            }
+            self.blocks.insert(position, block);
+            true
+        }
+    }
+
+    #[rstest]
+    #[case(0, Block::Water)]
+    #[case(1, Block::FlowingWater7)]
+    #[case(4, Block::FlowingWater4)]
+    #[case(7, Block::FlowingWater1)]
+    #[case(8, Block::Empty)]
+    fn fluid_loses_a_level_for_each_block_it_flows(#[case] distance: i32, #[case] expected: Block) {
+        let mut world = TestWorld::default();
+        let mut fluids = FluidSimulation::new(1);
+
+        world.edit(&mut fluids, 0, 1, 0, Block::Water);
+        world.settle(&mut fluids);
+
+        assert_eq!(expected, world.block(distance, 1, 0));
+        assert_eq!(expected, world.block(0, 1, -distance));
+    }
+
+    /// A view of a `TestWorld` in which one chunk isn't loaded
+    struct PartlyLoaded<'a> {
+        world: &'a mut TestWorld,
+        unloaded: ChunkCoordinate,
+    }
+
+    impl BlockLookup for PartlyLoaded<'_> {
+        fn get_block_at(&self, position: BlockPosition) -> Option<Block> {
+            if ChunkCoordinate::from_block_position(position) == self.unloaded {
+                None
+            } else {
+                self.world.get_block_at(position)
+            }
+        }
+    }
+
+    impl BlockStorage for PartlyLoaded<'_> {
+        fn set_block_at(&mut self, position: BlockPosition, block: Block) -> bool {
+            self.get_block_at(position).is_some() && self.world.set_block_at(position, block)
+        }
+    }
+
+    #[rstest]
+    fn fluid_flows_into_a_chunk_once_it_loads() {
+        let mut world = TestWorld::default();
+        let mut fluids = FluidSimulation::new(1);
+        let unloaded = ChunkCoordinate { i: -1, j: 0 };
+
+        world.edit(&mut fluids, 2, 1, 0, Block::Water);
+        let mut partly_loaded = PartlyLoaded {
+            world: &mut world,
+            unloaded,
+        };
+        for _ in 0..100 {
+            fluids.tick(&mut partly_loaded);
+        }
+        assert!(fluids.is_settled());
+        assert_eq!(Block::FlowingWater6, world.block(0, 1, 0));
+        assert_eq!(Block::Empty, world.block(-1, 1, 0));
+
+        fluids.chunk_loaded(unloaded);
+        world.settle(&mut fluids);
+        assert_eq!(Block::FlowingWater5, world.block(-1, 1, 0));
+        assert_eq!(Block::FlowingWater1, world.block(-5, 1, 0));
+    }
+
+    #[rstest]
+    fn fluid_spreads_one_block_per_update() {
+        let mut world = TestWorld::default();
+        let mut fluids = FluidSimulation::new(3);
+        world.edit(&mut fluids, 0, 1, 0, Block::Water);
+
+        for _ in 0..3 {
+            fluids.tick(&mut world);
+        }
+        assert_eq!(Block::FlowingWater7, world.block(1, 1, 0));
+        assert_eq!(Block::Empty, world.block(2, 1, 0));
+
+        for _ in 0..2 {
+            fluids.tick(&mut world);
+        }
+        assert_eq!(Block::Empty, world.block(2, 1, 0));
+        fluids.tick(&mut world);
+        assert_eq!(Block::FlowingWater6, world.block(2, 1, 0));
+    }
+
+    #[rstest]
+    fn fluid_falls_straight_down_and_spreads_out_when_it_lands() {
+        let mut world = TestWorld::default();
+        let mut fluids = FluidSimulation::new(1);
+
+        world.edit(&mut fluids, 0, 5, 0, Block::Water);
+        world.settle(&mut fluids);
+
+        for y in 1..5 {
+            assert_eq!(Block::flowing_water(SOURCE_LEVEL), world.block(0, y, 0));
+        }
+        assert_eq!(Block::Empty, world.block(1, 5, 0));
+        assert_eq!(Block::Empty, world.block(1, 4, 0));
+        assert_eq!(Block::FlowingWater7, world.block(1, 1, 0));
+        assert_eq!(Block::FlowingWater1, world.block(7, 1, 0));
+    }
+
+    #[rstest]
+    fn fluid_pours_over_a_ledge() {
+        let mut world = TestWorld::default();
+        let mut fluids = FluidSimulation::new(1);
+        for x in -2..=2 {
+            for z in -2..=2 {
+                world.set_block_at(BlockPosition::new(x, 1, z), Block::Stone);
+            }
+        }
+
+        world.edit(&mut fluids, 0, 2, 0, Block::Water);
+        world.settle(&mut fluids);
+
+        assert_eq!(Block::FlowingWater6, world.block(2, 2, 0));
+        assert_eq!(Block::FlowingWater5, world.block(3, 2, 0));
+        assert_eq!(Block::Empty, world.block(4, 2, 0));
+        assert_eq!(Block::flowing_water(SOURCE_LEVEL), world.block(3, 1, 0));
+        assert_eq!(Block::FlowingWater7, world.block(4, 1, 0));
+    }
+
+    #[rstest]
+    fn fluid_drains_away_once_its_source_is_removed() {
+        let mut world = TestWorld::default();
+        let mut fluids = FluidSimulation::new(1);
+        world.edit(&mut fluids, 0, 4, 0, Block::Water);
+        world.settle(&mut fluids);
+
+        world.edit(&mut fluids, 0, 4, 0, Block::Empty);
+        world.settle(&mut fluids);
+
+        assert!(world.blocks.values().all(|block| *block == Block::Empty));
+    }
+
+    #[rstest]
+    fn breaking_a_wall_lets_fluid_through() {
+        let mut world = TestWorld::default();
+        let mut fluids = FluidSimulation::new(1);
+        world.set_block_at(BlockPosition::new(1, 1, 0), Block::Stone);
+        world.set_block_at(BlockPosition::new(0, 1, 0), Block::Water);
+
+        world.edit(&mut fluids, 1, 1, 0, Block::Empty);
+        world.settle(&mut fluids);
+
+        assert_eq!(Block::FlowingWater7, world.block(1, 1, 0));
+        assert_eq!(Block::FlowingWater6, world.block(2, 1, 0));
+    }
+
+    #[rstest]
+    fn undisturbed_fluid_is_left_alone() {
+        let mut world = TestWorld::default();
+        let mut fluids = FluidSimulation::new(1);
+        world.set_block_at(BlockPosition::new(0, 1, 0), Block::Water);
+
+        world.edit(&mut fluids, 10, 1, 10, Block::Stone);
+        world.settle(&mut fluids);
+
+        assert_eq!(Block::Empty, world.block(1, 1, 0));
+    }
+
+    #[rstest]
+    fn flowing_water_blocks_have_the_level_they_were_made_with() {
+        for level in 1..=SOURCE_LEVEL {
+            assert_eq!(Some(level), Block::flowing_water(level).fluid_level());
+        }
+    }
+
+    #[rstest]
+    fn fluid_stops_at_the_edge_of_the_world() {
+        let mut world = TestWorld::default();
+        let mut fluids = FluidSimulation::new(1);
+
+        world.edit(&mut fluids, WORLD_RADIUS, 1, 0, Block::Water);
+        world.settle(&mut fluids);
+
+        assert_eq!(Block::FlowingWater7, world.block(WORLD_RADIUS - 1, 1, 0));
+    }
+}
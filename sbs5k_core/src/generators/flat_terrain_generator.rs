@@ -1,5 +1,7 @@
 use crate::block::Block;
-use crate::chunk::{Chunk, ChunkCoordinate, ChunkSource, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH};
+use crate::chunk::{
+    Chunk, ChunkCoordinate, ChunkSource, Surface, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH,
+};
 
 /// A basic `ChunkSource` that just emits flat chunks containing a layer of grass, three layers of
 /// dirt, and 61 layers of stone
@@ -28,4 +30,12 @@ impl ChunkSource for FlatTerrainGenerator {
         }
         chunk
     }
+
+    fn get_surface_at(&mut self, _global_x: i32, _global_z: i32) -> Option<Surface> {
+        Some(Surface {
+            ground_height: 65,
+            ground_block: Block::Grass,
+            water_height: None,
+        })
+    }
 }
@@ -2,4 +2,4 @@ mod flat_terrain_generator;
 mod perlin_noise_generator;
 
 pub use flat_terrain_generator::FlatTerrainGenerator;
-pub use perlin_noise_generator::PerlinNoiseGenerator;
+pub use perlin_noise_generator::{PerlinNoiseGenerator, DEFAULT_SEA_LEVEL};
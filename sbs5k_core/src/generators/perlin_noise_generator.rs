@@ -2,7 +2,9 @@ use std::collections::BTreeMap;
 use std::f32::consts::PI;
 
 use crate::block::Block;
-use crate::chunk::{Chunk, ChunkCoordinate, ChunkSource, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH};
+use crate::chunk::{
+    Chunk, ChunkCoordinate, ChunkSource, Surface, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH,
+};
 use crate::maths::{interpolate, modulo_fp};
 
 use glm::{cos, floor, sin};
@@ -12,13 +14,30 @@ use rand::Rng;
 
 type Index = (i32, i32);
 
+/// The height of the terrain's surface when the noise offset is zero
+const BASE_SURFACE_HEIGHT: i32 = 65;
+
+/// The sea level of worlds that don't choose their own
+pub const DEFAULT_SEA_LEVEL: i32 = 62;
+
+/// How far above the sea the ground can be and still be covered in sand
+const BEACH_HEIGHT: i32 = 2;
+
+/// How far below the sea the ground can be and still be covered in sand, rather than gravel
+const SHALLOWS_DEPTH: i32 = 3;
+
+/// How many blocks of dirt or sand lie between the surface and the stone underneath
+const SOIL_DEPTH: i32 = 3;
+
 struct NormalisedPerlinNoiseSource {
+    seed: u64,
     generated_vectors: BTreeMap<Index, Vector2<f32>>,
 }
 
 impl NormalisedPerlinNoiseSource {
-    pub fn new() -> Self {
+    pub fn new(seed: u64) -> Self {
         NormalisedPerlinNoiseSource {
+            seed,
             generated_vectors: Default::default(),
         }
     }
@@ -47,7 +66,7 @@ impl NormalisedPerlinNoiseSource {
         if let Some(v) = self.generated_vectors.get(&index) {
             return *v;
         }
-        let vector = generate_random_vector();
+        let vector = generate_gradient_vector(self.seed, index);
         self.generated_vectors.insert(index, vector);
         vector
     }
@@ -71,11 +90,11 @@ struct PerlinNoiseComponent {
 }
 
 impl PerlinNoiseComponent {
-    pub fn new(period: u32, amplitude: f32) -> Self {
+    pub fn new(period: u32, amplitude: f32, seed: u64) -> Self {
         PerlinNoiseComponent {
             period,
             amplitude,
-            noise_source: NormalisedPerlinNoiseSource::new(),
+            noise_source: NormalisedPerlinNoiseSource::new(seed),
         }
     }
 
@@ -90,20 +109,35 @@ impl PerlinNoiseComponent {
 
 pub struct PerlinNoiseGenerator {
     components: Vec<PerlinNoiseComponent>,
+    sea_level: i32,
 }
 
 impl PerlinNoiseGenerator {
+    /// Create a generator for a new world with a randomly chosen seed
     pub fn new() -> Self {
+        PerlinNoiseGenerator::with_seed(rand::thread_rng().gen())
+    }
+
+    /// Create a generator that always produces the same terrain for the same seed
+    pub fn with_seed(seed: u64) -> Self {
+        // Give each component a different seed so that their noise is uncorrelated
         PerlinNoiseGenerator {
             components: vec![
-                PerlinNoiseComponent::new(128, 30.0),
-                PerlinNoiseComponent::new(64, 30.0),
-                PerlinNoiseComponent::new(32, 15.0),
-                PerlinNoiseComponent::new(16, 2.0),
+                PerlinNoiseComponent::new(128, 30.0, seed),
+                PerlinNoiseComponent::new(64, 30.0, seed.wrapping_add(1)),
+                PerlinNoiseComponent::new(32, 15.0, seed.wrapping_add(2)),
+                PerlinNoiseComponent::new(16, 2.0, seed.wrapping_add(3)),
             ],
+            sea_level: DEFAULT_SEA_LEVEL,
         }
     }
 
+    /// Flood the terrain below `sea_level` with water, rather than the default sea level
+    pub fn with_sea_level(mut self, sea_level: i32) -> Self {
+        self.sea_level = sea_level;
+        self
+    }
+
     pub fn get_offset_at(&mut self, global_x: i32, global_z: i32) -> i32 {
         self.components
             .iter_mut()
@@ -116,35 +150,32 @@ impl ChunkSource for PerlinNoiseGenerator {
     fn get_chunk_at(&mut self, coordinate: ChunkCoordinate) -> Box<Chunk> {
         let mut chunk: Box<Chunk> = Default::default();
 
-        #[allow(clippy::needless_range_loop)]
         for x in 0..CHUNK_WIDTH {
-            for y in 0..CHUNK_HEIGHT {
-                for z in 0..CHUNK_DEPTH {
-                    let global_x = coordinate.i * (CHUNK_WIDTH as i32) + (x as i32);
-                    let global_z = coordinate.j * (CHUNK_DEPTH as i32) + (z as i32);
-
-                    let offset = self.get_offset_at(global_x, global_z);
-
-                    let empty_start = glm::max(65 + offset, 0);
-                    let grass_start = glm::max(empty_start - 1, 0);
-                    let dirt_start = glm::max(grass_start - 3, 0);
-
-                    let block_type = if y >= empty_start as usize {
-                        Block::Empty
-                    } else if y >= grass_start as usize {
-                        Block::Grass
-                    } else if y >= dirt_start as usize {
-                        Block::Dirt
-                    } else {
-                        Block::Stone
-                    };
-                    chunk.set_block_at(x, y, z, block_type);
+            for z in 0..CHUNK_DEPTH {
+                let global_x = coordinate.i * (CHUNK_WIDTH as i32) + (x as i32);
+                let global_z = coordinate.j * (CHUNK_DEPTH as i32) + (z as i32);
+
+                let offset = self.get_offset_at(global_x, global_z);
+                let column = column_blocks(BASE_SURFACE_HEIGHT + offset, self.sea_level);
+                for y in 0..CHUNK_HEIGHT {
+                    chunk.set_block_at(x, y, z, column(y as i32));
                 }
             }
         }
 
         chunk
     }
+
+    fn get_surface_at(&mut self, global_x: i32, global_z: i32) -> Option<Surface> {
+        let surface_height = BASE_SURFACE_HEIGHT + self.get_offset_at(global_x, global_z);
+        let column = column_blocks(surface_height, self.sea_level);
+        let ground_height = surface_height.max(0);
+        Some(Surface {
+            ground_height,
+            ground_block: column(ground_height - 1),
+            water_height: column(ground_height).is_fluid().then_some(self.sea_level),
+        })
+    }
 }
 
 impl Default for PerlinNoiseGenerator {
@@ -153,8 +184,144 @@ impl Default for PerlinNoiseGenerator {
     }
 }
 
-fn generate_random_vector() -> Vector2<f32> {
-    let mut rng = rand::thread_rng();
-    let theta = rng.gen::<f32>() * PI;
+/// Work out which block goes at each height in a column whose ground ends just below
+/// `surface_height`
+///
+/// The ground is topped with grass over a few layers of dirt, except along the shore, where it's
+/// sand, and further out to sea, where the seabed is gravel. Anything below `sea_level` that isn't
+/// ground is water.
+fn column_blocks(surface_height: i32, sea_level: i32) -> impl Fn(i32) -> Block {
+    let (top, soil) = if surface_height < sea_level - SHALLOWS_DEPTH {
+        (Block::Gravel, Block::Dirt)
+    } else if surface_height <= sea_level + BEACH_HEIGHT {
+        (Block::Sand, Block::Sand)
+    } else {
+        (Block::Grass, Block::Dirt)
+    };
+
+    move |y| {
+        if y >= surface_height {
+            if y < sea_level {
+                Block::Water
+            } else {
+                Block::Empty
+            }
+        } else if y == surface_height - 1 {
+            top
+        } else if y >= surface_height - 1 - SOIL_DEPTH {
+            soil
+        } else {
+            Block::Stone
+        }
+    }
+}
+
+/// Pick the gradient vector for a lattice point, deterministically from the seed and its index
+fn generate_gradient_vector(seed: u64, index: Index) -> Vector2<f32> {
+    let (x, y) = index;
+    let packed_index = ((x as u32 as u64) << 32) | (y as u32 as u64);
+    let hash = mix(seed ^ mix(packed_index));
+
+    // Use the top 24 bits, which is all the precision an f32 can hold
+    let unit = (hash >> 40) as f32 / (1u64 << 24) as f32;
+    let theta = unit * PI;
     Vector2::new(cos(theta), sin(theta))
 }
+
+/// Scramble the bits of a 64-bit value (this is the finaliser from SplitMix64)
+#[inline]
+fn mix(mut z: u64) -> u64 {
+    z = z.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    fn same_seed_produces_same_terrain() {
+        let mut a = PerlinNoiseGenerator::with_seed(1234);
+        let mut b = PerlinNoiseGenerator::with_seed(1234);
+        for (x, z) in [(0, 0), (17, -40), (-300, 2), (1000, 1000)] {
+            assert_eq!(a.get_offset_at(x, z), b.get_offset_at(x, z));
+        }
+    }
+
+    #[rstest]
+    fn different_seeds_produce_different_terrain() {
+        let mut a = PerlinNoiseGenerator::with_seed(1);
+        let mut b = PerlinNoiseGenerator::with_seed(2);
+        let differs =
+            (0..64).any(|i| a.get_offset_at(i * 7, i * 3) != b.get_offset_at(i * 7, i * 3));
+        assert!(differs);
+    }
+
+    #[rstest]
+    #[case(70, Block::Grass, Block::Dirt)]
+    #[case(64, Block::Sand, Block::Sand)]
+    #[case(60, Block::Sand, Block::Sand)]
+    #[case(50, Block::Gravel, Block::Dirt)]
+    fn the_ground_is_covered_according_to_its_height_above_the_sea(
+        #[case] surface_height: i32,
+        #[case] top: Block,
+        #[case] soil: Block,
+    ) {
+        let column = column_blocks(surface_height, 62);
+
+        assert_eq!(top, column(surface_height - 1));
+        assert_eq!(soil, column(surface_height - 2));
+        assert_eq!(soil, column(surface_height - 1 - SOIL_DEPTH));
+        assert_eq!(Block::Stone, column(surface_height - 2 - SOIL_DEPTH));
+    }
+
+    #[rstest]
+    fn terrain_below_sea_level_is_flooded() {
+        let column = column_blocks(50, 62);
+
+        assert_eq!(Block::Water, column(50));
+        assert_eq!(Block::Water, column(61));
+        assert_eq!(Block::Empty, column(62));
+    }
+
+    #[rstest]
+    fn columns_below_sea_level_report_water() {
+        let mut generator = PerlinNoiseGenerator::with_seed(5).with_sea_level(CHUNK_HEIGHT as i32);
+        let offset = generator.get_offset_at(3, 4);
+
+        assert_eq!(
+            Some(Surface {
+                ground_height: BASE_SURFACE_HEIGHT + offset,
+                ground_block: Block::Gravel,
+                water_height: Some(CHUNK_HEIGHT as i32),
+            }),
+            generator.get_surface_at(3, 4)
+        );
+    }
+
+    #[rstest]
+    fn columns_above_sea_level_report_their_ground() {
+        let mut generator = PerlinNoiseGenerator::with_seed(5).with_sea_level(0);
+        let offset = generator.get_offset_at(3, 4);
+
+        assert_eq!(
+            Some(Surface {
+                ground_height: BASE_SURFACE_HEIGHT + offset,
+                ground_block: Block::Grass,
+                water_height: None,
+            }),
+            generator.get_surface_at(3, 4)
+        );
+    }
+
+    #[rstest]
+    fn gradient_vectors_are_unit_length() {
+        for index in [(0, 0), (-1, 5), (i32::MAX, i32::MIN)] {
+            let v = generate_gradient_vector(99, index);
+            assert!((v.norm() - 1.0).abs() < 1e-5);
+        }
+    }
+}
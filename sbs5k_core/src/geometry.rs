@@ -1,3 +1,4 @@
+use glm::{cos, sin};
 use serde::{Deserialize, Serialize};
 
 /// A location within the 3D world
@@ -6,6 +7,12 @@ pub type Location = na::Point3<f32>;
 /// A change in location
 pub type LocationDelta = na::Vector3<f32>;
 
+/// The global integral coordinates of a block in the world
+///
+/// The block at position (x, y, z) occupies the unit cube spanning from (x, y, z) to
+/// (x + 1, y + 1, z + 1).
+pub type BlockPosition = na::Point3<i32>;
+
 /// An Euler-angle-based orientation in the world
 #[derive(Clone, Copy, Debug, Deserialize, Serialize)]
 pub struct Orientation {
@@ -19,6 +26,18 @@ pub struct Orientation {
     pub roll: f32,
 }
 
+impl Orientation {
+    /// Compute the unit vector pointing in the direction that this orientation faces
+    ///
+    /// Roll is ignored, since it can't affect the viewing direction.
+    pub fn direction(&self) -> LocationDelta {
+        let x = -sin(self.yaw) * cos(self.pitch);
+        let y = sin(self.pitch);
+        let z = cos(self.yaw) * cos(self.pitch);
+        LocationDelta::new(x, y, z)
+    }
+}
+
 /// A change in orientation
 pub struct OrientationDelta {
     pub delta_pitch: f32,
@@ -32,3 +51,31 @@ pub struct EntityPosition {
     pub location: Location,
     pub orientation: Orientation,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use std::f32::consts::PI;
+
+    #[rstest]
+    #[case(0.0, 0.0, LocationDelta::new(0.0, 0.0, 1.0))]
+    #[case(PI, 0.0, LocationDelta::new(0.0, 0.0, -1.0))]
+    #[case(PI / 2.0, 0.0, LocationDelta::new(-1.0, 0.0, 0.0))]
+    #[case(-PI / 2.0, 0.0, LocationDelta::new(1.0, 0.0, 0.0))]
+    #[case(0.0, PI / 2.0, LocationDelta::new(0.0, 1.0, 0.0))]
+    #[case(0.0, -PI / 4.0, LocationDelta::new(0.0, -1.0, 1.0).normalize())]
+    fn orientation_direction_works(
+        #[case] yaw: f32,
+        #[case] pitch: f32,
+        #[case] expected: LocationDelta,
+    ) {
+        let orientation = Orientation {
+            pitch,
+            yaw,
+            roll: 0.0,
+        };
+        let actual = orientation.direction();
+        assert!((expected - actual).norm() < 1e-6);
+    }
+}
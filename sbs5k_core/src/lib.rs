@@ -1,9 +1,17 @@
 pub mod block;
 pub mod chunk;
+pub mod clock;
 pub mod cube;
+pub mod fluid;
 pub mod generators;
 pub mod geometry;
+pub mod light;
+pub mod lod;
 pub mod maths;
+pub mod physics;
+pub mod raycast;
+pub mod spawn;
+pub mod world;
 
 extern crate nalgebra as na;
 extern crate serde_big_array;
@@ -0,0 +1,775 @@
+use std::collections::VecDeque;
+
+use nalgebra::Vector3;
+
+use crate::block::Block;
+use crate::chunk::{
+    block_index, Chunk, ChunkCoordinate, BLOCKS_IN_CHUNK, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH,
+};
+use crate::geometry::BlockPosition;
+use crate::world::BlockLookup;
+
+/// The brightest possible light level
+pub const MAX_LIGHT_LEVEL: u8 = 15;
+
+/// The two independent kinds of light tracked for every block
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LightChannel {
+    /// Light from the sky, which shines straight down without dimming until it hits something
+    Sky,
+
+    /// Light given off by emissive blocks
+    Block,
+}
+
+/// The light levels of every block in a chunk
+///
+/// Each block's levels are packed into a single byte, with the sky light in the high nibble and
+/// the block light in the low nibble. Blocks are stored in the same order as in `Chunk`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChunkLight {
+    levels: Vec<u8>,
+}
+
+impl Default for ChunkLight {
+    fn default() -> Self {
+        ChunkLight {
+            levels: vec![0; BLOCKS_IN_CHUNK],
+        }
+    }
+}
+
+impl ChunkLight {
+    /// Light a chunk as though it were the only chunk in the world
+    ///
+    /// Light arriving from neighbouring chunks is accounted for separately by `stitch_chunk` once
+    /// the chunk is placed in the world.
+    pub fn compute(chunk: &Chunk) -> Self {
+        let mut light = ChunkLight::default();
+        let mut sky_queue = VecDeque::new();
+        let mut block_queue = VecDeque::new();
+
+        for x in 0..CHUNK_WIDTH {
+            for z in 0..CHUNK_DEPTH {
+                // Sky light falls straight down each column until it reaches an opaque block
+                for y in (0..CHUNK_HEIGHT).rev() {
+                    if chunk.get_block_at(x, y, z).is_opaque() {
+                        break;
+                    }
+                    light.set(x, y, z, LightChannel::Sky, MAX_LIGHT_LEVEL);
+                    sky_queue.push_back(BlockPosition::new(x as i32, y as i32, z as i32));
+                }
+
+                for y in 0..CHUNK_HEIGHT {
+                    let emission = chunk.get_block_at(x, y, z).light_emission();
+                    if emission > 0 {
+                        light.set(x, y, z, LightChannel::Block, emission);
+                        block_queue.push_back(BlockPosition::new(x as i32, y as i32, z as i32));
+                    }
+                }
+            }
+        }
+
+        let mut view = IsolatedChunk {
+            chunk,
+            light: &mut light,
+        };
+        let mut dirty = DirtyChunks::default();
+        propagate(&mut view, LightChannel::Sky, sky_queue, &mut dirty);
+        propagate(&mut view, LightChannel::Block, block_queue, &mut dirty);
+
+        light
+    }
+
+    /// Get the light level of one channel at the block (x, y, z)
+    #[inline]
+    pub fn get(&self, x: usize, y: usize, z: usize, channel: LightChannel) -> u8 {
+        let packed = self.levels[block_index(x, y, z)];
+        match channel {
+            LightChannel::Sky => packed >> 4,
+            LightChannel::Block => packed & 0x0F,
+        }
+    }
+
+    /// Set the light level of one channel at the block (x, y, z)
+    #[inline]
+    pub fn set(&mut self, x: usize, y: usize, z: usize, channel: LightChannel, level: u8) {
+        debug_assert!(level <= MAX_LIGHT_LEVEL);
+        let packed = &mut self.levels[block_index(x, y, z)];
+        *packed = match channel {
+            LightChannel::Sky => (*packed & 0x0F) | (level << 4),
+            LightChannel::Block => (*packed & 0xF0) | level,
+        };
+    }
+}
+
+/// A world whose light levels can be read and updated
+///
+/// Implementations should return `None` for positions outside the loaded part of the world, which
+/// stops light from spreading there.
+pub trait LightStorage: BlockLookup {
+    fn get_light_at(&self, position: BlockPosition, channel: LightChannel) -> Option<u8>;
+
+    fn set_light_at(&mut self, position: BlockPosition, channel: LightChannel, level: u8);
+
+    /// The level of block light given off at `position`
+    fn light_emission_at(&self, position: BlockPosition) -> u8 {
+        self.get_block_at(position)
+            .map_or(0, |block| block.light_emission())
+    }
+}
+
+/// Update the lighting around `position` after the block there changed
+///
+/// This handles blocks becoming more or less opaque as well as light sources appearing,
+/// disappearing and changing brightness. Returns the coordinates of every chunk whose lighting
+/// changed.
+pub fn update_light_at(
+    world: &mut impl LightStorage,
+    position: BlockPosition,
+) -> Vec<ChunkCoordinate> {
+    let mut dirty = DirtyChunks::default();
+
+    let transparent = world
+        .get_block_at(position)
+        .is_some_and(|block| !block.is_opaque());
+
+    for channel in [LightChannel::Sky, LightChannel::Block] {
+        // Take away any light that used to pass through (or come from) this block, remembering
+        // the independently-lit blocks around the darkened area so they can fill it back in
+        let mut queue = remove_light_at(world, channel, position, &mut dirty);
+
+        if channel == LightChannel::Block {
+            let emission = world.light_emission_at(position);
+            if emission > 0 {
+                world.set_light_at(position, channel, emission);
+                dirty.mark(position);
+                queue.push_back(position);
+            }
+        }
+
+        if transparent {
+            if channel == LightChannel::Sky && position.y == CHUNK_HEIGHT as i32 - 1 {
+                world.set_light_at(position, channel, MAX_LIGHT_LEVEL);
+                dirty.mark(position);
+                queue.push_back(position);
+            }
+
+            // Let light from the surrounding blocks flow into the newly-opened space
+            for (offset, _) in NEIGHBOURS {
+                let neighbour = position + offset;
+                if world.get_light_at(neighbour, channel).unwrap_or(0) > 0 {
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+
+        propagate(world, channel, queue, &mut dirty);
+    }
+
+    dirty.into_vec()
+}
+
+/// Spread light across the borders between a newly-loaded chunk and its neighbours
+///
+/// The chunk's own light should already have been computed with `ChunkLight::compute`. Returns the
+/// coordinates of the neighbouring chunks whose lighting changed, which are the only ones that
+/// need remeshing besides the chunk itself.
+pub fn stitch_chunk(
+    world: &mut impl LightStorage,
+    coordinate: ChunkCoordinate,
+) -> Vec<ChunkCoordinate> {
+    let min_x = coordinate.i * CHUNK_WIDTH as i32;
+    let min_z = coordinate.j * CHUNK_DEPTH as i32;
+    let max_x = min_x + CHUNK_WIDTH as i32 - 1;
+    let max_z = min_z + CHUNK_DEPTH as i32 - 1;
+
+    // Each pair is a block just inside the chunk and its neighbour just outside
+    let mut border_pairs = vec![];
+    for y in 0..CHUNK_HEIGHT as i32 {
+        for z in min_z..=max_z {
+            border_pairs.push((
+                BlockPosition::new(min_x, y, z),
+                BlockPosition::new(min_x - 1, y, z),
+            ));
+            border_pairs.push((
+                BlockPosition::new(max_x, y, z),
+                BlockPosition::new(max_x + 1, y, z),
+            ));
+        }
+        for x in min_x..=max_x {
+            border_pairs.push((
+                BlockPosition::new(x, y, min_z),
+                BlockPosition::new(x, y, min_z - 1),
+            ));
+            border_pairs.push((
+                BlockPosition::new(x, y, max_z),
+                BlockPosition::new(x, y, max_z + 1),
+            ));
+        }
+    }
+
+    let mut dirty = DirtyChunks::default();
+    for channel in [LightChannel::Sky, LightChannel::Block] {
+        let mut queue = VecDeque::new();
+        for (inside, outside) in border_pairs.iter() {
+            let inside_level = world.get_light_at(*inside, channel);
+            let outside_level = world.get_light_at(*outside, channel);
+            if let (Some(inside_level), Some(outside_level)) = (inside_level, outside_level) {
+                // Only the brighter side of each pair can light up the other
+                if inside_level > outside_level + 1 {
+                    queue.push_back(*inside);
+                } else if outside_level > inside_level + 1 {
+                    queue.push_back(*outside);
+                }
+            }
+        }
+        propagate(world, channel, queue, &mut dirty);
+    }
+
+    let mut changed_neighbours = dirty.into_vec();
+    changed_neighbours.retain(|changed| *changed != coordinate);
+    changed_neighbours
+}
+
+/// The six face-adjacent neighbours of a block, and whether each is directly below it
+const NEIGHBOURS: [(Vector3<i32>, bool); 6] = [
+    (Vector3::new(1, 0, 0), false),
+    (Vector3::new(-1, 0, 0), false),
+    (Vector3::new(0, 1, 0), false),
+    (Vector3::new(0, -1, 0), true),
+    (Vector3::new(0, 0, 1), false),
+    (Vector3::new(0, 0, -1), false),
+];
+
+/// Work out the light level that a block passes on to a neighbour
+#[inline]
+fn attenuate(channel: LightChannel, level: u8, is_downwards: bool) -> u8 {
+    if channel == LightChannel::Sky && is_downwards && level == MAX_LIGHT_LEVEL {
+        MAX_LIGHT_LEVEL
+    } else {
+        level.saturating_sub(1)
+    }
+}
+
+/// Spread light outwards from every block in `queue` until nothing else gets brighter
+fn propagate(
+    world: &mut impl LightStorage,
+    channel: LightChannel,
+    mut queue: VecDeque<BlockPosition>,
+    dirty: &mut DirtyChunks,
+) {
+    while let Some(position) = queue.pop_front() {
+        let level = match world.get_light_at(position, channel) {
+            Some(level) if level > 0 => level,
+            _ => continue,
+        };
+
+        for (offset, is_downwards) in NEIGHBOURS {
+            let neighbour = position + offset;
+            let transparent = world
+                .get_block_at(neighbour)
+                .is_some_and(|block| !block.is_opaque());
+            if !transparent {
+                continue;
+            }
+
+            let new_level = attenuate(channel, level, is_downwards);
+            let current = match world.get_light_at(neighbour, channel) {
+                Some(current) => current,
+                None => continue,
+            };
+            if new_level > current {
+                world.set_light_at(neighbour, channel, new_level);
+                dirty.mark(neighbour);
+                queue.push_back(neighbour);
+            }
+        }
+    }
+}
+
+/// Darken `position` and everything that was lit through it
+///
+/// Returns the blocks bordering the darkened area that still have light of their own, from which
+/// light should be propagated again to fill in whatever the removed light no longer reaches.
+fn remove_light_at(
+    world: &mut impl LightStorage,
+    channel: LightChannel,
+    position: BlockPosition,
+    dirty: &mut DirtyChunks,
+) -> VecDeque<BlockPosition> {
+    let mut relight = VecDeque::new();
+    let level = match world.get_light_at(position, channel) {
+        Some(level) if level > 0 => level,
+        _ => return relight,
+    };
+
+    world.set_light_at(position, channel, 0);
+    dirty.mark(position);
+    let mut removal_queue = VecDeque::from([(position, level)]);
+
+    while let Some((position, level)) = removal_queue.pop_front() {
+        for (offset, is_downwards) in NEIGHBOURS {
+            let neighbour = position + offset;
+            let neighbour_level = match world.get_light_at(neighbour, channel) {
+                Some(neighbour_level) if neighbour_level > 0 => neighbour_level,
+                _ => continue,
+            };
+
+            let lit_from_here = neighbour_level < level
+                || (neighbour_level == MAX_LIGHT_LEVEL
+                    && attenuate(channel, level, is_downwards) == MAX_LIGHT_LEVEL);
+            if lit_from_here {
+                world.set_light_at(neighbour, channel, 0);
+                dirty.mark(neighbour);
+                removal_queue.push_back((neighbour, neighbour_level));
+
+                // Emissive blocks keep shining regardless
+                if channel == LightChannel::Block {
+                    let emission = world.light_emission_at(neighbour);
+                    if emission > 0 {
+                        world.set_light_at(neighbour, channel, emission);
+                        relight.push_back(neighbour);
+                    }
+                }
+            } else {
+                relight.push_back(neighbour);
+            }
+        }
+    }
+
+    relight
+}
+
+/// Collects the coordinates of the chunks touched by a lighting update
+#[derive(Default)]
+struct DirtyChunks {
+    coordinates: Vec<ChunkCoordinate>,
+}
+
+impl DirtyChunks {
+    #[inline]
+    fn mark(&mut self, position: BlockPosition) {
+        let coordinate = ChunkCoordinate::from_block_position(position);
+        if !self.coordinates.contains(&coordinate) {
+            self.coordinates.push(coordinate);
+        }
+    }
+
+    fn into_vec(self) -> Vec<ChunkCoordinate> {
+        self.coordinates
+    }
+}
+
+/// A view of a single chunk on its own, with its minimum corner at the origin
+struct IsolatedChunk<'a> {
+    chunk: &'a Chunk,
+    light: &'a mut ChunkLight,
+}
+
+impl IsolatedChunk<'_> {
+    #[inline]
+    fn local_position(position: BlockPosition) -> Option<(usize, usize, usize)> {
+        let in_bounds = (0..CHUNK_WIDTH as i32).contains(&position.x)
+            && (0..CHUNK_HEIGHT as i32).contains(&position.y)
+            && (0..CHUNK_DEPTH as i32).contains(&position.z);
+        in_bounds.then_some((
+            position.x as usize,
+            position.y as usize,
+            position.z as usize,
+        ))
+    }
+}
+
+impl BlockLookup for IsolatedChunk<'_> {
+    fn get_block_at(&self, position: BlockPosition) -> Option<Block> {
+        let (x, y, z) = Self::local_position(position)?;
+        Some(self.chunk.get_block_at(x, y, z))
+    }
+}
+
+impl LightStorage for IsolatedChunk<'_> {
+    fn get_light_at(&self, position: BlockPosition, channel: LightChannel) -> Option<u8> {
+        let (x, y, z) = Self::local_position(position)?;
+        Some(self.light.get(x, y, z, channel))
+    }
+
+    fn set_light_at(&mut self, position: BlockPosition, channel: LightChannel, level: u8) {
+        if let Some((x, y, z)) = Self::local_position(position) {
+            self.light.set(x, y, z, channel, level);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::chunk::block_position_within_chunk;
+    use rstest::*;
+
+    /// A small world made of a handful of chunks, with extra light sources that can be placed
+    /// anywhere
+    #[derive(Default)]
+    struct TestWorld {
+        chunks: HashMap<ChunkCoordinate, (Box<Chunk>, ChunkLight)>,
+        sources: HashMap<BlockPosition, u8>,
+    }
+
+    impl TestWorld {
+        /// Build a world from chunks that are each lit on their own and then stitched together
+        fn new(chunks: Vec<(ChunkCoordinate, Box<Chunk>)>) -> Self {
+            let mut world = TestWorld::default();
+            for (coordinate, chunk) in chunks {
+                let light = ChunkLight::compute(&chunk);
+                world.chunks.insert(coordinate, (chunk, light));
+                stitch_chunk(&mut world, coordinate);
+            }
+            world
+        }
+
+        /// A row of empty chunks from `(min_i, 0)` to `(max_i, 0)`
+        fn open(min_i: i32, max_i: i32) -> Self {
+            TestWorld::new(
+                (min_i..=max_i)
+                    .map(|i| (ChunkCoordinate { i, j: 0 }, Box::default()))
+                    .collect(),
+            )
+        }
+
+        fn set_block(&mut self, position: BlockPosition, block: Block) {
+            let (chunk, _) = self
+                .chunks
+                .get_mut(&ChunkCoordinate::from_block_position(position))
+                .unwrap();
+            let (x, y, z) = block_position_within_chunk(position).unwrap();
+            chunk.set_block_at(x, y, z, block);
+        }
+
+        fn light(&self, x: i32, y: i32, z: i32, channel: LightChannel) -> u8 {
+            self.get_light_at(BlockPosition::new(x, y, z), channel)
+                .unwrap()
+        }
+
+        /// Relight the whole world from scratch, for comparing with incremental updates
+        fn relit(&self) -> Self {
+            let mut world = TestWorld::new(
+                self.chunks
+                    .iter()
+                    .map(|(coordinate, (chunk, _))| (*coordinate, chunk.clone()))
+                    .collect(),
+            );
+            world.sources = self.sources.clone();
+            for position in self.sources.keys() {
+                update_light_at(&mut world, *position);
+            }
+            world
+        }
+    }
+
+    impl BlockLookup for TestWorld {
+        fn get_block_at(&self, position: BlockPosition) -> Option<Block> {
+            let (chunk, _) = self
+                .chunks
+                .get(&ChunkCoordinate::from_block_position(position))?;
+            let (x, y, z) = block_position_within_chunk(position)?;
+            Some(chunk.get_block_at(x, y, z))
+        }
+    }
+
+    impl LightStorage for TestWorld {
+        fn get_light_at(&self, position: BlockPosition, channel: LightChannel) -> Option<u8> {
+            let (_, light) = self
+                .chunks
+                .get(&ChunkCoordinate::from_block_position(position))?;
+            let (x, y, z) = block_position_within_chunk(position)?;
+            Some(light.get(x, y, z, channel))
+        }
+
+        fn set_light_at(&mut self, position: BlockPosition, channel: LightChannel, level: u8) {
+            let coordinate = ChunkCoordinate::from_block_position(position);
+            if let (Some((_, light)), Some((x, y, z))) = (
+                self.chunks.get_mut(&coordinate),
+                block_position_within_chunk(position),
+            ) {
+                light.set(x, y, z, channel, level);
+            }
+        }
+
+        fn light_emission_at(&self, position: BlockPosition) -> u8 {
+            self.sources.get(&position).copied().unwrap_or(0)
+        }
+    }
+
+    /// A chunk with a solid stone roof at `y`, optionally with a hole in it
+    fn roofed_chunk(y: usize, hole: Option<(usize, usize)>) -> Box<Chunk> {
+        let mut chunk: Box<Chunk> = Box::default();
+        for x in 0..CHUNK_WIDTH {
+            for z in 0..CHUNK_DEPTH {
+                if hole != Some((x, z)) {
+                    chunk.set_block_at(x, y, z, Block::Stone);
+                }
+            }
+        }
+        chunk
+    }
+
+    #[rstest]
+    fn channels_are_stored_independently() {
+        let mut light = ChunkLight::default();
+        light.set(1, 2, 3, LightChannel::Sky, 12);
+        light.set(1, 2, 3, LightChannel::Block, 7);
+        assert_eq!(12, light.get(1, 2, 3, LightChannel::Sky));
+        assert_eq!(7, light.get(1, 2, 3, LightChannel::Block));
+
+        light.set(1, 2, 3, LightChannel::Sky, 0);
+        assert_eq!(0, light.get(1, 2, 3, LightChannel::Sky));
+        assert_eq!(7, light.get(1, 2, 3, LightChannel::Block));
+    }
+
+    #[rstest]
+    fn open_sky_lights_everything() {
+        let light = ChunkLight::compute(&Chunk::default());
+        for (x, y, z) in [(0, 0, 0), (5, 64, 9), (15, CHUNK_HEIGHT - 1, 15)] {
+            assert_eq!(MAX_LIGHT_LEVEL, light.get(x, y, z, LightChannel::Sky));
+            assert_eq!(0, light.get(x, y, z, LightChannel::Block));
+        }
+    }
+
+    #[rstest]
+    #[case((8, 99, 8), 15)]
+    #[case((8, 10, 8), 15)]
+    #[case((9, 99, 8), 14)]
+    #[case((10, 99, 8), 13)]
+    #[case((8, 99, 10), 13)]
+    #[case((12, 50, 8), 11)]
+    #[case((0, 50, 0), 0)]
+    #[case((0, 101, 0), 15)]
+    fn sky_light_spreads_out_below_a_hole_in_the_roof(
+        #[case] position: (usize, usize, usize),
+        #[case] expected: u8,
+    ) {
+        let (x, y, z) = position;
+        let light = ChunkLight::compute(&roofed_chunk(100, Some((8, 8))));
+        assert_eq!(expected, light.get(x, y, z, LightChannel::Sky));
+    }
+
+    #[rstest]
+    #[case((8, 60, 8), 14)]
+    #[case((11, 60, 8), 11)]
+    #[case((9, 61, 9), 11)]
+    #[case((8, 60, 1), 7)]
+    #[case((1, 60, 1), 0)]
+    fn block_light_falls_off_with_distance(
+        #[case] position: (i32, i32, i32),
+        #[case] expected: u8,
+    ) {
+        let (x, y, z) = position;
+        let mut world = TestWorld::open(0, 0);
+        let source = BlockPosition::new(8, 60, 8);
+        world.sources.insert(source, 14);
+        update_light_at(&mut world, source);
+        assert_eq!(expected, world.light(x, y, z, LightChannel::Block));
+    }
+
+    #[rstest]
+    fn block_light_travels_around_walls() {
+        let mut world = TestWorld::open(0, 0);
+
+        // A wall at x = 6 spanning the whole chunk, apart from a gap at z = 0
+        for y in 50..=70 {
+            for z in 1..CHUNK_DEPTH as i32 {
+                world.set_block(BlockPosition::new(6, y, z), Block::Stone);
+            }
+        }
+
+        let source = BlockPosition::new(8, 60, 8);
+        world.sources.insert(source, 15);
+        update_light_at(&mut world, source);
+
+        // Blocked straight through the wall, so the light has to go round via z = 0
+        assert_eq!(0, world.light(6, 60, 8, LightChannel::Block));
+        assert_eq!(5, world.light(6, 60, 0, LightChannel::Block));
+        assert_eq!(3, world.light(5, 60, 1, LightChannel::Block));
+        assert_eq!(0, world.light(5, 60, 8, LightChannel::Block));
+    }
+
+    #[rstest]
+    fn removing_a_source_removes_its_light() {
+        let mut world = TestWorld::open(0, 1);
+        let source = BlockPosition::new(15, 60, 8);
+        world.sources.insert(source, 15);
+        update_light_at(&mut world, source);
+        assert_eq!(13, world.light(17, 60, 8, LightChannel::Block));
+
+        world.sources.remove(&source);
+        let dirty = update_light_at(&mut world, source);
+
+        assert_eq!(2, dirty.len());
+        for (_, (_, light)) in world.chunks.iter() {
+            assert!(light.levels.iter().all(|packed| packed & 0x0F == 0));
+        }
+    }
+
+    #[rstest]
+    fn removing_one_source_keeps_the_others_light() {
+        let mut world = TestWorld::open(0, 0);
+        let first = BlockPosition::new(4, 60, 8);
+        let second = BlockPosition::new(12, 60, 8);
+        for source in [first, second] {
+            world.sources.insert(source, 15);
+            update_light_at(&mut world, source);
+        }
+        assert_eq!(15, world.light(4, 60, 8, LightChannel::Block));
+
+        world.sources.remove(&first);
+        update_light_at(&mut world, first);
+
+        assert_eq!(7, world.light(4, 60, 8, LightChannel::Block));
+        assert_eq!(11, world.light(8, 60, 8, LightChannel::Block));
+        assert_eq!(15, world.light(12, 60, 8, LightChannel::Block));
+        assert!(world.relit().chunks == world.chunks);
+    }
+
+    #[rstest]
+    #[case((16, 60, 8), 14)]
+    #[case((-1, 60, 0), 14)]
+    #[case((-2, 60, -3), 10)]
+    #[case((-16, 60, 0), 0)]
+    fn block_light_crosses_chunk_borders(#[case] position: (i32, i32, i32), #[case] expected: u8) {
+        let (x, y, z) = position;
+        let mut world = TestWorld::new(
+            [(-1, -1), (-1, 0), (0, -1), (0, 0), (1, 0)]
+                .into_iter()
+                .map(|(i, j)| (ChunkCoordinate { i, j }, Box::default()))
+                .collect(),
+        );
+        for source in [BlockPosition::new(15, 60, 8), BlockPosition::new(0, 60, 0)] {
+            world.sources.insert(source, 15);
+            update_light_at(&mut world, source);
+        }
+        assert_eq!(expected, world.light(x, y, z, LightChannel::Block));
+    }
+
+    #[rstest]
+    fn stitching_lets_sky_light_in_from_a_neighbouring_chunk() {
+        let open = ChunkCoordinate { i: 0, j: 0 };
+        let roofed = ChunkCoordinate { i: 1, j: 0 };
+
+        let mut world = TestWorld::new(vec![(open, Box::default())]);
+        let roofed_chunk = roofed_chunk(100, None);
+        let light = ChunkLight::compute(&roofed_chunk);
+        assert_eq!(0, light.get(0, 50, 8, LightChannel::Sky));
+        world.chunks.insert(roofed, (roofed_chunk, light));
+
+        let changed_neighbours = stitch_chunk(&mut world, roofed);
+
+        assert!(changed_neighbours.is_empty());
+        assert_eq!(14, world.light(16, 50, 8, LightChannel::Sky));
+        assert_eq!(13, world.light(17, 50, 8, LightChannel::Sky));
+        assert_eq!(0, world.light(31, 50, 8, LightChannel::Sky));
+        assert_eq!(15, world.light(16, 101, 8, LightChannel::Sky));
+    }
+
+    #[rstest]
+    fn stitching_reports_the_neighbours_it_lights_up() {
+        let roofed = ChunkCoordinate { i: 0, j: 0 };
+        let open = ChunkCoordinate { i: 1, j: 0 };
+
+        let mut world = TestWorld::new(vec![(roofed, roofed_chunk(100, None))]);
+        let light = ChunkLight::compute(&Chunk::default());
+        world.chunks.insert(open, (Box::default(), light));
+
+        let changed_neighbours = stitch_chunk(&mut world, open);
+
+        assert_eq!(vec![roofed], changed_neighbours);
+        assert_eq!(14, world.light(15, 50, 8, LightChannel::Sky));
+    }
+
+    #[rstest]
+    fn stitching_matching_chunks_changes_no_neighbours() {
+        let mut world = TestWorld::open(0, 0);
+        let coordinate = ChunkCoordinate { i: 1, j: 0 };
+        let light = ChunkLight::compute(&Chunk::default());
+        world.chunks.insert(coordinate, (Box::default(), light));
+
+        let changed_neighbours = stitch_chunk(&mut world, coordinate);
+
+        assert!(changed_neighbours.is_empty());
+    }
+
+    #[rstest]
+    fn placing_and_breaking_a_block_updates_the_sky_light_below() {
+        let mut world = TestWorld::open(0, 0);
+        let position = BlockPosition::new(8, 100, 8);
+
+        world.set_block(position, Block::Stone);
+        update_light_at(&mut world, position);
+        assert_eq!(0, world.light(8, 100, 8, LightChannel::Sky));
+        assert_eq!(14, world.light(8, 99, 8, LightChannel::Sky));
+        assert_eq!(14, world.light(8, 10, 8, LightChannel::Sky));
+        assert_eq!(15, world.light(9, 99, 8, LightChannel::Sky));
+
+        world.set_block(position, Block::Empty);
+        update_light_at(&mut world, position);
+        assert_eq!(15, world.light(8, 100, 8, LightChannel::Sky));
+        assert_eq!(15, world.light(8, 10, 8, LightChannel::Sky));
+    }
+
+    #[rstest]
+    fn breaking_into_a_sealed_room_lets_the_light_in() {
+        let mut world = TestWorld::new(vec![(
+            ChunkCoordinate { i: 0, j: 0 },
+            roofed_chunk(100, None),
+        )]);
+        assert_eq!(0, world.light(8, 99, 8, LightChannel::Sky));
+
+        let position = BlockPosition::new(8, 100, 8);
+        world.set_block(position, Block::Empty);
+        let dirty = update_light_at(&mut world, position);
+
+        assert_eq!(vec![ChunkCoordinate { i: 0, j: 0 }], dirty);
+        assert_eq!(15, world.light(8, 99, 8, LightChannel::Sky));
+        assert_eq!(15, world.light(8, 0, 8, LightChannel::Sky));
+        assert_eq!(14, world.light(7, 0, 8, LightChannel::Sky));
+    }
+
+    #[rstest]
+    fn incremental_updates_match_relighting_from_scratch() {
+        let mut world = TestWorld::new(
+            [(-1, 0), (0, 0), (0, 1)]
+                .into_iter()
+                .map(|(i, j)| (ChunkCoordinate { i, j }, roofed_chunk(20, Some((3, 3)))))
+                .collect(),
+        );
+
+        // A simple linear congruential generator gives a repeatable sequence of edits
+        let mut state: u32 = 12345;
+        let mut next = |n: i32| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            ((state >> 16) % n as u32) as i32
+        };
+
+        for _ in 0..200 {
+            let position = BlockPosition::new(next(32) - 16, 14 + next(10), next(32));
+            if ChunkCoordinate::from_block_position(position) == (ChunkCoordinate { i: -1, j: 1 }) {
+                continue;
+            }
+
+            match next(4) {
+                0 => world.set_block(position, Block::Stone),
+                1 => world.set_block(position, Block::Empty),
+                2 => {
+                    world.sources.insert(position, 1 + next(15) as u8);
+                }
+                _ => {
+                    world.sources.remove(&position);
+                }
+            }
+            update_light_at(&mut world, position);
+        }
+
+        assert!(world.relit().chunks == world.chunks);
+    }
+}
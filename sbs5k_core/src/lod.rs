@@ -0,0 +1,217 @@
+use nalgebra::Vector3;
+
+use crate::chunk::{ChunkCoordinate, ChunkSource, Surface, CHUNK_DEPTH, CHUNK_WIDTH};
+
+/// A coarse approximation of the terrain surface covering one chunk, used for drawing distant
+/// terrain beyond the render distance
+///
+/// The surface is sampled once every `step` blocks along each axis. One extra sample is kept on
+/// each side of the tile (belonging to the neighbouring tiles) so that surface normals can be
+/// computed at the tile's edges.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LodTile {
+    /// The chunk covered by this tile
+    pub coordinate: ChunkCoordinate,
+
+    /// The distance in blocks between adjacent samples
+    pub step: u32,
+
+    /// The sampled surfaces, including the border, in X-major order
+    surfaces: Vec<Surface>,
+}
+
+impl LodTile {
+    /// Sample the surface of the chunk at `coordinate` from a chunk source
+    ///
+    /// Returns `None` if the source is unable to report its surface. `step` must divide the
+    /// chunk's width and depth.
+    pub fn generate(
+        source: &mut dyn ChunkSource,
+        coordinate: ChunkCoordinate,
+        step: u32,
+    ) -> Option<Self> {
+        assert!(
+            (CHUNK_WIDTH as u32).is_multiple_of(step) && (CHUNK_DEPTH as u32).is_multiple_of(step),
+            "LOD step {} does not divide the chunk size",
+            step
+        );
+
+        let samples_per_edge = samples_per_edge(step) as i32;
+        let base_x = coordinate.i * CHUNK_WIDTH as i32;
+        let base_z = coordinate.j * CHUNK_DEPTH as i32;
+
+        let mut surfaces =
+            Vec::with_capacity(((samples_per_edge + 2) * (samples_per_edge + 2)) as usize);
+        for i in -1..=samples_per_edge {
+            for j in -1..=samples_per_edge {
+                let x = base_x + i * step as i32;
+                let z = base_z + j * step as i32;
+                surfaces.push(source.get_surface_at(x, z)?);
+            }
+        }
+
+        Some(LodTile {
+            coordinate,
+            step,
+            surfaces,
+        })
+    }
+
+    /// The number of samples along each edge of the tile, not including the border
+    #[inline]
+    pub fn samples_per_edge(&self) -> u32 {
+        samples_per_edge(self.step)
+    }
+
+    /// Get the surface at sample (i, j)
+    ///
+    /// Both `i` and `j` may range from -1 to `samples_per_edge()` inclusive, where the outermost
+    /// values refer to the border samples.
+    #[inline]
+    pub fn surface_at(&self, i: i32, j: i32) -> Surface {
+        let stride = self.samples_per_edge() as i32 + 2;
+        self.surfaces[((i + 1) * stride + (j + 1)) as usize]
+    }
+
+    /// Get the height of the ground at sample (i, j), ignoring any water covering it
+    ///
+    /// The range of `i` and `j` is the same as for `surface_at`.
+    #[inline]
+    pub fn height_at(&self, i: i32, j: i32) -> i32 {
+        self.surface_at(i, j).ground_height
+    }
+
+    /// Estimate the unit normal of the ground at sample (i, j) using central differences
+    ///
+    /// Both `i` and `j` must lie in the range 0 to `samples_per_edge() - 1` inclusive.
+    pub fn normal_at(&self, i: i32, j: i32) -> Vector3<f32> {
+        let dx = (self.height_at(i + 1, j) - self.height_at(i - 1, j)) as f32;
+        let dz = (self.height_at(i, j + 1) - self.height_at(i, j - 1)) as f32;
+        let span = 2.0 * self.step as f32;
+        Vector3::new(-dx, span, -dz).normalize()
+    }
+}
+
+/// Choose the sample spacing for a tile in the `ring`th ring of tiles beyond the render distance
+///
+/// Ring 0 is the ring immediately outside the full-detail chunks. Rings further away from the
+/// player use progressively coarser tiles.
+pub fn lod_step_for_ring(ring: u32) -> u32 {
+    match ring {
+        0..=3 => 2,
+        4..=7 => 4,
+        _ => 8,
+    }
+}
+
+#[inline]
+fn samples_per_edge(step: u32) -> u32 {
+    CHUNK_WIDTH as u32 / step + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use crate::chunk::{Chunk, CHUNK_HEIGHT};
+    use crate::generators::{FlatTerrainGenerator, PerlinNoiseGenerator};
+    use rstest::*;
+
+    /// A chunk source whose surface is a slope rising by one block per block in the x direction
+    struct SlopeSource;
+
+    impl ChunkSource for SlopeSource {
+        fn get_chunk_at(&mut self, _coordinate: ChunkCoordinate) -> Box<Chunk> {
+            Box::default()
+        }
+
+        fn get_surface_at(&mut self, global_x: i32, _global_z: i32) -> Option<Surface> {
+            Some(Surface {
+                ground_height: 64 + global_x,
+                ground_block: Block::Grass,
+                water_height: None,
+            })
+        }
+    }
+
+    /// A chunk source that can't report its surface
+    struct NoHeightsSource;
+
+    impl ChunkSource for NoHeightsSource {
+        fn get_chunk_at(&mut self, _coordinate: ChunkCoordinate) -> Box<Chunk> {
+            Box::default()
+        }
+    }
+
+    #[rstest]
+    #[case(2, 9)]
+    #[case(4, 5)]
+    #[case(8, 3)]
+    fn samples_per_edge_works(#[case] step: u32, #[case] expected: u32) {
+        let tile =
+            LodTile::generate(&mut FlatTerrainGenerator, ChunkCoordinate::default(), step).unwrap();
+        assert_eq!(expected, tile.samples_per_edge());
+    }
+
+    #[rstest]
+    fn flat_terrain_has_constant_height_and_upwards_normals() {
+        let coordinate = ChunkCoordinate { i: -3, j: 7 };
+        let tile = LodTile::generate(&mut FlatTerrainGenerator, coordinate, 4).unwrap();
+        let n = tile.samples_per_edge() as i32;
+        for i in -1..=n {
+            for j in -1..=n {
+                assert_eq!(65, tile.height_at(i, j));
+            }
+        }
+        for i in 0..n {
+            for j in 0..n {
+                assert_eq!(Vector3::new(0.0, 1.0, 0.0), tile.normal_at(i, j));
+            }
+        }
+    }
+
+    #[rstest]
+    fn tile_samples_global_coordinates_including_border() {
+        let coordinate = ChunkCoordinate { i: 1, j: 0 };
+        let tile = LodTile::generate(&mut SlopeSource, coordinate, 8).unwrap();
+        assert_eq!(64 + 16 - 8, tile.height_at(-1, 0));
+        assert_eq!(64 + 16, tile.height_at(0, 0));
+        assert_eq!(64 + 32, tile.height_at(2, 0));
+        assert_eq!(64 + 32 + 8, tile.height_at(3, 3));
+    }
+
+    #[rstest]
+    fn slope_normal_points_away_from_the_slope() {
+        let tile = LodTile::generate(&mut SlopeSource, ChunkCoordinate::default(), 2).unwrap();
+        let normal = tile.normal_at(1, 1);
+        let expected = Vector3::new(-1.0, 1.0, 0.0).normalize();
+        assert!((normal - expected).norm() < 1e-6);
+    }
+
+    #[rstest]
+    fn samples_below_sea_level_are_underwater() {
+        let mut source = PerlinNoiseGenerator::with_seed(5).with_sea_level(CHUNK_HEIGHT as i32);
+        let tile = LodTile::generate(&mut source, ChunkCoordinate::default(), 8).unwrap();
+
+        let surface = tile.surface_at(1, 1);
+        assert_eq!(Some(CHUNK_HEIGHT as i32), surface.water_height);
+        assert_eq!(Block::Gravel, surface.ground_block);
+    }
+
+    #[rstest]
+    fn generate_fails_for_sources_without_heights() {
+        let tile = LodTile::generate(&mut NoHeightsSource, ChunkCoordinate::default(), 2);
+        assert_eq!(None, tile);
+    }
+
+    #[rstest]
+    #[case(0, 2)]
+    #[case(3, 2)]
+    #[case(4, 4)]
+    #[case(7, 4)]
+    #[case(8, 8)]
+    #[case(100, 8)]
+    fn lod_step_for_ring_works(#[case] ring: u32, #[case] expected: u32) {
+        assert_eq!(expected, lod_step_for_ring(ring));
+    }
+}
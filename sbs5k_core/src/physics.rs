@@ -0,0 +1,614 @@
+use crate::geometry::{BlockPosition, Location, LocationDelta};
+use crate::world::BlockLookup;
+
+/// The width of the player's body along both the X and Z axes, in blocks
+pub const PLAYER_WIDTH: f32 = 0.6;
+
+/// The height of the player's body, in blocks
+pub const PLAYER_HEIGHT: f32 = 1.8;
+
+/// The height of the player's eyes above their feet, in blocks
+pub const PLAYER_EYE_HEIGHT: f32 = 1.62;
+
+/// The downwards acceleration due to gravity, in blocks per second squared
+pub const GRAVITY: f32 = 32.0;
+
+/// The fastest speed at which an entity can fall, in blocks per second
+pub const TERMINAL_VELOCITY: f32 = 60.0;
+
+/// The initial upwards speed of a jump, in blocks per second
+///
+/// This is enough to clear a single block but not two.
+pub const JUMP_VELOCITY: f32 = 9.0;
+
+/// The tallest obstacle that an entity can walk up onto without jumping, in blocks
+pub const STEP_HEIGHT: f32 = 1.0;
+
+/// The longest period of time that a single physics step will simulate, in seconds
+///
+/// This stops a long frame (for example, while the world is loading) from launching the player
+/// through the world.
+pub const MAX_TIMESTEP: f32 = 0.1;
+
+/// Tolerance used to stop entities from snagging on the faces of blocks they are touching
+const EPSILON: f32 = 1e-4;
+
+/// An axis-aligned bounding box
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Location,
+    pub max: Location,
+}
+
+impl Aabb {
+    /// Compute the bounding box of a player whose eyes are at `eye_location`
+    pub fn for_player(eye_location: Location) -> Self {
+        let half_width = PLAYER_WIDTH / 2.0;
+        let feet_y = eye_location.y - PLAYER_EYE_HEIGHT;
+        Aabb {
+            min: Location::new(
+                eye_location.x - half_width,
+                feet_y,
+                eye_location.z - half_width,
+            ),
+            max: Location::new(
+                eye_location.x + half_width,
+                feet_y + PLAYER_HEIGHT,
+                eye_location.z + half_width,
+            ),
+        }
+    }
+
+    /// Whether this box overlaps the block at `position`
+    pub fn overlaps_block(&self, position: BlockPosition) -> bool {
+        (0..3).all(|axis| self.blocks_overlapped_along(axis).contains(&position[axis]))
+    }
+
+    /// Move the box by `distance` along `axis`
+    #[inline]
+    fn translate_along(&mut self, axis: usize, distance: f32) {
+        self.min[axis] += distance;
+        self.max[axis] += distance;
+    }
+
+    /// Work out how far this box can move along `axis` before it collides with a solid block
+    ///
+    /// Blocks that the box already overlaps are ignored, so that an entity that somehow ends up
+    /// inside the terrain is able to escape. Blocks that aren't available from `world` are
+    /// treated as solid.
+    pub fn sweep(&self, world: &impl BlockLookup, axis: usize, distance: f32) -> f32 {
+        if distance == 0.0 {
+            return 0.0;
+        }
+
+        let (a, b) = match axis {
+            0 => (1, 2),
+            1 => (0, 2),
+            _ => (0, 1),
+        };
+        let a_range = self.blocks_overlapped_along(a);
+        let b_range = self.blocks_overlapped_along(b);
+
+        let is_blocked = |layer: i32| {
+            for i in a_range.clone() {
+                for j in b_range.clone() {
+                    let mut position = BlockPosition::new(0, 0, 0);
+                    position[axis] = layer;
+                    position[a] = i;
+                    position[b] = j;
+                    let solid = world
+                        .get_block_at(position)
+                        .map(|block| block.is_solid())
+                        .unwrap_or(true);
+                    if solid {
+                        return true;
+                    }
+                }
+            }
+            false
+        };
+
+        if distance > 0.0 {
+            let edge = self.max[axis];
+            let first = (edge - EPSILON).floor() as i32;
+            let last = (edge + distance).floor() as i32;
+            for layer in first..=last {
+                if (layer as f32) < edge - EPSILON {
+                    continue;
+                }
+                if (layer as f32) >= edge + distance {
+                    break;
+                }
+                if is_blocked(layer) {
+                    return (layer as f32 - edge).max(0.0);
+                }
+            }
+        } else {
+            let edge = self.min[axis];
+            let first = (edge + EPSILON).floor() as i32 - 1;
+            let last = (edge + distance).floor() as i32;
+            for layer in (last..=first).rev() {
+                if ((layer + 1) as f32) <= edge + distance {
+                    break;
+                }
+                if is_blocked(layer) {
+                    return ((layer + 1) as f32 - edge).min(0.0);
+                }
+            }
+        }
+
+        distance
+    }
+
+    /// The range of block indices along `axis` that this box overlaps
+    #[inline]
+    fn blocks_overlapped_along(&self, axis: usize) -> std::ops::RangeInclusive<i32> {
+        let first = (self.min[axis] + EPSILON).floor() as i32;
+        let last = (self.max[axis] - EPSILON).floor() as i32;
+        first..=last
+    }
+}
+
+/// The physical state of a walking player
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PlayerBody {
+    /// The player's current velocity, in blocks per second
+    pub velocity: LocationDelta,
+
+    /// Whether the player was standing on solid ground at the end of the last step
+    pub on_ground: bool,
+}
+
+impl PlayerBody {
+    /// Advance the simulation by `dt` seconds, moving the player's eyes from `eye_location`
+    ///
+    /// `walk_velocity` is the horizontal velocity that the player is trying to move at; its Y
+    /// component is ignored. If `jump` is set and the player is on the ground, they will jump.
+    pub fn step(
+        &mut self,
+        world: &impl BlockLookup,
+        eye_location: &mut Location,
+        walk_velocity: LocationDelta,
+        jump: bool,
+        dt: f32,
+    ) {
+        let dt = dt.min(MAX_TIMESTEP);
+
+        self.velocity.x = walk_velocity.x;
+        self.velocity.z = walk_velocity.z;
+        self.velocity.y = (self.velocity.y - GRAVITY * dt).max(-TERMINAL_VELOCITY);
+        if jump && self.on_ground {
+            self.velocity.y = JUMP_VELOCITY;
+        }
+
+        let start = Aabb::for_player(*eye_location);
+        let mut aabb = self.move_horizontally(world, start, dt);
+
+        let dy = self.velocity.y * dt;
+        let allowed_dy = aabb.sweep(world, 1, dy);
+        aabb.translate_along(1, allowed_dy);
+        if allowed_dy != dy {
+            self.on_ground = dy < 0.0;
+            self.velocity.y = 0.0;
+        } else {
+            self.on_ground = false;
+        }
+
+        *eye_location += aabb.min - start.min;
+    }
+
+    /// Move the player's body horizontally, stepping up onto low obstacles if possible
+    fn move_horizontally(&self, world: &impl BlockLookup, start: Aabb, dt: f32) -> Aabb {
+        let dx = self.velocity.x * dt;
+        let dz = self.velocity.z * dt;
+
+        let (flat, blocked) = slide(world, start, dx, dz);
+        if !blocked || !self.on_ground {
+            return flat;
+        }
+
+        // Try again from STEP_HEIGHT higher up, then drop back down onto whatever is there
+        let mut stepped = start;
+        let rise = stepped.sweep(world, 1, STEP_HEIGHT);
+        stepped.translate_along(1, rise);
+        (stepped, _) = slide(world, stepped, dx, dz);
+        let fall = stepped.sweep(world, 1, -rise);
+        stepped.translate_along(1, fall);
+
+        let horizontal_distance_squared = |aabb: &Aabb| {
+            let delta = aabb.min - start.min;
+            delta.x * delta.x + delta.z * delta.z
+        };
+        if horizontal_distance_squared(&stepped) > horizontal_distance_squared(&flat) + EPSILON {
+            stepped
+        } else {
+            flat
+        }
+    }
+}
+
+/// Move a box along the X axis and then the Z axis, stopping at any solid blocks in the way
+///
+/// Also reports whether the box was stopped short along either axis.
+fn slide(world: &impl BlockLookup, mut aabb: Aabb, dx: f32, dz: f32) -> (Aabb, bool) {
+    let allowed_dx = aabb.sweep(world, 0, dx);
+    aabb.translate_along(0, allowed_dx);
+    let allowed_dz = aabb.sweep(world, 2, dz);
+    aabb.translate_along(2, allowed_dz);
+    (aabb, allowed_dx != dx || allowed_dz != dz)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::Block;
+    use rstest::*;
+    use std::collections::HashSet;
+
+    const DT: f32 = 1.0 / 60.0;
+
+    /// A world with solid ground below `floor_height` and a handful of extra solid blocks
+    struct TestWorld {
+        floor_height: Option<i32>,
+        solid_blocks: HashSet<(i32, i32, i32)>,
+        unloaded_blocks: HashSet<(i32, i32, i32)>,
+    }
+
+    impl TestWorld {
+        fn new(floor_height: Option<i32>, solid_blocks: &[(i32, i32, i32)]) -> Self {
+            TestWorld {
+                floor_height,
+                solid_blocks: solid_blocks.iter().copied().collect(),
+                unloaded_blocks: HashSet::new(),
+            }
+        }
+    }
+
+    impl BlockLookup for TestWorld {
+        fn get_block_at(&self, position: BlockPosition) -> Option<Block> {
+            let key = (position.x, position.y, position.z);
+            let below_floor = self.floor_height.is_some_and(|h| position.y < h);
+            if self.unloaded_blocks.contains(&key) {
+                None
+            } else if below_floor || self.solid_blocks.contains(&key) {
+                Some(Block::Stone)
+            } else {
+                Some(Block::Empty)
+            }
+        }
+    }
+
+    fn eye_height_standing_on(floor_height: i32) -> f32 {
+        floor_height as f32 + PLAYER_EYE_HEIGHT
+    }
+
+    fn simulate(
+        world: &TestWorld,
+        body: &mut PlayerBody,
+        eye: &mut Location,
+        walk_velocity: LocationDelta,
+        steps: usize,
+    ) {
+        for _ in 0..steps {
+            body.step(world, eye, walk_velocity, false, DT);
+        }
+    }
+
+    fn assert_close(expected: f32, actual: f32) {
+        assert!(
+            (expected - actual).abs() < 1e-3,
+            "Expected {} but got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[rstest]
+    fn player_aabb_surrounds_eyes() {
+        let aabb = Aabb::for_player(Location::new(0.5, 65.62, 0.5));
+        assert_close(0.2, aabb.min.x);
+        assert_close(64.0, aabb.min.y);
+        assert_close(0.2, aabb.min.z);
+        assert_close(0.8, aabb.max.x);
+        assert_close(65.8, aabb.max.y);
+        assert_close(0.8, aabb.max.z);
+    }
+
+    #[rstest]
+    #[case(0, 2.0, 1.7)]
+    #[case(0, 0.5, 0.5)]
+    #[case(2, 2.0, 1.7)]
+    #[case(0, -5.0, -3.0)]
+    #[case(2, -0.1, -0.1)]
+    #[case(1, -3.0, -1.0)]
+    #[case(1, 1.0, 0.2)]
+    fn sweep_stops_at_solid_blocks(
+        #[case] axis: usize,
+        #[case] distance: f32,
+        #[case] expected: f32,
+    ) {
+        let world = TestWorld::new(
+            None,
+            &[(2, 65, 0), (0, 65, 2), (-4, 64, 0), (0, 62, 0), (0, 66, 0)],
+        );
+        let aabb = Aabb {
+            min: Location::new(0.0, 64.0, 0.0),
+            max: Location::new(0.3, 65.8, 0.3),
+        };
+        assert_close(expected, aabb.sweep(&world, axis, distance));
+    }
+
+    #[rstest]
+    #[case(BlockPosition::new(0, 64, 0), true)]
+    #[case(BlockPosition::new(0, 65, 0), true)]
+    #[case(BlockPosition::new(0, 66, 0), false)]
+    #[case(BlockPosition::new(0, 63, 0), false)]
+    #[case(BlockPosition::new(1, 64, 0), false)]
+    #[case(BlockPosition::new(0, 64, -1), false)]
+    fn player_aabb_overlaps_blocks_it_occupies(
+        #[case] position: BlockPosition,
+        #[case] expected: bool,
+    ) {
+        let aabb = Aabb::for_player(Location::new(0.5, 65.62, 0.5));
+        assert_eq!(expected, aabb.overlaps_block(position));
+    }
+
+    #[rstest]
+    fn sweep_ignores_blocks_already_overlapped() {
+        let world = TestWorld::new(None, &[(0, 64, 0), (3, 64, 0)]);
+        let aabb = Aabb {
+            min: Location::new(0.2, 64.2, 0.2),
+            max: Location::new(0.8, 64.8, 0.8),
+        };
+        assert_close(2.2, aabb.sweep(&world, 0, 4.0));
+    }
+
+    #[rstest]
+    fn sweep_treats_unloaded_blocks_as_solid() {
+        let mut world = TestWorld::new(None, &[]);
+        world.unloaded_blocks.insert((0, 60, 0));
+        let aabb = Aabb::for_player(Location::new(0.5, 65.62, 0.5));
+        assert_close(-3.0, aabb.sweep(&world, 1, -10.0));
+    }
+
+    #[rstest]
+    fn player_falls_and_lands_on_the_ground() {
+        let world = TestWorld::new(Some(64), &[]);
+        let mut body = PlayerBody::default();
+        let mut eye = Location::new(0.5, 80.0, 0.5);
+
+        simulate(&world, &mut body, &mut eye, LocationDelta::zeros(), 120);
+
+        assert_close(eye_height_standing_on(64), eye.y);
+        assert!(body.on_ground);
+        assert_eq!(0.0, body.velocity.y);
+    }
+
+    #[rstest]
+    fn falling_player_reaches_terminal_velocity() {
+        let world = TestWorld::new(None, &[]);
+        let mut body = PlayerBody::default();
+        let mut eye = Location::new(0.5, 10000.0, 0.5);
+
+        simulate(&world, &mut body, &mut eye, LocationDelta::zeros(), 600);
+
+        assert_eq!(-TERMINAL_VELOCITY, body.velocity.y);
+        assert!(!body.on_ground);
+    }
+
+    #[rstest]
+    fn fast_falls_do_not_tunnel_through_thin_floors() {
+        let world = TestWorld::new(None, &[(0, 10, 0)]);
+        let mut body = PlayerBody {
+            velocity: LocationDelta::new(0.0, -TERMINAL_VELOCITY, 0.0),
+            on_ground: false,
+        };
+        let mut eye = Location::new(0.5, 14.0, 0.5);
+
+        body.step(
+            &world,
+            &mut eye,
+            LocationDelta::zeros(),
+            false,
+            MAX_TIMESTEP,
+        );
+
+        assert_close(eye_height_standing_on(11), eye.y);
+        assert!(body.on_ground);
+    }
+
+    #[rstest]
+    #[case(LocationDelta::new(4.0, 0.0, 0.0), Location::new(4.7, 0.0, 0.5))]
+    #[case(LocationDelta::new(-4.0, 0.0, 0.0), Location::new(-2.7, 0.0, 0.5))]
+    #[case(LocationDelta::new(0.0, 0.0, 4.0), Location::new(0.5, 0.0, 4.7))]
+    #[case(LocationDelta::new(0.0, 0.0, -4.0), Location::new(0.5, 0.0, -2.7))]
+    fn walls_block_walking(#[case] walk_velocity: LocationDelta, #[case] expected: Location) {
+        // Two-block-high walls in each direction
+        let world = TestWorld::new(
+            Some(64),
+            &[
+                (5, 64, 0),
+                (5, 65, 0),
+                (-4, 64, 0),
+                (-4, 65, 0),
+                (0, 64, 5),
+                (0, 65, 5),
+                (0, 64, -4),
+                (0, 65, -4),
+            ],
+        );
+        let mut body = PlayerBody {
+            on_ground: true,
+            ..Default::default()
+        };
+        let mut eye = Location::new(0.5, eye_height_standing_on(64), 0.5);
+
+        simulate(&world, &mut body, &mut eye, walk_velocity, 180);
+
+        assert_close(expected.x, eye.x);
+        assert_close(expected.z, eye.z);
+        assert_close(eye_height_standing_on(64), eye.y);
+    }
+
+    #[rstest]
+    fn player_slides_along_walls() {
+        let world = TestWorld::new(
+            Some(64),
+            &[
+                (2, 64, 0),
+                (2, 65, 0),
+                (2, 64, 1),
+                (2, 65, 1),
+                (2, 64, 2),
+                (2, 65, 2),
+            ],
+        );
+        let mut body = PlayerBody {
+            on_ground: true,
+            ..Default::default()
+        };
+        let mut eye = Location::new(1.5, eye_height_standing_on(64), 0.5);
+
+        simulate(
+            &world,
+            &mut body,
+            &mut eye,
+            LocationDelta::new(2.0, 0.0, 2.0),
+            30,
+        );
+
+        assert_close(1.7, eye.x);
+        assert_close(1.5, eye.z);
+    }
+
+    #[rstest]
+    fn player_steps_up_onto_single_blocks() {
+        let world = TestWorld::new(Some(64), &[(2, 64, 0), (3, 64, 0), (4, 64, 0)]);
+        let mut body = PlayerBody {
+            on_ground: true,
+            ..Default::default()
+        };
+        let mut eye = Location::new(0.5, eye_height_standing_on(64), 0.5);
+
+        simulate(
+            &world,
+            &mut body,
+            &mut eye,
+            LocationDelta::new(4.0, 0.0, 0.0),
+            45,
+        );
+
+        assert!(eye.x > 2.5, "Player didn't move past the step: {}", eye.x);
+        assert_close(eye_height_standing_on(65), eye.y);
+        assert!(body.on_ground);
+    }
+
+    #[rstest]
+    fn player_cannot_step_up_two_blocks() {
+        let world = TestWorld::new(Some(64), &[(2, 64, 0), (2, 65, 0)]);
+        let mut body = PlayerBody {
+            on_ground: true,
+            ..Default::default()
+        };
+        let mut eye = Location::new(0.5, eye_height_standing_on(64), 0.5);
+
+        simulate(
+            &world,
+            &mut body,
+            &mut eye,
+            LocationDelta::new(4.0, 0.0, 0.0),
+            60,
+        );
+
+        assert_close(1.7, eye.x);
+        assert_close(eye_height_standing_on(64), eye.y);
+    }
+
+    #[rstest]
+    fn player_cannot_step_up_in_mid_air() {
+        let world = TestWorld::new(Some(64), &[(2, 66, 0)]);
+        let mut body = PlayerBody::default();
+        let mut eye = Location::new(0.5, eye_height_standing_on(66) + 0.05, 0.5);
+
+        body.step(
+            &world,
+            &mut eye,
+            LocationDelta::new(120.0, 0.0, 0.0),
+            false,
+            DT,
+        );
+
+        assert_close(1.7, eye.x);
+    }
+
+    #[rstest]
+    fn jumping_clears_one_block_but_not_two() {
+        let world = TestWorld::new(Some(64), &[]);
+        let mut body = PlayerBody {
+            on_ground: true,
+            ..Default::default()
+        };
+        let mut eye = Location::new(0.5, eye_height_standing_on(64), 0.5);
+
+        let mut peak: f32 = eye.y;
+        body.step(&world, &mut eye, LocationDelta::zeros(), true, DT);
+        assert!(body.velocity.y > 0.0);
+        assert!(!body.on_ground);
+        for _ in 0..120 {
+            body.step(&world, &mut eye, LocationDelta::zeros(), false, DT);
+            peak = peak.max(eye.y);
+        }
+
+        let jump_height = peak - eye_height_standing_on(64);
+        assert!(jump_height > 1.0 && jump_height < 2.0);
+        assert_close(eye_height_standing_on(64), eye.y);
+        assert!(body.on_ground);
+    }
+
+    #[rstest]
+    fn player_cannot_jump_in_mid_air() {
+        let world = TestWorld::new(Some(0), &[]);
+        let mut body = PlayerBody::default();
+        let mut eye = Location::new(0.5, 100.0, 0.5);
+
+        body.step(&world, &mut eye, LocationDelta::zeros(), true, DT);
+
+        assert!(body.velocity.y < 0.0);
+    }
+
+    #[rstest]
+    fn ceilings_stop_jumps() {
+        let world = TestWorld::new(Some(64), &[(0, 66, 0)]);
+        let mut body = PlayerBody {
+            on_ground: true,
+            ..Default::default()
+        };
+        let mut eye = Location::new(0.5, eye_height_standing_on(64), 0.5);
+
+        body.step(&world, &mut eye, LocationDelta::zeros(), true, DT);
+        for _ in 0..3 {
+            body.step(&world, &mut eye, LocationDelta::zeros(), false, DT);
+        }
+
+        assert!(eye.y <= 66.0 - PLAYER_HEIGHT + PLAYER_EYE_HEIGHT + 1e-4);
+        assert!(body.velocity.y <= 0.0);
+        assert!(!body.on_ground);
+    }
+
+    #[rstest]
+    fn player_does_not_fall_into_unloaded_chunks() {
+        let mut world = TestWorld::new(None, &[]);
+        for x in -1..=1 {
+            for z in -1..=1 {
+                world.unloaded_blocks.insert((x, 40, z));
+            }
+        }
+        let mut body = PlayerBody::default();
+        let mut eye = Location::new(0.5, 50.0, 0.5);
+
+        simulate(&world, &mut body, &mut eye, LocationDelta::zeros(), 120);
+
+        assert_close(eye_height_standing_on(41), eye.y);
+        assert!(body.on_ground);
+    }
+}
@@ -0,0 +1,354 @@
+use crate::block::Block;
+use crate::cube::CubeFace;
+use crate::geometry::{BlockPosition, EntityPosition, Location, LocationDelta};
+use crate::world::BlockLookup;
+
+/// Information about the first block struck by a ray
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RaycastHit {
+    /// The position of the block that was hit
+    pub block: BlockPosition,
+
+    /// The face of the block through which the ray entered it
+    pub face: CubeFace,
+
+    /// The distance travelled along the ray before hitting the block
+    pub distance: f32,
+}
+
+/// Find the first block that an entity is looking at, up to `max_distance` away
+pub fn cast_ray_from_entity(
+    world: &impl BlockLookup,
+    entity: &EntityPosition,
+    max_distance: f32,
+) -> Option<RaycastHit> {
+    cast_ray(
+        world,
+        entity.location,
+        entity.orientation.direction(),
+        max_distance,
+    )
+}
+
+/// Find the first non-empty block along a ray, up to `max_distance` away
+///
+/// This is an implementation of the Amanatides-Woo voxel traversal algorithm, which visits every
+/// block that the ray passes through in order. The block containing `origin` itself is never
+/// reported as a hit. Blocks that aren't available in `world` are treated as empty.
+pub fn cast_ray(
+    world: &impl BlockLookup,
+    origin: Location,
+    direction: LocationDelta,
+    max_distance: f32,
+) -> Option<RaycastHit> {
+    if direction == LocationDelta::zeros() {
+        return None;
+    }
+    let direction = direction.normalize();
+
+    let mut block = origin.map(|c| c.floor() as i32);
+    let mut step = [0; 3];
+    let mut t_max = [f32::INFINITY; 3];
+    let mut t_delta = [f32::INFINITY; 3];
+
+    for axis in 0..3 {
+        let d = direction[axis];
+        if d > 0.0 {
+            step[axis] = 1;
+            t_max[axis] = ((block[axis] + 1) as f32 - origin[axis]) / d;
+            t_delta[axis] = 1.0 / d;
+        } else if d < 0.0 {
+            step[axis] = -1;
+            t_max[axis] = (origin[axis] - block[axis] as f32) / -d;
+            t_delta[axis] = -1.0 / d;
+        }
+    }
+
+    loop {
+        // Cross whichever block boundary the ray reaches first
+        let axis = if t_max[0] <= t_max[1] && t_max[0] <= t_max[2] {
+            0
+        } else if t_max[1] <= t_max[2] {
+            1
+        } else {
+            2
+        };
+
+        let distance = t_max[axis];
+        if distance > max_distance {
+            return None;
+        }
+
+        block[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
+
+        match world.get_block_at(block) {
+            // Rays carry on through water, so that the blocks under it can be reached
+            Some(Block::Empty) | None => continue,
+            Some(block) if block.is_fluid() => continue,
+            Some(_) => {
+                return Some(RaycastHit {
+                    block,
+                    face: entry_face(axis, step[axis]),
+                    distance,
+                })
+            }
+        }
+    }
+}
+
+/// Work out which face of a block a ray enters through, given the axis along which it crossed
+/// into the block and the direction in which it was travelling along that axis
+#[inline]
+fn entry_face(axis: usize, step: i32) -> CubeFace {
+    match (axis, step > 0) {
+        (0, true) => CubeFace::NegX,
+        (0, false) => CubeFace::PosX,
+        (1, true) => CubeFace::NegY,
+        (1, false) => CubeFace::PosY,
+        (_, true) => CubeFace::NegZ,
+        (_, false) => CubeFace::PosZ,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Orientation;
+    use rstest::*;
+    use std::collections::HashSet;
+    use std::f32::consts::PI;
+
+    const EPSILON: f32 = 1e-5;
+
+    /// A world in which only the listed blocks are solid
+    struct SparseWorld {
+        solid_blocks: HashSet<(i32, i32, i32)>,
+        unloaded_blocks: HashSet<(i32, i32, i32)>,
+    }
+
+    impl SparseWorld {
+        fn new(solid_blocks: &[(i32, i32, i32)]) -> Self {
+            SparseWorld {
+                solid_blocks: solid_blocks.iter().copied().collect(),
+                unloaded_blocks: HashSet::new(),
+            }
+        }
+    }
+
+    impl BlockLookup for SparseWorld {
+        fn get_block_at(&self, position: BlockPosition) -> Option<Block> {
+            let key = (position.x, position.y, position.z);
+            if self.unloaded_blocks.contains(&key) {
+                None
+            } else if self.solid_blocks.contains(&key) {
+                Some(Block::Stone)
+            } else {
+                Some(Block::Empty)
+            }
+        }
+    }
+
+    fn assert_hit(hit: Option<RaycastHit>, block: (i32, i32, i32), face: CubeFace, distance: f32) {
+        let hit = hit.expect("Expected the ray to hit a block");
+        assert_eq!(BlockPosition::new(block.0, block.1, block.2), hit.block);
+        assert_eq!(face, hit.face);
+        assert!(
+            (distance - hit.distance).abs() < EPSILON,
+            "Expected distance {} but got {}",
+            distance,
+            hit.distance
+        );
+    }
+
+    #[rstest]
+    #[case(LocationDelta::new(1.0, 0.0, 0.0), (3, 0, 0), CubeFace::NegX)]
+    #[case(LocationDelta::new(-1.0, 0.0, 0.0), (-3, 0, 0), CubeFace::PosX)]
+    #[case(LocationDelta::new(0.0, 1.0, 0.0), (0, 3, 0), CubeFace::NegY)]
+    #[case(LocationDelta::new(0.0, -1.0, 0.0), (0, -3, 0), CubeFace::PosY)]
+    #[case(LocationDelta::new(0.0, 0.0, 1.0), (0, 0, 3), CubeFace::NegZ)]
+    #[case(LocationDelta::new(0.0, 0.0, -1.0), (0, 0, -3), CubeFace::PosZ)]
+    fn axis_aligned_rays_hit_the_facing_face(
+        #[case] direction: LocationDelta,
+        #[case] block: (i32, i32, i32),
+        #[case] face: CubeFace,
+    ) {
+        let world = SparseWorld::new(&[
+            (3, 0, 0),
+            (-3, 0, 0),
+            (0, 3, 0),
+            (0, -3, 0),
+            (0, 0, 3),
+            (0, 0, -3),
+        ]);
+        let origin = Location::new(0.5, 0.5, 0.5);
+        let hit = cast_ray(&world, origin, direction, 10.0);
+        assert_hit(hit, block, face, 2.5);
+    }
+
+    #[rstest]
+    fn ray_hits_the_nearest_of_several_blocks() {
+        let world = SparseWorld::new(&[(5, 0, 0), (2, 0, 0), (7, 0, 0)]);
+        let origin = Location::new(0.25, 0.5, 0.5);
+        let hit = cast_ray(&world, origin, LocationDelta::new(1.0, 0.0, 0.0), 10.0);
+        assert_hit(hit, (2, 0, 0), CubeFace::NegX, 1.75);
+    }
+
+    #[rstest]
+    fn direction_does_not_need_to_be_normalised() {
+        let world = SparseWorld::new(&[(0, 0, 4)]);
+        let origin = Location::new(0.5, 0.5, 0.5);
+        let hit = cast_ray(&world, origin, LocationDelta::new(0.0, 0.0, 20.0), 10.0);
+        assert_hit(hit, (0, 0, 4), CubeFace::NegZ, 3.5);
+    }
+
+    #[rstest]
+    #[case(3.4, None)]
+    #[case(3.5, Some(3.5))]
+    fn ray_stops_at_max_distance(#[case] max_distance: f32, #[case] expected: Option<f32>) {
+        let world = SparseWorld::new(&[(4, 0, 0)]);
+        let origin = Location::new(0.5, 0.5, 0.5);
+        let hit = cast_ray(
+            &world,
+            origin,
+            LocationDelta::new(1.0, 0.0, 0.0),
+            max_distance,
+        );
+        assert_eq!(expected, hit.map(|h| h.distance));
+    }
+
+    #[rstest]
+    fn ray_that_hits_nothing_returns_none() {
+        let world = SparseWorld::new(&[(0, 5, 0)]);
+        let origin = Location::new(0.5, 0.5, 0.5);
+        let hit = cast_ray(&world, origin, LocationDelta::new(1.0, 0.0, 1.0), 50.0);
+        assert_eq!(None, hit);
+    }
+
+    #[rstest]
+    fn zero_direction_returns_none() {
+        let world = SparseWorld::new(&[(0, 0, 0), (1, 0, 0)]);
+        let origin = Location::new(0.5, 0.5, 0.5);
+        let hit = cast_ray(&world, origin, LocationDelta::zeros(), 10.0);
+        assert_eq!(None, hit);
+    }
+
+    #[rstest]
+    fn block_containing_origin_is_ignored() {
+        let world = SparseWorld::new(&[(0, 0, 0), (2, 0, 0)]);
+        let origin = Location::new(0.5, 0.5, 0.5);
+        let hit = cast_ray(&world, origin, LocationDelta::new(1.0, 0.0, 0.0), 10.0);
+        assert_hit(hit, (2, 0, 0), CubeFace::NegX, 1.5);
+    }
+
+    #[rstest]
+    fn unavailable_blocks_are_treated_as_empty() {
+        let mut world = SparseWorld::new(&[(2, 0, 0), (4, 0, 0)]);
+        world.unloaded_blocks.insert((2, 0, 0));
+        let origin = Location::new(0.5, 0.5, 0.5);
+        let hit = cast_ray(&world, origin, LocationDelta::new(1.0, 0.0, 0.0), 10.0);
+        assert_hit(hit, (4, 0, 0), CubeFace::NegX, 3.5);
+    }
+
+    #[rstest]
+    fn diagonal_ray_in_plane_steps_through_each_block() {
+        // From (0.5, 0.2) travelling along (1, 1) the ray crosses x = 1 at t = 0.5, y = 1 at
+        // t = 0.8, x = 2 at t = 1.5 and y = 2 at t = 1.8 (in unnormalised units)
+        let world = SparseWorld::new(&[(2, 2, 0), (0, 1, 0), (1, 2, 0)]);
+        let origin = Location::new(0.5, 0.2, 0.5);
+        let hit = cast_ray(&world, origin, LocationDelta::new(1.0, 1.0, 0.0), 10.0);
+        assert_hit(hit, (2, 2, 0), CubeFace::NegY, 1.8 * 2.0_f32.sqrt());
+    }
+
+    #[rstest]
+    fn diagonal_ray_in_three_dimensions() {
+        // The ray crosses z = 1 first, then y = 1, and finally enters (1, 1, 1) through x = 1
+        let world = SparseWorld::new(&[(1, 1, 1)]);
+        let origin = Location::new(0.1, 0.2, 0.3);
+        let hit = cast_ray(&world, origin, LocationDelta::new(1.0, 1.0, 1.0), 10.0);
+        assert_hit(hit, (1, 1, 1), CubeFace::NegX, 0.9 * 3.0_f32.sqrt());
+    }
+
+    #[rstest]
+    fn diagonal_ray_misses_blocks_it_only_passes_near() {
+        // These blocks are adjacent to the path of the ray but never intersected by it
+        let world = SparseWorld::new(&[(1, 0, 0), (0, 2, 0), (2, 1, 0)]);
+        let origin = Location::new(0.2, 0.5, 0.5);
+        let hit = cast_ray(&world, origin, LocationDelta::new(1.0, 1.0, 0.0), 3.0);
+        assert_eq!(None, hit);
+    }
+
+    #[rstest]
+    fn diagonal_ray_through_negative_coordinates() {
+        // From (-0.3, -0.6) travelling along (-1, -1) in the XZ plane, the ray crosses z = -1 at
+        // t = 0.4, x = -1 at t = 0.7, z = -2 at t = 1.4 and x = -2 at t = 1.7
+        let world = SparseWorld::new(&[(-3, 10, -3), (-2, 10, -1)]);
+        let origin = Location::new(-0.3, 10.5, -0.6);
+        let hit = cast_ray(&world, origin, LocationDelta::new(-1.0, 0.0, -1.0), 10.0);
+        assert_hit(hit, (-3, 10, -3), CubeFace::PosX, 1.7 * 2.0_f32.sqrt());
+    }
+
+    #[rstest]
+    #[case(Location::new(-0.5, 0.5, 0.5), LocationDelta::new(-1.0, 0.0, 0.0), (-3, 0, 0), CubeFace::PosX, 1.5)]
+    #[case(Location::new(0.5, -4.5, 0.5), LocationDelta::new(0.0, -1.0, 0.0), (0, -7, 0), CubeFace::PosY, 1.5)]
+    #[case(Location::new(-7.25, 0.5, -7.25), LocationDelta::new(0.0, 0.0, 1.0), (-8, 0, -5), CubeFace::NegZ, 2.25)]
+    fn axis_aligned_rays_in_negative_coordinates(
+        #[case] origin: Location,
+        #[case] direction: LocationDelta,
+        #[case] block: (i32, i32, i32),
+        #[case] face: CubeFace,
+        #[case] distance: f32,
+    ) {
+        let world = SparseWorld::new(&[(-3, 0, 0), (0, -7, 0), (-8, 0, -5)]);
+        let hit = cast_ray(&world, origin, direction, 10.0);
+        assert_hit(hit, block, face, distance);
+    }
+
+    #[rstest]
+    #[case(Location::new(15.5, 64.5, 0.5), LocationDelta::new(1.0, 0.0, 0.0), (17, 64, 0), CubeFace::NegX, 1.5)]
+    #[case(Location::new(0.5, 64.5, 0.5), LocationDelta::new(-1.0, 0.0, 0.0), (-1, 64, 0), CubeFace::PosX, 0.5)]
+    #[case(Location::new(8.5, 64.5, -0.5), LocationDelta::new(0.0, 0.0, 1.0), (8, 64, 17), CubeFace::NegZ, 17.5)]
+    fn rays_cross_chunk_boundaries(
+        #[case] origin: Location,
+        #[case] direction: LocationDelta,
+        #[case] block: (i32, i32, i32),
+        #[case] face: CubeFace,
+        #[case] distance: f32,
+    ) {
+        let world = SparseWorld::new(&[(17, 64, 0), (-1, 64, 0), (8, 64, 17)]);
+        let hit = cast_ray(&world, origin, direction, 20.0);
+        assert_hit(hit, block, face, distance);
+    }
+
+    #[rstest]
+    fn ray_starting_on_a_block_boundary() {
+        let world = SparseWorld::new(&[(1, 0, 0), (-1, 0, 0)]);
+        let origin = Location::new(2.0, 0.5, 0.5);
+        let hit = cast_ray(&world, origin, LocationDelta::new(-1.0, 0.0, 0.0), 10.0);
+        assert_hit(hit, (1, 0, 0), CubeFace::PosX, 0.0);
+    }
+
+    #[rstest]
+    #[case(0.0, 0.0, (8, 64, 12), CubeFace::NegZ, 3.5)]
+    #[case(PI / 2.0, 0.0, (4, 64, 8), CubeFace::PosX, 3.5)]
+    #[case(0.0, -PI / 2.0, (8, 63, 8), CubeFace::PosY, 0.5)]
+    fn rays_cast_from_entity_follow_its_orientation(
+        #[case] yaw: f32,
+        #[case] pitch: f32,
+        #[case] block: (i32, i32, i32),
+        #[case] face: CubeFace,
+        #[case] distance: f32,
+    ) {
+        let world = SparseWorld::new(&[(8, 64, 12), (4, 64, 8), (8, 63, 8)]);
+        let entity = EntityPosition {
+            location: Location::new(8.5, 64.5, 8.5),
+            orientation: Orientation {
+                pitch,
+                yaw,
+                roll: 0.0,
+            },
+        };
+        let hit = cast_ray_from_entity(&world, &entity, 10.0);
+        assert_hit(hit, block, face, distance);
+    }
+}
@@ -0,0 +1,167 @@
+use crate::block::Block;
+use crate::chunk::{
+    square_ring, Chunk, ChunkCoordinate, ChunkSource, CHUNK_DEPTH, CHUNK_HEIGHT, CHUNK_WIDTH,
+};
+use crate::geometry::Location;
+use crate::physics::PLAYER_EYE_HEIGHT;
+
+/// The number of empty blocks needed above a spawn point for the player to fit
+const SPAWN_HEADROOM: usize = 2;
+
+/// Search outwards from the origin for somewhere safe for the player to spawn
+///
+/// A safe spawn point is the top of a solid block with enough empty space above it for the player
+/// to stand up. The search visits rings of chunks around the origin's chunk in order of distance,
+/// and within the first ring containing any safe spots picks the one closest to the origin.
+/// Returns the location of the player's eyes when standing there, or `None` if nowhere within
+/// `max_search_radius` chunks is suitable.
+pub fn find_spawn_point(source: &mut dyn ChunkSource, max_search_radius: u32) -> Option<Location> {
+    let origin_chunk = ChunkCoordinate::default();
+
+    for d in 0..=max_search_radius {
+        let mut best: Option<(i64, Location)> = None;
+
+        for coordinate in square_ring(origin_chunk, d) {
+            let chunk = source.get_chunk_at(coordinate);
+            let heightmap = chunk.heightmap();
+
+            for (x, column) in heightmap.iter().enumerate() {
+                for (z, height) in column.iter().enumerate() {
+                    let y = match height {
+                        Some(y) if is_safe_spawn_column(&chunk, x, *y, z) => *y,
+                        _ => continue,
+                    };
+
+                    let global_x = coordinate.i as i64 * CHUNK_WIDTH as i64 + x as i64;
+                    let global_z = coordinate.j as i64 * CHUNK_DEPTH as i64 + z as i64;
+                    let distance_squared = global_x * global_x + global_z * global_z;
+                    if best.is_none_or(|(best_distance, _)| distance_squared < best_distance) {
+                        let location = Location::new(
+                            global_x as f32 + 0.5,
+                            (y + 1) as f32 + PLAYER_EYE_HEIGHT,
+                            global_z as f32 + 0.5,
+                        );
+                        best = Some((distance_squared, location));
+                    }
+                }
+            }
+        }
+
+        if let Some((_, location)) = best {
+            return Some(location);
+        }
+    }
+
+    None
+}
+
+/// Determine whether the player could safely stand on the block at (x, y, z)
+///
+/// `y` must be the highest solid block in its column. The space above it must be genuinely empty,
+/// rather than just non-solid, so that the player doesn't spawn underwater.
+fn is_safe_spawn_column(chunk: &Chunk, x: usize, y: usize, z: usize) -> bool {
+    if y + SPAWN_HEADROOM >= CHUNK_HEIGHT {
+        return false;
+    }
+    (1..=SPAWN_HEADROOM).all(|dy| chunk.get_block_at(x, y + dy, z) == Block::Empty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generators::FlatTerrainGenerator;
+    use rstest::*;
+
+    /// A chunk source whose chunks are either completely solid or contain a floor at `y = 10`
+    struct PatchySource {
+        floored_chunks: Vec<ChunkCoordinate>,
+    }
+
+    impl ChunkSource for PatchySource {
+        fn get_chunk_at(&mut self, coordinate: ChunkCoordinate) -> Box<Chunk> {
+            let mut chunk: Box<Chunk> = Box::default();
+            let height = if self.floored_chunks.contains(&coordinate) {
+                11
+            } else {
+                CHUNK_HEIGHT
+            };
+            for x in 0..CHUNK_WIDTH {
+                for y in 0..height {
+                    for z in 0..CHUNK_DEPTH {
+                        chunk.set_block_at(x, y, z, Block::Stone);
+                    }
+                }
+            }
+            chunk
+        }
+    }
+
+    fn assert_location_eq(expected: Location, actual: Location) {
+        assert!(
+            (expected - actual).norm() < 1e-5,
+            "Expected {} but got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[rstest]
+    fn flat_terrain_spawns_at_origin() {
+        let spawn = find_spawn_point(&mut FlatTerrainGenerator, 0).unwrap();
+        assert_location_eq(Location::new(0.5, 65.0 + PLAYER_EYE_HEIGHT, 0.5), spawn);
+    }
+
+    #[rstest]
+    #[case(ChunkCoordinate{i: 1, j: 0}, Location::new(16.5, 11.0 + PLAYER_EYE_HEIGHT, 0.5))]
+    #[case(ChunkCoordinate{i: -1, j: -1}, Location::new(-0.5, 11.0 + PLAYER_EYE_HEIGHT, -0.5))]
+    #[case(ChunkCoordinate{i: 0, j: -3}, Location::new(0.5, 11.0 + PLAYER_EYE_HEIGHT, -32.5))]
+    fn search_moves_outwards_past_unsafe_chunks(
+        #[case] floored_chunk: ChunkCoordinate,
+        #[case] expected: Location,
+    ) {
+        let mut source = PatchySource {
+            floored_chunks: vec![floored_chunk],
+        };
+        let spawn = find_spawn_point(&mut source, 5).unwrap();
+        assert_location_eq(expected, spawn);
+    }
+
+    #[rstest]
+    fn nearest_ring_wins_over_closer_columns_further_out() {
+        let mut source = PatchySource {
+            floored_chunks: vec![
+                ChunkCoordinate { i: 2, j: 0 },
+                ChunkCoordinate { i: 1, j: 1 },
+            ],
+        };
+        let spawn = find_spawn_point(&mut source, 5).unwrap();
+        assert_location_eq(Location::new(16.5, 11.0 + PLAYER_EYE_HEIGHT, 16.5), spawn);
+    }
+
+    #[rstest]
+    fn search_gives_up_beyond_max_radius() {
+        let mut source = PatchySource {
+            floored_chunks: vec![ChunkCoordinate { i: 4, j: 0 }],
+        };
+        assert_eq!(None, find_spawn_point(&mut source, 3));
+    }
+
+    #[rstest]
+    #[case(10, true)]
+    #[case(CHUNK_HEIGHT - 3, true)]
+    #[case(CHUNK_HEIGHT - 2, false)]
+    #[case(CHUNK_HEIGHT - 1, false)]
+    fn columns_need_headroom(#[case] y: usize, #[case] expected: bool) {
+        let mut chunk = Chunk::default();
+        chunk.set_block_at(0, y, 0, Block::Stone);
+        assert_eq!(expected, is_safe_spawn_column(&chunk, 0, y, 0));
+    }
+
+    #[rstest]
+    fn columns_with_a_low_ceiling_are_unsafe() {
+        let mut chunk = Chunk::default();
+        chunk.set_block_at(0, 10, 0, Block::Stone);
+        chunk.set_block_at(0, 12, 0, Block::Stone);
+        assert!(!is_safe_spawn_column(&chunk, 0, 10, 0));
+    }
+}
@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+use crate::block::Block;
+use crate::geometry::{BlockPosition, Location};
+
+/// Information about a world that must be kept for as long as the world exists
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct WorldMetadata {
+    /// The seed used to generate the world's terrain
+    pub seed: u64,
+
+    /// Where the player's eyes are placed when they first enter the world
+    pub spawn_point: Location,
+
+    /// The height of the sea's surface, below which low-lying terrain is flooded
+    ///
+    /// Worlds created before there was any water default to zero, leaving them dry.
+    #[serde(default)]
+    pub sea_level: i32,
+}
+
+/// A view of the blocks making up the world, addressed by global block coordinates
+///
+/// Implementations are free to span as many chunks as they like, so consumers can walk across
+/// chunk boundaries without needing to know how the world is divided up.
+pub trait BlockLookup {
+    /// Get the block at `position`, or `None` if that part of the world isn't currently available
+    fn get_block_at(&self, position: BlockPosition) -> Option<Block>;
+}
+
+/// A view of the blocks making up the world that allows them to be changed
+pub trait BlockStorage: BlockLookup {
+    /// Replace the block at `position`
+    ///
+    /// Returns `false` if the block couldn't be changed because that part of the world isn't
+    /// currently available.
+    fn set_block_at(&mut self, position: BlockPosition, block: Block) -> bool;
+}
@@ -0,0 +1,514 @@
+//! Measures how much CPU time the renderer spends on each draw call, by drawing a grid of small
+//! objects into a window
+//!
+//! Only the submission of the draw calls is timed. The shadow pass, clearing the screen and
+//! swapping the buffers all happen between the timed draws, with vsync turned off so that the
+//! swap never waits for the display.
+//!
+//! Alongside the renderer itself, two cases draw the same objects with a stand-in for the scene
+//! objects shader, to show what caching uniform locations and sharing per-frame data through a
+//! uniform buffer saves:
+//!
+//! - `uncached_uniforms` looks up every uniform's location by name each time it's written, and
+//!   writes the per-frame values one uniform at a time, as the renderer used to
+//! - `cached_uniforms` looks the locations up once and writes the per-frame values to a uniform
+//!   buffer, as the renderer does now
+//!
+//! Both of these time the per-frame uniforms as well as the draws.
+//!
+//! This needs a display to open the window on. To compare two versions of the renderer, run
+//! `cargo bench -p sbs5k_engine -- --save-baseline before` on the first and
+//! `cargo bench -p sbs5k_engine -- --baseline before` on the second.
+
+use std::ffi::CString;
+use std::io::Cursor;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use std::{mem, ptr};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use gl::types::*;
+use image::{ImageOutputFormat, RgbaImage};
+use nalgebra::{Matrix4, Point3, Vector2, Vector3};
+
+use sbs5k_engine::lighting::{GlobalLight, SceneLighting};
+use sbs5k_engine::model::{Material, Model, VertexData, VertexDataLayoutInfo};
+use sbs5k_engine::texture::{ImageFileFormat, TextureArray};
+use sbs5k_engine::{
+    CameraPosition, FogParameters, RenderTarget, Renderer, Scene, SceneObject, SkyParameters,
+    Window,
+};
+
+/// The numbers of objects to draw in each frame
+const OBJECT_COUNTS: [usize; 3] = [16, 256, 1024];
+
+/// The uniform buffer binding point for the stand-in shader's per-frame block, clear of the ones
+/// that the renderer uses
+const FRAME_BLOCK_BINDING: GLuint = 8;
+
+/// The number of floats in the stand-in shader's `Frame` block: two matrices, followed by five
+/// `vec4`s' worth of vectors and the floats packed in after them
+const FRAME_BLOCK_FLOATS: usize = 2 * 16 + 5 * 4;
+
+/// The per-frame uniforms of the stand-in shader, declared one at a time
+const INDIVIDUAL_UNIFORMS: &str = "
+uniform mat4 View;
+uniform mat4 Projection;
+uniform vec3 cameraPos;
+uniform float globalIlluminantIntensity;
+uniform vec3 globalIlluminantDirection;
+uniform float globalIlluminantAmbientIntensity;
+uniform vec3 globalIlluminantColour;
+uniform float fogNearDistance;
+uniform vec3 skyZenithColour;
+uniform float fogFarDistance;
+uniform vec3 skyHorizonColour;
+";
+
+/// The same per-frame uniforms, gathered into a uniform block
+const UNIFORM_BLOCK: &str = "
+layout (std140) uniform Frame {
+    mat4 View;
+    mat4 Projection;
+    vec3 cameraPos;
+    float globalIlluminantIntensity;
+    vec3 globalIlluminantDirection;
+    float globalIlluminantAmbientIntensity;
+    vec3 globalIlluminantColour;
+    float fogNearDistance;
+    vec3 skyZenithColour;
+    float fogFarDistance;
+    vec3 skyHorizonColour;
+};
+";
+
+/// The stand-in vertex shader, which follows its per-frame uniforms. Every uniform is used, so that
+/// none of them are optimised out.
+const STAND_IN_VERT: &str = "
+layout (location = 0) in vec3 aPos;
+
+uniform mat4 Model;
+
+out vec3 Colour;
+
+void main()
+{
+    vec4 world = Model * vec4(aPos, 1.0);
+    gl_Position = Projection * View * world;
+
+    float lit = max(globalIlluminantDirection.y, 0.0) * globalIlluminantIntensity
+              + globalIlluminantAmbientIntensity;
+    float fog = clamp((distance(world.xyz, cameraPos) - fogNearDistance)
+                      / (fogFarDistance - fogNearDistance), 0.0, 1.0);
+    Colour = mix(globalIlluminantColour * lit, mix(skyHorizonColour, skyZenithColour, 0.5), fog);
+}
+";
+
+const STAND_IN_FRAG: &str = "#version 410 core
+
+in vec3 Colour;
+
+uniform sampler2D albedoTexture;
+
+out vec4 FragColor;
+
+void main()
+{
+    FragColor = vec4(Colour, 1.0) * texture(albedoTexture, vec2(0.5));
+}
+";
+
+/// How the stand-in shader is given its uniforms
+#[derive(Clone, Copy)]
+enum UniformPath {
+    Uncached,
+    Cached,
+}
+
+/// A stand-in for the scene objects shader, along with a square to draw with it
+struct StandIn {
+    path: UniformPath,
+    program: GLuint,
+    vertex_array: GLuint,
+    buffers: [GLuint; 3],
+
+    /// The locations of `Model` and `albedoTexture`, looked up in advance
+    model_location: GLint,
+    albedo_location: GLint,
+}
+
+/// A single upward-facing square, laid out like a face of a chunk mesh
+fn square(texture: &Rc<TextureArray>) -> Model {
+    #[rustfmt::skip]
+    let vertices = [
+        0.0, 0.0, 0.0,  0.0, 1.0, 0.0,  0.0, 0.0, 0.0, 1.0,  1.0, 1.0,
+        0.0, 0.0, 1.0,  0.0, 1.0, 0.0,  0.0, 1.0, 0.0, 1.0,  1.0, 1.0,
+        1.0, 0.0, 1.0,  0.0, 1.0, 0.0,  1.0, 1.0, 0.0, 1.0,  1.0, 1.0,
+        1.0, 0.0, 0.0,  0.0, 1.0, 0.0,  1.0, 0.0, 0.0, 1.0,  1.0, 1.0,
+    ];
+    let layout = VertexDataLayoutInfo {
+        position_offset: 0,
+        normal_offset: Some(3),
+        texture_offset: Some(6),
+        light_offset: Some(10),
+    };
+    Model {
+        vertices: VertexData::new(&vertices, &[0, 1, 2, 2, 3, 0], layout),
+        material: Material::new(texture.clone()),
+    }
+}
+
+/// A texture array with a single plain white layer
+fn plain_texture() -> Rc<TextureArray> {
+    let mut png = Cursor::new(vec![]);
+    RgbaImage::from_pixel(16, 16, image::Rgba([255, 255, 255, 255]))
+        .write_to(&mut png, ImageOutputFormat::Png)
+        .unwrap();
+    Rc::new(TextureArray::from_grid(
+        &png.into_inner(),
+        ImageFileFormat::Png,
+        16,
+        None,
+    ))
+}
+
+fn sky() -> SkyParameters {
+    SkyParameters {
+        zenith_colour: Vector3::new(0.3, 0.5, 0.9),
+        horizon_colour: Vector3::new(0.7, 0.8, 0.95),
+        sun_direction: Vector3::new(0.0, 1.0, 0.0),
+        sun_colour: Vector3::new(1.0, 0.95, 0.8),
+        star_visibility: 0.0,
+        cloud_colour: Vector3::new(1.0, 1.0, 1.0),
+        cloud_offset: Vector2::zeros(),
+    }
+}
+
+impl StandIn {
+    fn new(path: UniformPath) -> Self {
+        let declarations = match path {
+            UniformPath::Uncached => INDIVIDUAL_UNIFORMS,
+            UniformPath::Cached => UNIFORM_BLOCK,
+        };
+        let vert = format!("#version 410 core\n{}{}", declarations, STAND_IN_VERT);
+        let program = link_program(&vert, STAND_IN_FRAG);
+
+        #[rustfmt::skip]
+        let vertices: [f32; 12] = [
+            0.0, 0.0, 0.0,
+            0.0, 0.0, 1.0,
+            1.0, 0.0, 1.0,
+            1.0, 0.0, 0.0,
+        ];
+        let indices: [u32; 6] = [0, 1, 2, 2, 3, 0];
+
+        let mut vertex_array = 0;
+        let mut buffers = [0; 3];
+        unsafe {
+            gl::GenVertexArrays(1, &mut vertex_array);
+            gl::GenBuffers(3, buffers.as_mut_ptr());
+            gl::BindVertexArray(vertex_array);
+
+            gl::BindBuffer(gl::ARRAY_BUFFER, buffers[0]);
+            gl::BufferData(
+                gl::ARRAY_BUFFER,
+                mem::size_of_val(&vertices) as GLsizeiptr,
+                vertices.as_ptr().cast(),
+                gl::STATIC_DRAW,
+            );
+            gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, 0, ptr::null());
+            gl::EnableVertexAttribArray(0);
+
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, buffers[1]);
+            gl::BufferData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                mem::size_of_val(&indices) as GLsizeiptr,
+                indices.as_ptr().cast(),
+                gl::STATIC_DRAW,
+            );
+            gl::BindVertexArray(0);
+
+            if let UniformPath::Cached = path {
+                let name = CString::new("Frame").unwrap();
+                let index = gl::GetUniformBlockIndex(program, name.as_ptr());
+                gl::UniformBlockBinding(program, index, FRAME_BLOCK_BINDING);
+
+                gl::BindBuffer(gl::UNIFORM_BUFFER, buffers[2]);
+                gl::BufferData(
+                    gl::UNIFORM_BUFFER,
+                    (FRAME_BLOCK_FLOATS * mem::size_of::<f32>()) as GLsizeiptr,
+                    ptr::null(),
+                    gl::DYNAMIC_DRAW,
+                );
+                gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+            }
+        }
+
+        StandIn {
+            path,
+            program,
+            vertex_array,
+            buffers,
+            model_location: uniform_location(program, "Model"),
+            albedo_location: uniform_location(program, "albedoTexture"),
+        }
+    }
+
+    /// Write the per-frame uniforms, then draw the square once for each model matrix
+    fn draw(&self, models: &[Matrix4<f32>], scene: &Scene) {
+        unsafe {
+            gl::UseProgram(self.program);
+            gl::BindVertexArray(self.vertex_array);
+        }
+
+        match self.path {
+            UniformPath::Uncached => self.write_individual_uniforms(scene),
+            UniformPath::Cached => self.write_uniform_block(scene),
+        }
+
+        for model in models {
+            unsafe {
+                match self.path {
+                    UniformPath::Uncached => {
+                        gl::Uniform1i(uniform_location(self.program, "albedoTexture"), 0);
+                        gl::UniformMatrix4fv(
+                            uniform_location(self.program, "Model"),
+                            1,
+                            gl::FALSE,
+                            model.as_ptr(),
+                        );
+                    }
+                    UniformPath::Cached => {
+                        gl::Uniform1i(self.albedo_location, 0);
+                        gl::UniformMatrix4fv(self.model_location, 1, gl::FALSE, model.as_ptr());
+                    }
+                }
+                gl::DrawElements(gl::TRIANGLES, 6, gl::UNSIGNED_INT, ptr::null());
+            }
+        }
+
+        // Leave things as the renderer expects to find them
+        unsafe {
+            gl::BindVertexArray(0);
+            gl::UseProgram(0);
+        }
+    }
+
+    fn write_individual_uniforms(&self, scene: &Scene) {
+        let light = &scene.lighting.global_light;
+        let location = |name| uniform_location(self.program, name);
+        unsafe {
+            gl::UniformMatrix4fv(
+                location("View"),
+                1,
+                gl::FALSE,
+                scene.camera.view_matrix().as_ptr(),
+            );
+            gl::UniformMatrix4fv(
+                location("Projection"),
+                1,
+                gl::FALSE,
+                scene.camera.projection_matrix().as_ptr(),
+            );
+            gl::Uniform3fv(
+                location("cameraPos"),
+                1,
+                scene.camera.position.coords.as_ptr(),
+            );
+            gl::Uniform1f(location("globalIlluminantIntensity"), light.intensity);
+            gl::Uniform3fv(
+                location("globalIlluminantDirection"),
+                1,
+                light.direction.as_ptr(),
+            );
+            gl::Uniform1f(
+                location("globalIlluminantAmbientIntensity"),
+                light.ambient_intensity,
+            );
+            gl::Uniform3fv(location("globalIlluminantColour"), 1, light.colour.as_ptr());
+            gl::Uniform1f(location("fogNearDistance"), scene.fog.start_threshold);
+            gl::Uniform3fv(
+                location("skyZenithColour"),
+                1,
+                scene.sky.zenith_colour.as_ptr(),
+            );
+            gl::Uniform1f(location("fogFarDistance"), scene.fog.end_threshold);
+            gl::Uniform3fv(
+                location("skyHorizonColour"),
+                1,
+                scene.sky.horizon_colour.as_ptr(),
+            );
+        }
+    }
+
+    fn write_uniform_block(&self, scene: &Scene) {
+        let light = &scene.lighting.global_light;
+        let mut data = Vec::with_capacity(FRAME_BLOCK_FLOATS);
+        data.extend_from_slice(scene.camera.view_matrix().as_slice());
+        data.extend_from_slice(scene.camera.projection_matrix().as_slice());
+        data.extend_from_slice(scene.camera.position.coords.as_slice());
+        data.push(light.intensity);
+        data.extend_from_slice(light.direction.as_slice());
+        data.push(light.ambient_intensity);
+        data.extend_from_slice(light.colour.as_slice());
+        data.push(scene.fog.start_threshold);
+        data.extend_from_slice(scene.sky.zenith_colour.as_slice());
+        data.push(scene.fog.end_threshold);
+        data.extend_from_slice(scene.sky.horizon_colour.as_slice());
+        data.push(0.0);
+
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.buffers[2]);
+            gl::BufferSubData(
+                gl::UNIFORM_BUFFER,
+                0,
+                (data.len() * mem::size_of::<f32>()) as GLsizeiptr,
+                data.as_ptr().cast(),
+            );
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, FRAME_BLOCK_BINDING, self.buffers[2]);
+        }
+    }
+}
+
+impl Drop for StandIn {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(3, self.buffers.as_ptr());
+            gl::DeleteVertexArrays(1, &self.vertex_array);
+            gl::DeleteProgram(self.program);
+        }
+    }
+}
+
+/// Look up a uniform's location by name, as the renderer used to for every uniform it wrote
+fn uniform_location(program: GLuint, name: &str) -> GLint {
+    let name = CString::new(name).unwrap();
+    unsafe { gl::GetUniformLocation(program, name.as_ptr()) }
+}
+
+fn compile_shader(src: &str, shader_type: GLenum) -> GLuint {
+    let src = CString::new(src).unwrap();
+    unsafe {
+        let shader = gl::CreateShader(shader_type);
+        gl::ShaderSource(shader, 1, &src.as_ptr(), ptr::null());
+        gl::CompileShader(shader);
+
+        let mut success = 0;
+        gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut success);
+        assert!(success != 0, "Failed to compile the stand-in shader");
+        shader
+    }
+}
+
+fn link_program(vert: &str, frag: &str) -> GLuint {
+    let shaders = [
+        compile_shader(vert, gl::VERTEX_SHADER),
+        compile_shader(frag, gl::FRAGMENT_SHADER),
+    ];
+    unsafe {
+        let program = gl::CreateProgram();
+        for shader in shaders {
+            gl::AttachShader(program, shader);
+        }
+        gl::LinkProgram(program);
+        for shader in shaders {
+            gl::DeleteShader(shader);
+        }
+
+        let mut success = 0;
+        gl::GetProgramiv(program, gl::LINK_STATUS, &mut success);
+        assert!(success != 0, "Failed to link the stand-in shader");
+        program
+    }
+}
+
+fn draw_calls(c: &mut Criterion) {
+    let mut window = Window::new(1280, 720, "Draw call benchmark");
+    window.set_vsync(false);
+    let mut renderer = Renderer::new();
+
+    let texture = plain_texture();
+    let camera = CameraPosition::default();
+    let lighting = SceneLighting {
+        point_lights: vec![],
+        global_light: GlobalLight {
+            direction: Vector3::new(0.0, 1.0, 0.0),
+            colour: Vector3::new(1.0, 1.0, 1.0),
+            intensity: 1.0,
+            ambient_intensity: 0.2,
+        },
+    };
+    let fog = FogParameters::linear(100.0, 150.0);
+    let sky = sky();
+    let stand_ins = [
+        ("uncached_uniforms", StandIn::new(UniformPath::Uncached)),
+        ("cached_uniforms", StandIn::new(UniformPath::Cached)),
+    ];
+    let scene = Scene {
+        lighting: &lighting,
+        camera: &camera,
+        fog: &fog,
+        sky: &sky,
+    };
+
+    let mut group = c.benchmark_group("draw_calls");
+    for count in OBJECT_COUNTS {
+        let objects: Vec<SceneObject> = (0..count)
+            .map(|i| SceneObject {
+                position: Point3::new((i % 32) as f32 * 2.0 - 32.0, 60.0, -((i / 32) as f32) * 2.0),
+                orientation: Vector3::zeros(),
+                scale: 1.0,
+                model: square(&texture),
+            })
+            .collect();
+        let object_refs: Vec<&SceneObject> = objects.iter().collect();
+        let models: Vec<Matrix4<f32>> = objects.iter().map(SceneObject::model_matrix).collect();
+
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(
+            BenchmarkId::new("renderer", count),
+            &object_refs,
+            |b, objects| {
+                b.iter_custom(|iterations| {
+                    let mut elapsed = Duration::ZERO;
+                    for _ in 0..iterations {
+                        // Finish a frame outside of the timing, which uploads the scene's
+                        // uniforms and lights, and lets the GPU keep up so that the draws never
+                        // queue up behind one another
+                        renderer.do_render_pass(&mut window, &scene, &|target| {
+                            target.render_skybox();
+                        });
+
+                        let start = Instant::now();
+                        renderer.render_objects(objects);
+                        elapsed += start.elapsed();
+                    }
+                    elapsed
+                });
+            },
+        );
+
+        for (name, stand_in) in &stand_ins {
+            group.bench_with_input(BenchmarkId::new(*name, count), &models, |b, models| {
+                b.iter_custom(|iterations| {
+                    let mut elapsed = Duration::ZERO;
+                    for _ in 0..iterations {
+                        renderer.do_render_pass(&mut window, &scene, &|target| {
+                            target.render_skybox();
+                        });
+
+                        let start = Instant::now();
+                        stand_in.draw(models, &scene);
+                        elapsed += start.elapsed();
+                    }
+                    elapsed
+                });
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, draw_calls);
+criterion_main!(benches);
@@ -2,6 +2,18 @@ use std::f32::consts::PI;
 
 use nalgebra::{Matrix4, Point3, Rotation3, Translation, Vector3};
 
+/// The distance from the camera to the near clipping plane
+pub(crate) const Z_NEAR: f32 = 0.1;
+
+/// The distance from the camera to the far clipping plane
+pub(crate) const Z_FAR: f32 = 1000.0;
+
+/// The camera's vertical field of view in radians
+pub(crate) const FOV_Y: f32 = PI * 0.5;
+
+/// The ratio of the width of the camera's view to its height
+pub(crate) const ASPECT_RATIO: f32 = 16.0 / 9.0;
+
 /// Encodes the position of the camera in the game world
 pub struct CameraPosition {
     /// Camera's world coordinates in (x, y, z) form
@@ -25,11 +37,7 @@ impl CameraPosition {
 
     /// Get the projection matrix of the camera, in homogeneous coordinates
     pub fn projection_matrix(&self) -> Matrix4<f32> {
-        let aspect = 16.0 / 9.0;
-        let fovy = PI * 0.5;
-        let znear = 0.1;
-        let zfar = 1000.0;
-        Matrix4::new_perspective(aspect, fovy, znear, zfar)
+        Matrix4::new_perspective(ASPECT_RATIO, FOV_Y, Z_NEAR, Z_FAR)
     }
 }
 
@@ -0,0 +1,264 @@
+use nalgebra::{Matrix4, Point3, Vector3};
+
+use crate::camera::{Z_FAR, Z_NEAR};
+use crate::lighting::{PointLight, MAX_POINT_LIGHTS};
+
+/// The number of columns of clusters across the screen
+///
+/// This is defined as `CLUSTER_GRID_X` in the shaders.
+pub(crate) const CLUSTER_GRID_X: usize = 16;
+
+/// The number of rows of clusters up the screen
+///
+/// This is defined as `CLUSTER_GRID_Y` in the shaders.
+pub(crate) const CLUSTER_GRID_Y: usize = 9;
+
+/// The number of slices of clusters between the near and far clipping planes
+///
+/// This is defined as `CLUSTER_GRID_Z` in the shaders.
+pub(crate) const CLUSTER_GRID_Z: usize = 24;
+
+const NUM_CLUSTERS: usize = CLUSTER_GRID_X * CLUSTER_GRID_Y * CLUSTER_GRID_Z;
+
+/// The point lights affecting each cluster of the view frustum
+///
+/// The frustum is divided into a grid of tiles on the screen, each of which is sliced up
+/// exponentially by depth. Each fragment then only needs to consider the lights whose range
+/// reaches into its own cluster, rather than every light in the scene.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct LightClusters {
+    /// For each cluster, the offset into `indices` of its first light followed by its number of
+    /// lights
+    pub(crate) ranges: Vec<[u32; 2]>,
+
+    /// The indices of the lights in each cluster, one cluster after another
+    pub(crate) indices: Vec<u32>,
+}
+
+/// The clusters spanned by a single light, as inclusive ranges on each axis
+struct ClusterBounds {
+    min: [usize; 3],
+    max: [usize; 3],
+}
+
+impl LightClusters {
+    /// Work out which lights affect which clusters when viewed through `view` and `projection`
+    pub(crate) fn assign(
+        lights: &[PointLight],
+        view: &Matrix4<f32>,
+        projection: &Matrix4<f32>,
+    ) -> Self {
+        let bounds: Vec<(u32, ClusterBounds)> = lights
+            .iter()
+            .take(MAX_POINT_LIGHTS)
+            .enumerate()
+            .filter_map(|(i, light)| Some((i as u32, cluster_bounds(light, view, projection)?)))
+            .collect();
+
+        // Count the lights in each cluster first so that they can all be packed into one list
+        let mut counts = vec![0u32; NUM_CLUSTERS];
+        for (_, light_bounds) in bounds.iter() {
+            for_each_cluster(light_bounds, |cluster| counts[cluster] += 1);
+        }
+
+        let mut ranges = Vec::with_capacity(NUM_CLUSTERS);
+        let mut offset = 0;
+        for count in counts {
+            ranges.push([offset, 0]);
+            offset += count;
+        }
+
+        let mut indices = vec![0; offset as usize];
+        for (light_index, light_bounds) in bounds.iter() {
+            for_each_cluster(light_bounds, |cluster| {
+                let [start, count] = &mut ranges[cluster];
+                indices[(*start + *count) as usize] = *light_index;
+                *count += 1;
+            });
+        }
+
+        LightClusters { ranges, indices }
+    }
+
+    /// Get the index of the cluster at the given tile and depth slice
+    #[inline]
+    pub(crate) fn cluster_index(x: usize, y: usize, z: usize) -> usize {
+        (z * CLUSTER_GRID_Y + y) * CLUSTER_GRID_X + x
+    }
+
+    /// Get the indices of the lights affecting a cluster
+    #[cfg(test)]
+    fn lights_in_cluster(&self, x: usize, y: usize, z: usize) -> &[u32] {
+        let [start, count] = self.ranges[Self::cluster_index(x, y, z)];
+        &self.indices[start as usize..(start + count) as usize]
+    }
+}
+
+/// Work out which depth slice a point at distance `depth` in front of the camera falls into
+#[inline]
+pub(crate) fn depth_slice(depth: f32) -> usize {
+    if depth <= Z_NEAR {
+        return 0;
+    }
+    let proportion = (depth / Z_NEAR).ln() / (Z_FAR / Z_NEAR).ln();
+    ((proportion * CLUSTER_GRID_Z as f32) as usize).min(CLUSTER_GRID_Z - 1)
+}
+
+/// Find the range of clusters touched by a light, or `None` if it can't be seen at all
+fn cluster_bounds(
+    light: &PointLight,
+    view: &Matrix4<f32>,
+    projection: &Matrix4<f32>,
+) -> Option<ClusterBounds> {
+    let centre = view.transform_point(&light.position);
+    let radius = light.radius();
+
+    // The camera looks down the negative z axis in view space
+    let depth = -centre.z;
+    if depth + radius < Z_NEAR || depth - radius > Z_FAR {
+        return None;
+    }
+    let min_z = depth_slice(depth - radius);
+    let max_z = depth_slice(depth + radius);
+
+    // Project the corners of the light's bounding box onto the screen. If it reaches behind the
+    // camera, the projection becomes meaningless, so assume it could cover the whole screen.
+    let (min_x, min_y, max_x, max_y) = if depth - radius <= Z_NEAR {
+        (0, 0, CLUSTER_GRID_X - 1, CLUSTER_GRID_Y - 1)
+    } else {
+        let mut ndc_min = [f32::INFINITY; 2];
+        let mut ndc_max = [f32::NEG_INFINITY; 2];
+        for i in 0..8 {
+            let sign = |bit: usize| if i & bit == 0 { -1.0 } else { 1.0 };
+            let corner = centre + Vector3::new(sign(1), sign(2), sign(4)) * radius;
+            let ndc = projection.transform_point(&Point3::from(corner.coords));
+            for axis in 0..2 {
+                ndc_min[axis] = ndc_min[axis].min(ndc[axis]);
+                ndc_max[axis] = ndc_max[axis].max(ndc[axis]);
+            }
+        }
+
+        if ndc_max[0] < -1.0 || ndc_min[0] > 1.0 || ndc_max[1] < -1.0 || ndc_min[1] > 1.0 {
+            return None;
+        }
+
+        (
+            ndc_to_tile(ndc_min[0], CLUSTER_GRID_X),
+            ndc_to_tile(ndc_min[1], CLUSTER_GRID_Y),
+            ndc_to_tile(ndc_max[0], CLUSTER_GRID_X),
+            ndc_to_tile(ndc_max[1], CLUSTER_GRID_Y),
+        )
+    };
+
+    Some(ClusterBounds {
+        min: [min_x, min_y, min_z],
+        max: [max_x, max_y, max_z],
+    })
+}
+
+/// Convert a normalised device coordinate into the index of the tile containing it
+#[inline]
+fn ndc_to_tile(ndc: f32, tiles: usize) -> usize {
+    let proportion = (ndc.clamp(-1.0, 1.0) + 1.0) / 2.0;
+    ((proportion * tiles as f32) as usize).min(tiles - 1)
+}
+
+#[inline]
+fn for_each_cluster(bounds: &ClusterBounds, mut f: impl FnMut(usize)) {
+    for z in bounds.min[2]..=bounds.max[2] {
+        for y in bounds.min[1]..=bounds.max[1] {
+            for x in bounds.min[0]..=bounds.max[0] {
+                f(LightClusters::cluster_index(x, y, z));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::CameraPosition;
+    use rstest::*;
+
+    fn light_at(x: f32, y: f32, z: f32, intensity: f32) -> PointLight {
+        PointLight {
+            position: Point3::new(x, y, z),
+            colour: Vector3::new(1.0, 1.0, 1.0),
+            intensity,
+        }
+    }
+
+    /// Assign lights as seen by a camera at the origin looking down the negative z axis
+    fn assign(lights: &[PointLight]) -> LightClusters {
+        let projection = CameraPosition::default().projection_matrix();
+        LightClusters::assign(lights, &Matrix4::identity(), &projection)
+    }
+
+    #[rstest]
+    #[case(Z_NEAR / 2.0, 0)]
+    #[case(Z_NEAR, 0)]
+    #[case(Z_FAR * 0.999, CLUSTER_GRID_Z - 1)]
+    #[case(Z_FAR * 2.0, CLUSTER_GRID_Z - 1)]
+    fn depth_slices_cover_the_view_range(#[case] depth: f32, #[case] expected: usize) {
+        assert_eq!(expected, depth_slice(depth));
+    }
+
+    #[rstest]
+    fn depth_slices_increase_with_depth() {
+        let slices: Vec<usize> = (1..2000).map(|d| depth_slice(d as f32 * 0.5)).collect();
+        assert!(slices.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[rstest]
+    fn every_cluster_has_a_range() {
+        let clusters = assign(&[]);
+        assert_eq!(NUM_CLUSTERS, clusters.ranges.len());
+        assert!(clusters.indices.is_empty());
+    }
+
+    #[rstest]
+    fn lights_straight_ahead_land_in_the_middle_of_the_screen() {
+        let clusters = assign(&[light_at(0.0, 0.0, -50.0, 0.01)]);
+        let z = depth_slice(50.0);
+
+        assert_eq!(&[0], clusters.lights_in_cluster(7, 4, z));
+        assert_eq!(&[0], clusters.lights_in_cluster(8, 4, z));
+        assert!(clusters.lights_in_cluster(0, 0, z).is_empty());
+        assert!(clusters.lights_in_cluster(8, 4, 0).is_empty());
+        assert!(clusters
+            .lights_in_cluster(8, 4, CLUSTER_GRID_Z - 1)
+            .is_empty());
+    }
+
+    #[rstest]
+    #[case(light_at(0.0, 0.0, 50.0, 1.0))]
+    #[case(light_at(0.0, 0.0, -5000.0, 1.0))]
+    #[case(light_at(500.0, 0.0, -50.0, 1.0))]
+    fn lights_out_of_view_are_left_out(#[case] light: PointLight) {
+        let clusters = assign(&[light]);
+        assert!(clusters.indices.is_empty());
+    }
+
+    #[rstest]
+    fn lights_around_the_camera_cover_the_whole_screen() {
+        let clusters = assign(&[light_at(0.0, 0.0, 1.0, 1.0)]);
+        for x in 0..CLUSTER_GRID_X {
+            for y in 0..CLUSTER_GRID_Y {
+                assert_eq!(&[0], clusters.lights_in_cluster(x, y, 0));
+            }
+        }
+    }
+
+    #[rstest]
+    fn overlapping_lights_share_clusters() {
+        let clusters = assign(&[
+            light_at(-1.0, 0.0, -50.0, 0.01),
+            light_at(40.0, 0.0, -50.0, 0.01),
+            light_at(1.0, 0.0, -50.0, 0.01),
+        ]);
+        let z = depth_slice(50.0);
+
+        assert_eq!(&[0, 2], clusters.lights_in_cluster(8, 4, z));
+        assert_eq!(&[1], clusters.lights_in_cluster(11, 4, z));
+    }
+}
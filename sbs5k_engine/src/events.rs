@@ -8,6 +8,12 @@ pub enum WindowEvent {
     /// A key was released
     KeyRelease(inputs::Key),
 
+    /// A mouse button was pressed
+    MouseButtonPress(inputs::MouseButton),
+
+    /// A mouse button was released
+    MouseButtonRelease(inputs::MouseButton),
+
     /// The mouse was moved by (dx, dy) proportion of the screen's dimensions
     MouseMove(f32, f32),
 }
@@ -1,8 +1,207 @@
+use nalgebra::{Point3, Vector3};
+
+/// The fraction of light that must be blocked for fog to be considered total
+const FOG_CUTOFF: f32 = 0.01;
+
+/// How fog thickens with distance from the camera
+///
+/// The discriminants are defined as the `FOG_MODE_*` constants in the shaders.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FogMode {
+    /// Fog fades in linearly between the start and end thresholds
+    Linear = 0,
+
+    /// Fog blocks a constant proportion of the remaining light per block travelled
+    Exponential = 1,
+
+    /// Like `Exponential`, but stays clear for longer before thickening more sharply
+    ExponentialSquared = 2,
+}
+
+/// The colour that objects fade into as they disappear into the fog
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FogColour {
+    /// Fade into whatever colour the sky is behind the object
+    Sky,
+
+    /// Fade into a single fixed colour
+    Fixed(Vector3<f32>),
+}
+
 /// Parameters about fog
+#[derive(Clone, Debug, PartialEq)]
 pub struct FogParameters {
-    /// The distance at which objects start to have fog applied on top
+    /// How the fog thickens with distance
+    pub mode: FogMode,
+
+    /// The distance at which objects start to have fog applied on top, in `Linear` mode
     pub start_threshold: f32,
 
-    /// The distance above which there will be total fog and the object will not be visible
+    /// The distance above which there will be total fog and the object will not be visible, in
+    /// `Linear` mode
     pub end_threshold: f32,
+
+    /// How thick the fog is, in the `Exponential` and `ExponentialSquared` modes
+    pub density: f32,
+
+    /// The extra density of fog at `height_base`, which thins out further up. Zero disables height
+    /// fog
+    pub height_density: f32,
+
+    /// How quickly the height fog thins out per block of altitude
+    pub height_falloff: f32,
+
+    /// The altitude at which the height fog has density `height_density`
+    pub height_base: f32,
+
+    /// The colour of the fog
+    pub colour: FogColour,
+}
+
+impl FogParameters {
+    /// Fog that fades in linearly between `start_threshold` and `end_threshold`
+    pub fn linear(start_threshold: f32, end_threshold: f32) -> Self {
+        FogParameters {
+            mode: FogMode::Linear,
+            start_threshold,
+            end_threshold,
+            density: 0.0,
+            height_density: 0.0,
+            height_falloff: 0.0,
+            height_base: 0.0,
+            colour: FogColour::Sky,
+        }
+    }
+
+    /// Fog that thickens exponentially, becoming total at roughly `end_threshold`
+    pub fn exponential(mode: FogMode, end_threshold: f32) -> Self {
+        let optical_depth = -FOG_CUTOFF.ln();
+        let density = match mode {
+            FogMode::Linear | FogMode::Exponential => optical_depth / end_threshold,
+            FogMode::ExponentialSquared => optical_depth.sqrt() / end_threshold,
+        };
+        FogParameters {
+            mode,
+            density,
+            ..FogParameters::linear(0.0, end_threshold)
+        }
+    }
+
+    /// Thick, murky fog for when the camera is underwater
+    pub fn underwater() -> Self {
+        FogParameters {
+            colour: FogColour::Fixed(Vector3::new(0.05, 0.2, 0.35)),
+            ..FogParameters::exponential(FogMode::Exponential, 24.0)
+        }
+    }
+
+    /// Work out how much of an object at `point` can be seen through the fog from `camera`, from 0
+    /// (hidden) to 1 (clear)
+    ///
+    /// This must match `computeOpacityFromFog` in `scene_objects.frag`.
+    pub fn visibility(&self, camera: &Point3<f32>, point: &Point3<f32>) -> f32 {
+        let distance_xz = (point.xz() - camera.xz()).norm();
+        let distance_visibility = match self.mode {
+            FogMode::Linear => {
+                let w = (distance_xz - self.start_threshold)
+                    / (self.end_threshold - self.start_threshold);
+                1.0 - w
+            }
+            FogMode::Exponential => (-self.density * distance_xz).exp(),
+            FogMode::ExponentialSquared => (-(self.density * distance_xz).powi(2)).exp(),
+        };
+
+        let height_visibility = (-self.height_fog_depth(camera, point)).exp();
+
+        distance_visibility.clamp(0.0, 1.0) * height_visibility
+    }
+
+    /// The total density of height fog along the line from `camera` to `point`
+    fn height_fog_depth(&self, camera: &Point3<f32>, point: &Point3<f32>) -> f32 {
+        if self.height_density <= 0.0 {
+            return 0.0;
+        }
+
+        let distance = (point - camera).norm();
+        let rise = point.y - camera.y;
+        let density_at_camera =
+            self.height_density * (-self.height_falloff * (camera.y - self.height_base)).exp();
+
+        // Integrate the density along the line, which is constant if it's level
+        let falloff_along_line = self.height_falloff * rise;
+        if falloff_along_line.abs() < 1e-4 {
+            density_at_camera * distance
+        } else {
+            density_at_camera * distance * (1.0 - (-falloff_along_line).exp()) / falloff_along_line
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    const CAMERA: Point3<f32> = Point3::new(0.0, 64.0, 0.0);
+
+    #[rstest]
+    #[case(10.0, 1.0)]
+    #[case(60.0, 0.5)]
+    #[case(100.0, 0.0)]
+    #[case(200.0, 0.0)]
+    fn linear_fog_fades_between_thresholds(#[case] distance: f32, #[case] expected: f32) {
+        let fog = FogParameters::linear(20.0, 100.0);
+        let visibility = fog.visibility(&CAMERA, &(CAMERA + Vector3::new(distance, 0.0, 0.0)));
+        assert!((expected - visibility).abs() < 1e-5);
+    }
+
+    #[rstest]
+    #[case(FogMode::Exponential)]
+    #[case(FogMode::ExponentialSquared)]
+    fn exponential_fog_is_nearly_total_at_the_end_threshold(#[case] mode: FogMode) {
+        let fog = FogParameters::exponential(mode, 100.0);
+        let at_end = fog.visibility(&CAMERA, &(CAMERA + Vector3::new(0.0, 0.0, 100.0)));
+        let halfway = fog.visibility(&CAMERA, &(CAMERA + Vector3::new(0.0, 0.0, 50.0)));
+
+        assert!((FOG_CUTOFF - at_end).abs() < 1e-4);
+        assert!(halfway > at_end);
+    }
+
+    #[rstest]
+    fn exponential_squared_fog_stays_clearer_up_close() {
+        let exponential = FogParameters::exponential(FogMode::Exponential, 100.0);
+        let squared = FogParameters::exponential(FogMode::ExponentialSquared, 100.0);
+        let point = CAMERA + Vector3::new(20.0, 0.0, 0.0);
+
+        assert!(squared.visibility(&CAMERA, &point) > exponential.visibility(&CAMERA, &point));
+    }
+
+    #[rstest]
+    fn height_fog_is_thicker_lower_down() {
+        let fog = FogParameters {
+            height_density: 0.05,
+            height_falloff: 0.1,
+            height_base: 60.0,
+            ..FogParameters::linear(1000.0, 2000.0)
+        };
+        let low = Point3::new(0.0, 60.0, 0.0);
+        let high = Point3::new(0.0, 90.0, 0.0);
+        let offset = Vector3::new(50.0, 0.0, 0.0);
+
+        assert!(fog.visibility(&low, &(low + offset)) < fog.visibility(&high, &(high + offset)));
+    }
+
+    #[rstest]
+    fn height_fog_matches_whether_looking_up_or_down() {
+        let fog = FogParameters {
+            height_density: 0.05,
+            height_falloff: 0.1,
+            height_base: 60.0,
+            ..FogParameters::linear(1000.0, 2000.0)
+        };
+        let a = Point3::new(0.0, 60.0, 0.0);
+        let b = Point3::new(0.0, 80.0, 10.0);
+
+        assert!((fog.visibility(&a, &b) - fog.visibility(&b, &a)).abs() < 1e-4);
+    }
 }
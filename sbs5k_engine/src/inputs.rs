@@ -27,10 +27,21 @@ pub enum Key {
     X,
     Y,
     Z,
+    Num0,
+    Num1,
+    Num2,
+    Num3,
+    Num4,
+    Num5,
+    Num6,
+    Num7,
+    Num8,
+    Num9,
     Up,
     Down,
     Left,
     Right,
+    Space,
     Escape,
     LeftShift,
     RightShift,
@@ -68,10 +79,21 @@ impl Key {
             glfw::Key::X => Some(Key::X),
             glfw::Key::Y => Some(Key::Y),
             glfw::Key::Z => Some(Key::Z),
+            glfw::Key::Num0 => Some(Key::Num0),
+            glfw::Key::Num1 => Some(Key::Num1),
+            glfw::Key::Num2 => Some(Key::Num2),
+            glfw::Key::Num3 => Some(Key::Num3),
+            glfw::Key::Num4 => Some(Key::Num4),
+            glfw::Key::Num5 => Some(Key::Num5),
+            glfw::Key::Num6 => Some(Key::Num6),
+            glfw::Key::Num7 => Some(Key::Num7),
+            glfw::Key::Num8 => Some(Key::Num8),
+            glfw::Key::Num9 => Some(Key::Num9),
             glfw::Key::Up => Some(Key::Up),
             glfw::Key::Down => Some(Key::Down),
             glfw::Key::Left => Some(Key::Left),
             glfw::Key::Right => Some(Key::Right),
+            glfw::Key::Space => Some(Key::Space),
             glfw::Key::Escape => Some(Key::Escape),
             glfw::Key::LeftShift => Some(Key::LeftShift),
             glfw::Key::RightShift => Some(Key::RightShift),
@@ -81,3 +103,23 @@ impl Key {
         }
     }
 }
+
+/// A button on the mouse
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+}
+
+impl MouseButton {
+    #[inline]
+    pub(crate) fn from_glfw_mouse_button(glfw_button: glfw::MouseButton) -> Option<Self> {
+        match glfw_button {
+            glfw::MouseButtonLeft => Some(MouseButton::Left),
+            glfw::MouseButtonRight => Some(MouseButton::Right),
+            glfw::MouseButtonMiddle => Some(MouseButton::Middle),
+            _ => None,
+        }
+    }
+}
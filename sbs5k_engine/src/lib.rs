@@ -1,12 +1,18 @@
 mod binding;
 mod camera;
+mod clustering;
 mod fog;
+mod preprocessor;
 mod rendering;
 mod resources;
 mod scene;
 mod shaders;
+mod shadows;
+mod sky;
 mod skybox;
+mod texture_units;
 mod time;
+mod uniform_blocks;
 mod uniforms;
 mod window;
 
@@ -17,9 +23,12 @@ pub mod model;
 pub mod texture;
 
 pub use camera::CameraPosition;
-pub use fog::FogParameters;
-pub use rendering::Renderer;
-pub use scene::SceneObject;
+pub use fog::{FogColour, FogMode, FogParameters};
+pub use rendering::{RenderTarget, Renderer};
+pub use scene::{Scene, SceneObject};
+pub use shaders::ShaderSourceProvider;
+pub use shadows::{ShadowSettings, NUM_SHADOW_CASCADES};
+pub use sky::SkyParameters;
 pub use skybox::Skybox;
-pub use time::TimeTracker;
+pub use time::{FixedTimestep, FrameStats, TimeTracker};
 pub use window::Window;
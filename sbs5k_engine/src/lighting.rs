@@ -1,5 +1,13 @@
 use nalgebra::{Point3, Vector3};
 
+/// The most point lights that can affect a scene at once
+///
+/// Any lights beyond this many are ignored, so callers should pass the most important lights first.
+pub const MAX_POINT_LIGHTS: usize = 512;
+
+/// The irradiance below which a point light is considered to have no effect
+const POINT_LIGHT_CUTOFF: f32 = 0.01;
+
 /// A point illumination source, whose radiance disperses equally in all directions
 #[derive(Debug)]
 pub struct PointLight {
@@ -8,6 +16,13 @@ pub struct PointLight {
     pub intensity: f32,
 }
 
+impl PointLight {
+    /// The distance beyond which this light has no effect
+    pub fn radius(&self) -> f32 {
+        (self.intensity / POINT_LIGHT_CUTOFF).sqrt()
+    }
+}
+
 /// A global illumination source, whose radiance is always observed as being received from the same
 /// direction and intensity, regardless of the location of the observer
 #[derive(Debug)]
@@ -16,6 +31,9 @@ pub struct GlobalLight {
     pub direction: Vector3<f32>,
     pub colour: Vector3<f32>,
     pub intensity: f32,
+
+    /// The intensity of the light scattered in from the whole sky, rather than from `direction`
+    pub ambient_intensity: f32,
 }
 
 /// Lighting information about a scene that can be rendered
@@ -5,7 +5,7 @@ use std::rc::Rc;
 use gl::types::*;
 
 use crate::binding::Bindable;
-use crate::texture::Texture;
+use crate::texture::TextureArray;
 
 /// Encodes information about the offsets of different data within a buffer of
 /// vertex data
@@ -13,7 +13,12 @@ use crate::texture::Texture;
 pub struct VertexDataLayoutInfo {
     pub position_offset: u32,
     pub normal_offset: Option<u32>,
+
+    /// The offset of the texture coordinates: a `u` and `v` within a texture array layer, followed
+    /// by the index of the layer and the number of layers that it's animated through, which is 1
+    /// for a texture that isn't animated
     pub texture_offset: Option<u32>,
+    pub light_offset: Option<u32>,
 }
 
 /// Vertex data describing the geometry of a model
@@ -32,11 +37,61 @@ pub struct VertexData {
     ebo: u32,
 }
 
+/// The textures that the surface of a model is drawn with
+///
+/// Every texture is sampled at the model's texture coordinates, so they must all have their images
+/// in the same layers.
+#[derive(Clone, Debug)]
+pub struct Material {
+    /// The base colour of the surface
+    pub albedo: Rc<TextureArray>,
+
+    /// Which way the surface faces at each point, if it's bumpier than the model's geometry
+    ///
+    /// Each texel holds a unit vector, mapped from [-1, 1] to [0, 1], relative to the face it's on:
+    /// red points along the face's `u` texture coordinate, green along its `v` and blue out of the
+    /// face.
+    pub normal: Option<Rc<TextureArray>>,
+
+    /// The light given off by the surface, if it glows
+    pub emissive: Option<Rc<TextureArray>>,
+
+    /// How the alpha channel of the albedo texture is used
+    pub alpha_mode: AlphaMode,
+}
+
+/// How a material's alpha channel affects the way it's drawn
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum AlphaMode {
+    /// The surface is fully opaque, whatever its alpha
+    #[default]
+    Opaque,
+
+    /// Texels less than half opaque are cut out, and the rest are drawn as fully opaque
+    Cutout,
+
+    /// The surface is blended over whatever is behind it, so must be drawn with
+    /// `RenderTarget::render_translucent_objects`
+    Blend,
+}
+
 /// A renderable model
 #[derive(Debug)]
 pub struct Model {
     pub vertices: VertexData,
-    pub texture: Rc<Texture>,
+    pub material: Material,
+}
+
+impl Material {
+    /// A material with a base colour, but no normal map and no glow
+    pub fn new(albedo: Rc<TextureArray>) -> Self {
+        Material {
+            albedo,
+            normal: None,
+            emissive: None,
+            alpha_mode: AlphaMode::Opaque,
+        }
+    }
 }
 
 impl VertexDataLayoutInfo {
@@ -47,11 +102,16 @@ impl VertexDataLayoutInfo {
             None => 0,
         };
         let texture_coords_size = match self.texture_offset {
+            Some(_) => 4,
+            None => 0,
+        };
+
+        let light_size = match self.light_offset {
             Some(_) => 2,
             None => 0,
         };
 
-        position_size + normals_size + texture_coords_size
+        position_size + normals_size + texture_coords_size + light_size
     }
 
     pub fn stride_bytes(&self) -> u32 {
@@ -84,6 +144,19 @@ impl VertexDataLayoutInfo {
             self.normals_index()
         }
     }
+
+    /// Reports the index of the light levels attribute
+    pub fn lights_index(&self) -> usize {
+        if self.light_offset.is_none() {
+            panic!("lights_index() called when there is no light attribute")
+        }
+
+        if self.texture_offset.is_some() {
+            self.textures_index() + 1
+        } else {
+            self.textures_index()
+        }
+    }
 }
 
 impl VertexData {
@@ -158,7 +231,7 @@ impl VertexData {
             if let Some(offset) = layout_info.texture_offset {
                 gl::VertexAttribPointer(
                     2,
-                    2,
+                    4,
                     gl::FLOAT,
                     gl::FALSE,
                     layout_info.stride_bytes() as i32,
@@ -167,6 +240,19 @@ impl VertexData {
                 gl::EnableVertexAttribArray(layout_info.textures_index() as u32);
             }
 
+            // Set up light levels attribute
+            if let Some(offset) = layout_info.light_offset {
+                gl::VertexAttribPointer(
+                    3,
+                    2,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    layout_info.stride_bytes() as i32,
+                    (offset * mem::size_of::<GLfloat>() as u32) as *const os::raw::c_void,
+                );
+                gl::EnableVertexAttribArray(layout_info.lights_index() as u32);
+            }
+
             // Unbind all the buffers now that we're done
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
             gl::BindBuffer(gl::ARRAY_BUFFER, 0);
@@ -186,6 +272,30 @@ impl VertexData {
     pub fn num_elements(&self) -> u32 {
         self.vertices_count
     }
+
+    /// Replace the index buffer, such as to draw the same triangles in a different order
+    ///
+    /// There must be as many indices as the model was created with.
+    pub fn set_indices(&self, index_buffer: &[u32]) {
+        assert_eq!(
+            self.vertices_count as usize,
+            index_buffer.len(),
+            "The number of indices in a model can't be changed"
+        );
+
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, self.ebo);
+            gl::BufferSubData(
+                gl::ELEMENT_ARRAY_BUFFER,
+                0,
+                mem::size_of_val(index_buffer) as GLsizeiptr,
+                index_buffer.as_ptr() as *const os::raw::c_void,
+            );
+            gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, 0);
+            gl::BindVertexArray(0);
+        }
+    }
 }
 
 impl Drop for VertexData {
@@ -0,0 +1,259 @@
+//! A small preprocessor that runs over shader source before it's handed to OpenGL, adding support
+//! for `#include "file.glsl"` and for `#define`s injected by the engine.
+
+use std::collections::HashSet;
+
+/// Shader source code that has been through the preprocessor
+#[derive(Debug)]
+pub(crate) struct PreprocessedSource {
+    /// The code to compile
+    pub(crate) code: String,
+
+    /// The name of each file that went into the code, indexed by its GLSL source string number
+    files: Vec<String>,
+}
+
+/// Expand the `#include` directives in the shader `src`, and define each of `defines` straight
+/// after its `#version` directive
+///
+/// `read_include` looks up the contents of an included file by name, along with a name for it to
+/// use in errors. Each file is only included once, however many times it's asked for, so included
+/// files don't need include guards.
+///
+/// `#line` directives are added around each included file so that errors reported by the compiler
+/// point at the right line, which `annotate_log` can then tag with the right file name.
+pub(crate) fn preprocess(
+    name: &str,
+    src: &str,
+    defines: &[(&str, String)],
+    read_include: &dyn Fn(&str) -> Option<(String, String)>,
+) -> Result<PreprocessedSource, String> {
+    let mut code = String::new();
+    let mut body = src;
+
+    if let Some(version_line) = src
+        .lines()
+        .position(|line| line.trim().starts_with("#version"))
+    {
+        let (head, rest) = split_after_line(src, version_line);
+        code.push_str(head);
+        body = rest;
+        push_defines(&mut code, defines);
+        code.push_str(&format!("#line {} 0\n", version_line + 2));
+    } else {
+        push_defines(&mut code, defines);
+        code.push_str("#line 1 0\n");
+    }
+
+    let mut preprocessor = Preprocessor {
+        read_include,
+        files: vec![name.to_owned()],
+        included: HashSet::new(),
+        code,
+    };
+    let first_line = src.lines().count() - body.lines().count() + 1;
+    preprocessor.expand(body, 0, first_line)?;
+
+    Ok(PreprocessedSource {
+        code: preprocessor.code,
+        files: preprocessor.files,
+    })
+}
+
+impl PreprocessedSource {
+    /// Rewrite the locations in a compiler error log to name the file each error is in, rather
+    /// than its source string number
+    ///
+    /// Drivers format locations differently, such as `0:12(5):`, `0(12) :` or `ERROR: 0:12:`, so
+    /// any line that starts with a source string number (perhaps after a word like `ERROR:`) and
+    /// then a `:` or `(` is rewritten.
+    pub(crate) fn annotate_log(&self, log: &str) -> String {
+        log.lines()
+            .map(|line| self.annotate_line(line))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn annotate_line(&self, line: &str) -> String {
+        let number_start = match line.find(|c: char| c.is_ascii_digit()) {
+            Some(i) => i,
+            None => return line.to_owned(),
+        };
+        let (prefix, rest) = line.split_at(number_start);
+        if !prefix
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c == ':' || c == ' ')
+        {
+            return line.to_owned();
+        }
+
+        let number_end = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (number, remainder) = rest.split_at(number_end);
+        if !remainder.starts_with(':') && !remainder.starts_with('(') {
+            return line.to_owned();
+        }
+
+        match number.parse::<usize>().ok().and_then(|n| self.files.get(n)) {
+            Some(file) => format!("{}{}{}", prefix, file, remainder),
+            None => line.to_owned(),
+        }
+    }
+}
+
+struct Preprocessor<'a> {
+    read_include: &'a dyn Fn(&str) -> Option<(String, String)>,
+    files: Vec<String>,
+    included: HashSet<String>,
+    code: String,
+}
+
+impl Preprocessor<'_> {
+    /// Append `src`, which is source string number `source` and starts at line `first_line` of it,
+    /// expanding any includes
+    fn expand(&mut self, src: &str, source: usize, first_line: usize) -> Result<(), String> {
+        for (i, line) in src.lines().enumerate() {
+            let line_number = first_line + i;
+            let directive = line.trim();
+
+            if directive.starts_with("#version") && source != 0 {
+                return Err(format!(
+                    "{}:{}: Included files can't have a #version directive",
+                    self.files[source], line_number
+                ));
+            }
+
+            if !directive.starts_with("#include") {
+                self.code.push_str(line);
+                self.code.push('\n');
+                continue;
+            }
+
+            let included_name = parse_include(directive).ok_or_else(|| {
+                format!(
+                    "{}:{}: Expected #include \"file\"",
+                    self.files[source], line_number
+                )
+            })?;
+
+            // Keep a blank line in place of the directive so that the line numbers still match
+            if !self.included.insert(included_name.to_owned()) {
+                self.code.push('\n');
+                continue;
+            }
+
+            let (contents, display_name) = (self.read_include)(included_name).ok_or_else(|| {
+                format!(
+                    "{}:{}: Can't find included file \"{}\"",
+                    self.files[source], line_number, included_name
+                )
+            })?;
+
+            let included_source = self.files.len();
+            self.files.push(display_name);
+            self.code
+                .push_str(&format!("#line 1 {}\n", included_source));
+            self.expand(&contents, included_source, 1)?;
+            self.code
+                .push_str(&format!("#line {} {}\n", line_number + 1, source));
+        }
+
+        Ok(())
+    }
+}
+
+fn push_defines(code: &mut String, defines: &[(&str, String)]) {
+    for (name, value) in defines {
+        code.push_str(&format!("#define {} {}\n", name, value));
+    }
+}
+
+/// Split `src` just after the end of its `index`th line
+fn split_after_line(src: &str, index: usize) -> (&str, &str) {
+    let mut end = 0;
+    for line in src.split_inclusive('\n').take(index + 1) {
+        end += line.len();
+    }
+    src.split_at(end)
+}
+
+/// Find the name of the file in an `#include "file"` directive
+fn parse_include(directive: &str) -> Option<&str> {
+    let quoted = directive.strip_prefix("#include")?.trim();
+    quoted.strip_prefix('"')?.strip_suffix('"')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn read_include(name: &str) -> Option<(String, String)> {
+        let contents = match name {
+            "a.glsl" => "float a;\n",
+            "b.glsl" => "#include \"a.glsl\"\nfloat b;\n",
+            "versioned.glsl" => "#version 410 core\n",
+            _ => return None,
+        };
+        Some((contents.to_owned(), format!("shaders/{}", name)))
+    }
+
+    fn run(src: &str) -> Result<PreprocessedSource, String> {
+        preprocess("main.frag", src, &[("N", "3".to_owned())], &read_include)
+    }
+
+    #[rstest]
+    fn defines_go_straight_after_the_version() {
+        let result = run("// Comment\n#version 410 core\nvoid main() {}\n").unwrap();
+        assert_eq!(
+            "// Comment\n#version 410 core\n#define N 3\n#line 3 0\nvoid main() {}\n",
+            result.code
+        );
+    }
+
+    #[rstest]
+    fn includes_are_expanded_with_line_markers() {
+        let result = run("#version 410 core\n#include \"a.glsl\"\nvoid main() {}\n").unwrap();
+        assert_eq!(
+            "#version 410 core\n#define N 3\n#line 2 0\n#line 1 1\nfloat a;\n#line 3 0\nvoid main() {}\n",
+            result.code
+        );
+    }
+
+    #[rstest]
+    fn files_are_only_included_once() {
+        let result = run("#version 410 core\n#include \"b.glsl\"\n#include \"a.glsl\"\n").unwrap();
+        assert_eq!(1, result.code.matches("float a;").count());
+        assert_eq!(1, result.code.matches("float b;").count());
+    }
+
+    #[rstest]
+    #[case(
+        "#include \"missing.glsl\"",
+        "main.frag:2: Can't find included file \"missing.glsl\""
+    )]
+    #[case("#include <a.glsl>", "main.frag:2: Expected #include \"file\"")]
+    #[case(
+        "#include \"versioned.glsl\"",
+        "shaders/versioned.glsl:1: Included files can't"
+    )]
+    fn bad_includes_are_reported(#[case] directive: &str, #[case] expected_error: &str) {
+        let error = run(&format!("#version 410 core\n{}\n", directive)).unwrap_err();
+        assert!(error.starts_with(expected_error), "{}", error);
+    }
+
+    #[rstest]
+    #[case("0:5(12): error: oops", "main.frag:5(12): error: oops")]
+    #[case(
+        "ERROR: 2:7: 'x' : undeclared",
+        "ERROR: shaders/a.glsl:7: 'x' : undeclared"
+    )]
+    #[case("1(3) : error C0000: oops", "shaders/b.glsl(3) : error C0000: oops")]
+    #[case("9:1(1): error: unknown file", "9:1(1): error: unknown file")]
+    #[case("Compilation failed with 2 errors", "Compilation failed with 2 errors")]
+    fn error_logs_name_the_file(#[case] line: &str, #[case] expected: &str) {
+        let result = run("#version 410 core\n#include \"b.glsl\"\n").unwrap();
+        assert_eq!(expected, result.annotate_log(line));
+    }
+}
@@ -1,44 +1,69 @@
-use std::cmp;
 use std::ptr;
+use std::time::Instant;
 
 use gl::types::*;
-use nalgebra::Vector3;
+use nalgebra::Vector2;
 
 use crate::binding::BindGuard;
-use crate::camera::CameraPosition;
-use crate::fog::FogParameters;
-use crate::lighting::{GlobalLight, PointLight, SceneLighting};
+use crate::camera;
+use crate::clustering::{self, LightClusters};
+use crate::fog::FogMode;
+use crate::lighting::{PointLight, MAX_POINT_LIGHTS};
+use crate::model::{AlphaMode, Material};
 use crate::resources;
-use crate::scene::SceneObject;
-use crate::shaders::{Shader, ShaderProgram};
+use crate::scene::{Scene, SceneObject};
+use crate::shaders::{BuiltInShaders, Shader, ShaderProgram, ShaderSourceProvider, ShaderSrc};
+use crate::shadows::{self, ShadowMaps, ShadowSettings, NUM_SHADOW_CASCADES};
 use crate::skybox::Skybox;
-use crate::texture::TextureBinding;
+use crate::texture::{BufferTexture, CubemapTexture};
+use crate::texture_units::{BindingScope, TextureUnit, TextureUnits};
+use crate::uniform_blocks::UniformBuffers;
 use crate::uniforms::Uniform;
 
+/// The vertex and fragment shaders making up the scene objects, skybox and shadow depth programs
+static SHADER_PROGRAMS: [(&ShaderSrc, &ShaderSrc); 3] = [
+    (
+        &resources::shaders::SCENE_OBJECTS_VERT_SHADERS,
+        &resources::shaders::SCENE_OBJECTS_FRAG_SHADER,
+    ),
+    (
+        &resources::shaders::SKYBOX_VERT_SHADER,
+        &resources::shaders::SKYBOX_FRAG_SHADER,
+    ),
+    (
+        &resources::shaders::SHADOW_DEPTH_VERT_SHADER,
+        &resources::shaders::SHADOW_DEPTH_FRAG_SHADER,
+    ),
+];
+
 const BACKGROUND_R: f32 = 0.2;
 const BACKGROUND_G: f32 = 0.2;
 const BACKGROUND_B: f32 = 0.2;
 
-const MAX_POINT_LIGHTS: usize = 4;
-
 /// A physical display to which a buffer can be displayed
 pub trait DisplayTarget {
     fn swap_buffers(&mut self);
 }
 
 /// A logical target to which scene objects can be rendered
+///
+/// The camera, lighting, fog and sky are those of the `Scene` passed to
+/// `Renderer::do_render_pass`.
 pub trait RenderTarget {
     /// Render objects to the target
-    fn render_objects(
-        &self,
-        objects: &[&SceneObject],
-        lighting: &SceneLighting,
-        camera: &CameraPosition,
-        fog: &FogParameters,
-    );
-
-    /// Render a skybox to the active render target
-    fn render_skybox(&self, skybox: &Skybox, camera: &CameraPosition);
+    fn render_objects(&self, objects: &[&SceneObject]);
+
+    /// Render the skybox set with `Renderer::set_skybox` to the target, along with the sun, moon,
+    /// stars and clouds
+    fn render_skybox(&self);
+
+    /// Render objects with translucent materials to the target, blending them over what's already
+    /// there
+    ///
+    /// This must come after everything else in the frame, including the skybox, since nothing
+    /// drawn later would show through them. The objects are drawn in the order given, which should
+    /// be from back to front.
+    fn render_translucent_objects(&self, objects: &[&SceneObject]);
 }
 
 /// An object capable of rendering `SceneObject`s to a `RenderingContext`
@@ -47,28 +72,75 @@ pub trait RenderTarget {
 ///
 /// 1. Create a `Renderer` object.
 /// 2. Call `Renderer::setup` to activate the renderer.
-/// 3. Call `Renderer::begin_rendering_pass` to commence a rendering pass.
-/// 4. Issue calls to `Renderer::draw_objects` and `Renderer::draw_skybox` as appropriate.
-/// 5. Finalise the rendering pass by calling `Renderer::complete_render_pass`. This call will block
-///    until the GPU has finished the render.
+/// 3. Call `Renderer::do_render_pass` with the scene's camera, lighting, fog and sky to commence a
+///    rendering pass.
+/// 4. Issue calls to `Renderer::draw_objects` and `Renderer::draw_skybox` as appropriate, followed
+///    by `RenderTarget::render_translucent_objects` for anything see-through.
+/// 5. Finalise the rendering pass by calling `Renderer::complete_render_pass`, which presents the
+///    frame.
+///
+/// Before the main pass, the same draw calls are replayed from the point of view of the global
+/// light to fill in its shadow maps.
 ///
 /// Rendering commands are buffered and executed asynchronously, so `draw_objects()` and
-/// `draw_skybox()` may return before the actual render command has been completed. The only point
-/// of synchronisation is the `complete_render_pass()` method.
+/// `draw_skybox()` may return before the actual render command has been completed. The driver is
+/// left to synchronise with the GPU when the buffers are swapped, which may block if vsync is
+/// enabled.
 pub struct Renderer {
     cubes_shader_program: ShaderProgram,
     skybox_shader_program: ShaderProgram,
+    shadow_depth_shader_program: ShaderProgram,
+    uniform_buffers: UniformBuffers,
+    texture_units: TextureUnits,
+    point_light_buffers: PointLightBuffers,
+    shadow_maps: ShadowMaps,
+    skybox: Skybox,
+
+    /// Pointed at by the scene objects shader's skybox sampler when the skybox has no texture, as
+    /// the sampler can't be left pointing at a unit that holds a texture of a different type
+    placeholder_cubemap: CubemapTexture,
+
+    /// When the renderer was created, which animated textures are timed from
+    started: Instant,
+}
+
+/// A render target that draws objects into the shadow maps, rather than onto the screen
+struct ShadowPass<'a> {
+    shader_program: &'a ShaderProgram,
+    shadow_maps: &'a ShadowMaps,
+    texture_units: &'a TextureUnits,
+}
+
+/// The buffers from which the scene objects shader reads the point lights affecting each fragment
+struct PointLightBuffers {
+    /// Two texels per light: its position and range, followed by its radiance
+    lights: BufferTexture,
+
+    /// One texel per cluster: the offset of its first light index and its number of lights
+    clusters: BufferTexture,
+
+    /// The indices of the lights in each cluster
+    indices: BufferTexture,
+}
+
+/// The texture units that the point light buffers are bound to
+struct PointLightBufferUnits {
+    lights: TextureUnit,
+    clusters: TextureUnit,
+    indices: TextureUnit,
 }
 
 impl Renderer {
     pub fn new() -> Renderer {
-        let scene_objs_vert = Shader::new(&resources::shaders::SCENE_OBJECTS_VERT_SHADERS);
-        let scene_objects_frag = Shader::new(&resources::shaders::SCENE_OBJECTS_FRAG_SHADER);
-        let cubes_shader_program = ShaderProgram::new(scene_objs_vert, scene_objects_frag);
+        Renderer::with_shader_sources(&BuiltInShaders)
+    }
 
-        let skybox_vert = Shader::new(&resources::shaders::SKYBOX_VERT_SHADER);
-        let skybox_frag = Shader::new(&resources::shaders::SKYBOX_FRAG_SHADER);
-        let skybox_shader_program = ShaderProgram::new(skybox_vert, skybox_frag);
+    /// Create a renderer whose shaders may be replaced by those found in `sources`
+    pub fn with_shader_sources(sources: &dyn ShaderSourceProvider) -> Renderer {
+        let [cubes_shader_program, skybox_shader_program, shadow_depth_shader_program] =
+            SHADER_PROGRAMS.map(|(vert, frag)| {
+                load_shader_program(vert, frag, sources).unwrap_or_else(|err| panic!("{}", err))
+            });
 
         // Initial setup of the OpenGL environment
         unsafe {
@@ -87,9 +159,144 @@ impl Renderer {
         Renderer {
             cubes_shader_program,
             skybox_shader_program,
+            shadow_depth_shader_program,
+            uniform_buffers: UniformBuffers::new(),
+            texture_units: TextureUnits::new(),
+            point_light_buffers: PointLightBuffers::new(),
+            shadow_maps: ShadowMaps::new(ShadowSettings::default()),
+            skybox: Skybox::new(),
+            placeholder_cubemap: CubemapTexture::placeholder(),
+            started: Instant::now(),
         }
     }
 
+    /// Recompile every shader program from `sources`
+    ///
+    /// Any program that fails to compile keeps its previous version, so that a mistake made while
+    /// editing a shader doesn't stop the game. The errors are returned.
+    pub fn reload_shaders(&mut self, sources: &dyn ShaderSourceProvider) -> Result<(), String> {
+        let programs = [
+            &mut self.cubes_shader_program,
+            &mut self.skybox_shader_program,
+            &mut self.shadow_depth_shader_program,
+        ];
+
+        let mut errors = vec![];
+        for (program, (vert, frag)) in programs.into_iter().zip(SHADER_PROGRAMS) {
+            match load_shader_program(vert, frag, sources) {
+                Ok(new_program) => *program = new_program,
+                Err(err) => errors.push(err),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+
+    /// Upload everything that stays the same for the whole frame, once, before anything is drawn
+    fn prepare_frame(&self, scene: &Scene) {
+        // Fit each shadow cascade around its slice of the view frustum
+        self.shadow_maps
+            .light_space_matrices
+            .set(shadows::cascade_matrices(
+                scene.camera,
+                &scene.lighting.global_light.direction,
+                &self.shadow_maps.settings,
+            ));
+
+        // Fill in the uniform blocks, which every program shares
+        self.uniform_buffers
+            .write_camera(scene.camera, self.started.elapsed().as_secs_f32());
+        self.uniform_buffers
+            .write_lighting(&scene.lighting.global_light);
+        self.uniform_buffers.write_fog(scene.fog);
+        self.uniform_buffers.write_sky(scene.sky);
+
+        // Sort the point lights into clusters so that each fragment only has to consider the
+        // lights near it
+        let clusters = LightClusters::assign(
+            &scene.lighting.point_lights,
+            &scene.camera.view_matrix(),
+            &scene.camera.projection_matrix(),
+        );
+        self.point_light_buffers
+            .upload(&scene.lighting.point_lights, &clusters);
+    }
+
+    /// Draw objects with the scene objects shader, in whatever blending mode is currently set
+    fn draw_scene_objects(&self, objects: &[&SceneObject]) {
+        // Bind shader program
+        let _shader_program_guard = BindGuard::create_bind(&self.cubes_shader_program);
+
+        self.texture_units.begin_batch();
+        let point_light_units = self.point_light_buffers.bind(&self.texture_units);
+        write_point_light_uniforms(&self.cubes_shader_program, &point_light_units);
+
+        let shadow_maps_unit = self
+            .texture_units
+            .bind(self.shadow_maps.handle(), BindingScope::Batch);
+        write_shadow_uniforms(
+            &self.cubes_shader_program,
+            &self.shadow_maps,
+            shadow_maps_unit,
+        );
+
+        // Fade distant objects into the skybox's texture, if it has one, so that they match the
+        // sky behind them
+        let skybox_texture = self.skybox.texture.as_ref();
+        let skybox_unit = self.texture_units.bind(
+            skybox_texture.unwrap_or(&self.placeholder_cubemap).handle(),
+            BindingScope::Batch,
+        );
+        self.cubes_shader_program
+            .write_uniform(Uniform::UseSkyboxTexture(skybox_texture.is_some()));
+        self.cubes_shader_program
+            .write_uniform(Uniform::SkyboxTexture(skybox_unit));
+
+        // Render each object
+        for object in objects.iter() {
+            // Set up textures, which are usually still bound from the last object
+            self.texture_units.begin_draw();
+            write_material_uniforms(
+                &self.cubes_shader_program,
+                &self.texture_units,
+                &object.model.material,
+            );
+
+            // Bind this object's vertex data
+            let _vertex_data_guard = BindGuard::create_bind(&object.model.vertices);
+
+            // Write uniforms specific to this object
+            write_model_uniforms(&self.cubes_shader_program, object);
+
+            // Do the render
+            unsafe {
+                gl::DrawElements(
+                    gl::TRIANGLES,
+                    object.model.vertices.num_elements() as i32,
+                    gl::UNSIGNED_INT,
+                    ptr::null_mut(),
+                );
+            }
+        }
+    }
+
+    /// Change the reach and resolution of the shadows cast by the global light
+    pub fn set_shadow_settings(&mut self, settings: ShadowSettings) {
+        if settings != self.shadow_maps.settings {
+            self.shadow_maps = ShadowMaps::new(settings);
+        }
+    }
+
+    /// Change the skybox drawn behind everything else, which distant objects fade into when the
+    /// fog takes on the sky's colour
+    pub fn set_skybox(&mut self, skybox: Skybox) {
+        self.skybox = skybox;
+    }
+
     /// Commence a render pass
     #[inline(always)]
     fn begin_render_pass(&self, _target: &impl DisplayTarget) {
@@ -100,31 +307,83 @@ impl Renderer {
     }
 
     /// Finalise a render pass
-    ///
-    /// This function will block until the GPU has finished all buffered draw calls.
     #[inline(always)]
     fn complete_render_pass(&self, target: &mut impl DisplayTarget) {
-        unsafe {
-            // TODO: Decide policy around this
-            gl::Finish();
-        }
         target.swap_buffers();
     }
 
-    /// Render to a `RenderTarget`.
+    /// Render `scene` to a `RenderTarget`.
     ///
     /// The display will be automatically cleared before rendering and the buffers swapped after.
     /// The supplied `render_impl` should contain *all* draw commands for this frame.
-    pub fn do_render_pass<F>(&mut self, target: &mut impl DisplayTarget, render_impl: &F)
-    where
+    pub fn do_render_pass<F>(
+        &mut self,
+        target: &mut impl DisplayTarget,
+        scene: &Scene,
+        render_impl: &F,
+    ) where
         F: Fn(&mut dyn RenderTarget),
     {
+        // Textures may have been created or reloaded since the last frame, which disturbs what's
+        // bound to the texture units
+        self.texture_units.forget_bindings();
+
+        self.prepare_frame(scene);
+
+        // Draw the shadow maps first, so that the main pass can tell what's in shadow
+        self.shadow_maps.clear();
+        render_impl(&mut ShadowPass {
+            shader_program: &self.shadow_depth_shader_program,
+            shadow_maps: &self.shadow_maps,
+            texture_units: &self.texture_units,
+        });
+
         self.begin_render_pass(target);
         render_impl(self);
         self.complete_render_pass(target);
     }
 }
 
+impl PointLightBuffers {
+    fn new() -> Self {
+        PointLightBuffers {
+            lights: BufferTexture::new(gl::RGBA32F),
+            clusters: BufferTexture::new(gl::RG32UI),
+            indices: BufferTexture::new(gl::R32UI),
+        }
+    }
+
+    /// Copy the lights and the clusters they've been assigned to over to the GPU
+    fn upload(&self, point_lights: &[PointLight], clusters: &LightClusters) {
+        let mut light_data: Vec<f32> = Vec::with_capacity(point_lights.len() * 8);
+        for light in point_lights.iter().take(MAX_POINT_LIGHTS) {
+            let radiance = light.colour * light.intensity;
+            light_data.extend_from_slice(&[
+                light.position.x,
+                light.position.y,
+                light.position.z,
+                light.radius(),
+                radiance.x,
+                radiance.y,
+                radiance.z,
+                0.0,
+            ]);
+        }
+
+        self.lights.upload(&light_data);
+        self.clusters.upload(&clusters.ranges);
+        self.indices.upload(&clusters.indices);
+    }
+
+    fn bind(&self, texture_units: &TextureUnits) -> PointLightBufferUnits {
+        PointLightBufferUnits {
+            lights: texture_units.bind(self.lights.handle(), BindingScope::Batch),
+            clusters: texture_units.bind(self.clusters.handle(), BindingScope::Batch),
+            indices: texture_units.bind(self.indices.handle(), BindingScope::Batch),
+        }
+    }
+}
+
 impl Default for Renderer {
     fn default() -> Self {
         Renderer::new()
@@ -132,54 +391,40 @@ impl Default for Renderer {
 }
 
 impl RenderTarget for Renderer {
-    fn render_objects(
-        &self,
-        objects: &[&SceneObject],
-        scene: &SceneLighting,
-        camera: &CameraPosition,
-        fog: &FogParameters,
-    ) {
-        // Bind shader program
-        let _shader_program_guard = BindGuard::create_bind(&self.cubes_shader_program);
-
-        // Write all the uniforms
-        write_camera_uniforms(&self.cubes_shader_program, camera);
-        write_point_light_uniforms(&self.cubes_shader_program, &scene.point_lights);
-        write_global_illuminant_uniforms(&self.cubes_shader_program, &scene.global_light);
-        write_fog_uniforms(&self.cubes_shader_program, fog);
-
-        // Render each object
-        for object in objects.iter() {
-            // Set up textures
-            // TODO: Allocate these more intelligently + consider integrating with BindGuard
-            // TODO: Don't keep writing the same texture data
-            let texture_binding = TextureBinding::new(&object.model.texture, gl::TEXTURE0);
-            write_texture_uniforms(&self.cubes_shader_program, &texture_binding);
+    fn render_objects(&self, objects: &[&SceneObject]) {
+        self.draw_scene_objects(objects);
+    }
 
-            // Bind this object's vertex data
-            let _vertex_data_guard = BindGuard::create_bind(&object.model.vertices);
+    fn render_translucent_objects(&self, objects: &[&SceneObject]) {
+        // Blend each surface over what's behind it. Depth isn't written, so that translucent
+        // surfaces never hide one another, but they're still hidden behind opaque ones.
+        unsafe {
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            gl::DepthMask(gl::FALSE);
+        }
 
-            // Write uniforms specific to this object
-            write_model_uniforms(&self.cubes_shader_program, object);
+        self.draw_scene_objects(objects);
 
-            // Do the render
-            unsafe {
-                gl::DrawElements(
-                    gl::TRIANGLES,
-                    object.model.vertices.num_elements() as i32,
-                    gl::UNSIGNED_INT,
-                    ptr::null_mut(),
-                );
-            }
+        unsafe {
+            gl::DepthMask(gl::TRUE);
+            gl::Disable(gl::BLEND);
         }
     }
 
-    fn render_skybox(&self, skybox: &Skybox, camera: &CameraPosition) {
+    fn render_skybox(&self) {
+        let skybox = &self.skybox;
+
         // Bind shader program
         let _shader_program_guard = BindGuard::create_bind(&self.skybox_shader_program);
 
-        // Write the uniforms we need
-        write_camera_uniforms(&self.skybox_shader_program, camera);
+        // Draw from the skybox's texture, if it has one, instead of the gradient
+        self.texture_units.begin_batch();
+        let texture_unit = skybox.texture.as_ref().map(|texture| {
+            self.texture_units
+                .bind(texture.handle(), BindingScope::Batch)
+        });
+        write_skybox_texture_uniforms(&self.skybox_shader_program, texture_unit);
 
         // Save the old depth function and
         let mut old_depth_func: GLint = 0;
@@ -214,11 +459,101 @@ impl RenderTarget for Renderer {
     }
 }
 
+impl RenderTarget for ShadowPass<'_> {
+    fn render_objects(&self, objects: &[&SceneObject]) {
+        let _shader_program_guard = BindGuard::create_bind(self.shader_program);
+
+        let matrices = self.shadow_maps.light_space_matrices.get();
+        let mut viewport = [0; 4];
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+
+            // Blocks are only drawn where they face open space, so both sides of each face need to
+            // cast shadows. The offset keeps surfaces from shadowing themselves.
+            gl::Disable(gl::CULL_FACE);
+            gl::Enable(gl::POLYGON_OFFSET_FILL);
+            gl::PolygonOffset(2.0, 4.0);
+        }
+
+        self.texture_units.begin_batch();
+        for (cascade, matrix) in matrices.iter().enumerate() {
+            self.shadow_maps.start_drawing_cascade(cascade);
+            self.shader_program
+                .write_uniform(Uniform::LightSpaceMatrix(matrix));
+
+            for object in objects.iter() {
+                self.texture_units.begin_draw();
+                write_shadow_material_uniforms(
+                    self.shader_program,
+                    self.texture_units,
+                    &object.model.material,
+                );
+
+                let _vertex_data_guard = BindGuard::create_bind(&object.model.vertices);
+                write_model_uniforms(self.shader_program, object);
+                unsafe {
+                    gl::DrawElements(
+                        gl::TRIANGLES,
+                        object.model.vertices.num_elements() as i32,
+                        gl::UNSIGNED_INT,
+                        ptr::null_mut(),
+                    );
+                }
+            }
+        }
+
+        self.shadow_maps.finish_drawing(viewport);
+        unsafe {
+            gl::Disable(gl::POLYGON_OFFSET_FILL);
+            gl::Enable(gl::CULL_FACE);
+        }
+    }
+
+    fn render_skybox(&self) {
+        // The sky doesn't cast shadows
+    }
+
+    fn render_translucent_objects(&self, _objects: &[&SceneObject]) {
+        // Translucent surfaces let most of the light through, so don't cast shadows
+    }
+}
+
+/// The constants shared between the engine and its shaders, which are defined at the top of every
+/// shader so that the two can't disagree
 #[inline]
-fn write_camera_uniforms(program: &ShaderProgram, camera: &CameraPosition) {
-    program.write_uniform(Uniform::CameraPosition(&camera.position.coords));
-    program.write_uniform(Uniform::ViewMatrix(&camera.view_matrix()));
-    program.write_uniform(Uniform::ProjectionMatrix(&camera.projection_matrix()));
+fn shader_defines() -> Vec<(&'static str, String)> {
+    vec![
+        ("MAX_POINT_LIGHTS", MAX_POINT_LIGHTS.to_string()),
+        ("CLUSTER_GRID_X", clustering::CLUSTER_GRID_X.to_string()),
+        ("CLUSTER_GRID_Y", clustering::CLUSTER_GRID_Y.to_string()),
+        ("CLUSTER_GRID_Z", clustering::CLUSTER_GRID_Z.to_string()),
+        ("Z_NEAR", format!("{:?}", camera::Z_NEAR)),
+        ("Z_FAR", format!("{:?}", camera::Z_FAR)),
+        ("NUM_SHADOW_CASCADES", NUM_SHADOW_CASCADES.to_string()),
+        ("FOG_MODE_LINEAR", (FogMode::Linear as i32).to_string()),
+        (
+            "FOG_MODE_EXPONENTIAL",
+            (FogMode::Exponential as i32).to_string(),
+        ),
+        (
+            "FOG_MODE_EXPONENTIAL_SQUARED",
+            (FogMode::ExponentialSquared as i32).to_string(),
+        ),
+    ]
+}
+
+/// Compile and link a shader program, using the replacements from `sources` for any of its
+/// shaders that have them
+fn load_shader_program(
+    vert: &ShaderSrc,
+    frag: &ShaderSrc,
+    sources: &dyn ShaderSourceProvider,
+) -> Result<ShaderProgram, String> {
+    let defines = shader_defines();
+    ShaderProgram::new(
+        Shader::load(vert, &defines, sources)?,
+        Shader::load(frag, &defines, sources)?,
+    )
 }
 
 #[inline]
@@ -227,41 +562,77 @@ fn write_model_uniforms(program: &ShaderProgram, model: &SceneObject) {
 }
 
 #[inline]
-fn write_global_illuminant_uniforms(program: &ShaderProgram, global_illuminant: &GlobalLight) {
-    program.write_uniform(Uniform::GlobalIlluminantDirection(
-        &global_illuminant.direction,
-    ));
-    program.write_uniform(Uniform::GlobalIlluminantColour(&global_illuminant.colour));
-    program.write_uniform(Uniform::GlobalIlluminantIntensity(
-        global_illuminant.intensity,
+fn write_skybox_texture_uniforms(program: &ShaderProgram, unit: Option<TextureUnit>) {
+    program.write_uniform(Uniform::UseSkyboxTexture(unit.is_some()));
+    if let Some(unit) = unit {
+        program.write_uniform(Uniform::SkyboxTexture(unit));
+    }
+}
+
+#[inline]
+fn write_shadow_uniforms(program: &ShaderProgram, shadow_maps: &ShadowMaps, unit: TextureUnit) {
+    let matrices: [_; NUM_SHADOW_CASCADES] = shadow_maps.light_space_matrices.get();
+    program.write_uniform(Uniform::LightSpaceMatrices(&matrices));
+    program.write_uniform(Uniform::ShadowCascadeDistances(
+        &shadow_maps.settings.cascade_distances,
     ));
+    program.write_uniform(Uniform::ShadowMaps(unit));
 }
 
 #[inline]
-fn write_point_light_uniforms(program: &ShaderProgram, point_lights: &[PointLight]) {
-    let mut light_positions: Vec<Vector3<f32>> = vec![];
-    let mut light_colours: Vec<Vector3<f32>> = vec![];
-    let mut light_intensities: Vec<f32> = vec![];
-
-    let count = cmp::min(point_lights.len(), MAX_POINT_LIGHTS);
-    for light in point_lights.iter().take(count) {
-        light_positions.push(light.position.coords);
-        light_colours.push(light.colour);
-        light_intensities.push(light.intensity);
+fn write_point_light_uniforms(program: &ShaderProgram, units: &PointLightBufferUnits) {
+    program.write_uniform(Uniform::PointLightsData(units.lights));
+    program.write_uniform(Uniform::LightClusters(units.clusters));
+    program.write_uniform(Uniform::LightIndices(units.indices));
+
+    let mut viewport = [0; 4];
+    unsafe {
+        gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
     }
-
-    program.write_uniform(Uniform::PointLightsPositions(&light_positions));
-    program.write_uniform(Uniform::PointLightsColours(&light_colours));
-    program.write_uniform(Uniform::PointLightsIntensities(&light_intensities));
+    let viewport_size = Vector2::new(viewport[2] as f32, viewport[3] as f32);
+    program.write_uniform(Uniform::ViewportSize(&viewport_size));
 }
 
-#[inline]
-fn write_texture_uniforms(program: &ShaderProgram, texture_binding: &TextureBinding) {
-    program.write_uniform(Uniform::ModelTexture(texture_binding));
+/// Point the shadow depth program at a material's albedo texture if it's a cutout material, so
+/// that light shines through the gaps in it
+fn write_shadow_material_uniforms(
+    program: &ShaderProgram,
+    texture_units: &TextureUnits,
+    material: &Material,
+) {
+    let cutout = material.alpha_mode == AlphaMode::Cutout;
+    program.write_uniform(Uniform::AlphaCutout(cutout));
+    if cutout {
+        let albedo = texture_units.bind(material.albedo.handle(), BindingScope::Draw);
+        program.write_uniform(Uniform::AlbedoTexture(albedo));
+    }
 }
 
-#[inline]
-fn write_fog_uniforms(program: &ShaderProgram, fog_parameters: &FogParameters) {
-    program.write_uniform(Uniform::FogNearDistance(fog_parameters.start_threshold));
-    program.write_uniform(Uniform::FogFarDistance(fog_parameters.end_threshold));
+/// Bind a material's textures and point the program at them
+///
+/// Missing textures are pointed at the albedo texture instead, as samplers can't be left pointing
+/// at a unit that holds a texture of a different type.
+fn write_material_uniforms(
+    program: &ShaderProgram,
+    texture_units: &TextureUnits,
+    material: &Material,
+) {
+    let albedo = texture_units.bind(material.albedo.handle(), BindingScope::Draw);
+    let normal = material
+        .normal
+        .as_ref()
+        .map(|texture| texture_units.bind(texture.handle(), BindingScope::Draw));
+    let emissive = material
+        .emissive
+        .as_ref()
+        .map(|texture| texture_units.bind(texture.handle(), BindingScope::Draw));
+
+    program.write_uniform(Uniform::AlbedoTexture(albedo));
+    program.write_uniform(Uniform::NormalTexture(normal.unwrap_or(albedo)));
+    program.write_uniform(Uniform::EmissiveTexture(emissive.unwrap_or(albedo)));
+    program.write_uniform(Uniform::UseNormalTexture(normal.is_some()));
+    program.write_uniform(Uniform::UseEmissiveTexture(emissive.is_some()));
+    program.write_uniform(Uniform::AlphaCutout(
+        material.alpha_mode == AlphaMode::Cutout,
+    ));
 }
@@ -10,16 +10,45 @@ macro_rules! shader {
     }};
 }
 
+macro_rules! shader_include {
+    ($name: expr) => {{
+        (
+            $name,
+            include_bytes!(concat!("../../shaders/", $name)) as &[u8],
+        )
+    }};
+}
+
 pub(crate) mod shaders {
     use super::*;
 
-    pub(crate) static SCENE_OBJECTS_VERT_SHADERS: ShaderSrc =
+    /// The files that shaders can `#include`
+    static INCLUDES: [(&str, &[u8]); 3] = [
+        shader_include!("types.glsl"),
+        shader_include!("sky.glsl"),
+        shader_include!("uniform_blocks.glsl"),
+    ];
+
+    /// Look up the contents of a built-in file that shaders can `#include`
+    pub(crate) fn find_include(name: &str) -> Option<&'static [u8]> {
+        INCLUDES
+            .iter()
+            .find(|(include_name, _)| *include_name == name)
+            .map(|(_, contents)| *contents)
+    }
+
+    pub(crate) static SCENE_OBJECTS_VERT_SHADERS: ShaderSrc<'static> =
         shader!("scene_objects.vert", ShaderType::VertexShader);
-    pub(crate) static SCENE_OBJECTS_FRAG_SHADER: ShaderSrc =
+    pub(crate) static SCENE_OBJECTS_FRAG_SHADER: ShaderSrc<'static> =
         shader!("scene_objects.frag", ShaderType::FragmentShader);
 
-    pub(crate) static SKYBOX_VERT_SHADER: ShaderSrc =
+    pub(crate) static SHADOW_DEPTH_VERT_SHADER: ShaderSrc<'static> =
+        shader!("shadow_depth.vert", ShaderType::VertexShader);
+    pub(crate) static SHADOW_DEPTH_FRAG_SHADER: ShaderSrc<'static> =
+        shader!("shadow_depth.frag", ShaderType::FragmentShader);
+
+    pub(crate) static SKYBOX_VERT_SHADER: ShaderSrc<'static> =
         shader!("skybox.vert", ShaderType::VertexShader);
-    pub(crate) static SKYBOX_FRAG_SHADER: ShaderSrc =
+    pub(crate) static SKYBOX_FRAG_SHADER: ShaderSrc<'static> =
         shader!("skybox.frag", ShaderType::FragmentShader);
 }
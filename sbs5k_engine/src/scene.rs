@@ -1,6 +1,26 @@
 use nalgebra::{Matrix4, Point3, Translation3, Vector3};
 
+use crate::camera::CameraPosition;
+use crate::fog::FogParameters;
+use crate::lighting::SceneLighting;
 use crate::model::Model;
+use crate::sky::SkyParameters;
+
+/// Everything about a scene other than the objects in it, which stays the same for a whole frame
+#[derive(Clone, Copy)]
+pub struct Scene<'a> {
+    /// The global light and any point lights
+    pub lighting: &'a SceneLighting,
+
+    /// Where the scene is being viewed from
+    pub camera: &'a CameraPosition,
+
+    /// How distant objects fade into the background
+    pub fog: &'a FogParameters,
+
+    /// The colours of the sky, and where the sun is
+    pub sky: &'a SkyParameters,
+}
 
 /// An object present in a `Scene`
 #[derive(Debug)]
@@ -1,4 +1,4 @@
-use std::convert::TryInto;
+use std::cell::Cell;
 use std::ffi;
 use std::ptr;
 use std::str;
@@ -6,7 +6,10 @@ use std::str;
 use gl::types::*;
 
 use crate::binding::Bindable;
-use crate::uniforms::Uniform;
+use crate::preprocessor;
+use crate::resources;
+use crate::uniform_blocks::UniformBlock;
+use crate::uniforms::{Uniform, UniformName};
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub(crate) enum ShaderType {
@@ -18,24 +21,68 @@ pub(crate) enum ShaderType {
 pub(crate) struct Shader {
     id: GLuint,
     shader_type: ShaderType,
-    debug_name: &'static str,
+    debug_name: String,
 }
 
 /// Wraps a linked shader program, consisting of both a vertex shader and a
 /// fragment shader.
 pub(crate) struct ShaderProgram {
     id: GLuint,
+    debug_name: String,
+
+    /// The location of each uniform, indexed by `UniformName`, or -1 if the program doesn't have it
+    uniform_locations: [GLint; UniformName::ALL.len()],
+
+    /// Whether a warning has been shown about each uniform that the program doesn't have
+    warned_about_uniforms: [Cell<bool>; UniformName::ALL.len()],
 }
 
-pub(crate) struct ShaderSrc {
-    pub src: &'static [u8],
-    pub debug_name: &'static str,
+pub(crate) struct ShaderSrc<'a> {
+    pub src: &'a [u8],
+    pub debug_name: &'a str,
     pub shader_type: ShaderType,
 }
 
+/// Somewhere to find shader source code that replaces the shaders built into the engine
+pub trait ShaderSourceProvider {
+    /// Look up replacement source code for the shader with the given file name, such as
+    /// `"skybox.frag"`, along with a description of where it came from to show in any errors.
+    /// `None` means that the built-in shader should be used.
+    fn shader_source(&self, name: &str) -> Option<(Vec<u8>, String)>;
+}
+
+/// Provides no replacements, so that every shader is the built-in one
+pub(crate) struct BuiltInShaders;
+
+impl ShaderSourceProvider for BuiltInShaders {
+    fn shader_source(&self, _name: &str) -> Option<(Vec<u8>, String)> {
+        None
+    }
+}
+
 impl Shader {
-    pub fn new(src: &ShaderSrc) -> Self {
-        let code = ffi::CString::new(src.src).unwrap();
+    /// Preprocess and compile a shader, returning the compiler's error log if it fails
+    ///
+    /// Any `#include`d files are looked up in `sources`, falling back to the engine's built-in
+    /// ones, and `defines` are defined at the top of the shader.
+    pub fn new(
+        src: &ShaderSrc,
+        defines: &[(&str, String)],
+        sources: &dyn ShaderSourceProvider,
+    ) -> Result<Self, String> {
+        let text = str::from_utf8(src.src)
+            .map_err(|_| format!("Shader {} isn't valid UTF-8", src.debug_name))?;
+        let read_include = |name: &str| {
+            let (contents, origin) = sources.shader_source(name).or_else(|| {
+                resources::shaders::find_include(name)
+                    .map(|contents| (contents.to_vec(), name.to_owned()))
+            })?;
+            Some((String::from_utf8_lossy(&contents).into_owned(), origin))
+        };
+        let preprocessed = preprocessor::preprocess(src.debug_name, text, defines, &read_include)?;
+
+        let code = ffi::CString::new(preprocessed.code.as_bytes())
+            .map_err(|_| format!("Shader {} contains a null byte", src.debug_name))?;
         let gl_shader_type = match src.shader_type {
             ShaderType::VertexShader => gl::VERTEX_SHADER,
             ShaderType::FragmentShader => gl::FRAGMENT_SHADER,
@@ -47,15 +94,41 @@ impl Shader {
             id
         };
 
+        // Take ownership straight away so that the shader is cleaned up if it failed to compile
+        let shader = Shader {
+            id,
+            shader_type: src.shader_type,
+            debug_name: src.debug_name.to_owned(),
+        };
+
         if !compiled_successfully(id) {
-            dump_shader_compile_error(id, src.debug_name);
-            panic!("Failed to compile shader");
+            return Err(format!(
+                "Failed to compile shader {}, error:\n{}",
+                src.debug_name,
+                preprocessed.annotate_log(&read_shader_error_log(id))
+            ));
         }
 
-        Shader {
-            id,
-            shader_type: src.shader_type,
-            debug_name: src.debug_name,
+        Ok(shader)
+    }
+
+    /// Compile a built-in shader, or the replacement for it from `sources` if there is one
+    pub fn load(
+        built_in: &ShaderSrc,
+        defines: &[(&str, String)],
+        sources: &dyn ShaderSourceProvider,
+    ) -> Result<Self, String> {
+        match sources.shader_source(built_in.debug_name) {
+            Some((src, origin)) => Shader::new(
+                &ShaderSrc {
+                    src: &src,
+                    debug_name: &origin,
+                    shader_type: built_in.shader_type,
+                },
+                defines,
+                sources,
+            ),
+            None => Shader::new(built_in, defines, sources),
         }
     }
 }
@@ -69,7 +142,9 @@ impl Drop for Shader {
 }
 
 impl ShaderProgram {
-    pub fn new(vertex_shader: Shader, fragment_shader: Shader) -> Self {
+    /// Link a vertex shader and a fragment shader together, returning the linker's error log if
+    /// it fails
+    pub fn new(vertex_shader: Shader, fragment_shader: Shader) -> Result<Self, String> {
         if vertex_shader.shader_type != ShaderType::VertexShader {
             panic!("Bad vertex shader type: {:?}", vertex_shader.shader_type);
         }
@@ -88,55 +163,110 @@ impl ShaderProgram {
             id
         };
 
+        let mut program = ShaderProgram {
+            id,
+            debug_name: format!(
+                "{} + {}",
+                vertex_shader.debug_name, fragment_shader.debug_name
+            ),
+            uniform_locations: [-1; UniformName::ALL.len()],
+            warned_about_uniforms: Default::default(),
+        };
+
         if !linked_successfully(id) {
-            dump_shader_link_error(id, vertex_shader.debug_name, fragment_shader.debug_name);
-            panic!("Failed to link shader program");
+            return Err(format!(
+                "Failed to link vertex shader {} with fragment shader {}, error:\n{}",
+                vertex_shader.debug_name,
+                fragment_shader.debug_name,
+                read_program_error_log(id)
+            ));
         }
 
-        ShaderProgram { id }
+        // Look up everything by name now, so that drawing never has to
+        for name in UniformName::ALL {
+            program.uniform_locations[name as usize] = program.lookup_uniform_location(name);
+        }
+        program.bind_uniform_blocks();
+
+        Ok(program)
     }
 
-    #[inline]
-    fn lookup_uniform_location(&self, name: &str) -> GLint {
-        unsafe {
-            let name_as_cstring = ffi::CString::new(name).unwrap();
-            gl::GetUniformLocation(self.id, name_as_cstring.as_ptr())
+    fn lookup_uniform_location(&self, name: UniformName) -> GLint {
+        let name_as_cstring = ffi::CString::new(name.name_in_shader()).unwrap();
+        unsafe { gl::GetUniformLocation(self.id, name_as_cstring.as_ptr()) }
+    }
+
+    /// Point each uniform block that the program uses at the binding point its buffer is bound to
+    fn bind_uniform_blocks(&self) {
+        for block in UniformBlock::ALL {
+            let name_as_cstring = ffi::CString::new(block.name_in_shader()).unwrap();
+            unsafe {
+                let index = gl::GetUniformBlockIndex(self.id, name_as_cstring.as_ptr());
+                if index != gl::INVALID_INDEX {
+                    gl::UniformBlockBinding(self.id, index, block.binding());
+                }
+            }
+        }
+    }
+
+    /// Warn, only the first time, that a uniform being written isn't in the program
+    ///
+    /// This usually means that the shader doesn't declare the uniform, or that it does but never
+    /// uses it, so the compiler has optimised it out.
+    #[cold]
+    fn warn_about_missing_uniform(&self, name: UniformName) {
+        if !self.warned_about_uniforms[name as usize].replace(true) {
+            eprintln!(
+                "Warning: shader program {} has no active uniform named {}, so it won't be set",
+                self.debug_name,
+                name.name_in_shader()
+            );
         }
     }
 
     #[inline]
     pub(crate) fn write_uniform(&self, uniform: Uniform) {
-        let name = uniform.get_name_in_shader();
-        let position = self.lookup_uniform_location(name);
+        let name = uniform.name();
+        let position = self.uniform_locations[name as usize];
+        if position == -1 {
+            self.warn_about_missing_uniform(name);
+            return;
+        }
+
         unsafe {
-            // TODO: Make another constant function in uniform.rs that extracts the "uniform type"
             match uniform {
-                Uniform::ModelMatrix(m) | Uniform::ViewMatrix(m) | Uniform::ProjectionMatrix(m) => {
+                Uniform::ModelMatrix(m) | Uniform::LightSpaceMatrix(m) => {
                     gl::UniformMatrix4fv(position, 1, gl::FALSE, m.as_ptr());
                 }
-                Uniform::PointLightsPositions(va) | Uniform::PointLightsColours(va) => {
-                    if !va.is_empty() {
-                        gl::Uniform3fv(position, va.len().try_into().unwrap(), va[0].as_ptr());
-                    }
+                Uniform::LightSpaceMatrices(ms) => {
+                    gl::UniformMatrix4fv(
+                        position,
+                        ms.len() as GLsizei,
+                        gl::FALSE,
+                        ms.as_ptr() as *const GLfloat,
+                    );
                 }
-                Uniform::PointLightsIntensities(v) => {
-                    if !v.is_empty() {
-                        gl::Uniform1fv(position, v.len().try_into().unwrap(), v.as_ptr())
-                    };
+                Uniform::ShadowCascadeDistances(vs) => {
+                    gl::Uniform1fv(position, vs.len() as GLsizei, vs.as_ptr());
                 }
-                Uniform::CameraPosition(v)
-                | Uniform::GlobalIlluminantDirection(v)
-                | Uniform::GlobalIlluminantColour(v) => {
-                    gl::Uniform3f(position, v.x, v.y, v.z);
+                Uniform::ShadowMaps(unit)
+                | Uniform::PointLightsData(unit)
+                | Uniform::LightClusters(unit)
+                | Uniform::LightIndices(unit)
+                | Uniform::SkyboxTexture(unit)
+                | Uniform::AlbedoTexture(unit)
+                | Uniform::NormalTexture(unit)
+                | Uniform::EmissiveTexture(unit) => {
+                    gl::Uniform1i(position, unit.index());
                 }
-                Uniform::GlobalIlluminantIntensity(v)
-                | Uniform::FogNearDistance(v)
-                | Uniform::FogFarDistance(v) => {
-                    gl::Uniform1f(position, v);
+                Uniform::UseSkyboxTexture(enabled)
+                | Uniform::UseNormalTexture(enabled)
+                | Uniform::UseEmissiveTexture(enabled)
+                | Uniform::AlphaCutout(enabled) => {
+                    gl::Uniform1i(position, enabled as GLint);
                 }
-                // TODO: Work out what's going on here - it seems broken
-                Uniform::ModelTexture(_texture_binding) => {
-                    gl::Uniform1i(position, 0);
+                Uniform::ViewportSize(v) => {
+                    gl::Uniform2f(position, v.x, v.y);
                 }
             }
         }
@@ -184,46 +314,52 @@ fn linked_successfully(shader_program_id: GLuint) -> bool {
     success == gl::TRUE as GLint
 }
 
-fn dump_shader_compile_error(shader: GLuint, debug_name: &str) {
-    let error_log = read_error_log(shader);
-    println!(
-        "Failed to compile shader program {}, error:\n{}",
-        debug_name, error_log
-    );
-}
-
-fn dump_shader_link_error(
-    shader_program_id: GLuint,
-    vertex_shader_debug_name: &str,
-    fragment_shader_debug_name: &str,
-) {
-    let error_log = read_error_log(shader_program_id);
-    println!(
-        "Failed to link vertex shader {} with fragment shader {}, error:\n{}",
-        vertex_shader_debug_name, fragment_shader_debug_name, error_log
-    );
-}
-
-fn read_error_log(shader_program: GLuint) -> String {
+fn read_shader_error_log(shader_id: GLuint) -> String {
     // Read error log length
     let mut len: GLint = 0;
     unsafe {
-        gl::GetShaderiv(shader_program, gl::INFO_LOG_LENGTH, &mut len);
+        gl::GetShaderiv(shader_id, gl::INFO_LOG_LENGTH, &mut len);
     }
 
     // Create a buffer of that length and fill it with null characters
-    let mut buffer: Vec<u8> = Vec::with_capacity(len as usize + 1);
-    buffer.extend([b'\0'].iter().cycle().take(len as usize));
+    let mut buffer: Vec<u8> = vec![b'\0'; len.max(0) as usize];
 
     // Read the info log from OpenGL
     unsafe {
         gl::GetShaderInfoLog(
-            shader_program,
+            shader_id,
             len,
             ptr::null_mut(),
             buffer.as_mut_ptr() as *mut GLchar,
         );
     }
 
+    info_log_to_string(buffer)
+}
+
+fn read_program_error_log(shader_program_id: GLuint) -> String {
+    let mut len: GLint = 0;
+    unsafe {
+        gl::GetProgramiv(shader_program_id, gl::INFO_LOG_LENGTH, &mut len);
+    }
+
+    let mut buffer: Vec<u8> = vec![b'\0'; len.max(0) as usize];
+    unsafe {
+        gl::GetProgramInfoLog(
+            shader_program_id,
+            len,
+            ptr::null_mut(),
+            buffer.as_mut_ptr() as *mut GLchar,
+        );
+    }
+
+    info_log_to_string(buffer)
+}
+
+/// Convert a null-terminated info log read from OpenGL into a string
+fn info_log_to_string(mut buffer: Vec<u8>) -> String {
+    while buffer.last() == Some(&b'\0') {
+        buffer.pop();
+    }
     String::from_utf8(buffer).expect("Invalid UTF-8 encoding in OpenGL info log")
 }
@@ -0,0 +1,371 @@
+use std::cell::Cell;
+use std::ptr;
+
+use gl::types::*;
+use nalgebra::{Matrix4, Point3, Vector3, Vector4};
+
+use crate::camera::{CameraPosition, ASPECT_RATIO, FOV_Y, Z_NEAR};
+use crate::texture_units::TextureHandle;
+
+/// The number of shadow maps that the view frustum is split between
+///
+/// This is defined as `NUM_SHADOW_CASCADES` in the shaders.
+pub const NUM_SHADOW_CASCADES: usize = 3;
+
+/// How far beyond each cascade's slice of the view frustum to look for objects that could cast
+/// shadows into it, towards the light
+const SHADOW_CASTER_MARGIN: f32 = 128.0;
+
+/// Controls the quality and reach of the shadows cast by the global light
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShadowSettings {
+    /// The distance from the camera at which each cascade ends, nearest first
+    ///
+    /// Each cascade covers the part of the view between the end of the previous one and its own
+    /// distance, so nearby shadows are drawn at a higher resolution than distant ones. Nothing
+    /// beyond the last distance is shadowed.
+    pub cascade_distances: [f32; NUM_SHADOW_CASCADES],
+
+    /// The width and height in texels of each cascade's shadow map
+    pub map_size: u32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        ShadowSettings {
+            cascade_distances: [20.0, 60.0, 200.0],
+            map_size: 2048,
+        }
+    }
+}
+
+/// The depth textures that the scene is rendered into from the global light's point of view
+#[derive(Debug)]
+pub(crate) struct ShadowMaps {
+    framebuffer_id: GLuint,
+    texture_id: GLuint,
+    pub(crate) settings: ShadowSettings,
+
+    /// The transformation from world space into the space of each cascade's shadow map, as used
+    /// when they were last drawn
+    pub(crate) light_space_matrices: Cell<[Matrix4<f32>; NUM_SHADOW_CASCADES]>,
+}
+
+impl ShadowMaps {
+    pub(crate) fn new(settings: ShadowSettings) -> Self {
+        let size = settings.map_size as GLsizei;
+        let (framebuffer_id, texture_id) = unsafe {
+            let mut texture_id: GLuint = 0;
+            gl::GenTextures(1, &mut texture_id);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, texture_id);
+            gl::TexImage3D(
+                gl::TEXTURE_2D_ARRAY,
+                0,
+                gl::DEPTH_COMPONENT32F as i32,
+                size,
+                size,
+                NUM_SHADOW_CASCADES as GLsizei,
+                0,
+                gl::DEPTH_COMPONENT,
+                gl::FLOAT,
+                ptr::null(),
+            );
+
+            // Linear filtering with depth comparison gives a little smoothing for free
+            gl::TexParameteri(
+                gl::TEXTURE_2D_ARRAY,
+                gl::TEXTURE_MIN_FILTER,
+                gl::LINEAR as i32,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D_ARRAY,
+                gl::TEXTURE_MAG_FILTER,
+                gl::LINEAR as i32,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D_ARRAY,
+                gl::TEXTURE_COMPARE_MODE,
+                gl::COMPARE_REF_TO_TEXTURE as i32,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D_ARRAY,
+                gl::TEXTURE_COMPARE_FUNC,
+                gl::LEQUAL as i32,
+            );
+
+            // Anything outside a shadow map is treated as lit
+            gl::TexParameteri(
+                gl::TEXTURE_2D_ARRAY,
+                gl::TEXTURE_WRAP_S,
+                gl::CLAMP_TO_BORDER as i32,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D_ARRAY,
+                gl::TEXTURE_WRAP_T,
+                gl::CLAMP_TO_BORDER as i32,
+            );
+            let border = [1.0f32; 4];
+            gl::TexParameterfv(
+                gl::TEXTURE_2D_ARRAY,
+                gl::TEXTURE_BORDER_COLOR,
+                border.as_ptr(),
+            );
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+
+            let mut framebuffer_id: GLuint = 0;
+            gl::GenFramebuffers(1, &mut framebuffer_id);
+            gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer_id);
+            gl::FramebufferTextureLayer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, texture_id, 0, 0);
+            gl::DrawBuffer(gl::NONE);
+            gl::ReadBuffer(gl::NONE);
+            if gl::CheckFramebufferStatus(gl::FRAMEBUFFER) != gl::FRAMEBUFFER_COMPLETE {
+                panic!("Failed to create the shadow map framebuffer");
+            }
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+
+            (framebuffer_id, texture_id)
+        };
+
+        ShadowMaps {
+            framebuffer_id,
+            texture_id,
+            settings,
+            light_space_matrices: Cell::new([Matrix4::identity(); NUM_SHADOW_CASCADES]),
+        }
+    }
+
+    /// Direct drawing into the shadow map for one cascade
+    ///
+    /// This must be followed by a call to `finish_drawing` once all the cascades have been drawn.
+    pub(crate) fn start_drawing_cascade(&self, cascade: usize) {
+        let size = self.settings.map_size as GLsizei;
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer_id);
+            gl::FramebufferTextureLayer(
+                gl::FRAMEBUFFER,
+                gl::DEPTH_ATTACHMENT,
+                self.texture_id,
+                0,
+                cascade as GLint,
+            );
+            gl::Viewport(0, 0, size, size);
+        }
+    }
+
+    /// Go back to drawing on the screen, whose viewport was `viewport` before the shadow maps were
+    /// drawn
+    pub(crate) fn finish_drawing(&self, viewport: [GLint; 4]) {
+        unsafe {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+            gl::Viewport(viewport[0], viewport[1], viewport[2], viewport[3]);
+        }
+    }
+
+    /// Empty every cascade, so that nothing is in shadow
+    pub(crate) fn clear(&self) {
+        let mut viewport = [0; 4];
+        unsafe {
+            gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        }
+        for cascade in 0..NUM_SHADOW_CASCADES {
+            self.start_drawing_cascade(cascade);
+            unsafe {
+                gl::Clear(gl::DEPTH_BUFFER_BIT);
+            }
+        }
+        self.finish_drawing(viewport);
+    }
+
+    pub(crate) fn handle(&self) -> TextureHandle {
+        TextureHandle {
+            target: gl::TEXTURE_2D_ARRAY,
+            id: self.texture_id,
+        }
+    }
+}
+
+impl Drop for ShadowMaps {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteFramebuffers(1, &self.framebuffer_id);
+            gl::DeleteTextures(1, &self.texture_id);
+        }
+    }
+}
+
+/// Work out the transformation into each cascade's shadow map, for a light shining from
+/// `light_direction` onto the view from `camera`
+pub(crate) fn cascade_matrices(
+    camera: &CameraPosition,
+    light_direction: &Vector3<f32>,
+    settings: &ShadowSettings,
+) -> [Matrix4<f32>; NUM_SHADOW_CASCADES] {
+    let mut near = Z_NEAR;
+    settings.cascade_distances.map(|far| {
+        let matrix = cascade_matrix(camera, light_direction, near, far, settings.map_size);
+        near = far;
+        matrix
+    })
+}
+
+/// Find the light-space transformation that fits the slice of the view frustum between `near` and
+/// `far` into a shadow map
+///
+/// The shadow map is fitted around a sphere enclosing the slice rather than the slice itself, so
+/// that its size doesn't change as the camera turns. It is also only ever moved in whole texels,
+/// so that the edges of shadows don't shimmer as the camera moves.
+fn cascade_matrix(
+    camera: &CameraPosition,
+    light_direction: &Vector3<f32>,
+    near: f32,
+    far: f32,
+    map_size: u32,
+) -> Matrix4<f32> {
+    let corners = frustum_slice_corners(camera, near, far);
+    let centre = Point3::from(
+        corners
+            .iter()
+            .fold(Vector3::zeros(), |sum, corner| sum + corner.coords)
+            / corners.len() as f32,
+    );
+    let radius = corners
+        .iter()
+        .map(|corner| (corner - centre).norm())
+        .fold(0.0, f32::max)
+        .ceil();
+
+    let direction = light_direction.normalize();
+    let up = if direction.y.abs() > 0.99 {
+        Vector3::z()
+    } else {
+        Vector3::y()
+    };
+    let eye = centre + direction * (radius + SHADOW_CASTER_MARGIN);
+    let view = Matrix4::look_at_rh(&eye, &centre, &up);
+    let mut projection = Matrix4::new_orthographic(
+        -radius,
+        radius,
+        -radius,
+        radius,
+        0.0,
+        2.0 * radius + SHADOW_CASTER_MARGIN,
+    );
+
+    // Snap the projection to the texel grid
+    let origin = (projection * view) * Vector4::new(0.0, 0.0, 0.0, 1.0);
+    let texels_per_unit = map_size as f32 / 2.0;
+    let offset_x =
+        ((origin.x * texels_per_unit).round() - origin.x * texels_per_unit) / texels_per_unit;
+    let offset_y =
+        ((origin.y * texels_per_unit).round() - origin.y * texels_per_unit) / texels_per_unit;
+    projection[(0, 3)] += offset_x;
+    projection[(1, 3)] += offset_y;
+
+    projection * view
+}
+
+/// Find the corners of the slice of the camera's view frustum between `near` and `far`, in world
+/// space
+fn frustum_slice_corners(camera: &CameraPosition, near: f32, far: f32) -> [Point3<f32>; 8] {
+    let tan_half_y = (FOV_Y / 2.0).tan();
+    let tan_half_x = tan_half_y * ASPECT_RATIO;
+    let view_to_world = camera
+        .view_matrix()
+        .try_inverse()
+        .expect("The camera's view matrix should always be invertible");
+
+    let mut corners = [Point3::origin(); 8];
+    for (i, corner) in corners.iter_mut().enumerate() {
+        let depth = if i & 4 == 0 { near } else { far };
+        let sign = |bit: usize| if i & bit == 0 { -1.0 } else { 1.0 };
+        let view_space = Point3::new(
+            sign(1) * depth * tan_half_x,
+            sign(2) * depth * tan_half_y,
+            -depth,
+        );
+        *corner = view_to_world.transform_point(&view_space);
+    }
+    corners
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn camera() -> CameraPosition {
+        CameraPosition {
+            position: Point3::new(10.0, 70.0, -5.0),
+            yaw: 0.7,
+            pitch: -0.3,
+        }
+    }
+
+    fn is_inside_shadow_map(matrix: &Matrix4<f32>, point: &Point3<f32>) -> bool {
+        let projected = matrix.transform_point(point);
+        projected
+            .iter()
+            .all(|coordinate| (-1.0..=1.0).contains(coordinate))
+    }
+
+    #[rstest]
+    #[case(Vector3::new(1.0, 1.2, 1.5))]
+    #[case(Vector3::new(0.0, 1.0, 0.0))]
+    #[case(Vector3::new(-1.0, 0.1, 0.0))]
+    fn cascades_cover_their_slices(#[case] light_direction: Vector3<f32>) {
+        let camera = camera();
+        let settings = ShadowSettings::default();
+        let matrices = cascade_matrices(&camera, &light_direction.normalize(), &settings);
+
+        let mut near = Z_NEAR;
+        for (matrix, &far) in matrices.iter().zip(settings.cascade_distances.iter()) {
+            for corner in frustum_slice_corners(&camera, near, far).iter() {
+                assert!(is_inside_shadow_map(matrix, corner));
+            }
+            near = far;
+        }
+    }
+
+    #[rstest]
+    fn shadow_casters_between_the_light_and_the_view_are_included() {
+        let camera = camera();
+        let light_direction = Vector3::new(1.0, 1.2, 1.5).normalize();
+        let matrices = cascade_matrices(&camera, &light_direction, &ShadowSettings::default());
+
+        let caster = camera.position + light_direction * (SHADOW_CASTER_MARGIN - 1.0);
+        assert!(is_inside_shadow_map(&matrices[0], &caster));
+    }
+
+    #[rstest]
+    fn points_towards_the_light_are_nearer_in_depth() {
+        let camera = camera();
+        let light_direction = Vector3::new(1.0, 1.2, 1.5).normalize();
+        let matrices = cascade_matrices(&camera, &light_direction, &ShadowSettings::default());
+
+        let point = camera.position + Vector3::new(0.0, 0.0, -5.0);
+        let above = point + light_direction * 10.0;
+        for matrix in matrices.iter() {
+            assert!(matrix.transform_point(&above).z < matrix.transform_point(&point).z);
+        }
+    }
+
+    #[rstest]
+    fn moving_the_camera_slightly_moves_shadow_maps_by_whole_texels() {
+        let light_direction = Vector3::new(1.0, 1.2, 1.5).normalize();
+        let settings = ShadowSettings::default();
+        let first = cascade_matrices(&camera(), &light_direction, &settings);
+
+        let mut moved = camera();
+        moved.position += Vector3::new(0.37, 0.0, 0.11);
+        let second = cascade_matrices(&moved, &light_direction, &settings);
+
+        // A fixed point in the world should land on the same fraction of a texel in both
+        let texels_per_unit = settings.map_size as f32 / 2.0;
+        let point = Point3::new(3.0, 60.0, 4.0);
+        for (a, b) in first.iter().zip(second.iter()) {
+            let shift = (b.transform_point(&point) - a.transform_point(&point)) * texels_per_unit;
+            assert!((shift.x - shift.x.round()).abs() < 0.05);
+            assert!((shift.y - shift.y.round()).abs() < 0.05);
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use nalgebra::{Vector2, Vector3};
+
+/// The appearance of the sky
+///
+/// Distant terrain fades into the same colours, so that it blends into the horizon.
+#[derive(Debug)]
+pub struct SkyParameters {
+    /// The colour of the sky directly overhead
+    pub zenith_colour: Vector3<f32>,
+
+    /// The colour of the sky at the horizon
+    pub horizon_colour: Vector3<f32>,
+
+    /// The unit vector pointing towards the sun. The moon is drawn directly opposite it
+    pub sun_direction: Vector3<f32>,
+
+    /// The colour of the sun's disc and the glow around it
+    pub sun_colour: Vector3<f32>,
+
+    /// How visible the stars are, from 0 (not at all) to 1 (fully)
+    pub star_visibility: f32,
+
+    /// The colour of the clouds
+    pub cloud_colour: Vector3<f32>,
+
+    /// How far the clouds have drifted along the x and z axes, in blocks
+    pub cloud_offset: Vector2<f32>,
+}
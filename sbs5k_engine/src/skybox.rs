@@ -1,4 +1,5 @@
 use crate::model::{VertexData, VertexDataLayoutInfo};
+use crate::texture::CubemapTexture;
 
 #[rustfmt::skip]
 const SKYBOX_CUBE_VERTICES: [f32; 72] = [
@@ -70,9 +71,13 @@ const SKYBOX_CUBE_INDICES: [u32; 36] = [
 ];
 
 /// A skybox that can be rendered in a scene
+///
+/// By default the sky is drawn as a gradient between the colours in the `SkyParameters`, but a
+/// cube map texture can be supplied to draw in its place.
 #[derive(Debug)]
 pub struct Skybox {
     pub model: VertexData,
+    pub(crate) texture: Option<CubemapTexture>,
 }
 
 impl Skybox {
@@ -81,10 +86,22 @@ impl Skybox {
             position_offset: 0,
             normal_offset: None,
             texture_offset: None,
+            light_offset: None,
         };
         let model = VertexData::new(&SKYBOX_CUBE_VERTICES, &SKYBOX_CUBE_INDICES, layout_info);
 
-        Skybox { model }
+        Skybox {
+            model,
+            texture: None,
+        }
+    }
+
+    /// Create a skybox that draws `texture` instead of the usual gradient
+    pub fn with_texture(texture: CubemapTexture) -> Self {
+        Skybox {
+            texture: Some(texture),
+            ..Skybox::new()
+        }
     }
 }
 
@@ -1,8 +1,15 @@
+use std::cell::Cell;
 use std::convert::TryInto;
+use std::f32::consts::PI;
+use std::mem;
 use std::os;
+use std::ptr;
 
 use gl::types::*;
-use image::ImageFormat;
+use image::{ImageFormat, RgbaImage};
+use nalgebra::Vector3;
+
+use crate::texture_units::TextureHandle;
 
 /// Holds a texture that can be passed to a shader program
 #[derive(Debug)]
@@ -10,6 +17,25 @@ pub struct Texture {
     pub(crate) texture_id: GLuint,
 }
 
+/// `GL_TEXTURE_MAX_ANISOTROPY`, from OpenGL 4.6 and `EXT_texture_filter_anisotropic`
+///
+/// The `gl` crate's bindings stop at OpenGL 4.5, so this has to be defined by hand.
+const TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FE;
+
+/// `GL_MAX_TEXTURE_MAX_ANISOTROPY`, the largest value that `TEXTURE_MAX_ANISOTROPY` can be set to
+const MAX_TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FF;
+
+/// Holds a stack of equally-sized images that can be passed to a shader program as one texture
+///
+/// Shaders sample these using a `sampler2DArray`, with the layer to read from given as the third
+/// texture coordinate. Unlike an atlas, neighbouring layers can never bleed into each other, so each
+/// layer can be filtered and mipmapped independently.
+#[derive(Debug)]
+pub struct TextureArray {
+    pub(crate) texture_id: GLuint,
+    layers: Cell<u32>,
+}
+
 /// A coordinate into a texture file
 ///
 /// For any valid coordinate, `u` and `v` must both fall in the range [0, 1].
@@ -20,31 +46,54 @@ pub struct TextureCoordinate {
 }
 
 /// The file format of the image to be loaded
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub enum ImageFileFormat {
     Png,
-    #[allow(dead_code)]
     Guess,
 }
 
-/// Represents the binding of a `Texture` to a particular texture unit on the GPU
+/// Holds a cube map texture, made up of six square faces surrounding the viewer
 ///
-/// The texture is bound to the texture unit at the time of creation, and will be automatically
-/// unbound when this object is dropped.
+/// Shaders sample these using a `samplerCube` and a direction vector.
 #[derive(Debug)]
-pub(crate) struct TextureBinding {
-    pub(crate) texture_unit: GLenum,
+pub struct CubemapTexture {
+    pub(crate) texture_id: GLuint,
+}
+
+/// The faces of a cube map, in the order that OpenGL expects them
+pub const CUBEMAP_FACES: [CubemapFace; 6] = [
+    CubemapFace::PosX,
+    CubemapFace::NegX,
+    CubemapFace::PosY,
+    CubemapFace::NegY,
+    CubemapFace::PosZ,
+    CubemapFace::NegZ,
+];
+
+/// One of the six faces of a cube map
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CubemapFace {
+    PosX,
+    NegX,
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
+}
+
+/// A texture backed by a buffer of raw data, for passing large arrays to shaders
+///
+/// Shaders read these using `texelFetch` on a `samplerBuffer` (or `usamplerBuffer`), where each
+/// texel is made up of however many components `format` specifies.
+#[derive(Debug)]
+pub(crate) struct BufferTexture {
+    buffer_id: GLuint,
+    texture_id: GLuint,
 }
 
 impl Texture {
     pub fn new(buffer: &[u8], format: ImageFileFormat) -> Self {
-        let image_option = match format {
-            ImageFileFormat::Png => image::load_from_memory_with_format(buffer, ImageFormat::Png),
-            ImageFileFormat::Guess => image::load_from_memory(buffer),
-        };
-        let img = image_option
-            .expect("Failed to load texture from buffer")
-            .to_rgba8();
+        let img = decode_image(buffer, format).expect("Failed to load texture from buffer");
 
         let data = img.as_ptr();
         let width: i32 = img.width().try_into().unwrap();
@@ -90,21 +139,518 @@ impl Drop for Texture {
     }
 }
 
-impl TextureBinding {
-    pub(crate) fn new(texture: &Texture, texture_unit: GLenum) -> Self {
+impl TextureArray {
+    /// Load a texture array from an image made up of a grid of `tile_size` square tiles
+    ///
+    /// Each tile becomes one layer, numbered along each row from the top left. The layers are
+    /// mipmapped so that distant surfaces don't shimmer, and `max_anisotropy` (if any) sets how
+    /// many extra samples can be taken to keep surfaces seen at a glancing angle sharp.
+    pub fn from_grid(
+        buffer: &[u8],
+        format: ImageFileFormat,
+        tile_size: u32,
+        max_anisotropy: Option<f32>,
+    ) -> Self {
+        let layers = split_into_layers(
+            &decode_image(buffer, format).expect("Failed to load texture from buffer"),
+            tile_size,
+        );
+
+        let texture_id = unsafe {
+            let mut id: GLuint = 0;
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, id);
+
+            upload_layers(&layers, tile_size);
+
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_S, gl::REPEAT as i32);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_T, gl::REPEAT as i32);
+
+            // Keep the blocky look up close, but blend between mipmaps further away
+            gl::TexParameteri(
+                gl::TEXTURE_2D_ARRAY,
+                gl::TEXTURE_MIN_FILTER,
+                gl::NEAREST_MIPMAP_LINEAR as i32,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_2D_ARRAY,
+                gl::TEXTURE_MAG_FILTER,
+                gl::NEAREST as i32,
+            );
+
+            if let Some(anisotropy) = max_anisotropy {
+                let mut supported: GLfloat = 1.0;
+                gl::GetFloatv(MAX_TEXTURE_MAX_ANISOTROPY, &mut supported);
+                gl::TexParameterf(
+                    gl::TEXTURE_2D_ARRAY,
+                    TEXTURE_MAX_ANISOTROPY,
+                    anisotropy.clamp(1.0, supported.max(1.0)),
+                );
+            }
+
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+            id
+        };
+
+        TextureArray {
+            texture_id,
+            layers: Cell::new(layers.len() as u32),
+        }
+    }
+
+    /// Replace the layers of the array with those from a new image, keeping its filtering settings
+    ///
+    /// Every model using the array is drawn with the new layers from then on, without needing to
+    /// be rebuilt.
+    pub fn reload(&self, buffer: &[u8], format: ImageFileFormat, tile_size: u32) {
+        let layers = split_into_layers(
+            &decode_image(buffer, format).expect("Failed to load texture from buffer"),
+            tile_size,
+        );
+
+        unsafe {
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.texture_id);
+            upload_layers(&layers, tile_size);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+        }
+
+        self.layers.set(layers.len() as u32);
+    }
+
+    /// The number of layers in the array
+    pub fn layers(&self) -> u32 {
+        self.layers.get()
+    }
+
+    pub(crate) fn handle(&self) -> TextureHandle {
+        TextureHandle {
+            target: gl::TEXTURE_2D_ARRAY,
+            id: self.texture_id,
+        }
+    }
+}
+
+impl Drop for TextureArray {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.texture_id);
+        }
+    }
+}
+
+impl CubemapTexture {
+    /// Load a cube map from six separate images, one per face in the order of `CUBEMAP_FACES`
+    ///
+    /// Each face must be square, and all of them must be the same size. The faces are referred to
+    /// by their `names`, such as the files they were read from, in any error.
+    pub fn from_faces(
+        buffers: [&[u8]; 6],
+        names: [&str; 6],
+        format: ImageFileFormat,
+    ) -> Result<Self, String> {
+        Ok(Self::from_images(&decode_faces(buffers, names, format)?))
+    }
+
+    /// Load a cube map from a single equirectangular (latitude/longitude) panorama
+    ///
+    /// The panorama should be twice as wide as it is tall, with the top of the sky along its top
+    /// edge and the -z direction in the middle. It's referred to by its `name` in any error.
+    pub fn from_equirectangular(
+        buffer: &[u8],
+        name: &str,
+        format: ImageFileFormat,
+    ) -> Result<Self, String> {
+        let panorama = decode_image(buffer, format)
+            .map_err(|e| format!("Failed to decode skybox panorama {}: {}", name, e))?;
+        let face_size = (panorama.width() / 4).max(1);
+        let faces = CUBEMAP_FACES.map(|face| equirectangular_to_face(&panorama, face, face_size));
+        Ok(Self::from_images(&faces))
+    }
+
+    /// A cube map with a single black texel on each face, for a sampler that has to point at a cube
+    /// map even when there's nothing to sample
+    pub(crate) fn placeholder() -> Self {
+        Self::from_images(&std::array::from_fn(|_| RgbaImage::new(1, 1)))
+    }
+
+    fn from_images(faces: &[RgbaImage; 6]) -> Self {
+        let texture_id = unsafe {
+            let mut id: GLuint = 0;
+            gl::GenTextures(1, &mut id);
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, id);
+
+            // Clamp to the edges so that the seams between faces don't show
+            gl::TexParameteri(
+                gl::TEXTURE_CUBE_MAP,
+                gl::TEXTURE_WRAP_S,
+                gl::CLAMP_TO_EDGE as i32,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_CUBE_MAP,
+                gl::TEXTURE_WRAP_T,
+                gl::CLAMP_TO_EDGE as i32,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_CUBE_MAP,
+                gl::TEXTURE_WRAP_R,
+                gl::CLAMP_TO_EDGE as i32,
+            );
+
+            gl::TexParameteri(
+                gl::TEXTURE_CUBE_MAP,
+                gl::TEXTURE_MIN_FILTER,
+                gl::LINEAR as i32,
+            );
+            gl::TexParameteri(
+                gl::TEXTURE_CUBE_MAP,
+                gl::TEXTURE_MAG_FILTER,
+                gl::LINEAR as i32,
+            );
+
+            for (i, img) in faces.iter().enumerate() {
+                gl::TexImage2D(
+                    gl::TEXTURE_CUBE_MAP_POSITIVE_X + i as GLenum,
+                    0,
+                    gl::RGBA as i32,
+                    img.width().try_into().unwrap(),
+                    img.height().try_into().unwrap(),
+                    0,
+                    gl::RGBA,
+                    gl::UNSIGNED_BYTE,
+                    img.as_ptr() as *const os::raw::c_void,
+                );
+            }
+
+            gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+            id
+        };
+
+        CubemapTexture { texture_id }
+    }
+
+    pub(crate) fn handle(&self) -> TextureHandle {
+        TextureHandle {
+            target: gl::TEXTURE_CUBE_MAP,
+            id: self.texture_id,
+        }
+    }
+}
+
+impl Drop for CubemapTexture {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteTextures(1, &self.texture_id);
+        }
+    }
+}
+
+impl CubemapFace {
+    /// Get the direction through the point (`s`, `t`) on this face, where both lie in [0, 1]
+    ///
+    /// This follows OpenGL's conventions for how each face is laid out, with `t` increasing from
+    /// the first row of the image to the last.
+    pub fn direction(&self, s: f32, t: f32) -> Vector3<f32> {
+        let a = 2.0 * s - 1.0;
+        let b = 2.0 * t - 1.0;
+        let direction = match self {
+            CubemapFace::PosX => Vector3::new(1.0, -b, -a),
+            CubemapFace::NegX => Vector3::new(-1.0, -b, a),
+            CubemapFace::PosY => Vector3::new(a, 1.0, b),
+            CubemapFace::NegY => Vector3::new(a, -1.0, -b),
+            CubemapFace::PosZ => Vector3::new(a, -b, 1.0),
+            CubemapFace::NegZ => Vector3::new(-a, -b, -1.0),
+        };
+        direction.normalize()
+    }
+}
+
+impl BufferTexture {
+    pub(crate) fn new(format: GLenum) -> Self {
+        let (buffer_id, texture_id) = unsafe {
+            let mut buffer_id: GLuint = 0;
+            gl::GenBuffers(1, &mut buffer_id);
+
+            let mut texture_id: GLuint = 0;
+            gl::GenTextures(1, &mut texture_id);
+            gl::BindTexture(gl::TEXTURE_BUFFER, texture_id);
+            gl::TexBuffer(gl::TEXTURE_BUFFER, format, buffer_id);
+            gl::BindTexture(gl::TEXTURE_BUFFER, 0);
+
+            (buffer_id, texture_id)
+        };
+
+        BufferTexture {
+            buffer_id,
+            texture_id,
+        }
+    }
+
+    /// Replace the contents of the buffer
+    pub(crate) fn upload<T: Copy>(&self, data: &[T]) {
         unsafe {
-            gl::ActiveTexture(texture_unit);
-            gl::BindTexture(gl::TEXTURE_2D, texture.texture_id);
+            gl::BindBuffer(gl::TEXTURE_BUFFER, self.buffer_id);
+            gl::BufferData(
+                gl::TEXTURE_BUFFER,
+                mem::size_of_val(data) as GLsizeiptr,
+                data.as_ptr() as *const os::raw::c_void,
+                gl::STREAM_DRAW,
+            );
+            gl::BindBuffer(gl::TEXTURE_BUFFER, 0);
+        }
+    }
+
+    pub(crate) fn handle(&self) -> TextureHandle {
+        TextureHandle {
+            target: gl::TEXTURE_BUFFER,
+            id: self.texture_id,
         }
-        TextureBinding { texture_unit }
     }
 }
 
-impl Drop for TextureBinding {
+impl Drop for BufferTexture {
     fn drop(&mut self) {
         unsafe {
-            gl::ActiveTexture(self.texture_unit);
-            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::DeleteTextures(1, &self.texture_id);
+            gl::DeleteBuffers(1, &self.buffer_id);
+        }
+    }
+}
+
+fn decode_image(buffer: &[u8], format: ImageFileFormat) -> Result<RgbaImage, String> {
+    let image = match format {
+        ImageFileFormat::Png => image::load_from_memory_with_format(buffer, ImageFormat::Png),
+        ImageFileFormat::Guess => image::load_from_memory(buffer),
+    };
+    image
+        .map(|image| image.to_rgba8())
+        .map_err(|e| e.to_string())
+}
+
+/// Decode the six faces of a cube map, checking that they're squares of the same size as the
+/// first
+fn decode_faces(
+    buffers: [&[u8]; 6],
+    names: [&str; 6],
+    format: ImageFileFormat,
+) -> Result<[RgbaImage; 6], String> {
+    let mut faces = Vec::with_capacity(6);
+    for (buffer, name) in buffers.into_iter().zip(names) {
+        let face = decode_image(buffer, format)
+            .map_err(|e| format!("Failed to decode skybox face {}: {}", name, e))?;
+
+        let size = faces.first().map_or(face.width(), RgbaImage::width);
+        if face.dimensions() != (size, size) {
+            return Err(format!(
+                "Skybox face {} is {}x{}, but expected a {}x{} square to match the other faces",
+                name,
+                face.width(),
+                face.height(),
+                size,
+                size
+            ));
+        }
+        faces.push(face);
+    }
+    Ok(faces.try_into().unwrap())
+}
+
+/// Fill the bound texture array with `layers`, each `tile_size` pixels square, and generate its
+/// mipmaps
+unsafe fn upload_layers(layers: &[RgbaImage], tile_size: u32) {
+    let size = tile_size as GLsizei;
+
+    gl::TexImage3D(
+        gl::TEXTURE_2D_ARRAY,
+        0,
+        gl::RGBA as i32,
+        size,
+        size,
+        layers.len() as GLsizei,
+        0,
+        gl::RGBA,
+        gl::UNSIGNED_BYTE,
+        ptr::null(),
+    );
+    for (i, layer) in layers.iter().enumerate() {
+        gl::TexSubImage3D(
+            gl::TEXTURE_2D_ARRAY,
+            0,
+            0,
+            0,
+            i as GLint,
+            size,
+            size,
+            1,
+            gl::RGBA,
+            gl::UNSIGNED_BYTE,
+            layer.as_ptr() as *const os::raw::c_void,
+        );
+    }
+    gl::GenerateMipmap(gl::TEXTURE_2D_ARRAY);
+}
+
+/// Cut an image into `tile_size` square tiles, reading along each row from the top left
+fn split_into_layers(img: &RgbaImage, tile_size: u32) -> Vec<RgbaImage> {
+    if tile_size == 0
+        || !img.width().is_multiple_of(tile_size)
+        || !img.height().is_multiple_of(tile_size)
+    {
+        panic!(
+            "A {}x{} image can't be split into {}x{} tiles",
+            img.width(),
+            img.height(),
+            tile_size,
+            tile_size
+        );
+    }
+
+    let columns = img.width() / tile_size;
+    let rows = img.height() / tile_size;
+    (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (row, column)))
+        .map(|(row, column)| {
+            image::imageops::crop_imm(
+                img,
+                column * tile_size,
+                row * tile_size,
+                tile_size,
+                tile_size,
+            )
+            .to_image()
+        })
+        .collect()
+}
+
+/// Resample one face of a cube map out of an equirectangular panorama
+fn equirectangular_to_face(panorama: &RgbaImage, face: CubemapFace, face_size: u32) -> RgbaImage {
+    RgbaImage::from_fn(face_size, face_size, |x, y| {
+        let s = (x as f32 + 0.5) / face_size as f32;
+        let t = (y as f32 + 0.5) / face_size as f32;
+        let direction = face.direction(s, t);
+
+        // Longitude runs around the horizon starting from -z, and latitude from the top down
+        let u = 0.5 + direction.x.atan2(-direction.z) / (2.0 * PI);
+        let v = direction.y.clamp(-1.0, 1.0).acos() / PI;
+
+        let px = ((u * panorama.width() as f32) as u32).min(panorama.width() - 1);
+        let py = ((v * panorama.height() as f32) as u32).min(panorama.height() - 1);
+        *panorama.get_pixel(px, py)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+    use rstest::*;
+
+    const SKY: Rgba<u8> = Rgba([0, 0, 255, 255]);
+    const GROUND: Rgba<u8> = Rgba([0, 255, 0, 255]);
+
+    #[rstest]
+    #[case(CubemapFace::PosX, Vector3::new(1.0, 0.0, 0.0))]
+    #[case(CubemapFace::NegX, Vector3::new(-1.0, 0.0, 0.0))]
+    #[case(CubemapFace::PosY, Vector3::new(0.0, 1.0, 0.0))]
+    #[case(CubemapFace::NegY, Vector3::new(0.0, -1.0, 0.0))]
+    #[case(CubemapFace::PosZ, Vector3::new(0.0, 0.0, 1.0))]
+    #[case(CubemapFace::NegZ, Vector3::new(0.0, 0.0, -1.0))]
+    fn face_centres_point_along_their_axes(
+        #[case] face: CubemapFace,
+        #[case] expected: Vector3<f32>,
+    ) {
+        assert!((expected - face.direction(0.5, 0.5)).norm() < 1e-5);
+    }
+
+    #[rstest]
+    #[case(CubemapFace::PosX)]
+    #[case(CubemapFace::NegX)]
+    #[case(CubemapFace::PosZ)]
+    #[case(CubemapFace::NegZ)]
+    fn side_faces_have_the_sky_at_the_top(#[case] face: CubemapFace) {
+        assert!(face.direction(0.5, 0.0).y > 0.0);
+        assert!(face.direction(0.5, 1.0).y < 0.0);
+    }
+
+    fn encode_png(image: &RgbaImage) -> Vec<u8> {
+        let mut png = std::io::Cursor::new(vec![]);
+        image.write_to(&mut png, ImageFormat::Png).unwrap();
+        png.into_inner()
+    }
+
+    #[rstest]
+    fn cube_map_faces_of_the_wrong_size_are_named() {
+        let face = encode_png(&RgbaImage::from_pixel(4, 4, SKY));
+        let odd_face = encode_png(&RgbaImage::from_pixel(4, 2, SKY));
+        let mut buffers = [face.as_slice(); 6];
+        buffers[3] = &odd_face;
+        let names = ["px.png", "nx.png", "py.png", "ny.png", "pz.png", "nz.png"];
+
+        let error = decode_faces(buffers, names, ImageFileFormat::Png).unwrap_err();
+
+        assert_eq!(
+            "Skybox face ny.png is 4x2, but expected a 4x4 square to match the other faces",
+            error
+        );
+    }
+
+    #[rstest]
+    fn undecodable_cube_map_faces_are_named() {
+        let face = encode_png(&RgbaImage::from_pixel(4, 4, SKY));
+        let mut buffers = [face.as_slice(); 6];
+        buffers[1] = b"not a png";
+        let names = ["px.png", "nx.png", "py.png", "ny.png", "pz.png", "nz.png"];
+
+        let error = decode_faces(buffers, names, ImageFileFormat::Png).unwrap_err();
+
+        assert!(error.starts_with("Failed to decode skybox face nx.png: "));
+    }
+
+    #[rstest]
+    fn grids_are_split_into_layers_along_each_row() {
+        let grid = RgbaImage::from_fn(6, 4, |x, y| Rgba([(x / 2) as u8, (y / 2) as u8, 0, 255]));
+
+        let layers = split_into_layers(&grid, 2);
+
+        assert_eq!(6, layers.len());
+        for (i, layer) in layers.iter().enumerate() {
+            assert_eq!((2, 2), layer.dimensions());
+            let expected = Rgba([(i % 3) as u8, (i / 3) as u8, 0, 255]);
+            assert!(layer.pixels().all(|pixel| *pixel == expected));
         }
     }
+
+    #[rstest]
+    #[should_panic]
+    fn grids_must_divide_into_whole_tiles() {
+        split_into_layers(&RgbaImage::new(5, 4), 2);
+    }
+
+    #[rstest]
+    fn panoramas_are_split_into_sky_and_ground() {
+        let panorama = RgbaImage::from_fn(16, 8, |_, y| if y < 4 { SKY } else { GROUND });
+
+        let top = equirectangular_to_face(&panorama, CubemapFace::PosY, 4);
+        let bottom = equirectangular_to_face(&panorama, CubemapFace::NegY, 4);
+        let side = equirectangular_to_face(&panorama, CubemapFace::PosX, 4);
+
+        assert!(top.pixels().all(|pixel| *pixel == SKY));
+        assert!(bottom.pixels().all(|pixel| *pixel == GROUND));
+        assert_eq!(SKY, *side.get_pixel(2, 0));
+        assert_eq!(GROUND, *side.get_pixel(2, 3));
+    }
+
+    #[rstest]
+    fn panorama_centre_faces_negative_z() {
+        let panorama = RgbaImage::from_fn(
+            16,
+            8,
+            |x, _| if (6..10).contains(&x) { SKY } else { GROUND },
+        );
+
+        let front = equirectangular_to_face(&panorama, CubemapFace::NegZ, 4);
+        let back = equirectangular_to_face(&panorama, CubemapFace::PosZ, 4);
+
+        assert_eq!(SKY, *front.get_pixel(2, 2));
+        assert_eq!(GROUND, *back.get_pixel(2, 2));
+    }
 }
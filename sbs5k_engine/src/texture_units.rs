@@ -0,0 +1,302 @@
+//! Hands out texture units to the textures that each draw samples from, keeping track of what's
+//! bound to each unit so that a texture that's still bound from an earlier draw isn't bound again.
+
+use std::cell::RefCell;
+
+use gl::types::*;
+
+/// A texture that can be bound to a texture unit
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct TextureHandle {
+    /// What kind of texture it is, such as `GL_TEXTURE_2D_ARRAY`
+    pub(crate) target: GLenum,
+
+    /// The texture's name
+    pub(crate) id: GLuint,
+}
+
+/// A texture unit that a texture has been bound to
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct TextureUnit(u32);
+
+/// How long a texture needs to keep its unit for
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub(crate) enum BindingScope {
+    /// Until the next call to `begin_draw`
+    Draw,
+
+    /// Until the next call to `begin_batch`, for textures shared by a batch of draws such as the
+    /// shadow maps
+    Batch,
+}
+
+/// The texture units available to the renderer, and the textures bound to them
+pub(crate) struct TextureUnits {
+    allocator: RefCell<UnitAllocator>,
+}
+
+/// The bookkeeping behind `TextureUnits`, kept apart from OpenGL so that it can be tested
+#[derive(Debug)]
+struct UnitAllocator {
+    /// The texture bound to each unit, if it's known
+    bound: Vec<Option<TextureHandle>>,
+
+    /// Whether each unit is being used, and until when
+    claims: Vec<Option<BindingScope>>,
+
+    /// When each unit was last handed out, so that the least recently used one can be reused
+    last_used: Vec<u64>,
+
+    clock: u64,
+}
+
+/// Where a texture should go, and whether it needs binding there
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Assignment {
+    AlreadyBound(u32),
+    Bind(u32),
+}
+
+impl TextureUnit {
+    /// The index of the texture unit, as expected by a sampler uniform
+    pub(crate) fn index(self) -> GLint {
+        self.0 as GLint
+    }
+}
+
+impl TextureUnits {
+    pub(crate) fn new() -> Self {
+        let mut count: GLint = 0;
+        unsafe {
+            gl::GetIntegerv(gl::MAX_TEXTURE_IMAGE_UNITS, &mut count);
+        }
+        TextureUnits {
+            allocator: RefCell::new(UnitAllocator::new(count.max(1) as usize)),
+        }
+    }
+
+    /// Start a batch of draws, freeing up every texture unit. Textures stay bound to them until
+    /// they're needed for something else.
+    pub(crate) fn begin_batch(&self) {
+        self.allocator.borrow_mut().begin_batch();
+    }
+
+    /// Start a draw within a batch, freeing up the units used by the last draw but not those used
+    /// by the whole batch
+    pub(crate) fn begin_draw(&self) {
+        self.allocator.borrow_mut().begin_draw();
+    }
+
+    /// Make sure that a texture is bound to a unit for as long as `scope`, binding it if it isn't
+    /// already, and return the unit
+    pub(crate) fn bind(&self, texture: TextureHandle, scope: BindingScope) -> TextureUnit {
+        match self.allocator.borrow_mut().assign(texture, scope) {
+            Assignment::AlreadyBound(unit) => TextureUnit(unit),
+            Assignment::Bind(unit) => {
+                unsafe {
+                    gl::ActiveTexture(gl::TEXTURE0 + unit);
+                    gl::BindTexture(texture.target, texture.id);
+                }
+                TextureUnit(unit)
+            }
+        }
+    }
+
+    /// Forget which textures are bound, so that they're all bound again when next needed
+    ///
+    /// Creating or reloading a texture binds it, and then unbinds it, on whichever unit happens to
+    /// be active, so this must be called after any textures are created or reloaded.
+    pub(crate) fn forget_bindings(&self) {
+        self.allocator.borrow_mut().forget_bindings();
+    }
+}
+
+impl UnitAllocator {
+    fn new(count: usize) -> Self {
+        UnitAllocator {
+            bound: vec![None; count],
+            claims: vec![None; count],
+            last_used: vec![0; count],
+            clock: 0,
+        }
+    }
+
+    fn begin_batch(&mut self) {
+        self.claims.fill(None);
+    }
+
+    fn begin_draw(&mut self) {
+        for claim in &mut self.claims {
+            if *claim == Some(BindingScope::Draw) {
+                *claim = None;
+            }
+        }
+    }
+
+    fn assign(&mut self, texture: TextureHandle, scope: BindingScope) -> Assignment {
+        if let Some(unit) = self.bound.iter().position(|t| *t == Some(texture)) {
+            self.claim(unit, scope);
+            return Assignment::AlreadyBound(unit as u32);
+        }
+
+        // Prefer a unit with nothing bound, and then the one whose texture was used longest ago
+        let unit = (0..self.bound.len())
+            .filter(|unit| self.claims[*unit].is_none())
+            .min_by_key(|unit| (self.bound[*unit].is_some(), self.last_used[*unit]))
+            .unwrap_or_else(|| {
+                panic!(
+                    "Ran out of texture units: only {} textures can be used at once",
+                    self.bound.len()
+                )
+            });
+
+        self.bound[unit] = Some(texture);
+        self.claim(unit, scope);
+        Assignment::Bind(unit as u32)
+    }
+
+    fn forget_bindings(&mut self) {
+        self.bound.fill(None);
+    }
+
+    fn claim(&mut self, unit: usize, scope: BindingScope) {
+        self.claims[unit] = self.claims[unit].max(Some(scope));
+        self.clock += 1;
+        self.last_used[unit] = self.clock;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    fn texture(id: GLuint) -> TextureHandle {
+        TextureHandle {
+            target: gl::TEXTURE_2D_ARRAY,
+            id,
+        }
+    }
+
+    #[rstest]
+    fn a_texture_used_by_consecutive_draws_is_only_bound_once() {
+        let mut allocator = UnitAllocator::new(4);
+        allocator.begin_batch();
+
+        allocator.begin_draw();
+        let first = allocator.assign(texture(1), BindingScope::Draw);
+        allocator.begin_draw();
+        let second = allocator.assign(texture(1), BindingScope::Draw);
+
+        assert_eq!(Assignment::Bind(0), first);
+        assert_eq!(Assignment::AlreadyBound(0), second);
+    }
+
+    #[rstest]
+    fn textures_in_the_same_draw_get_different_units() {
+        let mut allocator = UnitAllocator::new(4);
+        allocator.begin_batch();
+        allocator.begin_draw();
+
+        let albedo = allocator.assign(texture(1), BindingScope::Draw);
+        let normal = allocator.assign(texture(2), BindingScope::Draw);
+        let emissive = allocator.assign(texture(3), BindingScope::Draw);
+
+        assert_eq!(
+            [
+                Assignment::Bind(0),
+                Assignment::Bind(1),
+                Assignment::Bind(2)
+            ],
+            [albedo, normal, emissive]
+        );
+    }
+
+    #[rstest]
+    fn the_same_texture_twice_in_one_draw_shares_a_unit() {
+        let mut allocator = UnitAllocator::new(4);
+        allocator.begin_draw();
+
+        allocator.assign(texture(1), BindingScope::Draw);
+        assert_eq!(
+            Assignment::AlreadyBound(0),
+            allocator.assign(texture(1), BindingScope::Draw)
+        );
+    }
+
+    #[rstest]
+    fn batch_textures_keep_their_units_between_draws() {
+        let mut allocator = UnitAllocator::new(2);
+        allocator.begin_batch();
+        allocator.assign(texture(1), BindingScope::Batch);
+
+        allocator.begin_draw();
+        assert_eq!(
+            Assignment::Bind(1),
+            allocator.assign(texture(2), BindingScope::Draw)
+        );
+        allocator.begin_draw();
+        assert_eq!(
+            Assignment::Bind(1),
+            allocator.assign(texture(3), BindingScope::Draw)
+        );
+    }
+
+    #[rstest]
+    fn the_least_recently_used_texture_is_replaced() {
+        let mut allocator = UnitAllocator::new(2);
+        allocator.begin_draw();
+        allocator.assign(texture(1), BindingScope::Draw);
+        allocator.assign(texture(2), BindingScope::Draw);
+
+        allocator.begin_draw();
+        allocator.assign(texture(1), BindingScope::Draw);
+        allocator.begin_draw();
+
+        assert_eq!(
+            Assignment::Bind(1),
+            allocator.assign(texture(3), BindingScope::Draw)
+        );
+        assert_eq!(
+            Assignment::AlreadyBound(0),
+            allocator.assign(texture(1), BindingScope::Draw)
+        );
+    }
+
+    #[rstest]
+    fn textures_are_rebound_after_bindings_are_forgotten() {
+        let mut allocator = UnitAllocator::new(4);
+        allocator.assign(texture(1), BindingScope::Batch);
+
+        allocator.forget_bindings();
+        allocator.begin_batch();
+
+        assert!(matches!(
+            allocator.assign(texture(1), BindingScope::Batch),
+            Assignment::Bind(_)
+        ));
+    }
+
+    #[rstest]
+    #[case(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_CUBE_MAP)]
+    #[case(gl::TEXTURE_BUFFER, gl::TEXTURE_2D_ARRAY)]
+    fn textures_are_told_apart_by_target(#[case] a: GLenum, #[case] b: GLenum) {
+        let mut allocator = UnitAllocator::new(4);
+        allocator.assign(TextureHandle { target: a, id: 1 }, BindingScope::Batch);
+
+        assert_eq!(
+            Assignment::Bind(1),
+            allocator.assign(TextureHandle { target: b, id: 1 }, BindingScope::Batch)
+        );
+    }
+
+    #[rstest]
+    #[should_panic(expected = "Ran out of texture units")]
+    fn using_more_textures_than_units_in_one_draw_panics() {
+        let mut allocator = UnitAllocator::new(2);
+        allocator.begin_draw();
+        for id in 0..3 {
+            allocator.assign(texture(id), BindingScope::Draw);
+        }
+    }
+}
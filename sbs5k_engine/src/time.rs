@@ -1,40 +1,391 @@
-/// Tool to measure the time difference between successive frames
+use std::collections::VecDeque;
+use std::time::Duration;
+use std::{hint, thread};
+
+/// How long before the end of a frame to stop sleeping and start spinning, in seconds
+///
+/// The OS scheduler can oversleep by a millisecond or more, so the last stretch of each frame is
+/// spent busy-waiting to hit the target frame time precisely.
+const SPIN_THRESHOLD: f64 = 0.002;
+
+/// The number of recent frames that frame-time statistics are computed over
+const FRAME_STATS_WINDOW: usize = 300;
+
+/// The most simulation ticks that will be run in a single frame
+///
+/// If the game falls further behind than this (for example, after the window has been dragged),
+/// the remaining time is dropped rather than trying to catch up, which would only make the next
+/// frame slower still.
+const MAX_TICKS_PER_FRAME: u32 = 10;
+
+/// Tool to measure the time difference between successive frames, and to divide that time up into
+/// fixed-length simulation ticks
 ///
 /// The owner is required to call `tick()` each frame.
 pub struct TimeTracker {
     time_prev_frame: Option<f64>,
     time_curr_frame: f64,
-    _target_fps: Option<f32>,
+    timestep: FixedTimestep,
+    target_frame_time: Option<f64>,
+    stats: FrameStats,
 }
 
 impl TimeTracker {
-    pub fn new() -> Self {
+    /// Create a new tracker that runs the simulation `ticks_per_second` times per second
+    pub fn new(ticks_per_second: f64) -> Self {
         TimeTracker {
             time_prev_frame: None,
             time_curr_frame: get_time(),
-            _target_fps: None,
+            timestep: FixedTimestep::new(ticks_per_second),
+            target_frame_time: None,
+            stats: FrameStats::new(FRAME_STATS_WINDOW),
         }
     }
 
+    /// Limit the frame rate to at most `target_fps` frames per second, or remove the limit if
+    /// `None` is given
+    pub fn set_target_fps(&mut self, target_fps: Option<f32>) {
+        self.target_frame_time = target_fps
+            .filter(|fps| *fps > 0.0)
+            .map(|fps| 1.0 / fps as f64);
+    }
+
     /// Update the internal clock from the previous frame
     pub fn tick(&mut self) {
-        // TODO: Use target_fps if it's been set
         self.time_prev_frame = Some(self.time_curr_frame);
         self.time_curr_frame = get_time();
+        self.timestep.advance(self.dt());
+        self.stats.record(self.dt());
+    }
+
+    /// Block until it is time to start the next frame
+    ///
+    /// This should be called at the end of each frame. It returns immediately if no target frame
+    /// rate has been set.
+    pub fn wait_for_next_frame(&self) {
+        let target_frame_time = match self.target_frame_time {
+            Some(target_frame_time) => target_frame_time,
+            None => return,
+        };
+        let deadline = self.time_curr_frame + target_frame_time;
+
+        loop {
+            match pacing_action(deadline - get_time()) {
+                PacingAction::Sleep(duration) => thread::sleep(duration),
+                PacingAction::Spin => hint::spin_loop(),
+                PacingAction::Done => break,
+            }
+        }
+    }
+
+    /// Statistics about the times taken by recent frames
+    #[inline]
+    pub fn frame_stats(&self) -> &FrameStats {
+        &self.stats
     }
 
     /// Compute the amount of time that elapsed since the previous frame, in seconds
     pub fn dt(&self) -> f64 {
         self.time_curr_frame - self.time_prev_frame.unwrap()
     }
+
+    /// The number of simulation ticks that should be run this frame
+    #[inline]
+    pub fn ticks_due(&self) -> u32 {
+        self.timestep.ticks_due()
+    }
+
+    /// The total number of simulation ticks that have been run, including this frame's
+    #[inline]
+    pub fn tick_count(&self) -> u64 {
+        self.timestep.tick_count()
+    }
+
+    /// The length of each simulation tick, in seconds
+    #[inline]
+    pub fn tick_dt(&self) -> f64 {
+        self.timestep.tick_duration()
+    }
+
+    /// How far the current frame lies between the last simulation tick and the next one
+    ///
+    /// This is always in the range [0, 1), and should be used to interpolate between the previous
+    /// and current simulation states when rendering.
+    #[inline]
+    pub fn alpha(&self) -> f32 {
+        self.timestep.alpha()
+    }
 }
 
-impl Default for TimeTracker {
-    fn default() -> Self {
-        TimeTracker::new()
+/// Frame-time statistics over a sliding window of recent frames
+pub struct FrameStats {
+    frame_times: VecDeque<f64>,
+    capacity: usize,
+}
+
+impl FrameStats {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "Frame stats window must not be empty");
+        FrameStats {
+            frame_times: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Record the duration of a frame, in seconds, discarding the oldest if the window is full
+    pub fn record(&mut self, frame_time: f64) {
+        if self.frame_times.len() == self.capacity {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_time);
+    }
+
+    /// The mean frame time, in seconds
+    pub fn average_frame_time(&self) -> Option<f64> {
+        if self.frame_times.is_empty() {
+            return None;
+        }
+        Some(self.frame_times.iter().sum::<f64>() / self.frame_times.len() as f64)
+    }
+
+    /// The frame time that 99% of frames were at least as fast as, in seconds
+    pub fn p99_frame_time(&self) -> Option<f64> {
+        let sorted = self.sorted_frame_times();
+        if sorted.is_empty() {
+            return None;
+        }
+        let rank = (0.99 * sorted.len() as f64).ceil() as usize;
+        Some(sorted[rank.clamp(1, sorted.len()) - 1])
+    }
+
+    /// The average frame rate over the slowest 1% of frames (or the single slowest frame, if
+    /// there are fewer than 100)
+    pub fn one_percent_low_fps(&self) -> Option<f64> {
+        let sorted = self.sorted_frame_times();
+        if sorted.is_empty() {
+            return None;
+        }
+        let count = (sorted.len() / 100).max(1);
+        let slowest = &sorted[sorted.len() - count..];
+        let average = slowest.iter().sum::<f64>() / count as f64;
+        Some(1.0 / average)
+    }
+
+    fn sorted_frame_times(&self) -> Vec<f64> {
+        let mut sorted: Vec<f64> = self.frame_times.iter().copied().collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        sorted
+    }
+}
+
+/// What to do next while waiting for a frame's deadline
+#[derive(Debug, PartialEq)]
+enum PacingAction {
+    /// Hand the CPU back to the OS for a while
+    Sleep(Duration),
+
+    /// Busy-wait, since the deadline is too close to trust the OS to wake us up in time
+    Spin,
+
+    /// The deadline has passed
+    Done,
+}
+
+/// Decide how to wait out the `remaining` seconds until a frame's deadline
+#[inline]
+fn pacing_action(remaining: f64) -> PacingAction {
+    if remaining <= 0.0 {
+        PacingAction::Done
+    } else if remaining > SPIN_THRESHOLD {
+        PacingAction::Sleep(Duration::from_secs_f64(remaining - SPIN_THRESHOLD))
+    } else {
+        PacingAction::Spin
+    }
+}
+
+/// An accumulator that converts variable-length frames into a whole number of fixed-length ticks
+pub struct FixedTimestep {
+    tick_duration: f64,
+    accumulator: f64,
+    tick_count: u64,
+    ticks_due: u32,
+}
+
+impl FixedTimestep {
+    pub fn new(ticks_per_second: f64) -> Self {
+        assert!(ticks_per_second > 0.0, "Tick rate must be positive");
+        FixedTimestep {
+            tick_duration: 1.0 / ticks_per_second,
+            accumulator: 0.0,
+            tick_count: 0,
+            ticks_due: 0,
+        }
+    }
+
+    /// Add a frame's worth of elapsed time, returning the number of ticks that are now due
+    pub fn advance(&mut self, frame_dt: f64) -> u32 {
+        self.accumulator += frame_dt.max(0.0);
+
+        let mut ticks = 0;
+        while self.accumulator >= self.tick_duration && ticks < MAX_TICKS_PER_FRAME {
+            self.accumulator -= self.tick_duration;
+            ticks += 1;
+        }
+
+        // Drop any backlog that we're not going to be able to catch up on
+        if ticks == MAX_TICKS_PER_FRAME {
+            self.accumulator %= self.tick_duration;
+        }
+
+        self.tick_count += ticks as u64;
+        self.ticks_due = ticks;
+        ticks
+    }
+
+    #[inline]
+    pub fn ticks_due(&self) -> u32 {
+        self.ticks_due
+    }
+
+    #[inline]
+    pub fn tick_count(&self) -> u64 {
+        self.tick_count
+    }
+
+    #[inline]
+    pub fn tick_duration(&self) -> f64 {
+        self.tick_duration
+    }
+
+    #[inline]
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.tick_duration) as f32
     }
 }
 
 fn get_time() -> f64 {
     unsafe { glfw::ffi::glfwGetTime() }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    const EPSILON: f32 = 1e-5;
+
+    #[rstest]
+    #[case(0.0, 0)]
+    #[case(0.01, 0)]
+    #[case(0.05, 1)]
+    #[case(0.12, 2)]
+    #[case(0.3, 6)]
+    fn advance_runs_whole_ticks(#[case] frame_dt: f64, #[case] expected: u32) {
+        let mut timestep = FixedTimestep::new(20.0);
+        assert_eq!(expected, timestep.advance(frame_dt));
+        assert_eq!(expected, timestep.ticks_due());
+    }
+
+    #[rstest]
+    fn leftover_time_carries_over_between_frames() {
+        let mut timestep = FixedTimestep::new(20.0);
+        assert_eq!(0, timestep.advance(0.03));
+        assert_eq!(1, timestep.advance(0.03));
+        assert_eq!(0, timestep.advance(0.03));
+        assert_eq!(1, timestep.advance(0.03));
+        assert_eq!(2, timestep.tick_count());
+    }
+
+    #[rstest]
+    #[case(0.0, 0.0)]
+    #[case(0.025, 0.5)]
+    #[case(0.06, 0.2)]
+    #[case(0.1499, 0.998)]
+    fn alpha_is_fraction_of_next_tick(#[case] frame_dt: f64, #[case] expected: f32) {
+        let mut timestep = FixedTimestep::new(20.0);
+        timestep.advance(frame_dt);
+        assert!((expected - timestep.alpha()).abs() < 1e-3);
+        assert!(timestep.alpha() >= 0.0 && timestep.alpha() < 1.0);
+    }
+
+    #[rstest]
+    fn tick_rate_is_independent_of_frame_rate() {
+        let mut fast = FixedTimestep::new(60.0);
+        let mut slow = FixedTimestep::new(60.0);
+
+        let fast_ticks: u32 = (0..240).map(|_| fast.advance(1.0 / 240.0)).sum();
+        let slow_ticks: u32 = (0..30).map(|_| slow.advance(1.0 / 30.0)).sum();
+
+        assert!((59..=60).contains(&fast_ticks));
+        assert!((59..=60).contains(&slow_ticks));
+        assert!((fast_ticks as i64 - slow_ticks as i64).abs() <= 1);
+    }
+
+    #[rstest]
+    fn long_frames_are_capped() {
+        let mut timestep = FixedTimestep::new(60.0);
+        assert_eq!(MAX_TICKS_PER_FRAME, timestep.advance(5.0));
+        assert!(timestep.alpha() < 1.0);
+        assert_eq!(0, timestep.advance(0.0));
+    }
+
+    #[rstest]
+    #[case(-0.001, PacingAction::Done)]
+    #[case(0.0, PacingAction::Done)]
+    #[case(0.001, PacingAction::Spin)]
+    #[case(SPIN_THRESHOLD, PacingAction::Spin)]
+    #[case(0.010, PacingAction::Sleep(Duration::from_secs_f64(0.010 - SPIN_THRESHOLD)))]
+    fn pacing_sleeps_then_spins(#[case] remaining: f64, #[case] expected: PacingAction) {
+        assert_eq!(expected, pacing_action(remaining));
+    }
+
+    #[rstest]
+    fn frame_stats_are_empty_initially() {
+        let stats = FrameStats::new(10);
+        assert_eq!(None, stats.average_frame_time());
+        assert_eq!(None, stats.p99_frame_time());
+        assert_eq!(None, stats.one_percent_low_fps());
+    }
+
+    #[rstest]
+    fn frame_stats_for_steady_frame_rate() {
+        let mut stats = FrameStats::new(100);
+        for _ in 0..100 {
+            stats.record(0.01);
+        }
+        assert!((stats.average_frame_time().unwrap() - 0.01).abs() < 1e-9);
+        assert!((stats.p99_frame_time().unwrap() - 0.01).abs() < 1e-9);
+        assert!((stats.one_percent_low_fps().unwrap() - 100.0).abs() < 1e-6);
+    }
+
+    #[rstest]
+    fn frame_stats_pick_out_stutters() {
+        let mut stats = FrameStats::new(200);
+        for i in 0..200 {
+            // Four long frames among 196 quick ones
+            let frame_time = if i % 50 == 25 { 0.1 } else { 0.01 };
+            stats.record(frame_time);
+        }
+        assert!((stats.average_frame_time().unwrap() - 0.0118).abs() < 1e-9);
+        assert!((stats.p99_frame_time().unwrap() - 0.1).abs() < 1e-9);
+        assert!((stats.one_percent_low_fps().unwrap() - 10.0).abs() < 1e-6);
+    }
+
+    #[rstest]
+    fn frame_stats_only_cover_recent_frames() {
+        let mut stats = FrameStats::new(3);
+        for frame_time in [1.0, 1.0, 0.02, 0.02, 0.05] {
+            stats.record(frame_time);
+        }
+        assert!((stats.average_frame_time().unwrap() - 0.03).abs() < 1e-9);
+        assert!((stats.p99_frame_time().unwrap() - 0.05).abs() < 1e-9);
+        assert!((stats.one_percent_low_fps().unwrap() - 20.0).abs() < 1e-6);
+    }
+
+    #[rstest]
+    fn negative_frame_times_are_ignored() {
+        let mut timestep = FixedTimestep::new(60.0);
+        assert_eq!(0, timestep.advance(-1.0));
+        assert!(timestep.alpha().abs() < EPSILON);
+    }
+}
@@ -0,0 +1,336 @@
+//! Uniform buffers holding the data that stays the same for every draw in a frame, such as the
+//! camera, lighting and fog. Each buffer is bound to a fixed binding point and shared by every
+//! shader program, so it only needs to be written once however many programs read it.
+
+use std::mem;
+use std::os;
+
+use gl::types::*;
+use nalgebra::{Matrix4, Vector2, Vector3};
+
+use crate::camera::CameraPosition;
+use crate::fog::{FogColour, FogParameters};
+use crate::lighting::GlobalLight;
+use crate::sky::SkyParameters;
+
+/// The uniform blocks shared between the shader programs, declared in `uniform_blocks.glsl`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum UniformBlock {
+    Camera,
+    Lighting,
+    Sky,
+    Fog,
+}
+
+/// Builds the contents of a uniform block, laid out according to the std140 rules
+#[derive(Debug, Default)]
+pub(crate) struct Std140Writer {
+    bytes: Vec<u8>,
+}
+
+/// A uniform buffer object holding the contents of one uniform block
+struct UniformBuffer {
+    id: GLuint,
+    block: UniformBlock,
+}
+
+/// The uniform buffers for every uniform block
+pub(crate) struct UniformBuffers {
+    camera: UniformBuffer,
+    lighting: UniformBuffer,
+    sky: UniformBuffer,
+    fog: UniformBuffer,
+}
+
+impl UniformBlock {
+    pub(crate) const ALL: [UniformBlock; 4] = [
+        UniformBlock::Camera,
+        UniformBlock::Lighting,
+        UniformBlock::Sky,
+        UniformBlock::Fog,
+    ];
+
+    pub(crate) const fn name_in_shader(self) -> &'static str {
+        match self {
+            UniformBlock::Camera => "Camera",
+            UniformBlock::Lighting => "Lighting",
+            UniformBlock::Sky => "Sky",
+            UniformBlock::Fog => "Fog",
+        }
+    }
+
+    /// The uniform buffer binding point that the block is read from
+    pub(crate) const fn binding(self) -> GLuint {
+        self as GLuint
+    }
+}
+
+impl Std140Writer {
+    /// Pad the block out to a whole number of `vec4`s, as blocks are, and return its contents
+    pub(crate) fn finish(&mut self) -> Vec<u8> {
+        self.align(16);
+        mem::take(&mut self.bytes)
+    }
+
+    pub(crate) fn float(&mut self, value: f32) -> &mut Self {
+        self.align(4);
+        self.bytes.extend_from_slice(&value.to_ne_bytes());
+        self
+    }
+
+    pub(crate) fn int(&mut self, value: i32) -> &mut Self {
+        self.align(4);
+        self.bytes.extend_from_slice(&value.to_ne_bytes());
+        self
+    }
+
+    /// GLSL bools take up four bytes, like an int
+    pub(crate) fn bool(&mut self, value: bool) -> &mut Self {
+        self.int(value as i32)
+    }
+
+    pub(crate) fn vec2(&mut self, value: &Vector2<f32>) -> &mut Self {
+        self.align(8);
+        self.floats(value.as_slice())
+    }
+
+    /// A `vec3` is aligned like a `vec4`, but a scalar may be packed into the space after it
+    pub(crate) fn vec3(&mut self, value: &Vector3<f32>) -> &mut Self {
+        self.align(16);
+        self.floats(value.as_slice())
+    }
+
+    /// Matrices are written column by column, as `nalgebra` stores them
+    pub(crate) fn mat4(&mut self, value: &Matrix4<f32>) -> &mut Self {
+        self.align(16);
+        self.floats(value.as_slice())
+    }
+
+    /// Structs start and end on a 16-byte boundary
+    pub(crate) fn start_struct(&mut self) -> &mut Self {
+        self.align(16);
+        self
+    }
+
+    pub(crate) fn end_struct(&mut self) -> &mut Self {
+        self.align(16);
+        self
+    }
+
+    fn floats(&mut self, values: &[f32]) -> &mut Self {
+        for value in values {
+            self.bytes.extend_from_slice(&value.to_ne_bytes());
+        }
+        self
+    }
+
+    fn align(&mut self, alignment: usize) {
+        let len = self.bytes.len().next_multiple_of(alignment);
+        self.bytes.resize(len, 0);
+    }
+}
+
+impl UniformBuffer {
+    fn new(block: UniformBlock) -> Self {
+        let id = unsafe {
+            let mut id: GLuint = 0;
+            gl::GenBuffers(1, &mut id);
+            id
+        };
+        UniformBuffer { id, block }
+    }
+
+    /// Replace the contents of the buffer, and make it the one that its block is read from
+    fn upload(&self, bytes: &[u8]) {
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.id);
+            gl::BufferData(
+                gl::UNIFORM_BUFFER,
+                bytes.len() as GLsizeiptr,
+                bytes.as_ptr() as *const os::raw::c_void,
+                gl::STREAM_DRAW,
+            );
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+            gl::BindBufferBase(gl::UNIFORM_BUFFER, self.block.binding(), self.id);
+        }
+    }
+}
+
+impl Drop for UniformBuffer {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteBuffers(1, &self.id);
+        }
+    }
+}
+
+impl UniformBuffers {
+    pub(crate) fn new() -> Self {
+        UniformBuffers {
+            camera: UniformBuffer::new(UniformBlock::Camera),
+            lighting: UniformBuffer::new(UniformBlock::Lighting),
+            sky: UniformBuffer::new(UniformBlock::Sky),
+            fog: UniformBuffer::new(UniformBlock::Fog),
+        }
+    }
+
+    /// Write the camera, along with the number of seconds that textures have been animating for
+    pub(crate) fn write_camera(&self, camera: &CameraPosition, animation_time: f32) {
+        self.camera.upload(&camera_block(camera, animation_time));
+    }
+
+    pub(crate) fn write_lighting(&self, global_light: &GlobalLight) {
+        self.lighting.upload(&lighting_block(global_light));
+    }
+
+    pub(crate) fn write_sky(&self, sky: &SkyParameters) {
+        self.sky.upload(&sky_block(sky));
+    }
+
+    pub(crate) fn write_fog(&self, fog: &FogParameters) {
+        self.fog.upload(&fog_block(fog));
+    }
+}
+
+fn camera_block(camera: &CameraPosition, animation_time: f32) -> Vec<u8> {
+    let mut block = Std140Writer::default();
+    block
+        .mat4(&camera.view_matrix())
+        .mat4(&camera.projection_matrix())
+        .vec3(&camera.position.coords)
+        .float(animation_time);
+    block.finish()
+}
+
+fn lighting_block(global_light: &GlobalLight) -> Vec<u8> {
+    let mut block = Std140Writer::default();
+    block
+        .start_struct()
+        .vec3(&global_light.direction)
+        .vec3(&global_light.colour)
+        .float(global_light.intensity)
+        .float(global_light.ambient_intensity)
+        .end_struct();
+    block.finish()
+}
+
+fn sky_block(sky: &SkyParameters) -> Vec<u8> {
+    let mut block = Std140Writer::default();
+    block
+        .start_struct()
+        .vec3(&sky.zenith_colour)
+        .vec3(&sky.horizon_colour)
+        .end_struct()
+        .start_struct()
+        .vec3(&sky.sun_direction)
+        .vec3(&sky.sun_colour)
+        .end_struct()
+        .float(sky.star_visibility)
+        .start_struct()
+        .vec3(&sky.cloud_colour)
+        .vec2(&sky.cloud_offset)
+        .end_struct();
+    block.finish()
+}
+
+fn fog_block(fog: &FogParameters) -> Vec<u8> {
+    let (use_sky_colour, colour) = match fog.colour {
+        FogColour::Sky => (true, Vector3::zeros()),
+        FogColour::Fixed(colour) => (false, colour),
+    };
+
+    let mut block = Std140Writer::default();
+    block
+        .start_struct()
+        .int(fog.mode as i32)
+        .float(fog.start_threshold)
+        .float(fog.end_threshold)
+        .float(fog.density)
+        .float(fog.height_density)
+        .float(fog.height_falloff)
+        .float(fog.height_base)
+        .bool(use_sky_colour)
+        .vec3(&colour)
+        .end_struct();
+    block.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::Point3;
+    use rstest::*;
+
+    fn float_at(block: &[u8], offset: usize) -> f32 {
+        f32::from_ne_bytes(block[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[rstest]
+    #[case(|w: &mut Std140Writer| { w.float(1.0); }, 8)]
+    #[case(|w: &mut Std140Writer| { w.vec2(&Vector2::new(1.0, 2.0)); }, 8)]
+    #[case(|w: &mut Std140Writer| { w.vec3(&Vector3::new(1.0, 2.0, 3.0)); }, 16)]
+    #[case(|w: &mut Std140Writer| { w.mat4(&Matrix4::identity()); }, 16)]
+    #[case(|w: &mut Std140Writer| { w.start_struct().float(1.0); }, 16)]
+    fn values_are_aligned_after_two_floats(
+        #[case] write: fn(&mut Std140Writer),
+        #[case] expected_offset: usize,
+    ) {
+        let mut writer = Std140Writer::default();
+        writer.float(0.0).float(0.0);
+        write(&mut writer);
+        assert_eq!(1.0, float_at(&writer.finish(), expected_offset));
+    }
+
+    #[rstest]
+    fn a_float_packs_in_after_a_vec3() {
+        let mut writer = Std140Writer::default();
+        writer.vec3(&Vector3::new(1.0, 2.0, 3.0)).float(4.0);
+        let block = writer.finish();
+
+        assert_eq!(16, block.len());
+        assert_eq!(4.0, float_at(&block, 12));
+    }
+
+    #[rstest]
+    fn camera_block_layout() {
+        let camera = CameraPosition {
+            position: Point3::new(1.0, 2.0, 3.0),
+            yaw: 0.0,
+            pitch: 0.0,
+        };
+        let block = camera_block(&camera, 12.5);
+
+        assert_eq!(144, block.len());
+        assert_eq!(3.0, float_at(&block, 136));
+        assert_eq!(12.5, float_at(&block, 140));
+    }
+
+    #[rstest]
+    fn sky_block_layout() {
+        let sky = SkyParameters {
+            zenith_colour: Vector3::zeros(),
+            horizon_colour: Vector3::zeros(),
+            sun_direction: Vector3::zeros(),
+            sun_colour: Vector3::zeros(),
+            star_visibility: 0.5,
+            cloud_colour: Vector3::new(0.25, 0.25, 0.25),
+            cloud_offset: Vector2::new(7.0, 8.0),
+        };
+        let block = sky_block(&sky);
+
+        assert_eq!(112, block.len());
+        assert_eq!(0.5, float_at(&block, 64));
+        assert_eq!(0.25, float_at(&block, 80));
+        assert_eq!(7.0, float_at(&block, 96));
+    }
+
+    #[rstest]
+    fn fog_block_layout() {
+        let fog = FogParameters::underwater();
+        let block = fog_block(&fog);
+
+        assert_eq!(48, block.len());
+        assert_eq!(fog.density, float_at(&block, 12));
+        assert_eq!(0.05, float_at(&block, 32));
+    }
+}
@@ -1,68 +1,176 @@
-use nalgebra::{Matrix4, Vector3};
+use nalgebra::{Matrix4, Vector2};
 
-use crate::texture::TextureBinding;
+use crate::texture_units::TextureUnit;
 
 /// The supported uniforms that can be passed to a shader program
+///
+/// These are the uniforms that change between draws. Everything that stays the same for a whole
+/// frame lives in the uniform blocks in `uniform_blocks.rs` instead.
 pub(crate) enum Uniform<'a> {
-    /// The position of the camera in world space
-    CameraPosition(&'a Vector3<f32>),
-
     /// The model matrix for the object being rendered
     ModelMatrix(&'a Matrix4<f32>),
 
-    /// The view matrix for the camera
-    ViewMatrix(&'a Matrix4<f32>),
+    /// The transformation from world space into the shadow map currently being drawn
+    LightSpaceMatrix(&'a Matrix4<f32>),
+
+    /// The transformations from world space into each cascade's shadow map
+    LightSpaceMatrices(&'a [Matrix4<f32>]),
+
+    /// The distance from the camera at which each shadow cascade ends
+    ShadowCascadeDistances(&'a [f32]),
+
+    /// The depth textures holding each shadow cascade
+    ShadowMaps(TextureUnit),
+
+    /// The buffer holding the positions, ranges and radiances of the scene's point lights
+    PointLightsData(TextureUnit),
+
+    /// The buffer holding the range of light indices for each cluster of the view frustum
+    LightClusters(TextureUnit),
 
-    /// The projection matrix for the camera
-    ProjectionMatrix(&'a Matrix4<f32>),
+    /// The buffer holding the indices of the lights in each cluster
+    LightIndices(TextureUnit),
 
-    /// The positions of the scene's point light sources in world-space
-    PointLightsPositions(&'a Vec<Vector3<f32>>),
+    /// The size of the viewport in pixels
+    ViewportSize(&'a Vector2<f32>),
 
-    /// The colours of the scene's point light sources
-    PointLightsColours(&'a Vec<Vector3<f32>>),
+    /// Whether the sky should be drawn from the skybox's texture rather than a gradient
+    UseSkyboxTexture(bool),
 
-    /// The radiant intensities of the scene's point light sources
-    PointLightsIntensities(&'a Vec<f32>),
+    /// The cube map texture to draw the skybox from
+    SkyboxTexture(TextureUnit),
 
-    /// The direction of the scene's global illuminant in world-space
-    GlobalIlluminantDirection(&'a Vector3<f32>),
+    /// The base colour of the model being rendered
+    AlbedoTexture(TextureUnit),
 
-    /// The colour of the scene's global illuminant
-    GlobalIlluminantColour(&'a Vector3<f32>),
+    /// The normal map of the model being rendered
+    NormalTexture(TextureUnit),
 
-    /// The radiant intensity of the scene's global illuminant
-    GlobalIlluminantIntensity(f32),
+    /// The light given off by the model being rendered
+    EmissiveTexture(TextureUnit),
 
-    /// The texture to be used when rendering a model
-    ModelTexture(&'a TextureBinding),
+    /// Whether the model being rendered has a normal map
+    UseNormalTexture(bool),
 
-    /// Distance at which distance fog starts to be used
-    FogNearDistance(f32),
+    /// Whether the model being rendered has an emissive texture
+    UseEmissiveTexture(bool),
 
-    /// Distance at which distance fog becomes total
-    FogFarDistance(f32),
+    /// Whether to discard the mostly transparent fragments of the model being rendered
+    AlphaCutout(bool),
 }
 
-impl<'a> Uniform<'a> {
+/// Identifies each kind of `Uniform`, so that shader programs can look up its location in a table
+/// rather than by name
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum UniformName {
+    ModelMatrix,
+    LightSpaceMatrix,
+    LightSpaceMatrices,
+    ShadowCascadeDistances,
+    ShadowMaps,
+    PointLightsData,
+    LightClusters,
+    LightIndices,
+    ViewportSize,
+    UseSkyboxTexture,
+    SkyboxTexture,
+    AlbedoTexture,
+    NormalTexture,
+    EmissiveTexture,
+    UseNormalTexture,
+    UseEmissiveTexture,
+    AlphaCutout,
+}
+
+impl Uniform<'_> {
     #[inline]
-    pub(crate) const fn get_name_in_shader(&self) -> &str {
-        // TODO: Use PHF (https://crates.io/crates/phf) to speed this up
+    pub(crate) const fn name(&self) -> UniformName {
+        match self {
+            Uniform::ModelMatrix(_) => UniformName::ModelMatrix,
+            Uniform::LightSpaceMatrix(_) => UniformName::LightSpaceMatrix,
+            Uniform::LightSpaceMatrices(_) => UniformName::LightSpaceMatrices,
+            Uniform::ShadowCascadeDistances(_) => UniformName::ShadowCascadeDistances,
+            Uniform::ShadowMaps(_) => UniformName::ShadowMaps,
+            Uniform::PointLightsData(_) => UniformName::PointLightsData,
+            Uniform::LightClusters(_) => UniformName::LightClusters,
+            Uniform::LightIndices(_) => UniformName::LightIndices,
+            Uniform::ViewportSize(_) => UniformName::ViewportSize,
+            Uniform::UseSkyboxTexture(_) => UniformName::UseSkyboxTexture,
+            Uniform::SkyboxTexture(_) => UniformName::SkyboxTexture,
+            Uniform::AlbedoTexture(_) => UniformName::AlbedoTexture,
+            Uniform::NormalTexture(_) => UniformName::NormalTexture,
+            Uniform::EmissiveTexture(_) => UniformName::EmissiveTexture,
+            Uniform::UseNormalTexture(_) => UniformName::UseNormalTexture,
+            Uniform::UseEmissiveTexture(_) => UniformName::UseEmissiveTexture,
+            Uniform::AlphaCutout(_) => UniformName::AlphaCutout,
+        }
+    }
+}
+
+impl UniformName {
+    /// Every uniform, in the order of their discriminants, so that each one's discriminant can be
+    /// used as its index in a table
+    pub(crate) const ALL: [UniformName; 17] = [
+        UniformName::ModelMatrix,
+        UniformName::LightSpaceMatrix,
+        UniformName::LightSpaceMatrices,
+        UniformName::ShadowCascadeDistances,
+        UniformName::ShadowMaps,
+        UniformName::PointLightsData,
+        UniformName::LightClusters,
+        UniformName::LightIndices,
+        UniformName::ViewportSize,
+        UniformName::UseSkyboxTexture,
+        UniformName::SkyboxTexture,
+        UniformName::AlbedoTexture,
+        UniformName::NormalTexture,
+        UniformName::EmissiveTexture,
+        UniformName::UseNormalTexture,
+        UniformName::UseEmissiveTexture,
+        UniformName::AlphaCutout,
+    ];
+
+    pub(crate) const fn name_in_shader(self) -> &'static str {
         match self {
-            Uniform::CameraPosition(_) => "cameraPos",
-            Uniform::ModelMatrix(_) => "Model",
-            Uniform::ViewMatrix(_) => "View",
-            Uniform::ProjectionMatrix(_) => "Projection",
-            Uniform::PointLightsPositions(_) => "pointLights.positions",
-            Uniform::PointLightsColours(_) => "pointLights.colours",
-            Uniform::PointLightsIntensities(_) => "pointLights.intensities",
-            Uniform::GlobalIlluminantDirection(_) => "globalIlluminant.direction",
-            Uniform::GlobalIlluminantColour(_) => "globalIlluminant.colour",
-            Uniform::GlobalIlluminantIntensity(_) => "globalIlluminant.intensity",
-            // TODO: Investigate why the texture still seems to work if I spell the uniform name wrong
-            Uniform::ModelTexture(_) => "modelTexture",
-            Uniform::FogNearDistance(_) => "fogParameters.beginDistance",
-            Uniform::FogFarDistance(_) => "fogParameters.totalDistance",
+            UniformName::ModelMatrix => "Model",
+            UniformName::LightSpaceMatrix => "LightSpace",
+            UniformName::LightSpaceMatrices => "lightSpaceMatrices",
+            UniformName::ShadowCascadeDistances => "shadowCascadeDistances",
+            UniformName::ShadowMaps => "shadowMaps",
+            UniformName::PointLightsData => "pointLightsData",
+            UniformName::LightClusters => "lightClusters",
+            UniformName::LightIndices => "lightIndices",
+            UniformName::ViewportSize => "viewportSize",
+            UniformName::UseSkyboxTexture => "useSkyboxTexture",
+            UniformName::SkyboxTexture => "skyboxTexture",
+            UniformName::AlbedoTexture => "albedoTexture",
+            UniformName::NormalTexture => "normalTexture",
+            UniformName::EmissiveTexture => "emissiveTexture",
+            UniformName::UseNormalTexture => "useNormalTexture",
+            UniformName::UseEmissiveTexture => "useEmissiveTexture",
+            UniformName::AlphaCutout => "alphaCutout",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    #[rstest]
+    fn every_uniform_is_listed_at_its_own_index() {
+        for (i, name) in UniformName::ALL.iter().enumerate() {
+            assert_eq!(i, *name as usize);
+        }
+    }
+
+    #[rstest]
+    fn names_in_shader_are_distinct() {
+        for (i, a) in UniformName::ALL.iter().enumerate() {
+            for b in &UniformName::ALL[i + 1..] {
+                assert_ne!(a.name_in_shader(), b.name_in_shader());
+            }
         }
     }
 }
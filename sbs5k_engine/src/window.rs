@@ -1,7 +1,7 @@
 use glfw::{Action, Context};
 
 use crate::events::{EventSource, WindowEvent};
-use crate::inputs::Key;
+use crate::inputs::{Key, MouseButton};
 use crate::rendering::DisplayTarget;
 
 /// A window that will contain the game
@@ -35,6 +35,7 @@ impl Window {
         window.set_key_polling(true);
         window.set_framebuffer_size_polling(true);
 
+        window.set_mouse_button_polling(true);
         window.set_cursor_pos_polling(true);
         window.set_cursor_mode(glfw::CursorMode::Disabled);
         let supported = unsafe { glfw::ffi::glfwRawMouseMotionSupported() } != 0;
@@ -56,6 +57,16 @@ impl Window {
         }
     }
 
+    /// Enable or disable waiting for the display's vertical blank before presenting each frame
+    pub fn set_vsync(&mut self, enabled: bool) {
+        let interval = if enabled {
+            glfw::SwapInterval::Sync(1)
+        } else {
+            glfw::SwapInterval::None
+        };
+        self.glfw_instance.set_swap_interval(interval);
+    }
+
     pub fn alive(&self) -> bool {
         !self.glfw_window.should_close()
     }
@@ -85,10 +96,20 @@ impl EventSource for Window {
                     key.map(WindowEvent::KeyRelease)
                 }
 
+                glfw::WindowEvent::MouseButton(glfw_button, Action::Press, _) => {
+                    let button = MouseButton::from_glfw_mouse_button(glfw_button);
+                    button.map(WindowEvent::MouseButtonPress)
+                }
+
+                glfw::WindowEvent::MouseButton(glfw_button, Action::Release, _) => {
+                    let button = MouseButton::from_glfw_mouse_button(glfw_button);
+                    button.map(WindowEvent::MouseButtonRelease)
+                }
+
                 glfw::WindowEvent::CursorPos(x, y) => {
                     let (prev_x, prev_y) = self.last_mouse;
                     let dx = (x - prev_x) / (self.width as f64);
-                    let dy = -1.0 * (y - prev_y) / (self.height as f64);
+                    let dy = -(y - prev_y) / (self.height as f64);
                     last_mouse_pos = (x, y);
                     Some(WindowEvent::MouseMove(dx as f32, dy as f32))
                 }
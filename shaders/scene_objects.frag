@@ -1,63 +1,106 @@
 #version 410 core
 
 /**
- * TODO: Set up a system for declaring the types in a separate file
- *
- * An option might be to attempt to concatenate the two buffers at the point of
- * reading the GLSL files. Perhaps I could change the API to read a Vec<Path>
- * of files, in order?
+ * The engine defines MAX_POINT_LIGHTS, CLUSTER_GRID_X/Y/Z, Z_NEAR, Z_FAR,
+ * NUM_SHADOW_CASCADES and the FOG_MODE_* constants before compiling this
+ * shader, so that they always match its own.
  */
 
+#include "sky.glsl"
+#include "uniform_blocks.glsl"
 
-#define NUM_POINT_LIGHT_SOURCES 4
 
+/**
+ * How far to nudge each fragment out along its normal before looking it up in
+ * a shadow map, in blocks, to stop surfaces from shadowing themselves. This is
+ * scaled up for the coarser cascades.
+ */
+#define SHADOW_NORMAL_OFFSET 0.02
 
 /**
- * Information about the point lights in the scene
+ * How many texels either side of each fragment to average over when sampling
+ * a shadow map, to soften the edges of shadows
  */
-struct PointLights {
-    vec3 positions[NUM_POINT_LIGHT_SOURCES];
-    vec3 colours[NUM_POINT_LIGHT_SOURCES];
-    float intensities[NUM_POINT_LIGHT_SOURCES];
-};
+#define SHADOW_PCF_RADIUS 1
 
 /**
- * Information about the global illuminant of the scene
+ * How much dimmer each light level is than the one above it
  */
-struct GlobalIlluminant {
-    vec3 direction;
-    vec3 colour;
-    float intensity;
-};
+#define LIGHT_LEVEL_FALLOFF 0.8
 
 /**
- * Parameters about the thresholds for distance fog
+ * The colour of the light given off by emissive blocks
  */
-struct FogParameters {
-    float beginDistance;
-    float totalDistance;
-};
+#define BLOCK_LIGHT_COLOUR vec3(1.0, 0.85, 0.6)
+
+/**
+ * How opaque a texel of a cutout material must be to be drawn at all
+ */
+#define ALPHA_CUTOUT_THRESHOLD 0.5
 
 
 in vec4 WorldPosition;
 in vec4 Normal;
-in vec2 TexCoord;
-
-uniform vec3 cameraPos;
-uniform GlobalIlluminant globalIlluminant;
-uniform PointLights pointLights;
-uniform sampler2D modelTexture;
-uniform FogParameters fogParameters;
+in vec3 TexCoord;
+in vec2 Light;
+in float ViewDepth;
+
+uniform samplerBuffer pointLightsData;
+uniform usamplerBuffer lightClusters;
+uniform usamplerBuffer lightIndices;
+uniform vec2 viewportSize;
+uniform sampler2DArray albedoTexture;
+uniform sampler2DArray normalTexture;
+uniform sampler2DArray emissiveTexture;
+uniform bool useNormalTexture;
+uniform bool useEmissiveTexture;
+uniform bool alphaCutout;
+uniform bool useSkyboxTexture;
+uniform samplerCube skyboxTexture;
+uniform sampler2DArrayShadow shadowMaps;
+uniform mat4 lightSpaceMatrices[NUM_SHADOW_CASCADES];
+uniform float shadowCascadeDistances[NUM_SHADOW_CASCADES];
 
 out vec4 FragColor;
 
 
 /**
- * Determines the base colour by sampling the cubes texture
+ * Determines the base colour by sampling this face's layer of the blocks
+ * texture
  */
 vec4 baseColour()
 {
-    return texture(modelTexture, TexCoord);
+    return texture(albedoTexture, TexCoord);
+}
+
+/**
+ * Finds the direction that the surface faces at this fragment, taking the
+ * normal map into account if there is one.
+ *
+ * Models don't store tangents, so the directions that the texture coordinates
+ * run along the face are worked out from how they change between neighbouring
+ * fragments.
+ */
+vec3 surfaceNormal()
+{
+    vec3 normal = normalize(Normal.xyz);
+    if (!useNormalTexture) {
+        return normal;
+    }
+
+    vec3 dp1 = dFdx(WorldPosition.xyz);
+    vec3 dp2 = dFdy(WorldPosition.xyz);
+    vec2 duv1 = dFdx(TexCoord.xy);
+    vec2 duv2 = dFdy(TexCoord.xy);
+
+    vec3 dp2perp = cross(dp2, normal);
+    vec3 dp1perp = cross(normal, dp1);
+    vec3 tangent = dp2perp * duv1.x + dp1perp * duv2.x;
+    vec3 bitangent = dp2perp * duv1.y + dp1perp * duv2.y;
+    float scale = inversesqrt(max(max(dot(tangent, tangent), dot(bitangent, bitangent)), 1e-12));
+
+    vec3 mapped = texture(normalTexture, TexCoord).xyz * 2.0 - 1.0;
+    return normalize(mat3(tangent * scale, bitangent * scale, normal) * mapped);
 }
 
 
@@ -65,50 +108,156 @@ vec4 baseColour()
  * Calculates the additive irradiance component resulting from the scene's
  * global illuminant.
  */
-vec3 irradianceFromGlobalIlluminant()
+vec3 irradianceFromGlobalIlluminant(vec3 normal)
 {
-    float cosTheta = dot(Normal.xyz, globalIlluminant.direction);
+    float cosTheta = dot(normal, globalIlluminant.direction);
     return globalIlluminant.intensity
          * globalIlluminant.colour
          * max(cosTheta, 0.0);
 }
 
+/**
+ * Determines how much of the global illuminant reaches this fragment, from 0
+ * (fully in shadow) to 1 (fully lit).
+ *
+ * The fragment is looked up in the shadow map of whichever cascade covers its
+ * distance from the camera, with a few neighbouring texels averaged together
+ * (percentage-closer filtering) to soften the edges.
+ */
+float shadowFactor()
+{
+    int cascade = 0;
+    while (cascade < NUM_SHADOW_CASCADES && ViewDepth > shadowCascadeDistances[cascade]) {
+        cascade++;
+    }
+    if (cascade == NUM_SHADOW_CASCADES) {
+        return 1.0;
+    }
+
+    float offset = SHADOW_NORMAL_OFFSET * float(1 << (2 * cascade));
+    vec4 samplePosition = vec4(WorldPosition.xyz + Normal.xyz * offset, 1.0);
+    vec4 lightSpace = lightSpaceMatrices[cascade] * samplePosition;
+    vec3 coords = lightSpace.xyz / lightSpace.w * 0.5 + 0.5;
+    if (coords.z > 1.0) {
+        return 1.0;
+    }
+
+    vec2 texelSize = 1.0 / vec2(textureSize(shadowMaps, 0).xy);
+    float lit = 0.0;
+    for (int x = -SHADOW_PCF_RADIUS; x <= SHADOW_PCF_RADIUS; x++) {
+        for (int y = -SHADOW_PCF_RADIUS; y <= SHADOW_PCF_RADIUS; y++) {
+            vec2 uv = coords.xy + vec2(x, y) * texelSize;
+            lit += texture(shadowMaps, vec4(uv, float(cascade), coords.z));
+        }
+    }
+
+    float samples = float((2 * SHADOW_PCF_RADIUS + 1) * (2 * SHADOW_PCF_RADIUS + 1));
+    return lit / samples;
+}
+
 /**
  * Calculates the additive irradiance component resulting from one of the
  * scene's point light sources.
+ *
+ * The light fades out smoothly towards the edge of its range, so that it can
+ * be safely ignored by fragments outside its clusters.
  */
-vec3 irradianceFromPointLight(int i)
+vec3 irradianceFromPointLight(int i, vec3 normal)
 {
-    vec3 position = pointLights.positions[i];
-    vec3 colour = pointLights.colours[i];
-    float intensity = pointLights.intensities[i];
+    vec4 positionAndRange = texelFetch(pointLightsData, 2 * i);
+    vec3 radiance = texelFetch(pointLightsData, 2 * i + 1).rgb;
 
-    vec3 toLight = position - WorldPosition.xyz;
-    float coefficient = max(dot(normalize(toLight), Normal.xyz), 0.0);
-    float dist = clamp(length(toLight), 0.0001, 1000.0);
+    vec3 toLight = positionAndRange.xyz - WorldPosition.xyz;
+    float dist = max(length(toLight), 0.0001);
+    float coefficient = max(dot(toLight / dist, normal), 0.0);
 
-    return coefficient * intensity * colour / (dist*dist);
+    float window = clamp(1.0 - pow(dist / positionAndRange.w, 4.0), 0.0, 1.0);
+    return coefficient * radiance * window * window / (dist * dist + 1.0);
 }
 
-float computeOpacityFromFog()
+/**
+ * Finds the cluster of the view frustum containing the current fragment.
+ */
+int clusterIndex()
 {
-    float distanceXZ = length(WorldPosition.xz - cameraPos.xz);
-    float w = (distanceXZ - fogParameters.beginDistance)
-    / (fogParameters.totalDistance - fogParameters.beginDistance);
-    return clamp(1.0 - w, 0.0, 1.0);
+    ivec2 tile = ivec2(gl_FragCoord.xy / viewportSize * vec2(CLUSTER_GRID_X, CLUSTER_GRID_Y));
+    tile = clamp(tile, ivec2(0), ivec2(CLUSTER_GRID_X - 1, CLUSTER_GRID_Y - 1));
+
+    float proportion = log(max(ViewDepth, Z_NEAR) / Z_NEAR) / log(Z_FAR / Z_NEAR);
+    int slice = clamp(int(proportion * CLUSTER_GRID_Z), 0, CLUSTER_GRID_Z - 1);
+
+    return (slice * CLUSTER_GRID_Y + tile.y) * CLUSTER_GRID_X + tile.x;
 }
 
-vec3 sampleSkybox()
+/**
+ * Converts a light level between 0 and 1 into a brightness multiplier.
+ *
+ * Each level is a constant factor dimmer than the one above it, so that light
+ * fades out gradually rather than linearly.
+ */
+float brightnessFromLightLevel(float level)
+{
+    return pow(LIGHT_LEVEL_FALLOFF, 15.0 * (1.0 - level));
+}
+
+/**
+ * Calculates the total density of height fog between the camera and this
+ * fragment, which thins out exponentially with altitude.
+ */
+float heightFogDepth()
 {
+    if (fogParameters.heightDensity <= 0.0) {
+        return 0.0;
+    }
+
     vec3 toFragment = WorldPosition.xyz - cameraPos;
-    float r = length(toFragment.xz);
-    float elevation = atan(toFragment.y / r + 0.00001);
-    float proportion = 1 - pow(cos(abs(elevation)), 3.0);
+    float dist = length(toFragment);
+    float densityAtCamera = fogParameters.heightDensity
+                          * exp(-fogParameters.heightFalloff * (cameraPos.y - fogParameters.heightBase));
+
+    // Integrate the density along the line, which is constant if it's level
+    float falloffAlongLine = fogParameters.heightFalloff * toFragment.y;
+    if (abs(falloffAlongLine) < 0.0001) {
+        return densityAtCamera * dist;
+    }
+    return densityAtCamera * dist * (1.0 - exp(-falloffAlongLine)) / falloffAlongLine;
+}
 
-    vec3 topColour = vec3(28.0/255.0, 17.0/255.0, 188.0/255.0);
-    vec3 middleColour = vec3(168.0/255.0, 226.0/255.0, 231.0/255.0);
+/**
+ * Determines how much of this fragment can be seen through the fog, from 0
+ * (hidden) to 1 (clear). This must match `FogParameters::visibility` in
+ * fog.rs.
+ */
+float computeOpacityFromFog()
+{
+    float distanceXZ = length(WorldPosition.xz - cameraPos.xz);
+
+    float distanceVisibility;
+    if (fogParameters.mode == FOG_MODE_EXPONENTIAL) {
+        distanceVisibility = exp(-fogParameters.density * distanceXZ);
+    } else if (fogParameters.mode == FOG_MODE_EXPONENTIAL_SQUARED) {
+        float d = fogParameters.density * distanceXZ;
+        distanceVisibility = exp(-d * d);
+    } else {
+        float w = (distanceXZ - fogParameters.beginDistance)
+        / (fogParameters.totalDistance - fogParameters.beginDistance);
+        distanceVisibility = 1.0 - w;
+    }
 
-    return mix(middleColour, topColour, proportion);
+    return clamp(distanceVisibility, 0.0, 1.0) * exp(-heightFogDepth());
+}
+
+/**
+ * Finds the colour of the sky behind this fragment, from the skybox's texture
+ * if it has one, so that the fog matches the sky that it fades into.
+ */
+vec3 sampleSkybox()
+{
+    vec3 direction = normalize(WorldPosition.xyz - cameraPos);
+    if (useSkyboxTexture) {
+        return texture(skyboxTexture, direction).rgb;
+    }
+    return skyGradient(skyColours, direction);
 }
 
 vec3 toneMap(vec3 colourHDR)
@@ -124,29 +273,52 @@ vec3 gammaEncode(vec3 colour)
 void main()
 {
     vec3 irradiance = vec3(0.0);
+    vec3 normal = surfaceNormal();
 
-    // Global illumination
-    float ratio = 0.8;
-    irradiance += ratio * irradianceFromGlobalIlluminant() + (1.0 - ratio) * vec3(1.0);
+    // Cut out the gaps in materials like leaves. This comes after the normal is
+    // found, since that needs every fragment around it to still be running.
+    vec4 base = baseColour();
+    if (alphaCutout && base.a < ALPHA_CUTOUT_THRESHOLD) {
+        discard;
+    }
 
-    // Local illumination from each point light source
-    for (int i = 0; i < NUM_POINT_LIGHT_SOURCES; i++) {
-        irradiance += irradianceFromPointLight(i);
+    // Global illumination, which only reaches as far as the sky light does
+    float ratio = 0.8;
+    float skyBrightness = brightnessFromLightLevel(Light.x);
+    vec3 ambient = globalIlluminant.ambientIntensity * globalIlluminant.colour;
+    irradiance += skyBrightness
+                * (ratio * shadowFactor() * irradianceFromGlobalIlluminant(normal) + (1.0 - ratio) * ambient);
+
+    // Light from emissive blocks nearby
+    irradiance += brightnessFromLightLevel(Light.y) * BLOCK_LIGHT_COLOUR;
+
+    // Local illumination from each point light source whose range reaches this
+    // fragment's cluster
+    uvec2 cluster = texelFetch(lightClusters, clusterIndex()).rg;
+    for (uint k = 0u; k < cluster.y; k++) {
+        int lightIndex = int(texelFetch(lightIndices, int(cluster.x + k)).r);
+        irradiance += irradianceFromPointLight(lightIndex, normal);
     }
 
-    vec4 base = baseColour();
     vec3 radiance = base.rgb * irradiance;
 
+    // Glowing parts of the surface give off light of their own
+    if (useEmissiveTexture) {
+        radiance += texture(emissiveTexture, TexCoord).rgb;
+    }
+
     // Prepare colours for displaying
     vec3 toneMapped = toneMap(radiance);
     vec3 gammaEncoded = gammaEncode(toneMapped);
 
     // Mix with distance fog
-    float alpha = computeOpacityFromFog();
-    vec3 bgColour = sampleSkybox();
-    vec3 finalRadiance = mix(bgColour, gammaEncoded, alpha);
+    float visibility = computeOpacityFromFog();
+    vec3 bgColour = fogParameters.useSkyColour ? sampleSkybox() : fogParameters.colour;
+    vec3 finalRadiance = mix(bgColour, gammaEncoded, visibility);
 
-    FragColor = vec4(finalRadiance, alpha);
+    // The alpha is only used when blending translucent surfaces over what's
+    // behind them
+    FragColor = vec4(finalRadiance, base.a);
 }
 
 
@@ -1,16 +1,22 @@
 #version 410 core
 
+#include "uniform_blocks.glsl"
+
+/** How many frames of an animated texture are shown each second */
+#define TEXTURE_FRAMES_PER_SECOND 8.0
+
 layout (location = 0) in vec3 aPos;
 layout (location = 1) in vec3 aNormal;
-layout (location = 2) in vec2 aTexCoord;
+layout (location = 2) in vec4 aTexCoord;
+layout (location = 3) in vec2 aLight;
 
 uniform mat4 Model;
-uniform mat4 View;
-uniform mat4 Projection;
 
 out vec4 WorldPosition;
 out vec4 Normal;
-out vec2 TexCoord;
+out vec3 TexCoord;
+out vec2 Light;
+out float ViewDepth;
 
 
 void main()
@@ -24,11 +30,20 @@ void main()
     // The current vertex's location in world space
     WorldPosition = Model * modelCoords;
 
+    // The vertex's distance in front of the camera, for finding its light cluster
+    ViewDepth = -(View * WorldPosition).z;
+
     // The vertex's normal in world space
     // TODO: Confirm this rotates without translating
     // (It's supposed to abuse whether there's a 0 or a 1 in the w dimension in the
     // homogenous coordinates representation; it's an nalgebra thing)
     Normal = normalize(Model * normalCoords);
 
-    TexCoord = aTexCoord;
+    // The position within the face's texture, followed by the texture's layer, stepping through
+    // the layers after it if the texture is animated
+    float frame = mod(floor(animationTime * TEXTURE_FRAMES_PER_SECOND), aTexCoord.w);
+    TexCoord = vec3(aTexCoord.xy, aTexCoord.z + frame);
+
+    // The sky and block light levels, smoothly interpolated across each face
+    Light = aLight;
 }
@@ -0,0 +1,23 @@
+#version 410 core
+
+/**
+ * How opaque a texel of a cutout material must be to cast a shadow. This must
+ * match scene_objects.frag.
+ */
+#define ALPHA_CUTOUT_THRESHOLD 0.5
+
+
+in vec3 TexCoord;
+
+uniform sampler2DArray albedoTexture;
+uniform bool alphaCutout;
+
+
+void main()
+{
+    // The depth is written automatically, and there is no colour buffer, so
+    // all that's left is to let light through the gaps in cutout materials
+    if (alphaCutout && texture(albedoTexture, TexCoord).a < ALPHA_CUTOUT_THRESHOLD) {
+        discard;
+    }
+}
@@ -0,0 +1,28 @@
+#version 410 core
+
+#include "uniform_blocks.glsl"
+
+/**
+ * How many frames of an animated texture are shown each second. This must
+ * match scene_objects.vert.
+ */
+#define TEXTURE_FRAMES_PER_SECOND 8.0
+
+layout (location = 0) in vec3 aPos;
+layout (location = 2) in vec4 aTexCoord;
+
+uniform mat4 Model;
+uniform mat4 LightSpace;
+
+out vec3 TexCoord;
+
+
+void main()
+{
+    gl_Position = LightSpace * Model * vec4(aPos, 1.0f);
+
+    // The texture coordinates are only needed to cut the gaps out of cutout
+    // materials like leaves, so that light shines through them
+    float frame = mod(floor(animationTime * TEXTURE_FRAMES_PER_SECOND), aTexCoord.w);
+    TexCoord = vec3(aTexCoord.xy, aTexCoord.z + frame);
+}
@@ -0,0 +1,20 @@
+/**
+ * The gradient of the sky, shared between the skybox and the fog that fades
+ * distant terrain into it
+ */
+
+#include "types.glsl"
+
+
+/**
+ * Finds the colour of the sky gradient in the given direction, which blends
+ * from the horizon colour up to the zenith colour
+ */
+vec3 skyGradient(SkyColours colours, vec3 direction)
+{
+    float r = max(length(direction.xz), 0.000001);
+    float elevation = atan(direction.y / r);
+    float proportion = 1 - pow(cos(abs(elevation)), 3.0);
+
+    return mix(colours.horizon, colours.zenith, proportion);
+}
@@ -1,29 +1,173 @@
 #version 410 core
 
+#include "sky.glsl"
+#include "uniform_blocks.glsl"
+
 
 #define PI 3.1415926535
-#define EPSILON 0.000001
+
+/**
+ * The apparent sizes of the sun and moon, as angular radii in radians
+ */
+#define SUN_ANGULAR_RADIUS 0.035
+#define MOON_ANGULAR_RADIUS 0.03
+
+/**
+ * The colour of the moon's disc
+ */
+#define MOON_COLOUR vec3(0.85, 0.87, 0.95)
+
+/**
+ * How finely the sky is divided up when scattering stars, and the proportion
+ * of those divisions that contain a star
+ */
+#define STAR_GRID_SIZE 300.0
+#define STAR_DENSITY 0.0015
+
+/**
+ * The height of the cloud layer above the bottom of the world, in blocks
+ */
+#define CLOUD_ALTITUDE 160.0
+
+/**
+ * How large the clouds are. Smaller values make for bigger clouds.
+ */
+#define CLOUD_SCALE 0.008
+
+/**
+ * How much of the cloud noise is clear sky, between 0 (overcast) and 1 (clear)
+ */
+#define CLOUD_COVERAGE 0.55
+
+/**
+ * The distance over which the clouds fade out towards the horizon
+ */
+#define CLOUD_FADE_DISTANCE 2500.0
 
 
 in vec3 TexCoords;
 
+uniform bool useSkyboxTexture;
+uniform samplerCube skyboxTexture;
+
 out vec4 FragColour;
 
-float calculateElevation()
+/**
+ * Hashes a cell of a 3D grid into a pseudo-random value between 0 and 1
+ */
+float hash3(vec3 p)
+{
+    p = fract(p * 0.3183099 + 0.1);
+    p *= 17.0;
+    return fract(p.x * p.y * p.z * (p.x + p.y + p.z));
+}
+
+/**
+ * Hashes a cell of a 2D grid into a pseudo-random value between 0 and 1
+ */
+float hash2(vec2 p)
+{
+    return fract(sin(dot(p, vec2(127.1, 311.7))) * 43758.5453);
+}
+
+/**
+ * Smoothly interpolated value noise
+ */
+float valueNoise(vec2 p)
+{
+    vec2 i = floor(p);
+    vec2 f = fract(p);
+    vec2 u = f * f * (3.0 - 2.0 * f);
+
+    return mix(mix(hash2(i), hash2(i + vec2(1.0, 0.0)), u.x),
+               mix(hash2(i + vec2(0.0, 1.0)), hash2(i + vec2(1.0, 1.0)), u.x),
+               u.y);
+}
+
+/**
+ * Several octaves of value noise added together, for more natural detail
+ */
+float fractalNoise(vec2 p)
+{
+    float total = 0.0;
+    float amplitude = 0.5;
+    for (int i = 0; i < 4; i++) {
+        total += amplitude * valueNoise(p);
+        p *= 2.0;
+        amplitude *= 0.5;
+    }
+    return total;
+}
+
+/**
+ * Determines how brightly a star shines in the given direction, if there is
+ * one there at all
+ */
+float starBrightness(vec3 direction)
 {
-    float r = max(length(TexCoords.xz), EPSILON);
-    return atan(TexCoords.y / r);
+    float h = hash3(floor(direction * STAR_GRID_SIZE));
+    if (h > STAR_DENSITY) {
+        return 0.0;
+    }
+    return h / STAR_DENSITY;
+}
+
+/**
+ * Draws the discs of the sun and moon over the sky
+ */
+vec3 addSunAndMoon(vec3 colour, vec3 direction)
+{
+    float aboveHorizon = smoothstep(-0.02, 0.02, direction.y);
+
+    float cosToSun = dot(direction, sun.direction);
+    float sunDisc = smoothstep(cos(SUN_ANGULAR_RADIUS), cos(SUN_ANGULAR_RADIUS * 0.9), cosToSun);
+    float sunGlow = 0.4 * pow(max(cosToSun, 0.0), 64.0);
+    colour += (sunDisc + sunGlow) * sun.colour * aboveHorizon;
+
+    // The moon is faint during the day, and clearest when the stars are out
+    float cosToMoon = dot(direction, -sun.direction);
+    float moonDisc = smoothstep(cos(MOON_ANGULAR_RADIUS), cos(MOON_ANGULAR_RADIUS * 0.9), cosToMoon);
+    float moonOpacity = mix(0.3, 1.0, starVisibility);
+    return mix(colour, MOON_COLOUR, moonDisc * moonOpacity * aboveHorizon);
+}
+
+/**
+ * Finds the colour and opacity of the cloud layer in the given direction
+ */
+vec4 cloudLayer(vec3 direction)
+{
+    // Only look for clouds on the side of the camera that the layer is on
+    float heightAboveCamera = CLOUD_ALTITUDE - cameraPos.y;
+    if (direction.y * heightAboveCamera <= 0.0) {
+        return vec4(0.0);
+    }
+
+    float distanceToLayer = heightAboveCamera / direction.y;
+    vec2 position = cameraPos.xz + direction.xz * distanceToLayer + clouds.offset;
+
+    float noise = fractalNoise(position * CLOUD_SCALE);
+    float density = smoothstep(CLOUD_COVERAGE, CLOUD_COVERAGE + 0.2, noise);
+    float fade = 1.0 - smoothstep(0.0, CLOUD_FADE_DISTANCE, distanceToLayer);
+
+    return vec4(clouds.colour, density * fade);
 }
 
 void main()
 {
-    vec3 topColour = vec3(28.0/255.0, 17.0/255.0, 188.0/255.0);
-    vec3 middleColour = vec3(168.0/255.0, 226.0/255.0, 231.0/255.0);
+    vec3 direction = normalize(TexCoords);
+
+    vec3 colour;
+    if (useSkyboxTexture) {
+        colour = texture(skyboxTexture, direction).rgb;
+    } else {
+        colour = skyGradient(skyColours, direction);
+    }
 
-    float elevation = calculateElevation();
-    float proportion = 1 - pow(cos(abs(elevation)), 3.0);
+    colour += starVisibility * starBrightness(direction) * smoothstep(0.0, 0.1, direction.y);
+    colour = addSunAndMoon(colour, direction);
 
-    vec3 mixedColour = mix(middleColour, topColour, proportion);
+    vec4 cloud = cloudLayer(direction);
+    colour = mix(colour, cloud.rgb, cloud.a);
 
-    FragColour = vec4(mixedColour, 1.0);
+    FragColour = vec4(colour, 1.0);
 }
@@ -1,9 +1,8 @@
 #version 410 core
 
-layout (location = 0) in vec3 VertexPos;
+#include "uniform_blocks.glsl"
 
-uniform mat4 Projection;
-uniform mat4 View;
+layout (location = 0) in vec3 VertexPos;
 
 out vec3 TexCoords;
 
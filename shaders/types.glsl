@@ -1,11 +1,54 @@
-struct PointLight {
-    vec3 position;
+/**
+ * Declarations shared between shaders. Include this with
+ * `#include "types.glsl"`.
+ */
+
+
+/**
+ * Information about the global illuminant of the scene
+ */
+struct GlobalIlluminant {
+    vec3 direction;
     vec3 colour;
     float intensity;
+    float ambientIntensity;
 };
 
-struct GlobalIlluminant {
+/**
+ * The colours of the sky, which distant terrain fades into
+ */
+struct SkyColours {
+    vec3 zenith;
+    vec3 horizon;
+};
+
+/**
+ * The position and colour of the sun. The moon sits directly opposite it.
+ */
+struct Sun {
     vec3 direction;
     vec3 colour;
-    float intensity;
+};
+
+/**
+ * The appearance of the cloud layer
+ */
+struct Clouds {
+    vec3 colour;
+    vec2 offset;
+};
+
+/**
+ * Parameters about distance and height fog
+ */
+struct FogParameters {
+    int mode;
+    float beginDistance;
+    float totalDistance;
+    float density;
+    float heightDensity;
+    float heightFalloff;
+    float heightBase;
+    bool useSkyColour;
+    vec3 colour;
 };
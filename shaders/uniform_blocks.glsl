@@ -0,0 +1,32 @@
+/**
+ * The uniform blocks that the engine fills in once per frame and shares
+ * between every shader program. Their layouts must match the ones written in
+ * uniform_blocks.rs.
+ */
+
+#include "types.glsl"
+
+
+layout (std140) uniform Camera {
+    mat4 View;
+    mat4 Projection;
+    vec3 cameraPos;
+
+    /** Seconds since the renderer started, for animating textures */
+    float animationTime;
+};
+
+layout (std140) uniform Lighting {
+    GlobalIlluminant globalIlluminant;
+};
+
+layout (std140) uniform Sky {
+    SkyColours skyColours;
+    Sun sun;
+    float starVisibility;
+    Clouds clouds;
+};
+
+layout (std140) uniform Fog {
+    FogParameters fogParameters;
+};